@@ -0,0 +1,84 @@
+//! Bearer API keys for machine clients. Keys are shown once at creation and
+//! only their SHA-256 is stored, so lookups stay a single indexed query.
+
+use crate::auth::ApiScope;
+use chrono::{Duration, Utc};
+use entity::api_key;
+use rand::RngCore;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+use sha2::{Digest, Sha256};
+
+pub const API_KEY_PREFIX: &str = "sme_";
+/// Characters of the token kept in clear for listings, including `API_KEY_PREFIX`.
+const DISPLAY_PREFIX_LEN: usize = 12;
+/// `last_used_at` is only rewritten once it is this stale.
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+/// A fresh token plus what gets stored for it: `(token, display prefix, hash)`.
+pub fn generate_api_key() -> (String, String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = format!("{}{}", API_KEY_PREFIX, hex::encode(bytes));
+    let prefix = token[..DISPLAY_PREFIX_LEN].to_string();
+    let hash = hash_api_key(&token);
+    (token, prefix, hash)
+}
+
+pub fn hash_api_key(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn format_scopes(scopes: &[ApiScope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn parse_scopes(value: &str) -> Vec<ApiScope> {
+    value
+        .split_whitespace()
+        .filter_map(ApiScope::parse)
+        .collect()
+}
+
+/// Resolves a bearer token to its key when it is neither revoked nor expired,
+/// recording the use.
+pub async fn authenticate_api_key<C: ConnectionTrait>(
+    conn: &C,
+    token: &str,
+) -> Result<Option<api_key::Model>, DbErr> {
+    if !token.starts_with(API_KEY_PREFIX) {
+        return Ok(None);
+    }
+    let now = Utc::now();
+    let now_tz: DateTimeWithTimeZone = now.into();
+    let Some(key) = api_key::Entity::find()
+        .filter(api_key::Column::TokenHash.eq(hash_api_key(token)))
+        .filter(api_key::Column::RevokedAt.is_null())
+        .filter(
+            Condition::any()
+                .add(api_key::Column::ExpiresAt.is_null())
+                .add(api_key::Column::ExpiresAt.gt(now_tz)),
+        )
+        .one(conn)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let stale: DateTimeWithTimeZone = (now - Duration::seconds(LAST_USED_RESOLUTION_SECS)).into();
+    api_key::Entity::update_many()
+        .col_expr(api_key::Column::LastUsedAt, Expr::value(now_tz))
+        .filter(api_key::Column::Id.eq(key.id))
+        .filter(
+            Condition::any()
+                .add(api_key::Column::LastUsedAt.is_null())
+                .add(api_key::Column::LastUsedAt.lt(stale)),
+        )
+        .exec(conn)
+        .await?;
+    Ok(Some(key))
+}
@@ -0,0 +1,127 @@
+//! Append-only trail of changes made through the API, kept in `audit_log` for
+//! admins. Rows are written in the same transaction as the change they record.
+
+use chrono::Utc;
+use entity::audit_log::{self, EntityType, Operation};
+use sea_orm::sea_query::{sea_value_to_json_value, Value};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ConnectionTrait, DbErr, EntityTrait, IdenStatic, Iterable,
+    ModelTrait,
+};
+use serde_json::{json, Map, Value as Json};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+const MAX_REQUEST_ID: usize = 64;
+
+/// Stands in for a secret whose change is worth recording but whose value is not.
+pub const REDACTED: &str = "[redacted]";
+
+/// Columns left out of diffs: keys and bookkeeping the row already carries, and
+/// secrets that must never be copied anywhere.
+const UNRECORDED: &[&str] = &[
+    "id",
+    "org_id",
+    "created_at",
+    "created_by",
+    "updated_at",
+    "updated_by",
+    "last_used_at",
+    "token_hash",
+];
+
+/// Who made a change, and the request it arrived in.
+#[derive(Debug, Clone)]
+pub struct AuditActor {
+    /// Organization the change belongs to; its admins are the ones who see it.
+    pub org_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub request_id: Option<String>,
+}
+
+/// Records the change from `before` to `after`: a create when there was no row
+/// and a delete when none is left. Updates that changed nothing are skipped.
+pub async fn record_change<C, M>(
+    conn: &C,
+    actor: &AuditActor,
+    entity_type: EntityType,
+    entity_id: Uuid,
+    before: Option<&M>,
+    after: Option<&M>,
+) -> Result<(), DbErr>
+where
+    C: ConnectionTrait,
+    M: ModelTrait,
+{
+    let operation = match (before, after) {
+        (None, Some(_)) => Operation::Create,
+        (Some(_), Some(_)) => Operation::Update,
+        (Some(_), None) => Operation::Delete,
+        (None, None) => return Ok(()),
+    };
+    let changes = diff(
+        &before.map(snapshot).unwrap_or_default(),
+        &after.map(snapshot).unwrap_or_default(),
+    );
+    if operation == Operation::Update && changes.is_empty() {
+        return Ok(());
+    }
+    record_audit(conn, actor, entity_type, entity_id, operation, changes).await
+}
+
+/// Writes one row with a precomputed diff, for changes that span several tables.
+pub async fn record_audit<C: ConnectionTrait>(
+    conn: &C,
+    actor: &AuditActor,
+    entity_type: EntityType,
+    entity_id: Uuid,
+    operation: Operation,
+    changes: Map<String, Json>,
+) -> Result<(), DbErr> {
+    audit_log::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        org_id: Set(actor.org_id),
+        actor_id: Set(actor.user_id),
+        entity_type: Set(entity_type),
+        entity_id: Set(entity_id),
+        operation: Set(operation),
+        changes: Set(Json::Object(changes)),
+        request_id: Set(actor
+            .request_id
+            .as_ref()
+            .map(|id| id.chars().take(MAX_REQUEST_ID).collect())),
+        created_at: Set(Utc::now().into()),
+    }
+    .insert(conn)
+    .await?;
+    Ok(())
+}
+
+/// The model's recorded columns as JSON, keyed by column name.
+pub fn snapshot<M: ModelTrait>(model: &M) -> Map<String, Json> {
+    <M::Entity as EntityTrait>::Column::iter()
+        .filter(|column| !UNRECORDED.contains(&column.as_str()))
+        .map(|column| (column.as_str().to_string(), to_json(model.get(column))))
+        .collect()
+}
+
+/// `{"old": .., "new": ..}` for every key whose value differs. A missing key
+/// counts as null.
+pub fn diff(before: &Map<String, Json>, after: &Map<String, Json>) -> Map<String, Json> {
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = before.get(key).unwrap_or(&Json::Null);
+            let new = after.get(key).unwrap_or(&Json::Null);
+            (old != new).then(|| (key.clone(), json!({ "old": old, "new": new })))
+        })
+        .collect()
+}
+
+fn to_json(value: Value) -> Json {
+    match value {
+        Value::ChronoDateTimeWithTimeZone(Some(at)) => Json::String(at.to_rfc3339()),
+        Value::ChronoDate(Some(date)) => Json::String(date.to_string()),
+        other => sea_value_to_json_value(&other),
+    }
+}
@@ -1,53 +1,194 @@
+use crate::keyring::{Keyring, SigningKey};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::{Algorithm, Header, Validation};
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashSet;
 use uuid::Uuid;
 
 pub const SESSION_COOKIE: &str = "sme_session";
+/// Double-submit CSRF token. Unlike the session cookie, scripts can read it.
+pub const CSRF_COOKIE: &str = "sme_csrf";
+/// Cookie-authenticated writes must echo `CSRF_COOKIE` in this header.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+/// Absolute session lifetime unless `AUTH_SESSION_MAX_AGE_MINUTES` overrides it.
+pub const DEFAULT_SESSION_MAX_AGE_MINUTES: i64 = 7 * 24 * 60;
+/// How long a password-verified login waits for its second factor.
+pub const SECOND_FACTOR_CHALLENGE_SECS: i64 = 5 * 60;
+/// Shortest accepted password unless `PASSWORD_MIN_LENGTH` overrides it.
+pub const DEFAULT_MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+/// Refused even when `PASSWORD_DENY_LIST_FILE` is not set.
+const BUILTIN_DENIED_PASSWORDS: &[&str] = &[
+    "password",
+    "password1",
+    "password123",
+    "passw0rd",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "11111111",
+    "00000000",
+    "qwertyui",
+    "qwerty123",
+    "qwertyuiop",
+    "iloveyou",
+    "sunshine",
+    "letmein123",
+    "welcome1",
+    "changeme",
+    "abcd1234",
+];
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum AuthMode {
     Disabled,
+    /// Email and password via the `login` mutation.
     Local,
+    /// OpenID Connect only.
+    Oidc,
+    /// Both password and OpenID Connect login.
+    Mixed,
+}
+
+impl AuthMode {
+    pub fn allows_password(self) -> bool {
+        matches!(self, AuthMode::Local | AuthMode::Mixed)
+    }
+
+    pub fn allows_oidc(self) -> bool {
+        matches!(self, AuthMode::Oidc | AuthMode::Mixed)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct AuthConfig {
     pub mode: AuthMode,
-    secret: Option<String>,
+    pub keyring: Keyring,
+    /// Sliding window: a session idle for longer than this stops authenticating.
     pub session_ttl_minutes: i64,
+    /// Hard cap on a session's lifetime, however active it stays.
+    pub session_max_age_minutes: i64,
+    pub password_policy: PasswordPolicy,
+    /// Public URL of the web app, used to build links sent by email.
+    pub app_url: String,
 }
 
 impl AuthConfig {
+    /// Signs with HS256 over `secret`; see [`AuthConfig::with_keyring`] for more.
     pub fn new(mode: AuthMode, secret: Option<String>, ttl: i64) -> Self {
         Self {
             mode,
-            secret,
+            keyring: secret
+                .map(|secret| Keyring::new(SigningKey::hmac(&secret)))
+                .unwrap_or_default(),
             session_ttl_minutes: ttl,
+            session_max_age_minutes: DEFAULT_SESSION_MAX_AGE_MINUTES,
+            password_policy: PasswordPolicy::default(),
+            app_url: "http://localhost:8080".into(),
+        }
+    }
+
+    pub fn with_session_max_age(mut self, minutes: i64) -> Self {
+        self.session_max_age_minutes = minutes;
+        self
+    }
+
+    pub fn with_password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.password_policy = policy;
+        self
+    }
+
+    pub fn with_app_url(mut self, url: impl Into<String>) -> Self {
+        self.app_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = keyring;
+        self
+    }
+}
+
+/// Rules every new password must meet, whoever sets it.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// Lower-cased passwords refused outright.
+    denied: HashSet<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_PASSWORD_LENGTH)
+    }
+}
+
+impl PasswordPolicy {
+    pub fn new(min_length: usize) -> Self {
+        Self {
+            min_length,
+            denied: BUILTIN_DENIED_PASSWORDS
+                .iter()
+                .map(|word| word.to_string())
+                .collect(),
         }
     }
 
-    fn encoding_key(&self) -> Option<EncodingKey> {
-        self.secret
-            .as_ref()
-            .map(|secret| EncodingKey::from_secret(secret.as_bytes()))
+    /// Adds to the deny-list; blank entries are ignored.
+    pub fn with_denied<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.denied.extend(
+            words
+                .into_iter()
+                .map(|word| word.as_ref().trim().to_lowercase())
+                .filter(|word| !word.is_empty()),
+        );
+        self
     }
 
-    fn decoding_key(&self) -> Option<DecodingKey> {
-        self.secret
-            .as_ref()
-            .map(|secret| DecodingKey::from_secret(secret.as_bytes()))
+    /// Case-insensitive, so `Password1` is as weak as `password1`.
+    pub fn is_denied(&self, password: &str) -> bool {
+        self.denied.contains(&password.to_lowercase())
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionClaims {
     pub sub: Uuid,
+    /// Organization the session acts in; every query is scoped to it.
+    pub org: Uuid,
+    /// `user_session.id` backing this token.
+    pub sid: Uuid,
     pub exp: usize,
     pub iat: usize,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+/// What a second-factor challenge token lets its holder do next.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengePurpose {
+    /// Present a TOTP or recovery code.
+    Verify,
+    /// Enrol TOTP first, because policy requires it for the account.
+    Enroll,
+}
+
+/// Proof that the password step of a login passed. Carries no `sid`, so it
+/// never decodes as a session token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: Uuid,
+    /// Organization the finished login will sign in to.
+    pub org: Uuid,
+    pub purpose: ChallengePurpose,
+    pub exp: usize,
+}
+
+#[derive(async_graphql::Enum, Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
 pub enum UserRole {
     Owner,
     Admin,
@@ -65,6 +206,7 @@ impl UserRole {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(value: &str) -> Option<Self> {
         match value {
             "OWNER" => Some(UserRole::Owner),
@@ -85,10 +227,50 @@ impl UserRole {
     }
 }
 
+/// What an API key may do on top of its user's roles.
+#[derive(async_graphql::Enum, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ApiScope {
+    CrmRead,
+    CrmWrite,
+    Admin,
+}
+
+impl ApiScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiScope::CrmRead => "crm:read",
+            ApiScope::CrmWrite => "crm:write",
+            ApiScope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "crm:read" => Some(ApiScope::CrmRead),
+            "crm:write" => Some(ApiScope::CrmWrite),
+            "admin" => Some(ApiScope::Admin),
+            _ => None,
+        }
+    }
+
+    /// Highest role-gated operation the scope opens up.
+    pub fn ceiling(self) -> UserRole {
+        match self {
+            ApiScope::CrmRead => UserRole::Viewer,
+            ApiScope::CrmWrite => UserRole::Sales,
+            ApiScope::Admin => UserRole::Owner,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CurrentUser {
     pub user_id: Uuid,
+    /// Organization the request acts in. `roles` are the user's roles there.
+    pub org_id: Uuid,
     pub roles: Vec<UserRole>,
+    /// Set when the request authenticated with an API key; `None` for sessions.
+    pub scopes: Option<Vec<ApiScope>>,
 }
 
 impl CurrentUser {
@@ -96,15 +278,44 @@ impl CurrentUser {
         self.roles.iter().any(|r| r.level() >= role.level())
     }
 
+    /// `has_role`, further capped by the API key's scopes when there is one.
+    pub fn permits(&self, role: UserRole) -> bool {
+        let scoped = match &self.scopes {
+            Some(scopes) => scopes
+                .iter()
+                .any(|scope| scope.ceiling().level() >= role.level()),
+            None => true,
+        };
+        scoped && self.has_role(role)
+    }
+
     pub fn highest_role(&self) -> Option<UserRole> {
         self.roles.iter().copied().max_by_key(|role| role.level())
     }
 }
 
-pub fn issue_session_token(user_id: Uuid, config: &AuthConfig) -> anyhow::Result<String> {
-    let encoding = config
-        .encoding_key()
-        .ok_or_else(|| anyhow::anyhow!("missing session secret"))?;
+/// The server-side session that authenticated the request, when there is one.
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentSession {
+    pub id: Uuid,
+}
+
+/// Request details recorded against new sessions, sign-in events and audit rows.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    /// Peer address of the connection, when the server knows it.
+    pub ip: Option<String>,
+    /// The `x-request-id` the server assigned, stamped on audit rows.
+    pub request_id: Option<String>,
+}
+
+pub fn issue_session_token(
+    user_id: Uuid,
+    org_id: Uuid,
+    session_id: Uuid,
+    config: &AuthConfig,
+) -> anyhow::Result<String> {
     let now = Utc::now();
     let exp = now
         .checked_add_signed(Duration::minutes(config.session_ttl_minutes))
@@ -112,19 +323,83 @@ pub fn issue_session_token(user_id: Uuid, config: &AuthConfig) -> anyhow::Result
         .timestamp() as usize;
     let claims = SessionClaims {
         sub: user_id,
+        org: org_id,
+        sid: session_id,
         exp,
         iat: now.timestamp() as usize,
     };
-    jsonwebtoken::encode(&Header::default(), &claims, &encoding).map_err(|err| anyhow::anyhow!(err))
+    sign_claims(&claims, config)
 }
 
 pub fn decode_session_token(token: &str, config: &AuthConfig) -> anyhow::Result<SessionClaims> {
-    let decoding = config
-        .decoding_key()
-        .ok_or_else(|| anyhow::anyhow!("missing session secret"))?;
-    jsonwebtoken::decode::<SessionClaims>(token, &decoding, &Validation::default())
-        .map(|data| data.claims)
-        .map_err(|err| anyhow::anyhow!(err))
+    verify_claims(token, config)
+}
+
+pub fn issue_challenge_token(
+    user_id: Uuid,
+    org_id: Uuid,
+    purpose: ChallengePurpose,
+    config: &AuthConfig,
+) -> anyhow::Result<String> {
+    let exp = Utc::now() + Duration::seconds(SECOND_FACTOR_CHALLENGE_SECS);
+    let claims = ChallengeClaims {
+        sub: user_id,
+        org: org_id,
+        purpose,
+        exp: exp.timestamp() as usize,
+    };
+    sign_claims(&claims, config)
+}
+
+/// Decodes a challenge token, rejecting one issued for another purpose.
+pub fn decode_challenge_token(
+    token: &str,
+    purpose: ChallengePurpose,
+    config: &AuthConfig,
+) -> anyhow::Result<ChallengeClaims> {
+    let claims: ChallengeClaims = verify_claims(token, config)?;
+    if claims.purpose != purpose {
+        anyhow::bail!("challenge token issued for another purpose");
+    }
+    Ok(claims)
+}
+
+/// Signs any short-lived claims (with an `exp`) with the keyring's signer,
+/// naming it in the `kid` header.
+pub fn sign_claims<T: Serialize>(claims: &T, config: &AuthConfig) -> anyhow::Result<String> {
+    let key = config
+        .keyring
+        .signer()
+        .ok_or_else(|| anyhow::anyhow!("no signing key configured"))?;
+    let header = Header {
+        kid: Some(key.kid.clone()),
+        ..Header::new(key.algorithm)
+    };
+    jsonwebtoken::encode(&header, claims, key.encoding_key()).map_err(|err| anyhow::anyhow!(err))
+}
+
+/// Verifies with the key named by `kid`, pinned to that key's algorithm.
+/// Tokens from before keys had ids carry none and are tried against the
+/// shared secrets.
+pub fn verify_claims<T: DeserializeOwned>(token: &str, config: &AuthConfig) -> anyhow::Result<T> {
+    let header = jsonwebtoken::decode_header(token)?;
+    let candidates: Vec<_> = match header.kid.as_deref() {
+        Some(kid) => config.keyring.find(kid).into_iter().collect(),
+        None => config
+            .keyring
+            .keys()
+            .filter(|key| key.algorithm == Algorithm::HS256)
+            .collect(),
+    };
+    let mut last_error = anyhow::anyhow!("unknown signing key");
+    for key in candidates {
+        match jsonwebtoken::decode::<T>(token, key.decoding_key(), &Validation::new(key.algorithm))
+        {
+            Ok(data) => return Ok(data.claims),
+            Err(err) => last_error = anyhow::anyhow!(err),
+        }
+    }
+    Err(last_error)
 }
 
 pub fn build_session_cookie(token: &str, ttl_minutes: i64) -> String {
@@ -135,6 +410,37 @@ pub fn build_session_cookie(token: &str, ttl_minutes: i64) -> String {
     )
 }
 
+pub fn generate_csrf_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Not `HttpOnly`: the web app reads it to echo the token in `x-csrf-token`.
+/// Reissued alongside every session refresh, so it never expires first.
+pub fn build_csrf_cookie(token: &str, max_age_minutes: i64) -> String {
+    format!(
+        "{}={}; Max-Age={}; Path=/; SameSite=Lax",
+        CSRF_COOKIE,
+        token,
+        max_age_minutes.max(0) * 60
+    )
+}
+
+pub fn clear_csrf_cookie() -> String {
+    format!("{}=; Max-Age=0; Path=/; SameSite=Lax", CSRF_COOKIE)
+}
+
+/// Compares in constant time, so the cookie cannot be guessed byte by byte.
+pub fn csrf_matches(cookie: &str, submitted: &str) -> bool {
+    cookie.len() == submitted.len()
+        && cookie
+            .bytes()
+            .zip(submitted.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
 pub fn clear_session_cookie() -> String {
     format!(
         "{}=; Max-Age=0; Path=/; HttpOnly; SameSite=Lax",
@@ -0,0 +1,42 @@
+//! Append-only record of sign-in activity, kept in `auth_event` for admins.
+
+use crate::auth::ClientInfo;
+use chrono::Utc;
+use entity::auth_event;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ConnectionTrait, DbErr};
+use uuid::Uuid;
+
+const MAX_EMAIL: usize = 254;
+const MAX_USER_AGENT: usize = 512;
+const MAX_DETAIL: usize = 255;
+
+/// Records one event. `email` is what the client presented, which may not
+/// belong to any account.
+pub async fn record_auth_event<C: ConnectionTrait>(
+    conn: &C,
+    kind: auth_event::Kind,
+    user_id: Option<Uuid>,
+    email: Option<&str>,
+    client: Option<&ClientInfo>,
+    detail: Option<&str>,
+) -> Result<(), DbErr> {
+    auth_event::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        kind: Set(kind),
+        user_id: Set(user_id),
+        email: Set(email.map(|email| truncate(email, MAX_EMAIL))),
+        ip: Set(client.and_then(|client| client.ip.clone())),
+        user_agent: Set(client
+            .and_then(|client| client.user_agent.as_deref())
+            .map(|agent| truncate(agent, MAX_USER_AGENT))),
+        detail: Set(detail.map(|detail| truncate(detail, MAX_DETAIL))),
+        created_at: Set(Utc::now().into()),
+    }
+    .insert(conn)
+    .await?;
+    Ok(())
+}
+
+fn truncate(value: &str, max: usize) -> String {
+    value.chars().take(max).collect()
+}
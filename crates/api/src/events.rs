@@ -0,0 +1,179 @@
+//! Live CRM changes for GraphQL subscriptions. Mutations publish an event once their
+//! transaction has committed; subscribers filter by organization and book and load the
+//! record themselves, so events stay small enough for Postgres `NOTIFY` when several
+//! server instances share one database.
+
+use async_graphql::futures_util::{stream, Stream};
+use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Postgres channel events travel on between instances.
+pub const EVENT_CHANNEL: &str = "crm_events";
+/// Events a slow subscriber may fall behind by before it skips ahead.
+const BUFFER: usize = 1024;
+const LISTEN_RETRY: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrmEvent {
+    pub org_id: Uuid,
+    /// The record's owner when the change happened, for book-restricted subscribers.
+    pub assigned_user_id: Option<Uuid>,
+    pub kind: CrmEventKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CrmEventKind {
+    DealUpdated {
+        deal_id: Uuid,
+    },
+    DealStageMoved {
+        deal_id: Uuid,
+        from: String,
+        to: String,
+        changed_by: Option<Uuid>,
+    },
+    TaskChanged {
+        task_id: Uuid,
+        deal_id: Option<Uuid>,
+        change: TaskChange,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskChange {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// What goes over `NOTIFY`; `origin` lets an instance skip its own events.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    origin: Uuid,
+    event: CrmEvent,
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    sender: broadcast::Sender<CrmEvent>,
+    origin: Uuid,
+    fanout: Option<Arc<DatabaseConnection>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl EventBus {
+    /// A bus local to this process, or one that also relays events through `fanout`.
+    pub fn new(fanout: Option<Arc<DatabaseConnection>>) -> Self {
+        let (sender, _) = broadcast::channel(BUFFER);
+        Self {
+            inner: Arc::new(Inner {
+                sender,
+                origin: Uuid::new_v4(),
+                fanout,
+            }),
+        }
+    }
+
+    /// Whether events also travel to other instances, so [`EventBus::listen`] should run.
+    pub fn fans_out(&self) -> bool {
+        self.inner.fanout.is_some()
+    }
+
+    /// Hands `event` to local subscribers and, with fan-out, to other instances. Delivery
+    /// is best effort: the change is already committed, so failures are only logged.
+    pub async fn publish(&self, event: CrmEvent) {
+        let _ = self.inner.sender.send(event.clone());
+        let Some(db) = &self.inner.fanout else {
+            return;
+        };
+        let envelope = Envelope {
+            origin: self.inner.origin,
+            event,
+        };
+        let payload = match serde_json::to_string(&envelope) {
+            Ok(payload) => payload,
+            Err(err) => {
+                tracing::warn!("crm event not encoded: {}", err);
+                return;
+            }
+        };
+        let notify = Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            "SELECT pg_notify($1, $2)",
+            vec![EVENT_CHANNEL.into(), payload.into()],
+        );
+        if let Err(err) = db.execute(notify).await {
+            tracing::warn!("crm event not relayed: {}", err);
+        }
+    }
+
+    /// Events published from now on. A subscriber that lags past the buffer misses the
+    /// oldest events rather than blocking publishers.
+    pub fn subscribe(&self) -> impl Stream<Item = CrmEvent> + Send + 'static {
+        stream::unfold(self.inner.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "crm event subscriber lagged");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Relays events other instances `NOTIFY` to local subscribers over a dedicated
+    /// connection to `database_url`. Runs until the process exits, reconnecting after
+    /// connection failures.
+    pub async fn listen(self, database_url: String) {
+        loop {
+            let mut listener = match PgListener::connect(&database_url).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    tracing::warn!("crm event listener cannot connect: {}", err);
+                    tokio::time::sleep(LISTEN_RETRY).await;
+                    continue;
+                }
+            };
+            if let Err(err) = listener.listen(EVENT_CHANNEL).await {
+                tracing::warn!("crm event listener cannot listen: {}", err);
+                tokio::time::sleep(LISTEN_RETRY).await;
+                continue;
+            }
+            loop {
+                let notification = match listener.recv().await {
+                    Ok(notification) => notification,
+                    Err(err) => {
+                        tracing::warn!("crm event listener dropped: {}", err);
+                        break;
+                    }
+                };
+                match serde_json::from_str::<Envelope>(notification.payload()) {
+                    Ok(envelope) if envelope.origin != self.inner.origin => {
+                        let _ = self.inner.sender.send(envelope.event);
+                    }
+                    Ok(_) => {}
+                    Err(err) => tracing::warn!("crm event not decoded: {}", err),
+                }
+            }
+            tokio::time::sleep(LISTEN_RETRY).await;
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Keys that sign and verify session, challenge and OIDC flow tokens. Each
+//! token names its key in the JWT `kid` header, so a key can be rotated out by
+//! keeping it as verify-only until the tokens it signed have expired.
+
+use anyhow::{anyhow, bail, Context as _};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// Public half as a JWK. Shared secrets have none and are never published.
+    jwk: Option<Value>,
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// HS256 over a shared secret. The `kid` is derived from the secret, so every
+    /// instance configured with it agrees on the id.
+    pub fn hmac(secret: &str) -> Self {
+        let digest = hex::encode(Sha256::digest(secret.as_bytes()));
+        Self {
+            kid: format!("hs256-{}", &digest[..16]),
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            jwk: None,
+        }
+    }
+
+    /// EdDSA or RS256 from an Ed25519 or RSA private key PEM (PKCS#8, or PKCS#1
+    /// for RSA). Without an explicit `kid` the key's RFC 7638 thumbprint is used.
+    pub fn from_private_pem(pem: &[u8], kid: Option<String>) -> anyhow::Result<Self> {
+        let block = pem::parse(pem).context("not a PEM private key")?;
+        let der = block.contents();
+        let (algorithm, encoding, public) = match block.tag() {
+            "PRIVATE KEY" => match Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+                Ok(pair) => {
+                    let x = URL_SAFE_NO_PAD.encode(pair.public_key().as_ref());
+                    let public = json!({ "crv": "Ed25519", "kty": "OKP", "x": x });
+                    (Algorithm::EdDSA, EncodingKey::from_ed_der(der), public)
+                }
+                Err(_) => {
+                    let pair = RsaKeyPair::from_pkcs8(der)
+                        .map_err(|err| anyhow!("unsupported private key: {}", err))?;
+                    (
+                        Algorithm::RS256,
+                        EncodingKey::from_rsa_pem(pem)?,
+                        rsa_jwk(&pair),
+                    )
+                }
+            },
+            "RSA PRIVATE KEY" => {
+                let pair = RsaKeyPair::from_der(der)
+                    .map_err(|err| anyhow!("invalid RSA private key: {}", err))?;
+                (
+                    Algorithm::RS256,
+                    EncodingKey::from_rsa_der(der),
+                    rsa_jwk(&pair),
+                )
+            }
+            other => bail!("unsupported PEM block {:?}", other),
+        };
+        let decoding = match algorithm {
+            Algorithm::EdDSA => DecodingKey::from_ed_components(str_field(&public, "x"))?,
+            _ => {
+                DecodingKey::from_rsa_components(str_field(&public, "n"), str_field(&public, "e"))?
+            }
+        };
+        // `public` holds exactly the RFC 7638 members, already in lexicographic order.
+        let kid = kid.unwrap_or_else(|| {
+            URL_SAFE_NO_PAD.encode(Sha256::digest(public.to_string().as_bytes()))
+        });
+        let mut jwk = public;
+        jwk["kid"] = json!(kid);
+        jwk["alg"] = json!(format!("{:?}", algorithm));
+        jwk["use"] = json!("sig");
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding,
+            decoding,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub(crate) fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding
+    }
+
+    pub(crate) fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding
+    }
+}
+
+fn rsa_jwk(pair: &RsaKeyPair) -> Value {
+    let components = PublicKeyComponents::<Vec<u8>>::from(pair.public());
+    json!({
+        "e": URL_SAFE_NO_PAD.encode(&components.e),
+        "kty": "RSA",
+        "n": URL_SAFE_NO_PAD.encode(&components.n),
+    })
+}
+
+fn str_field<'a>(value: &'a Value, field: &str) -> &'a str {
+    value[field].as_str().unwrap_or_default()
+}
+
+/// The active signing key plus any retired keys still accepted for verification.
+#[derive(Clone, Debug, Default)]
+pub struct Keyring {
+    signer: Option<Arc<SigningKey>>,
+    verifiers: Vec<Arc<SigningKey>>,
+}
+
+impl Keyring {
+    pub fn new(signer: SigningKey) -> Self {
+        Self {
+            signer: Some(Arc::new(signer)),
+            verifiers: Vec::new(),
+        }
+    }
+
+    /// Accepts tokens signed by `key` without ever signing with it.
+    pub fn with_verifier(mut self, key: SigningKey) -> Self {
+        if self.find(&key.kid).is_none() {
+            self.verifiers.push(Arc::new(key));
+        }
+        self
+    }
+
+    pub fn signer(&self) -> Option<&SigningKey> {
+        self.signer.as_deref()
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys().find(|key| key.kid == kid)
+    }
+
+    /// Every key, the signer first.
+    pub fn keys(&self) -> impl Iterator<Item = &SigningKey> {
+        self.signer
+            .iter()
+            .chain(self.verifiers.iter())
+            .map(|key| key.as_ref())
+    }
+
+    /// Public keys as a JWK Set, for services that verify our tokens themselves.
+    pub fn jwks(&self) -> Value {
+        let keys: Vec<&Value> = self.keys().filter_map(|key| key.jwk.as_ref()).collect();
+        json!({ "keys": keys })
+    }
+}
@@ -1,2 +1,16 @@
+pub mod api_key;
+pub mod audit;
 pub mod auth;
+pub mod auth_event;
+pub mod events;
+pub mod keyring;
+pub mod mailer;
+pub mod oidc;
+pub mod organization;
+pub mod outbox;
+pub mod password_reset;
 pub mod schema;
+pub mod session;
+pub mod settings;
+pub mod throttle;
+pub mod totp;
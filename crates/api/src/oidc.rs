@@ -0,0 +1,402 @@
+//! OpenID Connect login: authorization code flow with PKCE against one issuer.
+//! Provider metadata and signing keys are fetched lazily and cached; the
+//! per-login state, nonce and PKCE verifier travel in a signed cookie.
+
+use crate::auth::UserRole;
+use crate::organization::{add_member, find_organization, DEFAULT_ORGANIZATION_SLUG};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use entity::{app_user, user_identity};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use rand::RngCore;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    TransactionTrait,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use url::Url;
+use uuid::Uuid;
+
+/// Holds the signed `OidcFlow` between the redirect to the IdP and the callback.
+pub const OIDC_FLOW_COOKIE: &str = "sme_oidc";
+/// How long a user has to finish signing in at the IdP.
+pub const OIDC_FLOW_TTL_SECS: i64 = 600;
+const METADATA_TTL: Duration = Duration::from_secs(3600);
+const JWKS_TTL: Duration = Duration::from_secs(3600);
+/// An unknown `kid` refetches the key set at most this often.
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BODY: usize = 1 << 20;
+
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    /// Sent to the token endpoint when set; public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+    /// Stored as `user_identity.provider`.
+    pub provider: String,
+    /// Role given to users provisioned on first login; `None` turns away unknown emails.
+    pub jit_role: Option<UserRole>,
+    /// Slug of the organization provisioned users join.
+    pub jit_organization: String,
+    /// Where the browser lands once the session cookie is set.
+    pub post_login_redirect: String,
+}
+
+impl OidcConfig {
+    pub fn new(issuer: String, client_id: String, redirect_url: String) -> Self {
+        Self {
+            issuer,
+            client_id,
+            client_secret: None,
+            redirect_url,
+            scopes: "openid email profile".into(),
+            provider: "oidc".into(),
+            jit_role: None,
+            jit_organization: DEFAULT_ORGANIZATION_SLUG.into(),
+            post_login_redirect: "/".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// State for one login attempt, signed into `OIDC_FLOW_COOKIE`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OidcFlow {
+    pub state: String,
+    pub nonce: String,
+    pub verifier: String,
+    pub exp: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+struct Cached<T> {
+    value: Arc<T>,
+    fetched: Instant,
+}
+
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: RwLock<Option<Cached<ProviderMetadata>>>,
+    jwks: RwLock<Option<Cached<JwkSet>>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("HTTP client with the bundled TLS roots");
+        Self {
+            config,
+            http,
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    /// Starts a login: the IdP authorization URL to redirect to, and the flow
+    /// state the callback must present.
+    pub async fn begin(&self) -> anyhow::Result<(String, OidcFlow)> {
+        let metadata = self.metadata().await?;
+        let flow = OidcFlow {
+            state: random_token(),
+            nonce: random_token(),
+            verifier: random_token(),
+            exp: (Utc::now().timestamp() + OIDC_FLOW_TTL_SECS) as usize,
+        };
+        let mut url = Url::parse(&metadata.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", &flow.state)
+            .append_pair("nonce", &flow.nonce)
+            .append_pair("code_challenge", &pkce_challenge(&flow.verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok((url.to_string(), flow))
+    }
+
+    /// Redeems the authorization code and returns the verified ID token claims.
+    pub async fn finish(&self, flow: &OidcFlow, code: &str) -> anyhow::Result<IdTokenClaims> {
+        let metadata = self.metadata().await?;
+        let form = {
+            let mut form = url::form_urlencoded::Serializer::new(String::new());
+            form.append_pair("grant_type", "authorization_code")
+                .append_pair("code", code)
+                .append_pair("redirect_uri", &self.config.redirect_url)
+                .append_pair("client_id", &self.config.client_id)
+                .append_pair("code_verifier", &flow.verifier);
+            if let Some(secret) = &self.config.client_secret {
+                form.append_pair("client_secret", secret);
+            }
+            form.finish()
+        };
+        let response: TokenResponse =
+            fetch_json(&self.http, &metadata.token_endpoint, Some(form)).await?;
+        let id_token = response
+            .id_token
+            .ok_or_else(|| anyhow::anyhow!("token response has no id_token"))?;
+        let claims = self.verify_id_token(&metadata, &id_token).await?;
+        if claims.nonce.as_deref() != Some(flow.nonce.as_str()) {
+            anyhow::bail!("ID token nonce does not match");
+        }
+        Ok(claims)
+    }
+
+    async fn verify_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> anyhow::Result<IdTokenClaims> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+        let key = match header.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret = self.config.client_secret.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("HMAC-signed ID token without a client secret")
+                })?;
+                DecodingKey::from_secret(secret.as_bytes())
+            }
+            _ => {
+                let kid = header
+                    .kid
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("ID token header has no kid"))?;
+                let jwk = match self.jwks(metadata, false).await?.find(kid) {
+                    Some(jwk) => jwk.clone(),
+                    None => self
+                        .jwks(metadata, true)
+                        .await?
+                        .find(kid)
+                        .cloned()
+                        .ok_or_else(|| anyhow::anyhow!("no signing key for kid {}", kid))?,
+                };
+                DecodingKey::from_jwk(&jwk)?
+            }
+        };
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        Ok(jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)?.claims)
+    }
+
+    async fn metadata(&self) -> anyhow::Result<Arc<ProviderMetadata>> {
+        if let Some(cached) = fresh(&self.metadata, METADATA_TTL) {
+            return Ok(cached);
+        }
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = fetch_json(&self.http, &url, None).await?;
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            anyhow::bail!("discovery issuer {} does not match", metadata.issuer);
+        }
+        Ok(store(&self.metadata, metadata))
+    }
+
+    async fn jwks(
+        &self,
+        metadata: &ProviderMetadata,
+        refresh: bool,
+    ) -> anyhow::Result<Arc<JwkSet>> {
+        let max_age = if refresh { JWKS_MIN_REFRESH } else { JWKS_TTL };
+        if let Some(cached) = fresh(&self.jwks, max_age) {
+            return Ok(cached);
+        }
+        let jwks: JwkSet = fetch_json(&self.http, &metadata.jwks_uri, None).await?;
+        Ok(store(&self.jwks, jwks))
+    }
+}
+
+fn fresh<T>(slot: &RwLock<Option<Cached<T>>>, max_age: Duration) -> Option<Arc<T>> {
+    let guard = slot.read().ok()?;
+    let cached = guard.as_ref()?;
+    (cached.fetched.elapsed() < max_age).then(|| cached.value.clone())
+}
+
+fn store<T>(slot: &RwLock<Option<Cached<T>>>, value: T) -> Arc<T> {
+    let value = Arc::new(value);
+    if let Ok(mut guard) = slot.write() {
+        *guard = Some(Cached {
+            value: value.clone(),
+            fetched: Instant::now(),
+        });
+    }
+    value
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Maps verified claims to an account: a linked identity first, then an
+/// existing user with the same email (which gets linked), then just-in-time
+/// provisioning into `jit_organization` when `jit_role` is configured. The last
+/// two need `email_verified: true`.
+pub async fn resolve_oidc_user(
+    db: &DatabaseConnection,
+    config: &OidcConfig,
+    claims: &IdTokenClaims,
+) -> anyhow::Result<app_user::Model> {
+    let identity = user_identity::Entity::find()
+        .filter(user_identity::Column::Provider.eq(config.provider.as_str()))
+        .filter(user_identity::Column::Subject.eq(claims.sub.as_str()))
+        .one(db)
+        .await?;
+    let user = match identity {
+        Some(identity) => app_user::Entity::find_by_id(identity.user_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("linked user no longer exists"))?,
+        None => link_or_provision(db, config, claims).await?,
+    };
+    if !user.is_active {
+        anyhow::bail!("Account disabled");
+    }
+    Ok(user)
+}
+
+async fn link_or_provision(
+    db: &DatabaseConnection,
+    config: &OidcConfig,
+    claims: &IdTokenClaims,
+) -> anyhow::Result<app_user::Model> {
+    let email = claims
+        .email
+        .as_deref()
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| email.contains('@'))
+        .ok_or_else(|| anyhow::anyhow!("ID token has no email claim"))?;
+    // Matching on an email the IdP has not vouched for would let anyone who can set
+    // one take over the local account it names, so a missing claim counts as unverified.
+    if claims.email_verified != Some(true) {
+        anyhow::bail!("Email {} is not verified by the identity provider", email);
+    }
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let txn = db.begin().await?;
+    let existing = app_user::Entity::find()
+        .filter(app_user::Column::Email.eq(email.as_str()))
+        .one(&txn)
+        .await?;
+    let user = match (existing, config.jit_role) {
+        (Some(user), _) => user,
+        (None, Some(role)) => {
+            let display_name = claims
+                .name
+                .clone()
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| email.split('@').next().unwrap_or_default().to_string());
+            let user = app_user::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                email: Set(email.clone()),
+                display_name: Set(display_name),
+                avatar_url: Set(None),
+                is_active: Set(true),
+                created_at: Set(now),
+                updated_at: Set(now),
+            }
+            .insert(&txn)
+            .await?;
+            let organization = find_organization(&txn, &config.jit_organization)
+                .await?
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Organization {} does not exist", config.jit_organization)
+                })?;
+            add_member(&txn, organization.id, user.id, &[role]).await?;
+            user
+        }
+        (None, None) => anyhow::bail!("No account exists for {}", email),
+    };
+    user_identity::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user.id),
+        provider: Set(config.provider.clone()),
+        subject: Set(claims.sub.clone()),
+        created_at: Set(now),
+    }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+    Ok(user)
+}
+
+/// GETs (or, with a form body, POSTs) `url` and decodes a JSON response of at most
+/// `MAX_BODY` bytes. Redirects are not followed.
+async fn fetch_json<T: DeserializeOwned>(
+    http: &reqwest::Client,
+    url: &str,
+    form: Option<String>,
+) -> anyhow::Result<T> {
+    let request = match form {
+        Some(body) => http
+            .post(url)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body),
+        None => http.get(url),
+    };
+    let mut response = request.header(ACCEPT, "application/json").send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("identity provider returned HTTP {}", status.as_u16());
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_BODY as u64)
+    {
+        anyhow::bail!("response body too large");
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_BODY {
+            anyhow::bail!("response body too large");
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(serde_json::from_slice(&body)?)
+}
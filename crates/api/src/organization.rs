@@ -0,0 +1,189 @@
+//! Tenants sharing one deployment. Users are global; what they can see and do
+//! comes from their `organization_member` row and the roles granted with it.
+
+use crate::auth::UserRole;
+use chrono::Utc;
+use entity::{organization, organization_member, pipeline, stage_meta, user_role};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, JoinType,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+};
+use uuid::Uuid;
+
+/// Created by the migration that introduced organizations; it owns all
+/// earlier data and takes users provisioned through OIDC.
+pub const DEFAULT_ORGANIZATION_SLUG: &str = "default";
+pub const MAX_SLUG_LENGTH: usize = 64;
+
+pub const DEFAULT_PIPELINE_NAME: &str = "Sales";
+
+/// The stages every organization's default pipeline starts with: key, display name,
+/// sort order, probability, won, lost.
+pub const DEFAULT_STAGES: [(&str, &str, i16, i16, bool, bool); 6] = [
+    ("NEW", "New", 10, 10, false, false),
+    ("QUALIFY", "Qualify", 20, 25, false, false),
+    ("PROPOSAL", "Proposal", 30, 50, false, false),
+    ("NEGOTIATE", "Negotiate", 40, 70, false, false),
+    ("WON", "Won", 90, 100, true, false),
+    ("LOST", "Lost", 95, 0, false, true),
+];
+
+/// Lower-cases `slug` and checks it is made of `a-z`, `0-9` and inner dashes.
+pub fn normalize_slug(slug: &str) -> Option<String> {
+    let slug = slug.trim().to_lowercase();
+    let valid = !slug.is_empty()
+        && slug.len() <= MAX_SLUG_LENGTH
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    valid.then_some(slug)
+}
+
+/// Creates an organization with the default pipeline and its stages.
+pub async fn create_organization<C: ConnectionTrait>(
+    conn: &C,
+    name: &str,
+    slug: &str,
+) -> Result<organization::Model, DbErr> {
+    let org = organization::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        name: Set(name.to_string()),
+        slug: Set(slug.to_string()),
+        created_at: Set(Utc::now().into()),
+    }
+    .insert(conn)
+    .await?;
+    insert_default_pipeline(conn, org.id).await?;
+    Ok(org)
+}
+
+/// The organization's default pipeline, created with [`DEFAULT_STAGES`] if it has none.
+pub async fn insert_default_pipeline<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+) -> Result<pipeline::Model, DbErr> {
+    let existing = pipeline::Entity::find()
+        .filter(pipeline::Column::OrgId.eq(org_id))
+        .filter(pipeline::Column::IsDefault.eq(true))
+        .one(conn)
+        .await?;
+    if let Some(existing) = existing {
+        return Ok(existing);
+    }
+    let pipeline = pipeline::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
+        name: Set(DEFAULT_PIPELINE_NAME.to_string()),
+        is_default: Set(true),
+        created_at: Set(Utc::now().into()),
+    }
+    .insert(conn)
+    .await?;
+    let rows = DEFAULT_STAGES.iter().map(
+        |(key, display_name, sort_order, probability, is_won, is_lost)| stage_meta::ActiveModel {
+            org_id: Set(org_id),
+            key: Set((*key).to_string()),
+            id: Set(Uuid::new_v4()),
+            pipeline_id: Set(pipeline.id),
+            display_name: Set((*display_name).to_string()),
+            sort_order: Set(*sort_order),
+            probability: Set(*probability),
+            is_won: Set(*is_won),
+            is_lost: Set(*is_lost),
+            is_archived: Set(false),
+            allowed_from: Set(None),
+            required_fields: Set(serde_json::Value::Array(Vec::new())),
+            requires_note: Set(*is_lost),
+            wip_limit: Set(None),
+        },
+    );
+    stage_meta::Entity::insert_many(rows)
+        .exec_without_returning(conn)
+        .await?;
+    Ok(pipeline)
+}
+
+pub async fn find_organization<C: ConnectionTrait>(
+    conn: &C,
+    slug: &str,
+) -> Result<Option<organization::Model>, DbErr> {
+    organization::Entity::find()
+        .filter(organization::Column::Slug.eq(slug.trim().to_lowercase()))
+        .one(conn)
+        .await
+}
+
+/// Makes `user_id` an active member of `org_id` holding `roles`.
+pub async fn add_member<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+    roles: &[UserRole],
+) -> Result<(), DbErr> {
+    organization_member::ActiveModel {
+        org_id: Set(org_id),
+        user_id: Set(user_id),
+        is_active: Set(true),
+        created_at: Set(Utc::now().into()),
+    }
+    .insert(conn)
+    .await?;
+    for role in roles {
+        user_role::ActiveModel {
+            org_id: Set(org_id),
+            user_id: Set(user_id),
+            role: Set((*role).into()),
+        }
+        .insert(conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// The user's roles in `org_id`, or `None` unless they are an active member.
+pub async fn member_roles<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Vec<UserRole>>, DbErr> {
+    let member = organization_member::Entity::find_by_id((org_id, user_id))
+        .one(conn)
+        .await?;
+    if !member.is_some_and(|member| member.is_active) {
+        return Ok(None);
+    }
+    let rows = user_role::Entity::find()
+        .filter(user_role::Column::OrgId.eq(org_id))
+        .filter(user_role::Column::UserId.eq(user_id))
+        .all(conn)
+        .await?;
+    Ok(Some(rows.into_iter().map(|row| row.role.into()).collect()))
+}
+
+/// Organizations where `user_id` is an active member, oldest membership first.
+pub async fn memberships<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+) -> Result<Vec<organization::Model>, DbErr> {
+    organization::Entity::find()
+        .join(
+            JoinType::InnerJoin,
+            organization::Relation::OrganizationMember.def(),
+        )
+        .filter(organization_member::Column::UserId.eq(user_id))
+        .filter(organization_member::Column::IsActive.eq(true))
+        .order_by_asc(organization_member::Column::CreatedAt)
+        .order_by_asc(organization::Column::Slug)
+        .all(conn)
+        .await
+}
+
+/// Where a login that names no organization lands.
+pub async fn default_organization<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+) -> Result<Option<organization::Model>, DbErr> {
+    Ok(memberships(conn, user_id).await?.into_iter().next())
+}
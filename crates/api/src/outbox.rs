@@ -0,0 +1,132 @@
+//! Transactional email outbox. Mail is queued in `email_outbox` in the same
+//! transaction as the change that causes it and sent later by [`deliver_due`],
+//! so a rolled-back change never sends and an SMTP outage never fails a request.
+
+use crate::mailer::Mailer;
+use chrono::{Duration, Utc};
+use entity::email_outbox;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ConnectionTrait, DatabaseBackend, DbErr, FromQueryResult,
+    Statement,
+};
+use uuid::Uuid;
+
+/// Messages that failed this many times stay in the table but are not retried.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+/// A claimed message is hidden from other workers for this long.
+const CLAIM_LEASE_SECS: i64 = 5 * 60;
+const RETRY_BASE_SECS: i64 = 60;
+const RETRY_CAP_SECS: i64 = 60 * 60;
+const MAX_ERROR: usize = 1000;
+
+pub async fn enqueue_email<C: ConnectionTrait>(
+    conn: &C,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<Uuid, DbErr> {
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let model = email_outbox::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        to_address: Set(to.to_string()),
+        subject: Set(subject.to_string()),
+        body_text: Set(body.to_string()),
+        created_at: Set(now),
+        attempts: Set(0),
+        next_attempt_at: Set(now),
+        last_error: Set(None),
+        sent_at: Set(None),
+    }
+    .insert(conn)
+    .await?;
+    Ok(model.id)
+}
+
+/// Takes up to `limit` due messages and counts the attempt. Claimed rows are
+/// pushed a lease into the future, so concurrent workers skip them.
+pub async fn claim_due<C: ConnectionTrait>(
+    conn: &C,
+    limit: u64,
+) -> Result<Vec<email_outbox::Model>, DbErr> {
+    let now = Utc::now();
+    let lease_until = now + Duration::seconds(CLAIM_LEASE_SECS);
+    email_outbox::Model::find_by_statement(Statement::from_sql_and_values(
+        DatabaseBackend::Postgres,
+        r#"
+        UPDATE email_outbox
+        SET attempts = attempts + 1, next_attempt_at = $1
+        WHERE id IN (
+            SELECT id FROM email_outbox
+            WHERE sent_at IS NULL AND attempts < $2 AND next_attempt_at <= $3
+            ORDER BY next_attempt_at
+            LIMIT $4
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+        [
+            DateTimeWithTimeZone::from(lease_until).into(),
+            MAX_DELIVERY_ATTEMPTS.into(),
+            DateTimeWithTimeZone::from(now).into(),
+            (limit as i64).into(),
+        ],
+    ))
+    .all(conn)
+    .await
+}
+
+pub async fn mark_sent<C: ConnectionTrait>(conn: &C, id: Uuid) -> Result<(), DbErr> {
+    email_outbox::ActiveModel {
+        id: Set(id),
+        sent_at: Set(Some(Utc::now().into())),
+        last_error: Set(None),
+        ..Default::default()
+    }
+    .update(conn)
+    .await?;
+    Ok(())
+}
+
+/// Records a failed attempt and schedules the next one, doubling the wait
+/// each time.
+pub async fn mark_failed<C: ConnectionTrait>(
+    conn: &C,
+    message: &email_outbox::Model,
+    error: &str,
+) -> Result<(), DbErr> {
+    let doublings = (message.attempts - 1).clamp(0, 16) as u32;
+    let delay = (RETRY_BASE_SECS << doublings).min(RETRY_CAP_SECS);
+    email_outbox::ActiveModel {
+        id: Set(message.id),
+        next_attempt_at: Set((Utc::now() + Duration::seconds(delay)).into()),
+        last_error: Set(Some(error.chars().take(MAX_ERROR).collect())),
+        ..Default::default()
+    }
+    .update(conn)
+    .await?;
+    Ok(())
+}
+
+/// Sends every due message through `mailer`, at most `limit` of them, and
+/// returns how many went out.
+pub async fn deliver_due<C: ConnectionTrait>(
+    conn: &C,
+    mailer: &Mailer,
+    limit: u64,
+) -> Result<usize, DbErr> {
+    let mut sent = 0;
+    for message in claim_due(conn, limit).await? {
+        match mailer.send(&message).await {
+            Ok(()) => {
+                mark_sent(conn, message.id).await?;
+                sent += 1;
+            }
+            Err(err) => {
+                tracing::warn!(email_id = %message.id, attempt = message.attempts, "email delivery failed: {:#}", err);
+                mark_failed(conn, &message, &format!("{:#}", err)).await?;
+            }
+        }
+    }
+    Ok(sent)
+}
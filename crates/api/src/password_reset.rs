@@ -0,0 +1,82 @@
+//! Single-use password reset links. As with API keys, the token only ever
+//! leaves in the email and just its SHA-256 is stored.
+
+use chrono::{Duration, Utc};
+use entity::password_reset_token;
+use rand::RngCore;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    PaginatorTrait, QueryFilter,
+};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub const RESET_TOKEN_TTL_MINUTES: i64 = 60;
+/// No new link is issued while the previous one is younger than this, so the
+/// form cannot be used to flood an inbox.
+const REISSUE_COOLDOWN_SECS: i64 = 60;
+
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Creates a reset token for `user_id`, or `None` while an earlier one is
+/// still inside the cooldown.
+pub async fn issue_reset_token<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+) -> Result<Option<String>, DbErr> {
+    let now = Utc::now();
+    let cooldown_start: DateTimeWithTimeZone =
+        (now - Duration::seconds(REISSUE_COOLDOWN_SECS)).into();
+    let recent = password_reset_token::Entity::find()
+        .filter(password_reset_token::Column::UserId.eq(user_id))
+        .filter(password_reset_token::Column::CreatedAt.gt(cooldown_start))
+        .count(conn)
+        .await?;
+    if recent > 0 {
+        return Ok(None);
+    }
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    password_reset_token::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        token_hash: Set(hash_reset_token(&token)),
+        created_at: Set(now.into()),
+        expires_at: Set((now + Duration::minutes(RESET_TOKEN_TTL_MINUTES)).into()),
+        used_at: Set(None),
+    }
+    .insert(conn)
+    .await?;
+    Ok(Some(token))
+}
+
+/// Spends a live token and returns whose it was. Any other outstanding link
+/// for that user is retired at the same time.
+pub async fn redeem_reset_token<C: ConnectionTrait>(
+    conn: &C,
+    token: &str,
+) -> Result<Option<Uuid>, DbErr> {
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let redeemed = password_reset_token::Entity::update_many()
+        .col_expr(password_reset_token::Column::UsedAt, Expr::value(now))
+        .filter(password_reset_token::Column::TokenHash.eq(hash_reset_token(token.trim())))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .filter(password_reset_token::Column::ExpiresAt.gt(now))
+        .exec_with_returning(conn)
+        .await?;
+    let Some(user_id) = redeemed.first().map(|row| row.user_id) else {
+        return Ok(None);
+    };
+    password_reset_token::Entity::update_many()
+        .col_expr(password_reset_token::Column::UsedAt, Expr::value(now))
+        .filter(password_reset_token::Column::UserId.eq(user_id))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .exec(conn)
+        .await?;
+    Ok(Some(user_id))
+}
@@ -1,26 +1,53 @@
+use crate::api_key::{format_scopes, generate_api_key, parse_scopes};
+use crate::audit::{diff, record_audit, record_change, snapshot, AuditActor, REDACTED};
 use crate::auth::{
-    build_session_cookie, clear_session_cookie, issue_session_token, AuthConfig, AuthMode,
-    CurrentUser, UserRole,
+    build_session_cookie, clear_session_cookie, decode_challenge_token, issue_challenge_token,
+    issue_session_token, ApiScope, AuthConfig, AuthMode, ChallengePurpose, ClientInfo,
+    CurrentSession, CurrentUser, PasswordPolicy, UserRole, MAX_PASSWORD_LENGTH,
+};
+use crate::auth_event::record_auth_event;
+use crate::events::{CrmEvent, CrmEventKind, EventBus, TaskChange};
+use crate::organization::{
+    add_member, default_organization, find_organization, insert_default_pipeline, member_roles,
+    memberships, DEFAULT_ORGANIZATION_SLUG,
+};
+use crate::outbox::enqueue_email;
+use crate::password_reset::{issue_reset_token, redeem_reset_token, RESET_TOKEN_TTL_MINUTES};
+use crate::session::{
+    live_sessions, revoke_member_sessions, revoke_session, revoke_user_sessions, start_session,
+};
+use crate::settings::{get_flag, set_flag, REQUIRE_ADMIN_TWO_FACTOR};
+use crate::throttle::{clear_failures, locked_until, login_keys, record_failure, ThrottleKey};
+use crate::totp::{
+    self, clear_totp, confirm_totp, generate_totp_secret, otpauth_uri, pending_second_factor,
+    replace_recovery_codes, stage_totp_secret, unused_recovery_codes, verify_second_factor,
 };
 use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use argon2::Argon2;
+use async_graphql::connection::{Connection, CursorType, Edge, OpaqueCursor};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::futures_util::{future, Stream, StreamExt};
 use async_graphql::{
-    Context, EmptySubscription, Enum, Error, ErrorExtensions, InputObject, Json, Object, Schema,
-    SimpleObject, ID,
+    ComplexObject, Context, Enum, Error, ErrorExtensions, Guard, InputObject, InputValueError,
+    InputValueResult, Json, MaybeUndefined, Object, OutputType, Scalar, ScalarType, Schema,
+    SimpleObject, Subscription, ID,
 };
 use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
 use entity::{
-    activity, app_user, company, contact, deal, deal_stage_history, stage_meta, task,
-    user_identity, user_role, user_secret,
+    activity, api_key, app_user, audit_log, auth_event, company, contact, deal, deal_reason,
+    deal_stage_history, organization, organization_member, pipeline, stage_meta, task,
+    user_identity, user_role, user_secret, user_session,
 };
 use rand_core::OsRng;
 use sea_orm::prelude::DateTimeWithTimeZone;
 use sea_orm::sea_query::{Expr, Func, OnConflict, SimpleExpr};
 use sea_orm::{
     ActiveModelTrait, ActiveValue::Set, ColumnTrait, Condition, ConnectionTrait, DatabaseBackend,
-    DatabaseConnection, DbErr, EntityTrait, FromQueryResult, Order, QueryFilter, QueryOrder,
-    QuerySelect, Select, Statement, TransactionTrait, Value,
+    DatabaseConnection, DbErr, EntityTrait, FromQueryResult, IntoSimpleExpr, JoinType, Order,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, RelationTrait, Select,
+    SqlErr, Statement, TransactionTrait, Value,
 };
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::{
     collections::{HashMap, HashSet},
@@ -29,21 +56,35 @@ use std::{
 use tracing::info_span;
 use uuid::Uuid;
 
-pub struct AppSchema(pub Schema<QueryRoot, MutationRoot, EmptySubscription>);
+pub struct AppSchema(pub Schema<QueryRoot, MutationRoot, SubscriptionRoot>);
 
 pub fn build_schema(db: Arc<DatabaseConnection>, auth: Arc<AuthConfig>) -> AppSchema {
-    let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+    build_schema_with_events(db, auth, EventBus::default())
+}
+
+/// Like [`build_schema`], with subscriptions fed from `events` instead of a private bus.
+pub fn build_schema_with_events(
+    db: Arc<DatabaseConnection>,
+    auth: Arc<AuthConfig>,
+    events: EventBus,
+) -> AppSchema {
+    let loader = DataLoader::new(CrmLoader { db: db.clone() }, tokio::spawn);
+    let schema = Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
         .data(db)
         .data(auth)
+        .data(loader)
+        .data(events)
         .finish();
     AppSchema(schema)
 }
 
 pub struct QueryRoot;
 pub struct MutationRoot;
+pub struct SubscriptionRoot;
 
 const MAX_TASKS_PAGE: i32 = 100;
 const MAX_SEARCH_PAGE: i32 = 50;
+const MAX_RECORDS_PAGE: i32 = 100;
 
 #[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
 pub enum CrmSearchKind {
@@ -81,6 +122,168 @@ impl MutationRoot {
     }
 }
 
+/// Live changes in the caller's organization, limited to their book like the queries.
+/// Each event is delivered with the record as it is when the subscriber loads it.
+#[Subscription]
+impl SubscriptionRoot {
+    /// Deals created, edited, reassigned or moved; only deal `id` when given.
+    #[graphql(name = "dealUpdated", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn deal_updated(
+        &self,
+        ctx: &Context<'_>,
+        id: Option<ID>,
+    ) -> async_graphql::Result<impl Stream<Item = DealNode>> {
+        let only = parse_optional_id("id", &id)?;
+        let db = database(ctx)?;
+        let visibility = visibility(ctx)?;
+        Ok(crm_events(ctx, visibility)?.filter_map(move |event| {
+            let db = db.clone();
+            async move {
+                let CrmEventKind::DealUpdated { deal_id } = event.kind else {
+                    return None;
+                };
+                if only.is_some_and(|id| id != deal_id) {
+                    return None;
+                }
+                let deal = load_visible::<deal::Entity>(db.as_ref(), visibility, deal_id).await?;
+                Some(DealNode::from(deal))
+            }
+        }))
+    }
+
+    /// Deals moved to another stage, as on the pipeline board; only deal `dealId`'s when
+    /// given.
+    #[graphql(name = "dealStageMoved", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn deal_stage_moved(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "dealId")] deal_id: Option<ID>,
+    ) -> async_graphql::Result<impl Stream<Item = DealStageMove>> {
+        let only = parse_optional_id("dealId", &deal_id)?;
+        let db = database(ctx)?;
+        let visibility = visibility(ctx)?;
+        Ok(crm_events(ctx, visibility)?.filter_map(move |event| {
+            let db = db.clone();
+            async move {
+                let CrmEventKind::DealStageMoved {
+                    deal_id,
+                    from,
+                    to,
+                    changed_by,
+                } = event.kind
+                else {
+                    return None;
+                };
+                if only.is_some_and(|id| id != deal_id) {
+                    return None;
+                }
+                let deal = load_visible::<deal::Entity>(db.as_ref(), visibility, deal_id).await?;
+                Some(DealStageMove {
+                    from_stage: DealStage(from),
+                    to_stage: DealStage(to),
+                    changed_by: changed_by.map(|id| ID::from(id.to_string())),
+                    deal: DealNode::from(deal),
+                })
+            }
+        }))
+    }
+
+    /// Tasks created, edited, completed, reassigned or deleted; only those on deal
+    /// `dealId` when given.
+    #[graphql(name = "taskChanged", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn task_changed(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "dealId")] deal_id: Option<ID>,
+    ) -> async_graphql::Result<impl Stream<Item = TaskChangedEvent>> {
+        let only = parse_optional_id("dealId", &deal_id)?;
+        let db = database(ctx)?;
+        let visibility = visibility(ctx)?;
+        Ok(crm_events(ctx, visibility)?.filter_map(move |event| {
+            let db = db.clone();
+            async move {
+                let CrmEventKind::TaskChanged {
+                    task_id,
+                    deal_id,
+                    change,
+                } = event.kind
+                else {
+                    return None;
+                };
+                if only.is_some() && only != deal_id {
+                    return None;
+                }
+                let task = match change {
+                    TaskChange::Deleted => None,
+                    _ => Some(
+                        load_visible::<task::Entity>(db.as_ref(), visibility, task_id)
+                            .await?
+                            .into(),
+                    ),
+                };
+                Some(TaskChangedEvent {
+                    change: change.into(),
+                    task_id: ID::from(task_id.to_string()),
+                    task,
+                })
+            }
+        }))
+    }
+}
+
+/// The bus's events this caller may see.
+fn crm_events(
+    ctx: &Context<'_>,
+    visibility: Visibility,
+) -> async_graphql::Result<impl Stream<Item = CrmEvent>> {
+    let events = ctx.data::<EventBus>()?;
+    Ok(events
+        .subscribe()
+        .filter(move |event| future::ready(visibility.admits(event))))
+}
+
+/// The record behind an event, unless it has since left the caller's view.
+async fn load_visible<E: OwnedRecord>(
+    db: &DatabaseConnection,
+    visibility: Visibility,
+    id: Uuid,
+) -> Option<E::Model> {
+    E::find()
+        .filter(E::id_column().eq(id))
+        .filter(visibility.scope::<E>())
+        .one(db)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Tells subscribers about a change that has been committed.
+async fn publish(ctx: &Context<'_>, event: CrmEvent) {
+    if let Ok(events) = ctx.data::<EventBus>() {
+        events.publish(event).await;
+    }
+}
+
+fn deal_updated_event(deal: &deal::Model) -> CrmEvent {
+    CrmEvent {
+        org_id: deal.org_id,
+        assigned_user_id: deal.assigned_user_id,
+        kind: CrmEventKind::DealUpdated { deal_id: deal.id },
+    }
+}
+
+fn task_changed_event(task: &task::Model, change: TaskChange) -> CrmEvent {
+    CrmEvent {
+        org_id: task.org_id,
+        assigned_user_id: task.assigned_user_id,
+        kind: CrmEventKind::TaskChanged {
+            task_id: task.id,
+            deal_id: task.deal_id,
+            change,
+        },
+    }
+}
+
 #[derive(Default)]
 pub struct CrmQuery;
 
@@ -92,23 +295,222 @@ impl CrmQuery {
     async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
         let db = database(ctx)?;
         if let Ok(viewer) = ctx.data::<CurrentUser>() {
-            let (model, roles) = load_user_with_roles(db.as_ref(), viewer.user_id).await?;
+            let (model, roles) =
+                load_user_with_roles(db.as_ref(), viewer.org_id, viewer.user_id).await?;
             return Ok(Some(UserNode::from_model(model, roles)));
         }
         Ok(None)
     }
 
+    /// Organizations the caller can sign in to; `current` is the one this session is in.
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn organizations(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<OrganizationNode>> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let orgs = memberships(db.as_ref(), current.user_id)
+            .await
+            .map_err(db_error)?;
+        Ok(orgs
+            .into_iter()
+            .map(|org| OrganizationNode {
+                current: org.id == current.org_id,
+                id: ID::from(org.id.to_string()),
+                name: org.name,
+                slug: org.slug,
+            })
+            .collect())
+    }
+
+    /// The caller's keys, or `userId`'s when the caller is an owner.
+    #[graphql(name = "apiKeys", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn api_keys(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "userId")] user_id: Option<ID>,
+    ) -> async_graphql::Result<Vec<ApiKeyNode>> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let user_id = api_key_holder(&current, parse_optional_id("userId", &user_id)?)?;
+        let keys = api_key::Entity::find()
+            .filter(api_key::Column::OrgId.eq(current.org_id))
+            .filter(api_key::Column::UserId.eq(user_id))
+            .order_by_desc(api_key::Column::CreatedAt)
+            .all(db.as_ref())
+            .await
+            .map_err(db_error)?;
+        Ok(keys.into_iter().map(ApiKeyNode::from).collect())
+    }
+
+    #[graphql(name = "mySessions", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn my_sessions(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<SessionNode>> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let auth = auth_config(ctx)?;
+        let this_session = ctx.data_opt::<CurrentSession>().map(|s| s.id);
+        let sessions = live_sessions(db.as_ref(), current.user_id, &auth)
+            .await
+            .map_err(db_error)?;
+        Ok(sessions
+            .into_iter()
+            .map(|model| SessionNode::from_model(model, this_session))
+            .collect())
+    }
+
+    #[graphql(name = "twoFactor", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn two_factor(&self, ctx: &Context<'_>) -> async_graphql::Result<TwoFactorStatus> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let secret = user_secret::Entity::find_by_id(current.user_id)
+            .one(db.as_ref())
+            .await
+            .map_err(db_error)?;
+        let enabled = secret.is_some_and(|secret| secret.totp_enabled_at.is_some());
+        let recovery_codes_remaining = match enabled {
+            true => unused_recovery_codes(db.as_ref(), current.user_id)
+                .await
+                .map_err(db_error)? as i32,
+            false => 0,
+        };
+        Ok(TwoFactorStatus {
+            enabled,
+            required: two_factor_required(db.as_ref(), current.org_id, &current.roles).await?,
+            recovery_codes_remaining,
+        })
+    }
+
+    #[graphql(name = "twoFactorPolicy", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn two_factor_policy(&self, ctx: &Context<'_>) -> async_graphql::Result<TwoFactorPolicy> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let require_for_admins = get_flag(db.as_ref(), current.org_id, REQUIRE_ADMIN_TWO_FACTOR)
+            .await
+            .map_err(db_error)?;
+        Ok(TwoFactorPolicy { require_for_admins })
+    }
+
+    /// Sign-in activity, newest first.
+    #[graphql(name = "authEvents", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn auth_events(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        #[graphql(name = "userId")] user_id: Option<ID>,
+        kinds: Option<Vec<AuthEventKind>>,
+    ) -> async_graphql::Result<CrmConnection<AuthEventNode>> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let limit = first.unwrap_or(50).clamp(1, 200) as u64;
+        // Events belong to accounts, which may sit in several organizations; admins see
+        // those of their own members only. Attempts against a locked account carry just
+        // the email, and attempts on unknown emails belong to no organization at all.
+        let members = organization_member::Entity::find()
+            .select_only()
+            .column(organization_member::Column::UserId)
+            .filter(organization_member::Column::OrgId.eq(current.org_id))
+            .into_query();
+        let member_emails = app_user::Entity::find()
+            .select_only()
+            .column(app_user::Column::Email)
+            .filter(app_user::Column::Id.in_subquery(members.clone()))
+            .into_query();
+        let mut query = auth_event::Entity::find().filter(
+            Condition::any()
+                .add(auth_event::Column::UserId.in_subquery(members))
+                .add(
+                    Condition::all()
+                        .add(auth_event::Column::UserId.is_null())
+                        .add(auth_event::Column::Email.in_subquery(member_emails)),
+                ),
+        );
+        if let Some(user_id) = parse_optional_id("userId", &user_id)? {
+            query = query.filter(auth_event::Column::UserId.eq(user_id));
+        }
+        if let Some(kinds) = kinds.filter(|kinds| !kinds.is_empty()) {
+            query = query.filter(
+                auth_event::Column::Kind.is_in(kinds.into_iter().map(auth_event::Kind::from)),
+            );
+        }
+        let keys = [
+            SortKey::desc(auth_event::Column::CreatedAt, |e: &auth_event::Model| {
+                CursorValue::time(e.created_at)
+            }),
+            SortKey::asc(auth_event::Column::Id, |e: &auth_event::Model| {
+                CursorValue::Id(e.id)
+            }),
+        ];
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(AuthEventNode::from))
+    }
+
+    /// Changes made through the API, newest first. `range` covers whole UTC days.
+    #[graphql(name = "auditLog", guard = "RoleGuard::new(UserRole::Admin)")]
+    #[allow(clippy::too_many_arguments)]
+    async fn audit_log(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        #[graphql(name = "entityType")] entity_type: Option<AuditEntityType>,
+        #[graphql(name = "entityId")] entity_id: Option<ID>,
+        actor: Option<ID>,
+        range: Option<DateRange>,
+    ) -> async_graphql::Result<CrmConnection<AuditEntryNode>> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let limit = first.unwrap_or(50).clamp(1, 200) as u64;
+        let mut query =
+            audit_log::Entity::find().filter(audit_log::Column::OrgId.eq(current.org_id));
+        if let Some(entity_type) = entity_type {
+            query = query
+                .filter(audit_log::Column::EntityType.eq(audit_log::EntityType::from(entity_type)));
+        }
+        if let Some(entity_id) = parse_optional_id("entityId", &entity_id)? {
+            query = query.filter(audit_log::Column::EntityId.eq(entity_id));
+        }
+        if let Some(actor) = parse_optional_id("actor", &actor)? {
+            query = query.filter(audit_log::Column::ActorId.eq(actor));
+        }
+        if let Some(range) = range {
+            if range.from > range.to {
+                return Err(validation_error("range.from must be on or before range.to"));
+            }
+            let start = range.from.and_time(chrono::NaiveTime::MIN).and_utc();
+            let end =
+                range.to.and_time(chrono::NaiveTime::MIN).and_utc() + chrono::Duration::days(1);
+            query = query
+                .filter(audit_log::Column::CreatedAt.gte(start))
+                .filter(audit_log::Column::CreatedAt.lt(end));
+        }
+        let keys = [
+            SortKey::desc(audit_log::Column::CreatedAt, |e: &audit_log::Model| {
+                CursorValue::time(e.created_at)
+            }),
+            SortKey::asc(audit_log::Column::Id, |e: &audit_log::Model| {
+                CursorValue::Id(e.id)
+            }),
+        ];
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(AuditEntryNode::from))
+    }
+
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn users(
         &self,
         ctx: &Context<'_>,
         first: Option<i32>,
-        offset: Option<i32>,
+        after: Option<String>,
         q: Option<String>,
-    ) -> async_graphql::Result<Vec<UserNode>> {
+    ) -> async_graphql::Result<CrmConnection<UserNode>> {
+        let current = current_user(ctx)?;
         let db = database(ctx)?;
         let limit = first.unwrap_or(50).clamp(1, 200) as u64;
-        let skip = offset.unwrap_or(0).max(0) as u64;
-        let mut query = app_user::Entity::find();
+        let mut query = app_user::Entity::find()
+            .join(JoinType::InnerJoin, app_user::Relation::Membership.def())
+            .filter(organization_member::Column::OrgId.eq(current.org_id));
         if let Some(filter) = sanitize_optional_filter(q) {
             let pattern = format!("%{}%", filter);
             query = query.filter(
@@ -117,40 +519,68 @@ impl CrmQuery {
                     .add(app_user::Column::DisplayName.like(pattern)),
             );
         }
-        let records = query
-            .order_by_asc(app_user::Column::Email)
-            .limit(limit)
-            .offset(skip)
-            .all(db.as_ref())
-            .await
-            .map_err(db_error)?;
-        let role_map = load_roles_for_users(db.as_ref(), &records).await?;
-        Ok(records
-            .into_iter()
-            .map(|model| {
-                let roles = role_map.get(&model.id).cloned().unwrap_or_default();
-                UserNode::from_model(model, roles)
-            })
-            .collect())
-    }
-
+        let keys = [
+            SortKey::asc(app_user::Column::Email, |u: &app_user::Model| {
+                CursorValue::Text(u.email.clone())
+            }),
+            SortKey::asc(app_user::Column::Id, |u: &app_user::Model| {
+                CursorValue::Id(u.id)
+            }),
+        ];
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        let ids: Vec<Uuid> = page.models().map(|model| model.id).collect();
+        let mut members = load_memberships(db.as_ref(), current.org_id, &ids).await?;
+        Ok(page.into_connection(|model| {
+            let membership = members.remove(&model.id).unwrap_or_default();
+            UserNode::from_member(model, membership)
+        }))
+    }
+
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn search(
         &self,
         ctx: &Context<'_>,
         q: String,
         kinds: Option<Vec<CrmSearchKind>>,
         first: Option<i32>,
-        offset: Option<i32>,
-    ) -> async_graphql::Result<Vec<SearchHit>> {
+        after: Option<String>,
+    ) -> async_graphql::Result<CrmConnection<SearchHit>> {
         let db = database(ctx)?;
         let trimmed = validate_search_query(&q)?;
         let requested = first.unwrap_or(20);
         let limit = enforce_search_limit(requested, MAX_SEARCH_PAGE)?;
-        let skip = offset.unwrap_or(0).max(0) as u64;
+        let after = match decode_cursor(after.as_deref())? {
+            Some(values) => Some(SearchKey::decode(&values).ok_or_else(invalid_cursor)?),
+            None => None,
+        };
         let selected_kinds = kinds.unwrap_or_else(default_search_kinds);
-        search_hits(db.as_ref(), trimmed, &selected_kinds, limit, skip).await
+        let visibility = visibility(ctx)?;
+        let page = search_hits(
+            db.as_ref(),
+            trimmed,
+            &selected_kinds,
+            visibility,
+            limit,
+            after.as_ref(),
+        )
+        .await?;
+        let total_count =
+            count_search_hits(db.as_ref(), page.mode, trimmed, &selected_kinds, visibility).await?;
+        let mut connection = Connection::with_additional_fields(
+            after.is_some(),
+            page.has_next,
+            ConnectionTotals { total_count },
+        );
+        for row in page.rows {
+            let cursor = SearchKey::from_row(page.mode, &row).encode();
+            connection
+                .edges
+                .push(Edge::new(cursor, SearchHit::try_from(row)?));
+        }
+        Ok(connection)
     }
 
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn suggest_companies(
         &self,
         ctx: &Context<'_>,
@@ -161,16 +591,22 @@ impl CrmQuery {
         let trimmed = validate_search_query(&q)?;
         let requested = first.unwrap_or(10);
         let limit = enforce_search_limit(requested, MAX_SEARCH_PAGE)?;
-        let hits = search_hits(db.as_ref(), trimmed, &[CrmSearchKind::Company], limit, 0).await?;
-        let ids: Vec<Uuid> = hits
-            .iter()
-            .filter_map(|hit| Uuid::parse_str(hit.id.as_str()).ok())
-            .collect();
+        let hits = search_hits(
+            db.as_ref(),
+            trimmed,
+            &[CrmSearchKind::Company],
+            visibility(ctx)?,
+            limit,
+            None,
+        )
+        .await?;
+        let ids: Vec<Uuid> = hits.rows.iter().map(|hit| hit.id).collect();
         if ids.is_empty() {
             return Ok(vec![]);
         }
         let records = company::Entity::find()
             .filter(company::Column::Id.is_in(ids.clone()))
+            .filter(visibility(ctx)?.scope::<company::Entity>())
             .all(db.as_ref())
             .await
             .map_err(db_error)?;
@@ -180,6 +616,7 @@ impl CrmQuery {
             .collect())
     }
 
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn suggest_contacts(
         &self,
         ctx: &Context<'_>,
@@ -190,16 +627,22 @@ impl CrmQuery {
         let trimmed = validate_search_query(&q)?;
         let requested = first.unwrap_or(10);
         let limit = enforce_search_limit(requested, MAX_SEARCH_PAGE)?;
-        let hits = search_hits(db.as_ref(), trimmed, &[CrmSearchKind::Contact], limit, 0).await?;
-        let ids: Vec<Uuid> = hits
-            .iter()
-            .filter_map(|hit| Uuid::parse_str(hit.id.as_str()).ok())
-            .collect();
+        let hits = search_hits(
+            db.as_ref(),
+            trimmed,
+            &[CrmSearchKind::Contact],
+            visibility(ctx)?,
+            limit,
+            None,
+        )
+        .await?;
+        let ids: Vec<Uuid> = hits.rows.iter().map(|hit| hit.id).collect();
         if ids.is_empty() {
             return Ok(vec![]);
         }
         let records = contact::Entity::find()
             .filter(contact::Column::Id.is_in(ids.clone()))
+            .filter(visibility(ctx)?.scope::<contact::Entity>())
             .all(db.as_ref())
             .await
             .map_err(db_error)?;
@@ -209,6 +652,7 @@ impl CrmQuery {
             .collect())
     }
 
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn suggest_deals(
         &self,
         ctx: &Context<'_>,
@@ -219,16 +663,22 @@ impl CrmQuery {
         let trimmed = validate_search_query(&q)?;
         let requested = first.unwrap_or(10);
         let limit = enforce_search_limit(requested, MAX_SEARCH_PAGE)?;
-        let hits = search_hits(db.as_ref(), trimmed, &[CrmSearchKind::Deal], limit, 0).await?;
-        let ids: Vec<Uuid> = hits
-            .iter()
-            .filter_map(|hit| Uuid::parse_str(hit.id.as_str()).ok())
-            .collect();
+        let hits = search_hits(
+            db.as_ref(),
+            trimmed,
+            &[CrmSearchKind::Deal],
+            visibility(ctx)?,
+            limit,
+            None,
+        )
+        .await?;
+        let ids: Vec<Uuid> = hits.rows.iter().map(|hit| hit.id).collect();
         if ids.is_empty() {
             return Ok(vec![]);
         }
         let records = deal::Entity::find()
             .filter(deal::Column::Id.is_in(ids.clone()))
+            .filter(visibility(ctx)?.scope::<deal::Entity>())
             .all(db.as_ref())
             .await
             .map_err(db_error)?;
@@ -238,70 +688,75 @@ impl CrmQuery {
             .collect())
     }
 
-    #[graphql(name = "dealStageHistory")]
+    #[graphql(name = "dealStageHistory", guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn deal_stage_history(
         &self,
         ctx: &Context<'_>,
         deal_id: ID,
         first: Option<i32>,
-        offset: Option<i32>,
-    ) -> async_graphql::Result<Vec<DealStageHistoryNode>> {
+        after: Option<String>,
+    ) -> async_graphql::Result<CrmConnection<DealStageHistoryNode>> {
         let db = database(ctx)?;
         let deal_uuid = parse_uuid(&deal_id)?;
         let limit = first.unwrap_or(50).clamp(1, 200) as u64;
-        let skip = offset.unwrap_or(0).max(0) as u64;
 
-        let rows = deal_stage_history::Entity::find()
+        let query = deal_stage_history::Entity::find()
             .filter(deal_stage_history::Column::DealId.eq(deal_uuid))
-            .order_by_desc(deal_stage_history::Column::ChangedAt)
-            .limit(limit)
-            .offset(skip)
-            .all(db.as_ref())
-            .await
-            .map_err(db_error)?;
-
-        Ok(rows.into_iter().map(DealStageHistoryNode::from).collect())
-    }
-
-    #[graphql(name = "dealActivities")]
+            .filter(visibility(ctx)?.deal_children(deal_stage_history::Column::DealId));
+        let keys = [
+            SortKey::desc(
+                deal_stage_history::Column::ChangedAt,
+                |h: &deal_stage_history::Model| CursorValue::time(h.changed_at),
+            ),
+            SortKey::asc(
+                deal_stage_history::Column::Id,
+                |h: &deal_stage_history::Model| CursorValue::Id(h.id),
+            ),
+        ];
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(DealStageHistoryNode::from))
+    }
+
+    #[graphql(name = "dealActivities", guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn deal_activities(
         &self,
         ctx: &Context<'_>,
         deal_id: ID,
         first: Option<i32>,
-        offset: Option<i32>,
-    ) -> async_graphql::Result<Vec<ActivityNode>> {
+        after: Option<String>,
+    ) -> async_graphql::Result<CrmConnection<ActivityNode>> {
         let db = database(ctx)?;
         let deal_uuid = parse_uuid(&deal_id)?;
         let limit = first.unwrap_or(50).clamp(1, 200) as u64;
-        let skip = offset.unwrap_or(0).max(0) as u64;
 
-        let rows = activity::Entity::find()
+        let query = activity::Entity::find()
             .filter(activity::Column::EntityType.eq("deal"))
             .filter(activity::Column::EntityId.eq(deal_uuid))
-            .order_by_desc(activity::Column::CreatedAt)
-            .limit(limit)
-            .offset(skip)
-            .all(db.as_ref())
-            .await
-            .map_err(db_error)?;
-
-        Ok(rows.into_iter().map(ActivityNode::from).collect())
-    }
-
-    #[graphql(name = "tasks")]
+            .filter(visibility(ctx)?.deal_children(activity::Column::EntityId));
+        let keys = [
+            SortKey::desc(activity::Column::CreatedAt, |a: &activity::Model| {
+                CursorValue::time(a.created_at)
+            }),
+            SortKey::asc(activity::Column::Id, |a: &activity::Model| {
+                CursorValue::Id(a.id)
+            }),
+        ];
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(ActivityNode::from))
+    }
+
+    #[graphql(name = "tasks", guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn tasks(
         &self,
         ctx: &Context<'_>,
         first: Option<i32>,
-        offset: Option<i32>,
+        after: Option<String>,
         filter: Option<TaskFilter>,
         #[graphql(name = "orderBy", default)] order_by: TaskOrder,
-    ) -> async_graphql::Result<Vec<TaskNode>> {
+    ) -> async_graphql::Result<CrmConnection<TaskNode>> {
         let db = database(ctx)?;
         let requested = first.unwrap_or(25);
         let limit = enforce_task_limit(requested)?;
-        let skip = offset.unwrap_or(0).max(0) as u64;
         let filter_snapshot = filter.clone();
         let status_tag = filter_snapshot
             .as_ref()
@@ -328,7 +783,7 @@ impl CrmQuery {
         );
         let _guard = span.enter();
 
-        let mut query = task::Entity::find();
+        let mut query = task::Entity::find().filter(visibility(ctx)?.scope::<task::Entity>());
         if let Some(filter) = filter {
             if let Some(company_id) = parse_optional_id("companyId", &filter.company_id)? {
                 query = query.filter(task::Column::CompanyId.eq(company_id));
@@ -367,41 +822,165 @@ impl CrmQuery {
                 }
             }
         }
-        query = apply_task_ordering(query, order_by);
-        let rows = query
-            .limit(limit)
-            .offset(skip)
-            .all(db.as_ref())
-            .await
-            .map_err(db_error)?;
-
-        Ok(rows.into_iter().map(TaskNode::from).collect())
+        let keys = task_sort_keys(order_by);
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(TaskNode::from))
     }
 
-    #[graphql(name = "task")]
+    #[graphql(name = "task", guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<TaskNode>> {
         let db = database(ctx)?;
         let task_id = parse_uuid(&id)?;
         let record = task::Entity::find_by_id(task_id)
+            .filter(visibility(ctx)?.scope::<task::Entity>())
             .one(db.as_ref())
             .await
             .map_err(db_error)?;
         Ok(record.map(TaskNode::from))
     }
 
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn company(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Option<CompanyNode>> {
+        let db = database(ctx)?;
+        let company_id = parse_uuid(&id)?;
+        let record = company::Entity::find_by_id(company_id)
+            .filter(visibility(ctx)?.scope::<company::Entity>())
+            .one(db.as_ref())
+            .await
+            .map_err(db_error)?;
+        Ok(record.map(CompanyNode::from))
+    }
+
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn companies(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        filter: Option<CompanyFilter>,
+        #[graphql(name = "orderBy", default)] order_by: CompanyOrder,
+    ) -> async_graphql::Result<CrmConnection<CompanyNode>> {
+        let db = database(ctx)?;
+        let limit = enforce_record_limit(first.unwrap_or(25), "companies")?;
+        let mut query = company::Entity::find().filter(visibility(ctx)?.scope::<company::Entity>());
+        if let Some(filter) = filter {
+            query = apply_company_filter(query, filter)?;
+        }
+        let keys = company_sort_keys(order_by);
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(CompanyNode::from))
+    }
+
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn contact(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Option<ContactNode>> {
+        let db = database(ctx)?;
+        let contact_id = parse_uuid(&id)?;
+        let record = contact::Entity::find_by_id(contact_id)
+            .filter(visibility(ctx)?.scope::<contact::Entity>())
+            .one(db.as_ref())
+            .await
+            .map_err(db_error)?;
+        Ok(record.map(ContactNode::from))
+    }
+
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn contacts(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        filter: Option<ContactFilter>,
+        #[graphql(name = "orderBy", default)] order_by: ContactOrder,
+    ) -> async_graphql::Result<CrmConnection<ContactNode>> {
+        let db = database(ctx)?;
+        let limit = enforce_record_limit(first.unwrap_or(25), "contacts")?;
+        let mut query = contact::Entity::find().filter(visibility(ctx)?.scope::<contact::Entity>());
+        if let Some(filter) = filter {
+            query = apply_contact_filter(query, filter)?;
+        }
+        let keys = contact_sort_keys(order_by);
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(ContactNode::from))
+    }
+
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn deal(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<DealNode>> {
+        let db = database(ctx)?;
+        let deal_id = parse_uuid(&id)?;
+        let record = deal::Entity::find_by_id(deal_id)
+            .filter(visibility(ctx)?.scope::<deal::Entity>())
+            .one(db.as_ref())
+            .await
+            .map_err(db_error)?;
+        Ok(record.map(DealNode::from))
+    }
+
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn deals(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        filter: Option<DealFilter>,
+        #[graphql(name = "orderBy", default)] order_by: DealOrder,
+    ) -> async_graphql::Result<CrmConnection<DealNode>> {
+        let db = database(ctx)?;
+        let limit = enforce_record_limit(first.unwrap_or(25), "deals")?;
+        let mut query = deal::Entity::find().filter(visibility(ctx)?.scope::<deal::Entity>());
+        if let Some(filter) = filter {
+            query = apply_deal_filter(query, filter)?;
+        }
+        let keys = deal_sort_keys(order_by);
+        let page = keyset_page(db.as_ref(), query, &keys, limit, after.as_deref()).await?;
+        Ok(page.into_connection(DealNode::from))
+    }
+
+    /// The organization's pipelines, default first.
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn pipelines(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Pipeline>> {
+        let db = database(ctx)?;
+        let org_id = current_user(ctx)?.org_id;
+        let pipelines = pipeline::Entity::find()
+            .filter(pipeline::Column::OrgId.eq(org_id))
+            .order_by_desc(pipeline::Column::IsDefault)
+            .order_by_asc(pipeline::Column::Name)
+            .all(db.as_ref())
+            .await
+            .map_err(db_error)?;
+        Ok(pipelines.into_iter().map(Pipeline::from).collect())
+    }
+
+    /// A pipeline's stages in board order; archived ones only when asked for. Without
+    /// `pipelineId`, the default pipeline's.
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn pipeline_stages(
         &self,
         ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+        #[graphql(name = "includeArchived")] include_archived: Option<bool>,
     ) -> async_graphql::Result<Vec<PipelineStage>> {
         let db = database(ctx)?;
-        let stages = load_stage_meta(db.as_ref()).await?;
+        let org_id = current_user(ctx)?.org_id;
+        let pipeline = resolve_pipeline(db.as_ref(), org_id, pipeline_id.as_ref()).await?;
+        let stages =
+            load_stage_meta(db.as_ref(), pipeline.id, include_archived.unwrap_or(false)).await?;
         Ok(stages.iter().map(PipelineStage::from).collect())
     }
 
     #[allow(clippy::too_many_arguments)]
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn pipeline_board(
         &self,
         ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
         #[graphql(name = "firstPerStage")] first_per_stage: Option<i32>,
         #[graphql(name = "stageKeys")] stage_keys: Option<Vec<String>>,
         #[graphql(name = "companyId")] company_id: Option<ID>,
@@ -435,7 +1014,10 @@ impl CrmQuery {
             order_by_updated
         );
         let _guard = span.enter();
-        let stages = load_stage_meta(db.as_ref()).await?;
+        let visibility = visibility(ctx)?;
+        let pipeline =
+            resolve_pipeline(db.as_ref(), visibility.org_id, pipeline_id.as_ref()).await?;
+        let stages = load_stage_meta(db.as_ref(), pipeline.id, false).await?;
         if stages.is_empty() {
             return Ok(PipelineBoard {
                 columns: vec![],
@@ -453,13 +1035,23 @@ impl CrmQuery {
                 total_expected_cents: Some(0),
             });
         }
-        let totals =
-            query_pipeline_stage_totals(db.as_ref(), company_filter, query_filter.as_deref())
-                .await?;
+        let totals = query_pipeline_stage_totals(
+            db.as_ref(),
+            visibility,
+            pipeline.id,
+            company_filter,
+            query_filter.as_deref(),
+        )
+        .await?;
         let totals_map: HashMap<String, StageAggregateRow> = totals
             .into_iter()
             .map(|row| (row.stage_key.clone(), row))
             .collect();
+        let occupancy = if stage_sequence.iter().any(|stage| stage.wip_limit.is_some()) {
+            count_deals_per_stage(db.as_ref(), pipeline.id).await?
+        } else {
+            HashMap::new()
+        };
         let mut columns: Vec<PipelineColumn> = Vec::new();
         for stage in stage_sequence {
             let totals_row = totals_map.get(&stage.key);
@@ -468,6 +1060,8 @@ impl CrmQuery {
             } else {
                 query_stage_deals(
                     db.as_ref(),
+                    visibility,
+                    pipeline.id,
                     &stage.key,
                     company_filter,
                     query_filter.as_deref(),
@@ -483,6 +1077,9 @@ impl CrmQuery {
                 expected_value_cents: Some(
                     totals_row.map(|row| row.total_expected_cents).unwrap_or(0),
                 ),
+                wip_exceeded: stage.wip_limit.is_some_and(|limit| {
+                    occupancy.get(&stage.key).copied().unwrap_or(0) > i64::from(limit)
+                }),
                 deals,
             };
             columns.push(column);
@@ -504,12 +1101,14 @@ impl CrmQuery {
         })
     }
 
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
     async fn pipeline_report(
         &self,
         ctx: &Context<'_>,
         range: DateRange,
         group: Option<TimeGroup>,
         #[graphql(name = "includeLost")] include_lost: Option<bool>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
     ) -> async_graphql::Result<PipelineReport> {
         if range.from > range.to {
             return Err(validation_error("range.from must be on or before range.to"));
@@ -527,8 +1126,13 @@ impl CrmQuery {
             include_lost
         );
         let _guard = span.enter();
-        let stages = load_stage_meta(db.as_ref()).await?;
-        let stage_rows = query_report_stage_totals(db.as_ref(), &range, include_lost).await?;
+        let visibility = visibility(ctx)?;
+        let pipeline =
+            resolve_pipeline(db.as_ref(), visibility.org_id, pipeline_id.as_ref()).await?;
+        let stages = load_stage_meta(db.as_ref(), pipeline.id, true).await?;
+        let stage_rows =
+            query_report_stage_totals(db.as_ref(), visibility, pipeline.id, &range, include_lost)
+                .await?;
         let stage_row_map: HashMap<String, StageReportRow> = stage_rows
             .into_iter()
             .map(|row| (row.stage_key.clone(), row))
@@ -544,9 +1148,12 @@ impl CrmQuery {
                 });
             }
         }
-        let forecast_rows = query_forecast_points(db.as_ref(), &range, include_lost).await?;
+        let forecast_rows =
+            query_forecast_points(db.as_ref(), visibility, pipeline.id, &range, include_lost)
+                .await?;
         let forecast = build_forecast_points(&range, forecast_rows);
-        let velocity_rows = query_velocity_rows(db.as_ref(), &range).await?;
+        let velocity_rows =
+            query_velocity_rows(db.as_ref(), visibility, pipeline.id, &range).await?;
         let velocity = compute_velocity_stats(velocity_rows);
 
         Ok(PipelineReport {
@@ -555,87 +1162,1037 @@ impl CrmQuery {
             velocity,
         })
     }
-}
 
-#[Object]
-impl CrmMutation {
-    async fn login(
+    /// The organization's win/loss reasons, optionally for one outcome.
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn deal_reasons(
         &self,
         ctx: &Context<'_>,
-        email: String,
-        password: String,
-    ) -> async_graphql::Result<AuthPayload> {
-        let auth = auth_config(ctx)?;
-        if auth.mode != AuthMode::Local {
-            return Ok(AuthPayload {
-                ok: false,
-                user: None,
-                error: Some("Authentication is disabled".into()),
-            });
-        }
+        outcome: Option<DealOutcome>,
+        #[graphql(name = "includeArchived")] include_archived: Option<bool>,
+    ) -> async_graphql::Result<Vec<DealReason>> {
         let db = database(ctx)?;
-        let normalized = normalize_email(&email)?;
-        let identity = user_identity::Entity::find()
-            .filter(user_identity::Column::Provider.eq("local"))
-            .filter(user_identity::Column::Subject.eq(normalized.clone()))
-            .one(db.as_ref())
-            .await
-            .map_err(db_error)?;
-        let Some(identity) = identity else {
-            return Ok(AuthPayload {
-                ok: false,
-                user: None,
-                error: Some("Invalid credentials".into()),
-            });
-        };
-        let user = app_user::Entity::find_by_id(identity.user_id)
-            .one(db.as_ref())
+        let org_id = current_user(ctx)?.org_id;
+        let mut query = deal_reason::Entity::find().filter(deal_reason::Column::OrgId.eq(org_id));
+        if let Some(outcome) = outcome {
+            query =
+                query.filter(deal_reason::Column::Outcome.eq(deal_reason::Outcome::from(outcome)));
+        }
+        if !include_archived.unwrap_or(false) {
+            query = query.filter(deal_reason::Column::IsArchived.eq(false));
+        }
+        let reasons = query
+            .order_by_asc(deal_reason::Column::Outcome)
+            .order_by_asc(deal_reason::Column::Label)
+            .all(db.as_ref())
             .await
             .map_err(db_error)?;
-        let Some(user) = user else {
-            return Ok(AuthPayload {
-                ok: false,
-                user: None,
-                error: Some("Invalid credentials".into()),
-            });
+        Ok(reasons.into_iter().map(DealReason::from).collect())
+    }
+
+    /// Won and lost deals closing in `range`, grouped by reason, owner or company. Without
+    /// `pipelineId` every pipeline counts.
+    #[graphql(guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn win_loss_report(
+        &self,
+        ctx: &Context<'_>,
+        range: DateRange,
+        #[graphql(name = "groupBy")] group_by: WinLossGroup,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+    ) -> async_graphql::Result<WinLossReport> {
+        if range.from > range.to {
+            return Err(validation_error("range.from must be on or before range.to"));
+        }
+        let db = database(ctx)?;
+        let visibility = visibility(ctx)?;
+        let pipeline_id = match pipeline_id {
+            Some(id) => Some(
+                resolve_pipeline(db.as_ref(), visibility.org_id, Some(&id))
+                    .await?
+                    .id,
+            ),
+            None => None,
+        };
+        let rows =
+            query_win_loss_rows(db.as_ref(), visibility, pipeline_id, &range, group_by).await?;
+        let (mut won, mut lost, mut won_amount_cents, mut lost_amount_cents) = (0, 0, 0, 0);
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                won += row.won;
+                lost += row.lost;
+                won_amount_cents += row.won_amount_cents;
+                lost_amount_cents += row.lost_amount_cents;
+                WinLossRow {
+                    key: row.group_key.map(ID::from),
+                    label: row.label,
+                    won: row.won as i32,
+                    lost: row.lost as i32,
+                    won_amount_cents: row.won_amount_cents,
+                    lost_amount_cents: row.lost_amount_cents,
+                    win_rate: win_rate(row.won, row.lost),
+                }
+            })
+            .collect();
+        Ok(WinLossReport {
+            rows,
+            won: won as i32,
+            lost: lost as i32,
+            won_amount_cents,
+            lost_amount_cents,
+            win_rate: win_rate(won, lost),
+        })
+    }
+}
+
+#[Object]
+impl CrmMutation {
+    /// Signs in to `organization` (a slug), or to the caller's oldest membership
+    /// when it is omitted.
+    async fn login(
+        &self,
+        ctx: &Context<'_>,
+        email: String,
+        password: String,
+        organization: Option<String>,
+    ) -> async_graphql::Result<AuthPayload> {
+        let auth = auth_config(ctx)?;
+        if !auth.mode.allows_password() {
+            return Ok(AuthPayload::failed(match auth.mode {
+                AuthMode::Disabled => "Authentication is disabled",
+                _ => "Password login is disabled",
+            }));
+        }
+        let db = database(ctx)?;
+        let normalized = normalize_email(&email)?;
+        let client = ctx.data_opt::<ClientInfo>();
+        let keys = login_keys(&normalized, client);
+        if let Some(until) = locked_until(db.as_ref(), &keys).await.map_err(db_error)? {
+            record_auth_event(
+                db.as_ref(),
+                auth_event::Kind::LoginFailed,
+                None,
+                Some(&normalized),
+                client,
+                Some("locked"),
+            )
+            .await
+            .map_err(db_error)?;
+            return Ok(AuthPayload::locked(until));
+        }
+        let Some(user) = find_local_user(db.as_ref(), &normalized).await? else {
+            login_failed(
+                ctx,
+                db.as_ref(),
+                &keys,
+                None,
+                &normalized,
+                "unknown_account",
+            )
+            .await?;
+            return Ok(AuthPayload::failed("Invalid credentials"));
         };
         if !user.is_active {
-            return Ok(AuthPayload {
-                ok: false,
-                user: None,
-                error: Some("Account disabled".into()),
-            });
+            login_failed(
+                ctx,
+                db.as_ref(),
+                &keys,
+                Some(user.id),
+                &normalized,
+                "inactive",
+            )
+            .await?;
+            return Ok(AuthPayload::failed("Account disabled"));
         }
         let secret = user_secret::Entity::find_by_id(user.id)
             .one(db.as_ref())
             .await
             .map_err(db_error)?;
         let Some(secret) = secret else {
-            return Ok(AuthPayload {
-                ok: false,
-                user: None,
-                error: Some("Invalid credentials".into()),
-            });
+            login_failed(
+                ctx,
+                db.as_ref(),
+                &keys,
+                Some(user.id),
+                &normalized,
+                "no_password",
+            )
+            .await?;
+            return Ok(AuthPayload::failed("Invalid credentials"));
         };
-        verify_password(&password, &secret.password_hash)?;
-        let roles = load_roles(db.as_ref(), user.id).await?;
-        let token = issue_session_token(user.id, &auth)
-            .map_err(|_| error_with_code("INTERNAL", "Failed to issue session"))?;
-        let cookie = build_session_cookie(&token, auth.session_ttl_minutes);
-        ctx.append_http_header("Set-Cookie", cookie);
-        Ok(AuthPayload {
-            ok: true,
-            user: Some(UserNode::from_model(user, roles)),
-            error: None,
-        })
+        if let Err(err) = verify_password(&password, &secret.password_hash) {
+            login_failed(
+                ctx,
+                db.as_ref(),
+                &keys,
+                Some(user.id),
+                &normalized,
+                "bad_password",
+            )
+            .await?;
+            return Err(err);
+        }
+        let org_id = match organization.as_deref() {
+            Some(slug) => find_organization(db.as_ref(), slug)
+                .await
+                .map_err(db_error)?
+                .map(|organization| organization.id),
+            None => default_organization(db.as_ref(), user.id)
+                .await
+                .map_err(db_error)?
+                .map(|organization| organization.id),
+        };
+        let roles = match org_id {
+            Some(org_id) => member_roles(db.as_ref(), org_id, user.id)
+                .await
+                .map_err(db_error)?,
+            None => None,
+        };
+        let (Some(org_id), Some(roles)) = (org_id, roles) else {
+            // The password was right, so this is not a guess worth a lockout.
+            let (reason, message) = match organization {
+                Some(_) => ("not_a_member", "No access to this organization"),
+                None => ("inactive", "Account disabled"),
+            };
+            let kind = auth_event::Kind::LoginFailed;
+            record_auth_event(
+                db.as_ref(),
+                kind,
+                Some(user.id),
+                Some(&normalized),
+                client,
+                Some(reason),
+            )
+            .await
+            .map_err(db_error)?;
+            return Ok(AuthPayload::failed(message));
+        };
+        if let Some(purpose) = pending_second_factor(db.as_ref(), user.id, org_id, &roles)
+            .await
+            .map_err(db_error)?
+        {
+            return AuthPayload::challenge(user.id, org_id, purpose, &auth);
+        }
+        sign_in(ctx, db.as_ref(), &auth, user, org_id, roles).await
+    }
+
+    /// Second step of a login that answered `nextStep: VERIFY_TOTP`: takes a
+    /// current authenticator code or one of the recovery codes.
+    #[graphql(name = "verifySecondFactor")]
+    async fn verify_second_factor(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "challengeToken")] challenge_token: String,
+        code: String,
+    ) -> async_graphql::Result<AuthPayload> {
+        let auth = auth_config(ctx)?;
+        let db = database(ctx)?;
+        let Ok(claims) = decode_challenge_token(&challenge_token, ChallengePurpose::Verify, &auth)
+        else {
+            return Ok(AuthPayload::failed("Sign-in expired, please retry"));
+        };
+        let user = find_user(db.as_ref(), claims.sub).await?;
+        let keys = login_keys(&user.email, ctx.data_opt::<ClientInfo>());
+        if let Some(until) = locked_until(db.as_ref(), &keys).await.map_err(db_error)? {
+            return Ok(AuthPayload::locked(until));
+        }
+        if !user.is_active {
+            return Ok(AuthPayload::failed("Account disabled"));
+        }
+        if !verify_second_factor(db.as_ref(), user.id, &code)
+            .await
+            .map_err(db_error)?
+        {
+            let reason = "bad_second_factor";
+            login_failed(ctx, db.as_ref(), &keys, Some(user.id), &user.email, reason).await?;
+            return Ok(AuthPayload::failed("Invalid code"));
+        }
+        let Some(roles) = member_roles(db.as_ref(), claims.org, user.id)
+            .await
+            .map_err(db_error)?
+        else {
+            return Ok(AuthPayload::failed("No access to this organization"));
+        };
+        sign_in(ctx, db.as_ref(), &auth, user, claims.org, roles).await
     }
 
     async fn logout(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        if let (Some(session), Ok(current)) = (ctx.data_opt::<CurrentSession>(), current_user(ctx))
+        {
+            let db = database(ctx)?;
+            revoke_session(db.as_ref(), session.id, current.user_id)
+                .await
+                .map_err(db_error)?;
+            record_auth_event(
+                db.as_ref(),
+                auth_event::Kind::Logout,
+                Some(current.user_id),
+                None,
+                ctx.data_opt::<ClientInfo>(),
+                None,
+            )
+            .await
+            .map_err(db_error)?;
+        }
         ctx.append_http_header("Set-Cookie", clear_session_cookie());
         Ok(true)
     }
 
-    #[graphql(name = "assignCompany")]
+    #[graphql(name = "revokeSession", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn revoke_session(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<bool> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let session_id = parse_uuid(&id)?;
+        let revoked = revoke_session(db.as_ref(), session_id, current.user_id)
+            .await
+            .map_err(db_error)?;
+        if ctx.data_opt::<CurrentSession>().map(|s| s.id) == Some(session_id) {
+            ctx.append_http_header("Set-Cookie", clear_session_cookie());
+        }
+        Ok(revoked)
+    }
+
+    /// Signs the caller out everywhere else; `includeCurrent` ends this session too.
+    #[graphql(name = "revokeAllSessions", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn revoke_all_sessions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "includeCurrent", default = false)] include_current: bool,
+    ) -> async_graphql::Result<i32> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let keep = match include_current {
+            true => None,
+            false => ctx.data_opt::<CurrentSession>().map(|s| s.id),
+        };
+        let revoked = revoke_user_sessions(db.as_ref(), current.user_id, keep)
+            .await
+            .map_err(db_error)?;
+        if keep.is_none() {
+            ctx.append_http_header("Set-Cookie", clear_session_cookie());
+        }
+        Ok(revoked as i32)
+    }
+
+    /// Changes the caller's own password. Other sessions are signed out; this
+    /// one stays. Wrong current passwords count towards login lockouts.
+    #[graphql(name = "changePassword", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn change_password(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "currentPassword")] current_password: String,
+        #[graphql(name = "newPassword")] new_password: String,
+    ) -> async_graphql::Result<bool> {
+        let current = current_user(ctx)?;
+        if current.scopes.is_some() {
+            return Err(error_with_code(
+                "FORBIDDEN",
+                "API keys cannot change passwords",
+            ));
+        }
+        let auth = auth_config(ctx)?;
+        let db = database(ctx)?;
+        let password = validate_password(&auth.password_policy, &new_password)?;
+        let user = find_user(db.as_ref(), current.user_id).await?;
+        let client = ctx.data_opt::<ClientInfo>();
+        let keys = login_keys(&user.email, client);
+        if locked_until(db.as_ref(), &keys)
+            .await
+            .map_err(db_error)?
+            .is_some()
+        {
+            return Err(error_with_code(
+                "FORBIDDEN",
+                "Too many failed attempts, try again later",
+            ));
+        }
+        let secret = user_secret::Entity::find_by_id(user.id)
+            .one(db.as_ref())
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| error_with_code("CONFLICT", "No password is set for this account"))?;
+        if verify_password(&current_password, &secret.password_hash).is_err() {
+            let reason = "bad_current_password";
+            login_failed(ctx, db.as_ref(), &keys, Some(user.id), &user.email, reason).await?;
+            return Err(error_with_code(
+                "FORBIDDEN",
+                "Current password is incorrect",
+            ));
+        }
+        let keep = ctx.data_opt::<CurrentSession>().map(|s| s.id);
+        let txn = db.begin().await.map_err(db_error)?;
+        upsert_password(&txn, user.id, &password)
+            .await
+            .map_err(db_error)?;
+        revoke_user_sessions(&txn, user.id, keep)
+            .await
+            .map_err(db_error)?;
+        let kind = auth_event::Kind::PasswordChanged;
+        record_auth_event(&txn, kind, Some(user.id), Some(&user.email), client, None)
+            .await
+            .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    /// Emails a reset link when `email` belongs to an active account that signs
+    /// in with a password. Always answers `true`, so it cannot probe for accounts.
+    #[graphql(name = "requestPasswordReset")]
+    async fn request_password_reset(
+        &self,
+        ctx: &Context<'_>,
+        email: String,
+    ) -> async_graphql::Result<bool> {
+        let auth = auth_config(ctx)?;
+        if !auth.mode.allows_password() {
+            return Ok(true);
+        }
+        let db = database(ctx)?;
+        let normalized = normalize_email(&email)?;
+        let Some(user) = find_local_user(db.as_ref(), &normalized).await? else {
+            return Ok(true);
+        };
+        if !user.is_active {
+            return Ok(true);
+        }
+        let txn = db.begin().await.map_err(db_error)?;
+        if let Some(token) = issue_reset_token(&txn, user.id).await.map_err(db_error)? {
+            let link = format!("{}/reset-password?token={}", auth.app_url, token);
+            let body = password_reset_email(&user.display_name, &link);
+            enqueue_email(&txn, &user.email, "Reset your SME Suite password", &body)
+                .await
+                .map_err(db_error)?;
+        }
+        txn.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    /// Sets a new password with the token from a reset email. The account is
+    /// signed out everywhere and any login lockout on it is lifted.
+    #[graphql(name = "resetPassword")]
+    async fn reset_password(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+        #[graphql(name = "newPassword")] new_password: String,
+    ) -> async_graphql::Result<bool> {
+        let auth = auth_config(ctx)?;
+        let db = database(ctx)?;
+        let password = validate_password(&auth.password_policy, &new_password)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let Some(user_id) = redeem_reset_token(&txn, &token).await.map_err(db_error)? else {
+            return Err(validation_error("Reset link is invalid or has expired"));
+        };
+        let user = find_user(&txn, user_id).await?;
+        upsert_password(&txn, user.id, &password)
+            .await
+            .map_err(db_error)?;
+        revoke_user_sessions(&txn, user.id, None)
+            .await
+            .map_err(db_error)?;
+        clear_failures(&txn, &ThrottleKey::Account(user.email.clone()))
+            .await
+            .map_err(db_error)?;
+        let client = ctx.data_opt::<ClientInfo>();
+        let kind = auth_event::Kind::PasswordReset;
+        record_auth_event(&txn, kind, Some(user.id), Some(&user.email), client, None)
+            .await
+            .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    /// Starts TOTP enrolment for the signed-in user, or for the holder of an
+    /// enrolment challenge from `login`. Repeating it replaces the pending secret.
+    #[graphql(name = "beginTotpEnrollment")]
+    async fn begin_totp_enrollment(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "challengeToken")] challenge_token: Option<String>,
+    ) -> async_graphql::Result<TotpEnrollment> {
+        let db = database(ctx)?;
+        let (user_id, _) = totp_subject(ctx, challenge_token.as_deref())?;
+        let user = find_user(db.as_ref(), user_id).await?;
+        if !user.is_active {
+            return Err(error_with_code("FORBIDDEN", "Account disabled"));
+        }
+        let secret = generate_totp_secret();
+        if !stage_totp_secret(db.as_ref(), user.id, &secret)
+            .await
+            .map_err(db_error)?
+        {
+            return Err(totp_unavailable(db.as_ref(), user.id).await);
+        }
+        Ok(TotpEnrollment {
+            otpauth_uri: otpauth_uri(&secret, &user.email),
+            secret,
+        })
+    }
+
+    /// Turns TOTP on once the authenticator produces a matching code and returns
+    /// the recovery codes, which are never shown again. Confirming with an
+    /// enrolment challenge also signs the user in.
+    #[graphql(name = "confirmTotpEnrollment")]
+    async fn confirm_totp_enrollment(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+        #[graphql(name = "challengeToken")] challenge_token: Option<String>,
+    ) -> async_graphql::Result<TotpConfirmation> {
+        let auth = auth_config(ctx)?;
+        let db = database(ctx)?;
+        let (user_id, org_id) = totp_subject(ctx, challenge_token.as_deref())?;
+        let user = find_user(db.as_ref(), user_id).await?;
+        if !user.is_active {
+            return Err(error_with_code("FORBIDDEN", "Account disabled"));
+        }
+        let txn = db.begin().await.map_err(db_error)?;
+        if !confirm_totp(&txn, user.id, &code).await.map_err(db_error)? {
+            return Err(validation_error("Invalid code"));
+        }
+        let recovery_codes = replace_recovery_codes(&txn, user.id)
+            .await
+            .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        let signed_in = challenge_token.is_some();
+        if signed_in {
+            let roles = member_roles(db.as_ref(), org_id, user.id)
+                .await
+                .map_err(db_error)?
+                .ok_or_else(|| error_with_code("FORBIDDEN", "No access to this organization"))?;
+            sign_in(ctx, db.as_ref(), &auth, user, org_id, roles).await?;
+        }
+        Ok(TotpConfirmation {
+            recovery_codes,
+            signed_in,
+        })
+    }
+
+    /// Turns TOTP off for the caller, who proves possession with a current or
+    /// recovery code. Not allowed while policy requires it for their role.
+    #[graphql(name = "disableTotp", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn disable_totp(&self, ctx: &Context<'_>, code: String) -> async_graphql::Result<bool> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let (user_id, org_id) = totp_subject(ctx, None)?;
+        if two_factor_required(db.as_ref(), org_id, &current.roles).await? {
+            return Err(error_with_code(
+                "CONFLICT",
+                "Two-factor authentication is required for your role",
+            ));
+        }
+        if !verify_second_factor(db.as_ref(), user_id, &code)
+            .await
+            .map_err(db_error)?
+        {
+            return Err(validation_error("Invalid code"));
+        }
+        clear_totp(db.as_ref(), user_id).await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    #[graphql(
+        name = "regenerateRecoveryCodes",
+        guard = "RoleGuard::new(UserRole::Viewer)"
+    )]
+    async fn regenerate_recovery_codes(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+    ) -> async_graphql::Result<Vec<String>> {
+        let db = database(ctx)?;
+        let (user_id, _) = totp_subject(ctx, None)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        if !verify_second_factor(&txn, user_id, &code)
+            .await
+            .map_err(db_error)?
+        {
+            return Err(validation_error("Invalid code"));
+        }
+        let codes = replace_recovery_codes(&txn, user_id)
+            .await
+            .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(codes)
+    }
+
+    /// Clears TOTP for a user who lost their authenticator and recovery codes. Users
+    /// who also belong to another organization are refused.
+    #[graphql(name = "resetUserTotp", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn reset_user_totp(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "userId")] user_id: ID,
+    ) -> async_graphql::Result<bool> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let user = find_sole_member(&txn, current.org_id, parse_uuid(&user_id)?).await?;
+        clear_totp(&txn, user.id).await.map_err(db_error)?;
+        record_audit(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::User,
+            user.id,
+            audit_log::Operation::Update,
+            secret_change("totp", None),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    /// Requires ADMIN and OWNER accounts to pass TOTP on password login. Takes
+    /// effect at their next login; those not yet enrolled must enrol first.
+    #[graphql(name = "setTwoFactorPolicy", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn set_two_factor_policy(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "requireForAdmins")] require_for_admins: bool,
+    ) -> async_graphql::Result<TwoFactorPolicy> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        if require_for_admins {
+            let secret = user_secret::Entity::find_by_id(current.user_id)
+                .one(db.as_ref())
+                .await
+                .map_err(db_error)?;
+            if secret.is_some_and(|secret| secret.totp_enabled_at.is_none()) {
+                return Err(error_with_code(
+                    "CONFLICT",
+                    "Enable two-factor authentication on your own account first",
+                ));
+            }
+        }
+        set_flag(
+            db.as_ref(),
+            current.org_id,
+            REQUIRE_ADMIN_TWO_FACTOR,
+            require_for_admins,
+            current.user_id,
+        )
+        .await
+        .map_err(db_error)?;
+        Ok(TwoFactorPolicy { require_for_admins })
+    }
+
+    /// Issues a key for the caller, or a service key for `userId` when the caller
+    /// is an owner. The token is only ever returned here.
+    #[graphql(name = "createApiKey", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn create_api_key(
+        &self,
+        ctx: &Context<'_>,
+        input: NewApiKeyInput,
+    ) -> async_graphql::Result<CreatedApiKey> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let user_id = api_key_holder(&current, parse_optional_id("userId", &input.user_id)?)?;
+        ensure_active_user(db.as_ref(), current.org_id, user_id).await?;
+        let name = validate_api_key_name(&input.name)?;
+        let mut scopes = input.scopes;
+        scopes.sort_by_key(|scope| scope.as_str());
+        scopes.dedup();
+        if scopes.is_empty() {
+            return Err(validation_error("At least one scope is required"));
+        }
+        let now = Utc::now();
+        if input.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(validation_error("expiresAt must be in the future"));
+        }
+        let kind = if user_id == current.user_id {
+            api_key::Kind::Personal
+        } else {
+            api_key::Kind::Service
+        };
+        let (token, prefix, token_hash) = generate_api_key();
+        let key = api_key::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            org_id: Set(current.org_id),
+            user_id: Set(user_id),
+            name: Set(name),
+            kind: Set(kind),
+            prefix: Set(prefix),
+            token_hash: Set(token_hash),
+            scopes: Set(format_scopes(&scopes)),
+            created_by: Set(Some(current.user_id)),
+            created_at: Set(now.into()),
+            expires_at: Set(input.expires_at.map(Into::into)),
+            last_used_at: Set(None),
+            revoked_at: Set(None),
+        };
+        let txn = db.begin().await.map_err(db_error)?;
+        let key = key.insert(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::ApiKey,
+            key.id,
+            None,
+            Some(&key),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(CreatedApiKey {
+            key: key.into(),
+            token,
+        })
+    }
+
+    #[graphql(name = "revokeApiKey", guard = "RoleGuard::new(UserRole::Viewer)")]
+    async fn revoke_api_key(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<bool> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let key_id = parse_uuid(&id)?;
+        let key = api_key::Entity::find_by_id(key_id)
+            .filter(api_key::Column::OrgId.eq(current.org_id))
+            .one(db.as_ref())
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| error_with_code("NOT_FOUND", "API key not found"))?;
+        api_key_holder(&current, Some(key.user_id))?;
+        if key.revoked_at.is_some() {
+            return Ok(false);
+        }
+        let before = key.clone();
+        let mut active: api_key::ActiveModel = key.into();
+        active.revoked_at = Set(Some(Utc::now().into()));
+        let txn = db.begin().await.map_err(db_error)?;
+        let revoked = active.update(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::ApiKey,
+            key_id,
+            Some(&before),
+            Some(&revoked),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    #[graphql(name = "createUser", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn create_user(
+        &self,
+        ctx: &Context<'_>,
+        input: NewUserInput,
+    ) -> async_graphql::Result<UserNode> {
+        let db = database(ctx)?;
+        let auth = auth_config(ctx)?;
+        let password = validate_password(&auth.password_policy, &input.password)?;
+        let (model, roles) = create_user_internal(
+            db.as_ref(),
+            &input.email,
+            &input.display_name,
+            input.roles,
+            &password,
+            &audit_actor(ctx)?,
+        )
+        .await?;
+        Ok(UserNode::from_model(model, roles))
+    }
+
+    /// Creates the account with a generated temporary password that is only
+    /// returned here; hand it to the invitee out of band.
+    #[graphql(name = "inviteUser", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn invite_user(
+        &self,
+        ctx: &Context<'_>,
+        input: InviteUserInput,
+    ) -> async_graphql::Result<InviteUserPayload> {
+        let db = database(ctx)?;
+        let temporary_password = Uuid::new_v4().simple().to_string();
+        let (model, roles) = create_user_internal(
+            db.as_ref(),
+            &input.email,
+            &input.display_name,
+            input.roles,
+            &temporary_password,
+            &audit_actor(ctx)?,
+        )
+        .await?;
+        Ok(InviteUserPayload {
+            user: UserNode::from_model(model, roles),
+            temporary_password,
+        })
+    }
+
+    #[graphql(name = "setUserRoles", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn set_user_roles(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "userId")] user_id: ID,
+        roles: Vec<UserRole>,
+    ) -> async_graphql::Result<UserNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let user_id = parse_uuid(&user_id)?;
+        let roles = validate_roles(roles)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let (model, member) = find_member(&txn, current.org_id, user_id).await?;
+        if !roles.contains(&UserRole::Owner) {
+            ensure_other_owner(&txn, current.org_id, user_id).await?;
+        }
+        let mut previous = load_roles(&txn, current.org_id, user_id).await?;
+        previous.sort();
+        let changes = diff(
+            &user_snapshot(&model, &previous),
+            &user_snapshot(&model, &roles),
+        );
+        if !changes.is_empty() {
+            record_audit(
+                &txn,
+                &audit_actor(ctx)?,
+                audit_log::EntityType::User,
+                user_id,
+                audit_log::Operation::Update,
+                changes,
+            )
+            .await
+            .map_err(db_error)?;
+        }
+        user_role::Entity::delete_many()
+            .filter(user_role::Column::OrgId.eq(current.org_id))
+            .filter(user_role::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await
+            .map_err(db_error)?;
+        insert_user_roles(&txn, current.org_id, user_id, &roles)
+            .await
+            .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(UserNode::from_member(
+            model,
+            Membership {
+                is_active: member.is_active,
+                roles,
+            },
+        ))
+    }
+
+    #[graphql(name = "deactivateUser", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn deactivate_user(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<UserNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let user_id = parse_uuid(&id)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let (model, member) = find_member(&txn, current.org_id, user_id).await?;
+        ensure_other_owner(&txn, current.org_id, user_id).await?;
+        let member = set_member_active(&txn, &audit_actor(ctx)?, member, false).await?;
+        revoke_member_sessions(&txn, current.org_id, user_id)
+            .await
+            .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        let roles = load_roles(db.as_ref(), current.org_id, user_id).await?;
+        Ok(UserNode::from_member(
+            model,
+            Membership {
+                is_active: member.is_active,
+                roles,
+            },
+        ))
+    }
+
+    #[graphql(name = "reactivateUser", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn reactivate_user(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<UserNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let user_id = parse_uuid(&id)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let (model, member) = find_member(&txn, current.org_id, user_id).await?;
+        let member = set_member_active(&txn, &audit_actor(ctx)?, member, true).await?;
+        txn.commit().await.map_err(db_error)?;
+        let roles = load_roles(db.as_ref(), current.org_id, user_id).await?;
+        Ok(UserNode::from_member(
+            model,
+            Membership {
+                is_active: member.is_active,
+                roles,
+            },
+        ))
+    }
+
+    /// Sets a user's password and signs them out everywhere. Users who also belong to
+    /// another organization are refused.
+    #[graphql(name = "adminResetPassword", guard = "RoleGuard::new(UserRole::Owner)")]
+    async fn admin_reset_password(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "userId")] user_id: ID,
+        password: String,
+    ) -> async_graphql::Result<bool> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let auth = auth_config(ctx)?;
+        let user_id = parse_uuid(&user_id)?;
+        let password = validate_password(&auth.password_policy, &password)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let model = find_sole_member(&txn, current.org_id, user_id).await?;
+        // Users that only ever signed in elsewhere get a local identity too.
+        user_identity::Entity::insert(user_identity::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(model.id),
+            provider: Set("local".into()),
+            subject: Set(model.email.clone()),
+            created_at: Set(Utc::now().into()),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                user_identity::Column::Provider,
+                user_identity::Column::Subject,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .do_nothing()
+        .exec(&txn)
+        .await
+        .map_err(db_error)?;
+        upsert_password(&txn, model.id, &password)
+            .await
+            .map_err(db_error)?;
+        revoke_user_sessions(&txn, model.id, None)
+            .await
+            .map_err(db_error)?;
+        record_audit(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::User,
+            model.id,
+            audit_log::Operation::Update,
+            secret_change("password", Some(REDACTED)),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(true)
+    }
+
+    #[graphql(name = "createCompany", guard = "RoleGuard::new(UserRole::Sales)")]
+    async fn create_company(
+        &self,
+        ctx: &Context<'_>,
+        input: NewCompanyInput,
+    ) -> async_graphql::Result<CompanyNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let company =
+            create_company_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        Ok(company.into())
+    }
+
+    #[graphql(name = "updateCompany", guard = "RoleGuard::new(UserRole::Sales)")]
+    async fn update_company(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateCompanyInput,
+    ) -> async_graphql::Result<CompanyNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        visibility(ctx)?
+            .ensure::<company::Entity>(db.as_ref(), parse_uuid(&input.id)?)
+            .await?;
+        let company =
+            update_company_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        Ok(company.into())
+    }
+
+    /// Deletes a company. Contacts are detached (their `companyId` is cleared) and
+    /// tasks attached directly to the company are removed. Companies that still
+    /// own deals are rejected with `CONFLICT` unless `cascade` is set, in which
+    /// case the deals, their tasks, stage history and activities go too.
+    #[graphql(name = "deleteCompany", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn delete_company(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        #[graphql(default)] cascade: bool,
+    ) -> async_graphql::Result<bool> {
+        let db = database(ctx)?;
+        let company_id = parse_uuid(&id)?;
+        delete_company_internal(db.as_ref(), company_id, cascade, &audit_actor(ctx)?).await
+    }
+
+    #[graphql(name = "createContact", guard = "RoleGuard::new(UserRole::Sales)")]
+    async fn create_contact(
+        &self,
+        ctx: &Context<'_>,
+        input: NewContactInput,
+    ) -> async_graphql::Result<ContactNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        if let Some(company_id) = parse_optional_id("companyId", &input.company_id)? {
+            visibility(ctx)?
+                .ensure::<company::Entity>(db.as_ref(), company_id)
+                .await?;
+        }
+        let contact =
+            create_contact_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        Ok(contact.into())
+    }
+
+    #[graphql(name = "updateContact", guard = "RoleGuard::new(UserRole::Sales)")]
+    async fn update_contact(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateContactInput,
+    ) -> async_graphql::Result<ContactNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        visibility(ctx)?
+            .ensure::<contact::Entity>(db.as_ref(), parse_uuid(&input.id)?)
+            .await?;
+        let contact =
+            update_contact_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        Ok(contact.into())
+    }
+
+    /// Moves a contact to another company, or detaches it when `companyId` is null.
+    #[graphql(
+        name = "moveContactToCompany",
+        guard = "RoleGuard::new(UserRole::Sales)"
+    )]
+    async fn move_contact_to_company(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        #[graphql(name = "companyId")] company_id: Option<ID>,
+    ) -> async_graphql::Result<ContactNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        let contact_id = parse_uuid(&id)?;
+        let target_company = parse_optional_id("companyId", &company_id)?;
+        let visibility = visibility(ctx)?;
+        visibility
+            .ensure::<contact::Entity>(db.as_ref(), contact_id)
+            .await?;
+        if let Some(company_id) = target_company {
+            visibility
+                .ensure::<company::Entity>(db.as_ref(), company_id)
+                .await?;
+        }
+        let contact = move_contact_internal(
+            db.as_ref(),
+            contact_id,
+            target_company,
+            &current,
+            &audit_actor(ctx)?,
+        )
+        .await?;
+        Ok(contact.into())
+    }
+
+    #[graphql(name = "deleteContact", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn delete_contact(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<bool> {
+        let db = database(ctx)?;
+        let contact_id = parse_uuid(&id)?;
+        delete_contact_internal(db.as_ref(), contact_id, &audit_actor(ctx)?).await
+    }
+
+    #[graphql(name = "assignCompany", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn assign_company(
         &self,
         ctx: &Context<'_>,
@@ -645,25 +2202,44 @@ impl CrmMutation {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
         let company_id = parse_uuid(&id)?;
+        visibility(ctx)?
+            .ensure::<company::Entity>(db.as_ref(), company_id)
+            .await?;
         let target_user = match user_id {
-            Some(uid) => Some(ensure_active_user(db.as_ref(), parse_uuid(&uid)?).await?),
+            Some(uid) => {
+                Some(ensure_active_user(db.as_ref(), current.org_id, parse_uuid(&uid)?).await?)
+            }
             None => None,
         };
         let now: DateTimeWithTimeZone = Utc::now().into();
+        let txn = db.begin().await.map_err(db_error)?;
         let company = company::Entity::find_by_id(company_id)
-            .one(db.as_ref())
+            .filter(company::Column::OrgId.eq(current.org_id))
+            .one(&txn)
             .await
             .map_err(db_error)?
             .ok_or_else(|| error_with_code("NOT_FOUND", "Company not found"))?;
+        let before = company.clone();
         let mut active: company::ActiveModel = company.into();
         active.assigned_user_id = Set(target_user);
         active.updated_by = Set(Some(current.user_id));
         active.updated_at = Set(now);
-        let updated = active.update(db.as_ref()).await.map_err(db_error)?;
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::Company,
+            company_id,
+            Some(&before),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
         Ok(updated.into())
     }
 
-    #[graphql(name = "assignContact")]
+    #[graphql(name = "assignContact", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn assign_contact(
         &self,
         ctx: &Context<'_>,
@@ -673,25 +2249,44 @@ impl CrmMutation {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
         let contact_id = parse_uuid(&id)?;
+        visibility(ctx)?
+            .ensure::<contact::Entity>(db.as_ref(), contact_id)
+            .await?;
         let target_user = match user_id {
-            Some(uid) => Some(ensure_active_user(db.as_ref(), parse_uuid(&uid)?).await?),
+            Some(uid) => {
+                Some(ensure_active_user(db.as_ref(), current.org_id, parse_uuid(&uid)?).await?)
+            }
             None => None,
         };
         let now: DateTimeWithTimeZone = Utc::now().into();
+        let txn = db.begin().await.map_err(db_error)?;
         let contact = contact::Entity::find_by_id(contact_id)
-            .one(db.as_ref())
+            .filter(contact::Column::OrgId.eq(current.org_id))
+            .one(&txn)
             .await
             .map_err(db_error)?
             .ok_or_else(|| error_with_code("NOT_FOUND", "Contact not found"))?;
+        let before = contact.clone();
         let mut active: contact::ActiveModel = contact.into();
         active.assigned_user_id = Set(target_user);
         active.updated_by = Set(Some(current.user_id));
         active.updated_at = Set(now);
-        let updated = active.update(db.as_ref()).await.map_err(db_error)?;
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::Contact,
+            contact_id,
+            Some(&before),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
         Ok(updated.into())
     }
 
-    #[graphql(name = "assignDeal")]
+    #[graphql(name = "assignDeal", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn assign_deal(
         &self,
         ctx: &Context<'_>,
@@ -701,25 +2296,45 @@ impl CrmMutation {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
         let deal_id = parse_uuid(&id)?;
+        visibility(ctx)?
+            .ensure::<deal::Entity>(db.as_ref(), deal_id)
+            .await?;
         let target_user = match user_id {
-            Some(uid) => Some(ensure_active_user(db.as_ref(), parse_uuid(&uid)?).await?),
+            Some(uid) => {
+                Some(ensure_active_user(db.as_ref(), current.org_id, parse_uuid(&uid)?).await?)
+            }
             None => None,
         };
         let now: DateTimeWithTimeZone = Utc::now().into();
+        let txn = db.begin().await.map_err(db_error)?;
         let deal = deal::Entity::find_by_id(deal_id)
-            .one(db.as_ref())
+            .filter(deal::Column::OrgId.eq(current.org_id))
+            .one(&txn)
             .await
             .map_err(db_error)?
             .ok_or_else(|| error_with_code("NOT_FOUND", "Deal not found"))?;
+        let before = deal.clone();
         let mut active: deal::ActiveModel = deal.into();
         active.assigned_user_id = Set(target_user);
         active.updated_by = Set(Some(current.user_id));
         active.updated_at = Set(now);
-        let updated = active.update(db.as_ref()).await.map_err(db_error)?;
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::Deal,
+            deal_id,
+            Some(&before),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        publish(ctx, deal_updated_event(&updated)).await;
         Ok(updated.into())
     }
 
-    #[graphql(name = "assignTask")]
+    #[graphql(name = "assignTask", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn assign_task(
         &self,
         ctx: &Context<'_>,
@@ -729,50 +2344,164 @@ impl CrmMutation {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
         let task_id = parse_uuid(&id)?;
+        visibility(ctx)?
+            .ensure::<task::Entity>(db.as_ref(), task_id)
+            .await?;
         let target_user = match user_id {
-            Some(uid) => Some(ensure_active_user(db.as_ref(), parse_uuid(&uid)?).await?),
+            Some(uid) => {
+                Some(ensure_active_user(db.as_ref(), current.org_id, parse_uuid(&uid)?).await?)
+            }
             None => None,
         };
         let now: DateTimeWithTimeZone = Utc::now().into();
+        let txn = db.begin().await.map_err(db_error)?;
         let task = task::Entity::find_by_id(task_id)
-            .one(db.as_ref())
+            .filter(task::Column::OrgId.eq(current.org_id))
+            .one(&txn)
             .await
             .map_err(db_error)?
             .ok_or_else(|| error_with_code("NOT_FOUND", "Task not found"))?;
+        let before = task.clone();
         let mut active: task::ActiveModel = task.into();
         active.assigned_user_id = Set(target_user);
         active.updated_by = Set(Some(current.user_id));
         active.updated_at = Set(now);
-        let updated = active.update(db.as_ref()).await.map_err(db_error)?;
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &audit_actor(ctx)?,
+            audit_log::EntityType::Task,
+            task_id,
+            Some(&before),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        publish(ctx, task_changed_event(&updated, TaskChange::Updated)).await;
         Ok(TaskNode::from(updated))
     }
-    #[graphql(name = "moveDealStage")]
+
+    /// Creates a deal. Without `stage` the deal starts in the first pipeline stage;
+    /// a different `stage` is recorded as a stage change from that initial stage.
+    #[graphql(name = "createDeal", guard = "RoleGuard::new(UserRole::Sales)")]
+    async fn create_deal(
+        &self,
+        ctx: &Context<'_>,
+        input: NewDealInput,
+    ) -> async_graphql::Result<DealNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        visibility(ctx)?
+            .ensure::<company::Entity>(db.as_ref(), parse_uuid(&input.company_id)?)
+            .await?;
+        let deal = create_deal_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        publish(ctx, deal_updated_event(&deal)).await;
+        Ok(deal.into())
+    }
+
+    #[graphql(name = "updateDeal", guard = "RoleGuard::new(UserRole::Sales)")]
+    async fn update_deal(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateDealInput,
+    ) -> async_graphql::Result<DealNode> {
+        let current = current_user(ctx)?;
+        let db = database(ctx)?;
+        visibility(ctx)?
+            .ensure::<deal::Entity>(db.as_ref(), parse_uuid(&input.id)?)
+            .await?;
+        let deal = update_deal_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        publish(ctx, deal_updated_event(&deal)).await;
+        Ok(deal.into())
+    }
+
+    #[graphql(name = "moveDealStage", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn move_deal_stage(
         &self,
         ctx: &Context<'_>,
         id: ID,
         stage: DealStage,
         note: Option<String>,
+        #[graphql(name = "reasonId")] reason_id: Option<ID>,
+        competitor: Option<String>,
     ) -> async_graphql::Result<DealNode> {
-        let current = current_user(ctx)?;
         let db = database(ctx)?;
         let deal_id = parse_uuid(&id)?;
-        let target_stage: deal::Stage = stage.into();
+        visibility(ctx)?
+            .ensure::<deal::Entity>(db.as_ref(), deal_id)
+            .await?;
+        let actor = audit_actor(ctx)?;
+        let outcome = DealOutcomeDetails {
+            reason_id: parse_optional_id("reasonId", &reason_id)?,
+            competitor,
+        };
+        let (model, from_stage) =
+            move_deal_stage_internal(db.as_ref(), deal_id, &stage.0, note, outcome, &actor)
+                .await
+                .map_err(stage_move_error)?;
+
+        if let Some(from_stage) = from_stage {
+            let event = CrmEvent {
+                org_id: model.org_id,
+                assigned_user_id: model.assigned_user_id,
+                kind: CrmEventKind::DealStageMoved {
+                    deal_id: model.id,
+                    from: from_stage,
+                    to: model.stage.clone(),
+                    changed_by: actor.user_id,
+                },
+            };
+            publish(ctx, event).await;
+            publish(ctx, deal_updated_event(&model)).await;
+        }
+        Ok(model.into())
+    }
 
-        let model = move_deal_stage_internal(
+    /// Moves a deal into another pipeline. Without `stage` it lands in the matching
+    /// stage there: won to won, lost to lost, and an open stage to the open stage with
+    /// the nearest probability. The change is recorded like any stage move.
+    #[graphql(name = "moveDealToPipeline", guard = "RoleGuard::new(UserRole::Sales)")]
+    async fn move_deal_to_pipeline(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        #[graphql(name = "pipelineId")] pipeline_id: ID,
+        stage: Option<DealStage>,
+        note: Option<String>,
+    ) -> async_graphql::Result<DealNode> {
+        let db = database(ctx)?;
+        let deal_id = parse_uuid(&id)?;
+        visibility(ctx)?
+            .ensure::<deal::Entity>(db.as_ref(), deal_id)
+            .await?;
+        let actor = audit_actor(ctx)?;
+        let stage = stage.map(|stage| stage.0);
+        let (model, from_stage) = move_deal_to_pipeline_internal(
             db.as_ref(),
             deal_id,
-            target_stage,
+            &pipeline_id,
+            stage.as_deref(),
             note,
-            Some(current.user_id),
+            &actor,
         )
-        .await
-        .map_err(stage_move_error)?;
-
+        .await?;
+        let event = CrmEvent {
+            org_id: model.org_id,
+            assigned_user_id: model.assigned_user_id,
+            kind: CrmEventKind::DealStageMoved {
+                deal_id: model.id,
+                from: from_stage,
+                to: model.stage.clone(),
+                changed_by: actor.user_id,
+            },
+        };
+        publish(ctx, event).await;
+        publish(ctx, deal_updated_event(&model)).await;
         Ok(model.into())
     }
 
-    #[graphql(name = "createTask")]
+    #[graphql(name = "createTask", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn create_task(
         &self,
         ctx: &Context<'_>,
@@ -789,11 +2518,12 @@ impl CrmMutation {
             first = 0
         );
         let _guard = span.enter();
-        let task = create_task_internal(db.as_ref(), input, &current).await?;
+        let task = create_task_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        publish(ctx, task_changed_event(&task, TaskChange::Created)).await;
         Ok(task.into())
     }
 
-    #[graphql(name = "updateTask")]
+    #[graphql(name = "updateTask", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn update_task(
         &self,
         ctx: &Context<'_>,
@@ -801,16 +2531,21 @@ impl CrmMutation {
     ) -> async_graphql::Result<TaskNode> {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
-        let task = update_task_internal(db.as_ref(), input, &current).await?;
+        visibility(ctx)?
+            .ensure::<task::Entity>(db.as_ref(), parse_uuid(&input.id)?)
+            .await?;
+        let task = update_task_internal(db.as_ref(), input, &current, &audit_actor(ctx)?).await?;
+        publish(ctx, task_changed_event(&task, TaskChange::Updated)).await;
         Ok(task.into())
     }
 
-    #[graphql(name = "completeTask")]
+    #[graphql(name = "completeTask", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn complete_task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<TaskNode> {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
         let task_id = parse_uuid(&id)?;
         let existing = task::Entity::find_by_id(task_id)
+            .filter(visibility(ctx)?.scope::<task::Entity>())
             .one(db.as_ref())
             .await
             .map_err(db_error)?;
@@ -832,17 +2567,20 @@ impl CrmMutation {
             task::Status::Done,
             Some(Utc::now().into()),
             &current,
+            &audit_actor(ctx)?,
         )
         .await?;
+        publish(ctx, task_changed_event(&task, TaskChange::Updated)).await;
         Ok(task.into())
     }
 
-    #[graphql(name = "cancelTask")]
+    #[graphql(name = "cancelTask", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn cancel_task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<TaskNode> {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
         let task_id = parse_uuid(&id)?;
         let existing = task::Entity::find_by_id(task_id)
+            .filter(visibility(ctx)?.scope::<task::Entity>())
             .one(db.as_ref())
             .await
             .map_err(db_error)?;
@@ -864,17 +2602,20 @@ impl CrmMutation {
             task::Status::Cancelled,
             None,
             &current,
+            &audit_actor(ctx)?,
         )
         .await?;
+        publish(ctx, task_changed_event(&task, TaskChange::Updated)).await;
         Ok(task.into())
     }
 
-    #[graphql(name = "reopenTask")]
+    #[graphql(name = "reopenTask", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn reopen_task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<TaskNode> {
         let current = current_user(ctx)?;
         let db = database(ctx)?;
         let task_id = parse_uuid(&id)?;
         let existing = task::Entity::find_by_id(task_id)
+            .filter(visibility(ctx)?.scope::<task::Entity>())
             .one(db.as_ref())
             .await
             .map_err(db_error)?;
@@ -890,74 +2631,590 @@ impl CrmMutation {
             first = 0
         );
         let _guard = span.enter();
-        let task =
-            transition_task_status(db.as_ref(), existing, task::Status::Open, None, &current)
-                .await?;
+        let task = transition_task_status(
+            db.as_ref(),
+            existing,
+            task::Status::Open,
+            None,
+            &current,
+            &audit_actor(ctx)?,
+        )
+        .await?;
+        publish(ctx, task_changed_event(&task, TaskChange::Updated)).await;
         Ok(task.into())
     }
 
-    #[graphql(name = "deleteTask")]
+    #[graphql(name = "deleteTask", guard = "RoleGuard::new(UserRole::Sales)")]
     async fn delete_task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<bool> {
-        current_user(ctx)?;
         let db = database(ctx)?;
         let task_id = parse_uuid(&id)?;
-        let res = task::Entity::delete_by_id(task_id)
-            .exec(db.as_ref())
+        let existing = task::Entity::find_by_id(task_id)
+            .filter(visibility(ctx)?.scope::<task::Entity>())
+            .one(db.as_ref())
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| error_with_code("NOT_FOUND", "Task not found"))?;
+        let deleted = delete_task_internal(db.as_ref(), task_id, &audit_actor(ctx)?).await?;
+        if deleted {
+            publish(ctx, task_changed_event(&existing, TaskChange::Deleted)).await;
+        }
+        Ok(deleted)
+    }
+
+    #[graphql(name = "createDealReason", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn create_deal_reason(
+        &self,
+        ctx: &Context<'_>,
+        outcome: DealOutcome,
+        label: String,
+    ) -> async_graphql::Result<DealReason> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let label = validate_reason_label(&label)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let created = deal_reason::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            org_id: Set(actor.org_id),
+            outcome: Set(outcome.into()),
+            label: Set(label),
+            is_archived: Set(false),
+            created_at: Set(Utc::now().into()),
+        }
+        .insert(&txn)
+        .await
+        .map_err(deal_reason_write_error)?;
+        record_change(
+            &txn,
+            &actor,
+            audit_log::EntityType::DealReason,
+            created.id,
+            None,
+            Some(&created),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(created.into())
+    }
+
+    /// Relabels or archives a reason. Archived reasons stay on the deals that have them
+    /// but can no longer be picked.
+    #[graphql(name = "updateDealReason", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn update_deal_reason(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        label: Option<String>,
+        #[graphql(name = "isArchived")] is_archived: Option<bool>,
+    ) -> async_graphql::Result<DealReason> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let existing = deal_reason::Entity::find_by_id(parse_uuid(&id)?)
+            .filter(deal_reason::Column::OrgId.eq(actor.org_id))
+            .one(&txn)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| error_with_code("NOT_FOUND", "Reason not found"))?;
+        let mut active: deal_reason::ActiveModel = existing.clone().into();
+        if let Some(label) = label {
+            active.label = Set(validate_reason_label(&label)?);
+        }
+        if let Some(is_archived) = is_archived {
+            active.is_archived = Set(is_archived);
+        }
+        let updated = active.update(&txn).await.map_err(deal_reason_write_error)?;
+        record_change(
+            &txn,
+            &actor,
+            audit_log::EntityType::DealReason,
+            updated.id,
+            Some(&existing),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(updated.into())
+    }
+
+    /// Adds a pipeline with its own stages; deals join it through `createDeal` or
+    /// `moveDealToPipeline`.
+    #[graphql(name = "createPipeline", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn create_pipeline(
+        &self,
+        ctx: &Context<'_>,
+        input: NewPipelineInput,
+    ) -> async_graphql::Result<Pipeline> {
+        let actor = audit_actor(ctx)?;
+        let org_id = actor.org_id;
+        let db = database(ctx)?;
+        let name = validate_pipeline_name(&input.name)?;
+        if input.stages.is_empty() {
+            return Err(validation_error("A pipeline needs at least one stage"));
+        }
+        ensure_stage_capacity(input.stages.len())?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let created = pipeline::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            org_id: Set(org_id),
+            name: Set(name),
+            is_default: Set(false),
+            created_at: Set(Utc::now().into()),
+        }
+        .insert(&txn)
+        .await
+        .map_err(pipeline_write_error)?;
+        record_change(
+            &txn,
+            &actor,
+            audit_log::EntityType::Pipeline,
+            created.id,
+            None,
+            Some(&created),
+        )
+        .await
+        .map_err(db_error)?;
+        let mut keys = HashSet::new();
+        for (position, stage) in (1..).zip(&input.stages) {
+            let row = new_stage_row(org_id, created.id, position * STAGE_ORDER_STEP, stage)?;
+            let key = row.key.clone().unwrap();
+            if !keys.insert(key.clone()) {
+                return Err(validation_error(format!("Stage {} is listed twice", key)));
+            }
+            let stage = row.insert(&txn).await.map_err(db_error)?;
+            record_stage_changes(&txn, &actor, &[], &[stage]).await?;
+        }
+        txn.commit().await.map_err(db_error)?;
+        Ok(created.into())
+    }
+
+    #[graphql(name = "renamePipeline", guard = "RoleGuard::new(UserRole::Admin)")]
+    async fn rename_pipeline(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        name: String,
+    ) -> async_graphql::Result<Pipeline> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let name = validate_pipeline_name(&name)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let existing = resolve_pipeline(&txn, actor.org_id, Some(&id)).await?;
+        let mut active: pipeline::ActiveModel = existing.clone().into();
+        active.name = Set(name);
+        let updated = active.update(&txn).await.map_err(pipeline_write_error)?;
+        record_change(
+            &txn,
+            &actor,
+            audit_log::EntityType::Pipeline,
+            updated.id,
+            Some(&existing),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(updated.into())
+    }
+
+    /// Adds a stage to `input.pipelineId` before `input.before`, or after its last one.
+    #[graphql(
+        name = "createPipelineStage",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn create_pipeline_stage(
+        &self,
+        ctx: &Context<'_>,
+        input: NewPipelineStageInput,
+    ) -> async_graphql::Result<PipelineStage> {
+        let actor = audit_actor(ctx)?;
+        let org_id = actor.org_id;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, org_id, input.pipeline_id.as_ref()).await?;
+        let stages = load_stage_meta(&txn, pipeline.id, true).await?;
+        ensure_stage_capacity(stages.len() + 1)?;
+        let last = stages
+            .iter()
+            .map(|stage| stage.sort_order)
+            .max()
+            .unwrap_or(0);
+        let row = new_stage_row(org_id, pipeline.id, last + STAGE_ORDER_STEP, &input.stage)?;
+        let key = row.key.clone().unwrap();
+        if stages.iter().any(|stage| stage.key == key) {
+            return Err(error_with_code(
+                "CONFLICT",
+                format!("Stage {} already exists", key),
+            ));
+        }
+        let mut order: Vec<String> = stages.iter().map(|stage| stage.key.clone()).collect();
+        match input.before.as_deref().and_then(normalize_stage_key) {
+            Some(before) => {
+                let position = stages
+                    .iter()
+                    .position(|stage| stage.key == before && !stage.is_archived)
+                    .ok_or_else(|| unknown_stage_error(&before))?;
+                order.insert(position, key.clone());
+            }
+            None => order.push(key.clone()),
+        }
+        row.insert(&txn).await.map_err(db_error)?;
+        renumber_stages(&txn, pipeline.id, &order)
             .await
             .map_err(db_error)?;
-        Ok(res.rows_affected > 0)
+        let renumbered = load_stage_meta(&txn, pipeline.id, true).await?;
+        record_stage_changes(&txn, &actor, &stages, &renumbered).await?;
+        let created = find_stage(&txn, pipeline.id, &key).await?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(PipelineStage::from(&created))
+    }
+
+    /// Changes how a stage is shown; its key, and so every deal in it, stays the same.
+    #[graphql(
+        name = "renamePipelineStage",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn rename_pipeline_stage(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+        key: String,
+        #[graphql(name = "displayName")] display_name: String,
+    ) -> async_graphql::Result<PipelineStage> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let display_name = validate_stage_name(&display_name)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, actor.org_id, pipeline_id.as_ref()).await?;
+        let stage = find_stage(&txn, pipeline.id, &key).await?;
+        let mut active: stage_meta::ActiveModel = stage.clone().into();
+        active.display_name = Set(display_name);
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_stage_changes(&txn, &actor, &[stage], std::slice::from_ref(&updated)).await?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(PipelineStage::from(&updated))
+    }
+
+    /// Changes a stage's name, probability, outcome or WIP limit. Deals in a stage that
+    /// stops being won or lost drop their win/loss reason.
+    #[graphql(
+        name = "updatePipelineStage",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn update_pipeline_stage(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdatePipelineStageInput,
+    ) -> async_graphql::Result<PipelineStage> {
+        let actor = audit_actor(ctx)?;
+        let org_id = actor.org_id;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, org_id, input.pipeline_id.as_ref()).await?;
+        let stage = find_stage(&txn, pipeline.id, &input.key).await?;
+        let is_won = input.is_won.unwrap_or(stage.is_won);
+        let is_lost = input.is_lost.unwrap_or(stage.is_lost);
+        if is_won && is_lost {
+            return Err(validation_error("A stage cannot be both won and lost"));
+        }
+        let outcome_changed = (is_won, is_lost) != (stage.is_won, stage.is_lost);
+        let key = stage.key.clone();
+        let mut active: stage_meta::ActiveModel = stage.clone().into();
+        if let Some(display_name) = &input.display_name {
+            active.display_name = Set(validate_stage_name(display_name)?);
+        }
+        if let Some(probability) = input.probability {
+            active.probability = Set(validate_probability(probability)?);
+        }
+        active.is_won = Set(is_won);
+        active.is_lost = Set(is_lost);
+        match input.wip_limit {
+            MaybeUndefined::Value(limit) => {
+                active.wip_limit = Set(validate_wip_limit(Some(limit))?)
+            }
+            MaybeUndefined::Null => active.wip_limit = Set(None),
+            MaybeUndefined::Undefined => {}
+        }
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &actor,
+            audit_log::EntityType::Stage,
+            updated.id,
+            Some(&stage),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        if outcome_changed {
+            deal::Entity::update_many()
+                .col_expr(deal::Column::ReasonId, Expr::value(Option::<Uuid>::None))
+                .col_expr(
+                    deal::Column::Competitor,
+                    Expr::value(Option::<String>::None),
+                )
+                .filter(deal::Column::PipelineId.eq(pipeline.id))
+                .filter(deal::Column::Stage.eq(key))
+                .exec(&txn)
+                .await
+                .map_err(db_error)?;
+        }
+        txn.commit().await.map_err(db_error)?;
+        Ok(PipelineStage::from(&updated))
     }
-}
 
-#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
-pub enum DealStage {
-    New,
-    Qualify,
-    Proposal,
-    Negotiate,
-    Won,
-    Lost,
-}
+    /// Puts the pipeline's active stages in the order of `keys`, which must name each of
+    /// them once. Archived stages keep their relative order after them.
+    #[graphql(
+        name = "reorderPipelineStages",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn reorder_pipeline_stages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+        keys: Vec<String>,
+    ) -> async_graphql::Result<Vec<PipelineStage>> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, actor.org_id, pipeline_id.as_ref()).await?;
+        let stages = load_stage_meta(&txn, pipeline.id, true).await?;
+        let mut order = Vec::with_capacity(stages.len());
+        for key in &keys {
+            let key = normalize_stage_key(key)
+                .ok_or_else(|| validation_error("keys cannot contain blank values"))?;
+            if !stages
+                .iter()
+                .any(|stage| stage.key == key && !stage.is_archived)
+            {
+                return Err(unknown_stage_error(&key));
+            }
+            if order.contains(&key) {
+                return Err(validation_error(format!("Stage {} is listed twice", key)));
+            }
+            order.push(key);
+        }
+        if order.len() != stages.iter().filter(|stage| !stage.is_archived).count() {
+            return Err(validation_error("keys must list every active stage"));
+        }
+        order.extend(
+            stages
+                .iter()
+                .filter(|stage| stage.is_archived)
+                .map(|stage| stage.key.clone()),
+        );
+        renumber_stages(&txn, pipeline.id, &order)
+            .await
+            .map_err(db_error)?;
+        let renumbered = load_stage_meta(&txn, pipeline.id, true).await?;
+        record_stage_changes(&txn, &actor, &stages, &renumbered).await?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(renumbered
+            .iter()
+            .filter(|stage| !stage.is_archived)
+            .map(PipelineStage::from)
+            .collect())
+    }
 
-impl From<deal::Stage> for DealStage {
-    fn from(value: deal::Stage) -> Self {
-        match value {
-            deal::Stage::New => DealStage::New,
-            deal::Stage::Qualify => DealStage::Qualify,
-            deal::Stage::Proposal => DealStage::Proposal,
-            deal::Stage::Negotiate => DealStage::Negotiate,
-            deal::Stage::Won => DealStage::Won,
-            deal::Stage::Lost => DealStage::Lost,
+    /// Retires an empty stage: it leaves the board and takes no more deals. Stages that
+    /// still hold deals are merged instead.
+    #[graphql(
+        name = "archivePipelineStage",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn archive_pipeline_stage(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+        key: String,
+    ) -> async_graphql::Result<PipelineStage> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, actor.org_id, pipeline_id.as_ref()).await?;
+        let stage = find_stage(&txn, pipeline.id, &key).await?;
+        let deals = deal::Entity::find()
+            .filter(deal::Column::PipelineId.eq(pipeline.id))
+            .filter(deal::Column::Stage.eq(stage.key.as_str()))
+            .count(&txn)
+            .await
+            .map_err(db_error)?;
+        if deals > 0 {
+            return Err(error_with_code(
+                "CONFLICT",
+                format!(
+                    "Stage {} still has {} deals; merge it into another stage instead",
+                    stage.key, deals
+                ),
+            ));
         }
+        ensure_other_active_stage(&txn, stage.pipeline_id, &stage.key).await?;
+        let updated = archive_stage(&txn, &actor, stage).await?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(PipelineStage::from(&updated))
     }
-}
 
-impl From<DealStage> for deal::Stage {
-    fn from(value: DealStage) -> Self {
-        match value {
-            DealStage::New => deal::Stage::New,
-            DealStage::Qualify => deal::Stage::Qualify,
-            DealStage::Proposal => deal::Stage::Proposal,
-            DealStage::Negotiate => deal::Stage::Negotiate,
-            DealStage::Won => deal::Stage::Won,
-            DealStage::Lost => deal::Stage::Lost,
+    #[graphql(
+        name = "restorePipelineStage",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn restore_pipeline_stage(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+        key: String,
+    ) -> async_graphql::Result<PipelineStage> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, actor.org_id, pipeline_id.as_ref()).await?;
+        let stage = find_stage(&txn, pipeline.id, &key).await?;
+        let mut active: stage_meta::ActiveModel = stage.clone().into();
+        active.is_archived = Set(false);
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_stage_changes(&txn, &actor, &[stage], std::slice::from_ref(&updated)).await?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(PipelineStage::from(&updated))
+    }
+
+    /// Replaces the rules deals must satisfy to enter stage `key`. `allowedFrom` only
+    /// governs moves within the pipeline; deals arriving from another pipeline still
+    /// need the required fields and note.
+    #[graphql(
+        name = "setPipelineStageRules",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn set_pipeline_stage_rules(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+        key: String,
+        rules: StageRulesInput,
+    ) -> async_graphql::Result<PipelineStage> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, actor.org_id, pipeline_id.as_ref()).await?;
+        let stage = find_stage(&txn, pipeline.id, &key).await?;
+        let allowed_from = match rules.allowed_from {
+            Some(keys) => {
+                let siblings = load_stage_meta(&txn, stage.pipeline_id, true).await?;
+                let mut allowed = Vec::with_capacity(keys.len());
+                for key in keys {
+                    let key = normalize_stage_key(&key).ok_or_else(|| {
+                        validation_error("allowedFrom cannot contain blank values")
+                    })?;
+                    if !siblings.iter().any(|sibling| sibling.key == key) {
+                        return Err(unknown_stage_error(&key));
+                    }
+                    if !allowed.contains(&key) {
+                        allowed.push(key);
+                    }
+                }
+                Some(json!(allowed))
+            }
+            None => None,
+        };
+        let mut required: Vec<&str> = Vec::new();
+        for field in rules.required_fields {
+            if !required.contains(&field.key()) {
+                required.push(field.key());
+            }
+        }
+        let mut active: stage_meta::ActiveModel = stage.clone().into();
+        active.allowed_from = Set(allowed_from);
+        active.required_fields = Set(json!(required));
+        active.requires_note = Set(rules.requires_note);
+        let updated = active.update(&txn).await.map_err(db_error)?;
+        record_change(
+            &txn,
+            &actor,
+            audit_log::EntityType::Stage,
+            updated.id,
+            Some(&stage),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        txn.commit().await.map_err(db_error)?;
+        Ok(PipelineStage::from(&updated))
+    }
+
+    /// Folds `source` into `target`: its deals move to `target`, rules that admitted deals
+    /// from `source` admit them from `target`, and `source` is archived. Stage history keeps
+    /// naming `source` for the moves that really went through it.
+    #[graphql(
+        name = "mergePipelineStages",
+        guard = "RoleGuard::new(UserRole::Admin)"
+    )]
+    async fn merge_pipeline_stages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(name = "pipelineId")] pipeline_id: Option<ID>,
+        source: String,
+        target: String,
+    ) -> async_graphql::Result<PipelineStage> {
+        let actor = audit_actor(ctx)?;
+        let db = database(ctx)?;
+        let txn = db.begin().await.map_err(db_error)?;
+        let pipeline = resolve_pipeline(&txn, actor.org_id, pipeline_id.as_ref()).await?;
+        let source = find_stage(&txn, pipeline.id, &source).await?;
+        let target = find_stage(&txn, pipeline.id, &target).await?;
+        if source.key == target.key {
+            return Err(validation_error("A stage cannot be merged into itself"));
+        }
+        if target.is_archived {
+            return Err(unknown_stage_error(&target.key));
+        }
+        let moved = merge_stage_internal(&txn, &actor, source, &target.key).await?;
+        txn.commit().await.map_err(db_error)?;
+        for deal in &moved {
+            publish(ctx, deal_updated_event(deal)).await;
         }
+        Ok(PipelineStage::from(&target))
     }
 }
 
-#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+/// Key of a pipeline stage, as listed by `pipelineStages`; keys repeat across pipelines.
+/// Parsed from a string or, as when stages were a fixed enum, a bare name like `WON`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DealStage(pub String);
+
+#[Scalar(name = "DealStage")]
+impl ScalarType for DealStage {
+    fn parse(value: async_graphql::Value) -> InputValueResult<Self> {
+        let key = match &value {
+            async_graphql::Value::String(key) => key.as_str(),
+            async_graphql::Value::Enum(name) => name.as_str(),
+            _ => return Err(InputValueError::expected_type(value)),
+        };
+        normalize_stage_key(key)
+            .map(DealStage)
+            .ok_or_else(|| InputValueError::custom("Stage cannot be blank"))
+    }
+
+    fn is_valid(value: &async_graphql::Value) -> bool {
+        matches!(
+            value,
+            async_graphql::Value::String(_) | async_graphql::Value::Enum(_)
+        )
+    }
+
+    fn to_value(&self) -> async_graphql::Value {
+        async_graphql::Value::String(self.0.clone())
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug, Default)]
 pub enum TaskStatus {
+    #[default]
     Open,
     Done,
     Cancelled,
 }
 
-impl Default for TaskStatus {
-    fn default() -> Self {
-        TaskStatus::Open
-    }
-}
-
 impl TaskStatus {
     fn as_str(self) -> &'static str {
         match self {
@@ -988,19 +3245,14 @@ impl From<TaskStatus> for task::Status {
     }
 }
 
-#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug, Default)]
 pub enum TaskPriority {
     Low,
+    #[default]
     Medium,
     High,
 }
 
-impl Default for TaskPriority {
-    fn default() -> Self {
-        TaskPriority::Medium
-    }
-}
-
 impl TaskPriority {
     fn as_str(self) -> &'static str {
         match self {
@@ -1031,20 +3283,15 @@ impl From<TaskPriority> for task::Priority {
     }
 }
 
-#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug, Default)]
 pub enum TaskOrder {
+    #[default]
     DueAsc,
     DueDesc,
     PriorityDesc,
     UpdatedDesc,
 }
 
-impl Default for TaskOrder {
-    fn default() -> Self {
-        TaskOrder::DueAsc
-    }
-}
-
 impl TaskOrder {
     fn as_str(self) -> &'static str {
         match self {
@@ -1057,19 +3304,99 @@ impl TaskOrder {
 }
 
 #[derive(InputObject, Default, Clone)]
-pub struct TaskFilter {
+pub struct TaskFilter {
+    #[graphql(name = "companyId")]
+    pub company_id: Option<ID>,
+    #[graphql(name = "contactId")]
+    pub contact_id: Option<ID>,
+    #[graphql(name = "dealId")]
+    pub deal_id: Option<ID>,
+    pub status: Option<TaskStatus>,
+    pub priority: Option<TaskPriority>,
+    #[graphql(name = "dueBefore")]
+    pub due_before: Option<DateTime<Utc>>,
+    #[graphql(name = "dueAfter")]
+    pub due_after: Option<DateTime<Utc>>,
+    pub q: Option<String>,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum CompanyOrder {
+    #[default]
+    NameAsc,
+    CreatedDesc,
+    UpdatedDesc,
+}
+
+/// `createdAfter`/`updatedAfter` bounds are inclusive, `*Before` bounds exclusive.
+#[derive(InputObject, Default, Clone)]
+pub struct CompanyFilter {
+    #[graphql(name = "assignedUserId")]
+    pub assigned_user_id: Option<ID>,
+    #[graphql(name = "createdAfter")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[graphql(name = "createdBefore")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[graphql(name = "updatedAfter")]
+    pub updated_after: Option<DateTime<Utc>>,
+    #[graphql(name = "updatedBefore")]
+    pub updated_before: Option<DateTime<Utc>>,
+    pub q: Option<String>,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ContactOrder {
+    #[default]
+    NameAsc,
+    EmailAsc,
+    CreatedDesc,
+    UpdatedDesc,
+}
+
+#[derive(InputObject, Default, Clone)]
+pub struct ContactFilter {
+    #[graphql(name = "assignedUserId")]
+    pub assigned_user_id: Option<ID>,
+    #[graphql(name = "companyId")]
+    pub company_id: Option<ID>,
+    #[graphql(name = "createdAfter")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[graphql(name = "createdBefore")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[graphql(name = "updatedAfter")]
+    pub updated_after: Option<DateTime<Utc>>,
+    #[graphql(name = "updatedBefore")]
+    pub updated_before: Option<DateTime<Utc>>,
+    pub q: Option<String>,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum DealOrder {
+    #[default]
+    UpdatedDesc,
+    CreatedDesc,
+    TitleAsc,
+    AmountDesc,
+    CloseDateAsc,
+}
+
+#[derive(InputObject, Default, Clone)]
+pub struct DealFilter {
+    #[graphql(name = "assignedUserId")]
+    pub assigned_user_id: Option<ID>,
     #[graphql(name = "companyId")]
     pub company_id: Option<ID>,
-    #[graphql(name = "contactId")]
-    pub contact_id: Option<ID>,
-    #[graphql(name = "dealId")]
-    pub deal_id: Option<ID>,
-    pub status: Option<TaskStatus>,
-    pub priority: Option<TaskPriority>,
-    #[graphql(name = "dueBefore")]
-    pub due_before: Option<DateTime<Utc>>,
-    #[graphql(name = "dueAfter")]
-    pub due_after: Option<DateTime<Utc>>,
+    #[graphql(name = "pipelineId")]
+    pub pipeline_id: Option<ID>,
+    pub stage: Option<DealStage>,
+    #[graphql(name = "createdAfter")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[graphql(name = "createdBefore")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[graphql(name = "updatedAfter")]
+    pub updated_after: Option<DateTime<Utc>>,
+    #[graphql(name = "updatedBefore")]
+    pub updated_before: Option<DateTime<Utc>>,
     pub q: Option<String>,
 }
 
@@ -1103,8 +3430,85 @@ pub struct UpdateTaskInput {
     pub due_at: Option<DateTime<Utc>>,
 }
 
+#[derive(InputObject, Clone)]
+pub struct NewCompanyInput {
+    pub name: String,
+    pub website: Option<String>,
+    pub phone: Option<String>,
+    #[graphql(name = "assignedUserId")]
+    pub assigned_user_id: Option<ID>,
+}
+
+/// Omitted fields are left untouched; an empty `website` or `phone` clears it.
+#[derive(InputObject, Clone)]
+pub struct UpdateCompanyInput {
+    pub id: ID,
+    pub name: Option<String>,
+    pub website: Option<String>,
+    pub phone: Option<String>,
+}
+
+#[derive(InputObject, Clone)]
+pub struct NewContactInput {
+    pub email: String,
+    #[graphql(name = "firstName")]
+    pub first_name: Option<String>,
+    #[graphql(name = "lastName")]
+    pub last_name: Option<String>,
+    pub phone: Option<String>,
+    #[graphql(name = "companyId")]
+    pub company_id: Option<ID>,
+    #[graphql(name = "assignedUserId")]
+    pub assigned_user_id: Option<ID>,
+}
+
+/// Omitted fields are left untouched; empty names or phone clear the value.
+#[derive(InputObject, Clone)]
+pub struct UpdateContactInput {
+    pub id: ID,
+    pub email: Option<String>,
+    #[graphql(name = "firstName")]
+    pub first_name: Option<String>,
+    #[graphql(name = "lastName")]
+    pub last_name: Option<String>,
+    pub phone: Option<String>,
+}
+
+#[derive(InputObject, Clone)]
+pub struct NewDealInput {
+    pub title: String,
+    #[graphql(name = "companyId")]
+    pub company_id: ID,
+    #[graphql(name = "amountCents")]
+    pub amount_cents: Option<i64>,
+    pub currency: Option<String>,
+    #[graphql(name = "closeDate")]
+    pub close_date: Option<NaiveDate>,
+    /// Defaults to the organization's default pipeline.
+    #[graphql(name = "pipelineId")]
+    pub pipeline_id: Option<ID>,
+    /// A stage of the deal's pipeline; defaults to its first.
+    pub stage: Option<DealStage>,
+    pub note: Option<String>,
+    #[graphql(name = "assignedUserId")]
+    pub assigned_user_id: Option<ID>,
+}
+
+/// Omitted fields are left untouched; an empty `currency` or an explicit null
+/// `amountCents`/`closeDate` clears the value. Stage changes go through `moveDealStage`.
+#[derive(InputObject, Clone)]
+pub struct UpdateDealInput {
+    pub id: ID,
+    pub title: Option<String>,
+    #[graphql(name = "amountCents")]
+    pub amount_cents: MaybeUndefined<i64>,
+    pub currency: Option<String>,
+    #[graphql(name = "closeDate")]
+    pub close_date: MaybeUndefined<NaiveDate>,
+}
+
 #[derive(Clone, Debug, SimpleObject)]
-#[graphql(name = "Company")]
+#[graphql(name = "Company", complex)]
 pub struct CompanyNode {
     pub id: ID,
     pub name: String,
@@ -1112,10 +3516,10 @@ pub struct CompanyNode {
     pub phone: Option<String>,
     #[graphql(name = "assignedUserId")]
     pub assigned_user_id: Option<ID>,
-    #[graphql(name = "createdBy")]
-    pub created_by: Option<ID>,
-    #[graphql(name = "updatedBy")]
-    pub updated_by: Option<ID>,
+    #[graphql(skip)]
+    pub created_by: Option<Uuid>,
+    #[graphql(skip)]
+    pub updated_by: Option<Uuid>,
     #[graphql(name = "createdAt")]
     pub created_at: DateTime<Utc>,
     #[graphql(name = "updatedAt")]
@@ -1130,8 +3534,8 @@ impl From<company::Model> for CompanyNode {
             website: model.website,
             phone: model.phone,
             assigned_user_id: model.assigned_user_id.map(|id| ID::from(id.to_string())),
-            created_by: model.created_by.map(|id| ID::from(id.to_string())),
-            updated_by: model.updated_by.map(|id| ID::from(id.to_string())),
+            created_by: model.created_by,
+            updated_by: model.updated_by,
             created_at: model.created_at.into(),
             updated_at: model.updated_at.into(),
         }
@@ -1139,7 +3543,7 @@ impl From<company::Model> for CompanyNode {
 }
 
 #[derive(Clone, Debug, SimpleObject)]
-#[graphql(name = "Contact")]
+#[graphql(name = "Contact", complex)]
 pub struct ContactNode {
     pub id: ID,
     pub email: String,
@@ -1152,10 +3556,10 @@ pub struct ContactNode {
     pub company_id: Option<ID>,
     #[graphql(name = "assignedUserId")]
     pub assigned_user_id: Option<ID>,
-    #[graphql(name = "createdBy")]
-    pub created_by: Option<ID>,
-    #[graphql(name = "updatedBy")]
-    pub updated_by: Option<ID>,
+    #[graphql(skip)]
+    pub created_by: Option<Uuid>,
+    #[graphql(skip)]
+    pub updated_by: Option<Uuid>,
     #[graphql(name = "createdAt")]
     pub created_at: DateTime<Utc>,
     #[graphql(name = "updatedAt")]
@@ -1172,8 +3576,8 @@ impl From<contact::Model> for ContactNode {
             phone: model.phone,
             company_id: model.company_id.map(|id| ID::from(id.to_string())),
             assigned_user_id: model.assigned_user_id.map(|id| ID::from(id.to_string())),
-            created_by: model.created_by.map(|id| ID::from(id.to_string())),
-            updated_by: model.updated_by.map(|id| ID::from(id.to_string())),
+            created_by: model.created_by,
+            updated_by: model.updated_by,
             created_at: model.created_at.into(),
             updated_at: model.updated_at.into(),
         }
@@ -1181,7 +3585,7 @@ impl From<contact::Model> for ContactNode {
 }
 
 #[derive(Clone, Debug, SimpleObject)]
-#[graphql(name = "Task")]
+#[graphql(name = "Task", complex)]
 pub struct TaskNode {
     pub id: ID,
     pub title: String,
@@ -1202,10 +3606,10 @@ pub struct TaskNode {
     pub contact_id: Option<ID>,
     #[graphql(name = "dealId")]
     pub deal_id: Option<ID>,
-    #[graphql(name = "createdBy")]
-    pub created_by: Option<ID>,
-    #[graphql(name = "updatedBy")]
-    pub updated_by: Option<ID>,
+    #[graphql(skip)]
+    pub created_by: Option<Uuid>,
+    #[graphql(skip)]
+    pub updated_by: Option<Uuid>,
     #[graphql(name = "createdAt")]
     pub created_at: DateTime<Utc>,
     #[graphql(name = "updatedAt")]
@@ -1227,8 +3631,8 @@ impl From<task::Model> for TaskNode {
             company_id: model.company_id.map(|id| ID::from(id.to_string())),
             contact_id: model.contact_id.map(|id| ID::from(id.to_string())),
             deal_id: model.deal_id.map(|id| ID::from(id.to_string())),
-            created_by: model.created_by.map(|id| ID::from(id.to_string())),
-            updated_by: model.updated_by.map(|id| ID::from(id.to_string())),
+            created_by: model.created_by,
+            updated_by: model.updated_by,
             created_at: model.created_at.into(),
             updated_at: model.updated_at.into(),
         }
@@ -1236,13 +3640,15 @@ impl From<task::Model> for TaskNode {
 }
 
 #[derive(Clone, Debug, SimpleObject)]
-#[graphql(name = "Deal")]
+#[graphql(name = "Deal", complex)]
 pub struct DealNode {
     pub id: ID,
     pub title: String,
     #[graphql(name = "amountCents")]
     pub amount_cents: Option<i64>,
     pub currency: Option<String>,
+    #[graphql(name = "pipelineId")]
+    pub pipeline_id: ID,
     pub stage: DealStage,
     #[graphql(name = "closeDate")]
     pub close_date: Option<NaiveDate>,
@@ -1250,63 +3656,448 @@ pub struct DealNode {
     pub company_id: ID,
     #[graphql(name = "assignedUserId")]
     pub assigned_user_id: Option<ID>,
-    #[graphql(name = "createdBy")]
-    pub created_by: Option<ID>,
-    #[graphql(name = "updatedBy")]
-    pub updated_by: Option<ID>,
+    #[graphql(name = "reasonId")]
+    pub reason_id: Option<ID>,
+    pub competitor: Option<String>,
+    #[graphql(skip)]
+    pub created_by: Option<Uuid>,
+    #[graphql(skip)]
+    pub updated_by: Option<Uuid>,
     #[graphql(name = "createdAt")]
     pub created_at: DateTime<Utc>,
     #[graphql(name = "updatedAt")]
     pub updated_at: DateTime<Utc>,
 }
 
-impl From<deal::Model> for DealNode {
-    fn from(model: deal::Model) -> Self {
+impl From<deal::Model> for DealNode {
+    fn from(model: deal::Model) -> Self {
+        Self {
+            id: ID::from(model.id.to_string()),
+            title: model.title,
+            amount_cents: model.amount_cents,
+            currency: model.currency,
+            pipeline_id: ID::from(model.pipeline_id.to_string()),
+            stage: DealStage(model.stage),
+            close_date: model.close_date,
+            company_id: ID::from(model.company_id.to_string()),
+            assigned_user_id: model.assigned_user_id.map(|id| ID::from(id.to_string())),
+            reason_id: model.reason_id.map(|id| ID::from(id.to_string())),
+            competitor: model.competitor,
+            created_by: model.created_by,
+            updated_by: model.updated_by,
+            created_at: model.created_at.into(),
+            updated_at: model.updated_at.into(),
+        }
+    }
+}
+
+#[ComplexObject]
+impl CompanyNode {
+    #[graphql(name = "assignedUser")]
+    async fn assigned_user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(
+            ctx,
+            parse_optional_id("assignedUserId", &self.assigned_user_id)?,
+        )
+        .await
+    }
+
+    #[graphql(name = "createdBy")]
+    async fn created_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.created_by).await
+    }
+
+    #[graphql(name = "updatedBy")]
+    async fn updated_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.updated_by).await
+    }
+
+    async fn contacts(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<ContactNode>> {
+        let key = CompanyContactsKey(parse_uuid(&self.id)?, visibility(ctx)?);
+        let rows = crm_loader(ctx)?.load_one(key).await?;
+        Ok(rows
+            .unwrap_or_default()
+            .into_iter()
+            .map(ContactNode::from)
+            .collect())
+    }
+
+    async fn deals(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<DealNode>> {
+        let key = CompanyDealsKey(parse_uuid(&self.id)?, visibility(ctx)?);
+        let rows = crm_loader(ctx)?.load_one(key).await?;
+        Ok(rows
+            .unwrap_or_default()
+            .into_iter()
+            .map(DealNode::from)
+            .collect())
+    }
+}
+
+#[ComplexObject]
+impl ContactNode {
+    async fn company(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<CompanyNode>> {
+        load_company(ctx, parse_optional_id("companyId", &self.company_id)?).await
+    }
+
+    #[graphql(name = "assignedUser")]
+    async fn assigned_user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(
+            ctx,
+            parse_optional_id("assignedUserId", &self.assigned_user_id)?,
+        )
+        .await
+    }
+
+    #[graphql(name = "createdBy")]
+    async fn created_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.created_by).await
+    }
+
+    #[graphql(name = "updatedBy")]
+    async fn updated_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.updated_by).await
+    }
+}
+
+#[ComplexObject]
+impl TaskNode {
+    #[graphql(name = "assignedUser")]
+    async fn assigned_user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(
+            ctx,
+            parse_optional_id("assignedUserId", &self.assigned_user_id)?,
+        )
+        .await
+    }
+
+    async fn company(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<CompanyNode>> {
+        load_company(ctx, parse_optional_id("companyId", &self.company_id)?).await
+    }
+
+    async fn contact(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ContactNode>> {
+        let Some(contact_id) = parse_optional_id("contactId", &self.contact_id)? else {
+            return Ok(None);
+        };
+        let record = crm_loader(ctx)?
+            .load_one(ContactKey(contact_id, current_user(ctx)?.org_id))
+            .await?;
+        Ok(record.map(ContactNode::from))
+    }
+
+    async fn deal(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<DealNode>> {
+        let Some(deal_id) = parse_optional_id("dealId", &self.deal_id)? else {
+            return Ok(None);
+        };
+        let record = crm_loader(ctx)?
+            .load_one(DealKey(deal_id, current_user(ctx)?.org_id))
+            .await?;
+        Ok(record.map(DealNode::from))
+    }
+
+    #[graphql(name = "createdBy")]
+    async fn created_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.created_by).await
+    }
+
+    #[graphql(name = "updatedBy")]
+    async fn updated_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.updated_by).await
+    }
+}
+
+#[ComplexObject]
+impl DealNode {
+    async fn company(&self, ctx: &Context<'_>) -> async_graphql::Result<CompanyNode> {
+        load_company(ctx, Some(parse_uuid(&self.company_id)?))
+            .await?
+            .ok_or_else(|| error_with_code("INTERNAL", "Deal company not found"))
+    }
+
+    async fn reason(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<DealReason>> {
+        let Some(reason_id) = parse_optional_id("reasonId", &self.reason_id)? else {
+            return Ok(None);
+        };
+        let reason = deal_reason::Entity::find_by_id(reason_id)
+            .one(database(ctx)?.as_ref())
+            .await
+            .map_err(db_error)?;
+        Ok(reason.map(DealReason::from))
+    }
+
+    async fn tasks(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<TaskNode>> {
+        let key = DealTasksKey(parse_uuid(&self.id)?, visibility(ctx)?);
+        let rows = crm_loader(ctx)?.load_one(key).await?;
+        Ok(rows
+            .unwrap_or_default()
+            .into_iter()
+            .map(TaskNode::from)
+            .collect())
+    }
+
+    #[graphql(name = "assignedUser")]
+    async fn assigned_user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(
+            ctx,
+            parse_optional_id("assignedUserId", &self.assigned_user_id)?,
+        )
+        .await
+    }
+
+    #[graphql(name = "createdBy")]
+    async fn created_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.created_by).await
+    }
+
+    #[graphql(name = "updatedBy")]
+    async fn updated_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, self.updated_by).await
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "DealStageHistory")]
+pub struct DealStageHistoryNode {
+    pub id: ID,
+    #[graphql(name = "dealId")]
+    pub deal_id: ID,
+    #[graphql(name = "fromStage")]
+    pub from_stage: DealStage,
+    #[graphql(name = "toStage")]
+    pub to_stage: DealStage,
+    #[graphql(name = "fromPipelineId")]
+    pub from_pipeline_id: ID,
+    #[graphql(name = "toPipelineId")]
+    pub to_pipeline_id: ID,
+    #[graphql(name = "note")]
+    pub note: Option<String>,
+    #[graphql(name = "reasonId")]
+    pub reason_id: Option<ID>,
+    pub competitor: Option<String>,
+    #[graphql(name = "changedAt")]
+    pub changed_at: DateTime<Utc>,
+    #[graphql(name = "changedBy")]
+    pub changed_by: Option<String>,
+}
+
+impl From<deal_stage_history::Model> for DealStageHistoryNode {
+    fn from(model: deal_stage_history::Model) -> Self {
+        Self {
+            id: ID::from(model.id.to_string()),
+            deal_id: ID::from(model.deal_id.to_string()),
+            from_stage: DealStage(model.from_stage),
+            to_stage: DealStage(model.to_stage),
+            from_pipeline_id: ID::from(model.from_pipeline_id.to_string()),
+            to_pipeline_id: ID::from(model.to_pipeline_id.to_string()),
+            note: model.note,
+            reason_id: model.reason_id.map(|id| ID::from(id.to_string())),
+            competitor: model.competitor,
+            changed_at: model.changed_at.into(),
+            changed_by: model.changed_by,
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct DealStageMove {
+    pub deal: DealNode,
+    #[graphql(name = "fromStage")]
+    pub from_stage: DealStage,
+    #[graphql(name = "toStage")]
+    pub to_stage: DealStage,
+    #[graphql(name = "changedBy")]
+    pub changed_by: Option<ID>,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+#[graphql(name = "TaskChange")]
+pub enum TaskChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl From<TaskChange> for TaskChangeKind {
+    fn from(value: TaskChange) -> Self {
+        match value {
+            TaskChange::Created => TaskChangeKind::Created,
+            TaskChange::Updated => TaskChangeKind::Updated,
+            TaskChange::Deleted => TaskChangeKind::Deleted,
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "TaskChanged")]
+pub struct TaskChangedEvent {
+    pub change: TaskChangeKind,
+    #[graphql(name = "taskId")]
+    pub task_id: ID,
+    /// The task as it is now; null once deleted.
+    pub task: Option<TaskNode>,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AuthEventKind {
+    LoginSucceeded,
+    LoginFailed,
+    LockedOut,
+    Logout,
+    PasswordChanged,
+    PasswordReset,
+}
+
+impl From<auth_event::Kind> for AuthEventKind {
+    fn from(value: auth_event::Kind) -> Self {
+        match value {
+            auth_event::Kind::LoginSucceeded => AuthEventKind::LoginSucceeded,
+            auth_event::Kind::LoginFailed => AuthEventKind::LoginFailed,
+            auth_event::Kind::LockedOut => AuthEventKind::LockedOut,
+            auth_event::Kind::Logout => AuthEventKind::Logout,
+            auth_event::Kind::PasswordChanged => AuthEventKind::PasswordChanged,
+            auth_event::Kind::PasswordReset => AuthEventKind::PasswordReset,
+        }
+    }
+}
+
+impl From<AuthEventKind> for auth_event::Kind {
+    fn from(value: AuthEventKind) -> Self {
+        match value {
+            AuthEventKind::LoginSucceeded => auth_event::Kind::LoginSucceeded,
+            AuthEventKind::LoginFailed => auth_event::Kind::LoginFailed,
+            AuthEventKind::LockedOut => auth_event::Kind::LockedOut,
+            AuthEventKind::Logout => auth_event::Kind::Logout,
+            AuthEventKind::PasswordChanged => auth_event::Kind::PasswordChanged,
+            AuthEventKind::PasswordReset => auth_event::Kind::PasswordReset,
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "AuthEvent")]
+pub struct AuthEventNode {
+    pub id: ID,
+    pub kind: AuthEventKind,
+    #[graphql(name = "userId")]
+    pub user_id: Option<ID>,
+    /// The email the attempt was made with, known account or not.
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    #[graphql(name = "userAgent")]
+    pub user_agent: Option<String>,
+    /// Why an attempt failed (`bad_password`, `locked`, ...), or how it succeeded.
+    pub detail: Option<String>,
+    #[graphql(name = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<auth_event::Model> for AuthEventNode {
+    fn from(model: auth_event::Model) -> Self {
         Self {
             id: ID::from(model.id.to_string()),
-            title: model.title,
-            amount_cents: model.amount_cents,
-            currency: model.currency,
-            stage: model.stage.into(),
-            close_date: model.close_date,
-            company_id: ID::from(model.company_id.to_string()),
-            assigned_user_id: model.assigned_user_id.map(|id| ID::from(id.to_string())),
-            created_by: model.created_by.map(|id| ID::from(id.to_string())),
-            updated_by: model.updated_by.map(|id| ID::from(id.to_string())),
+            kind: model.kind.into(),
+            user_id: model.user_id.map(|id| ID::from(id.to_string())),
+            email: model.email,
+            ip: model.ip,
+            user_agent: model.user_agent,
+            detail: model.detail,
             created_at: model.created_at.into(),
-            updated_at: model.updated_at.into(),
+        }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AuditEntityType {
+    Company,
+    Contact,
+    Deal,
+    Task,
+    User,
+    ApiKey,
+    Stage,
+    Pipeline,
+    DealReason,
+}
+
+impl From<audit_log::EntityType> for AuditEntityType {
+    fn from(value: audit_log::EntityType) -> Self {
+        match value {
+            audit_log::EntityType::Company => AuditEntityType::Company,
+            audit_log::EntityType::Contact => AuditEntityType::Contact,
+            audit_log::EntityType::Deal => AuditEntityType::Deal,
+            audit_log::EntityType::Task => AuditEntityType::Task,
+            audit_log::EntityType::User => AuditEntityType::User,
+            audit_log::EntityType::ApiKey => AuditEntityType::ApiKey,
+            audit_log::EntityType::Stage => AuditEntityType::Stage,
+            audit_log::EntityType::Pipeline => AuditEntityType::Pipeline,
+            audit_log::EntityType::DealReason => AuditEntityType::DealReason,
+        }
+    }
+}
+
+impl From<AuditEntityType> for audit_log::EntityType {
+    fn from(value: AuditEntityType) -> Self {
+        match value {
+            AuditEntityType::Company => audit_log::EntityType::Company,
+            AuditEntityType::Contact => audit_log::EntityType::Contact,
+            AuditEntityType::Deal => audit_log::EntityType::Deal,
+            AuditEntityType::Task => audit_log::EntityType::Task,
+            AuditEntityType::User => audit_log::EntityType::User,
+            AuditEntityType::ApiKey => audit_log::EntityType::ApiKey,
+            AuditEntityType::Stage => audit_log::EntityType::Stage,
+            AuditEntityType::Pipeline => audit_log::EntityType::Pipeline,
+            AuditEntityType::DealReason => audit_log::EntityType::DealReason,
+        }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AuditOperation {
+    Create,
+    Update,
+    Delete,
+}
+
+impl From<audit_log::Operation> for AuditOperation {
+    fn from(value: audit_log::Operation) -> Self {
+        match value {
+            audit_log::Operation::Create => AuditOperation::Create,
+            audit_log::Operation::Update => AuditOperation::Update,
+            audit_log::Operation::Delete => AuditOperation::Delete,
         }
     }
 }
 
 #[derive(Clone, Debug, SimpleObject)]
-#[graphql(name = "DealStageHistory")]
-pub struct DealStageHistoryNode {
+#[graphql(name = "AuditEntry")]
+pub struct AuditEntryNode {
     pub id: ID,
-    #[graphql(name = "dealId")]
-    pub deal_id: ID,
-    #[graphql(name = "fromStage")]
-    pub from_stage: DealStage,
-    #[graphql(name = "toStage")]
-    pub to_stage: DealStage,
-    #[graphql(name = "note")]
-    pub note: Option<String>,
-    #[graphql(name = "changedAt")]
-    pub changed_at: DateTime<Utc>,
-    #[graphql(name = "changedBy")]
-    pub changed_by: Option<String>,
+    /// Null for changes made by the system rather than a signed-in user.
+    #[graphql(name = "actorId")]
+    pub actor_id: Option<ID>,
+    #[graphql(name = "entityType")]
+    pub entity_type: AuditEntityType,
+    #[graphql(name = "entityId")]
+    pub entity_id: ID,
+    pub operation: AuditOperation,
+    /// `{"column": {"old": ..., "new": ...}}` for each column the change touched.
+    pub changes: Json<serde_json::Value>,
+    #[graphql(name = "requestId")]
+    pub request_id: Option<String>,
+    #[graphql(name = "createdAt")]
+    pub created_at: DateTime<Utc>,
 }
 
-impl From<deal_stage_history::Model> for DealStageHistoryNode {
-    fn from(model: deal_stage_history::Model) -> Self {
+impl From<audit_log::Model> for AuditEntryNode {
+    fn from(model: audit_log::Model) -> Self {
         Self {
             id: ID::from(model.id.to_string()),
-            deal_id: ID::from(model.deal_id.to_string()),
-            from_stage: model.from_stage.into(),
-            to_stage: model.to_stage.into(),
-            note: model.note,
-            changed_at: model.changed_at.into(),
-            changed_by: model.changed_by,
+            actor_id: model.actor_id.map(|id| ID::from(id.to_string())),
+            entity_type: model.entity_type.into(),
+            entity_id: ID::from(model.entity_id.to_string()),
+            operation: model.operation.into(),
+            changes: Json(model.changes),
+            request_id: model.request_id,
+            created_at: model.created_at.into(),
         }
     }
 }
@@ -1349,6 +4140,15 @@ impl From<activity::Model> for ActivityNode {
     }
 }
 
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "Organization")]
+pub struct OrganizationNode {
+    pub id: ID,
+    pub name: String,
+    pub slug: String,
+    pub current: bool,
+}
+
 #[derive(Clone, Debug, SimpleObject)]
 #[graphql(name = "User")]
 pub struct UserNode {
@@ -1380,6 +4180,143 @@ impl UserNode {
             updated_at: model.updated_at.into(),
         }
     }
+
+    /// The user as their organization sees them: deactivated there means inactive.
+    fn from_member(mut model: app_user::Model, membership: Membership) -> Self {
+        model.is_active &= membership.is_active;
+        Self::from_model(model, membership.roles)
+    }
+}
+
+/// A user's standing in one organization.
+#[derive(Clone, Debug, Default)]
+struct Membership {
+    is_active: bool,
+    roles: Vec<UserRole>,
+}
+
+#[derive(Clone, Debug, InputObject)]
+pub struct NewUserInput {
+    pub email: String,
+    #[graphql(name = "displayName")]
+    pub display_name: String,
+    pub roles: Vec<UserRole>,
+    pub password: String,
+}
+
+#[derive(Clone, Debug, InputObject)]
+pub struct InviteUserInput {
+    pub email: String,
+    #[graphql(name = "displayName")]
+    pub display_name: String,
+    pub roles: Vec<UserRole>,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct InviteUserPayload {
+    pub user: UserNode,
+    #[graphql(name = "temporaryPassword")]
+    pub temporary_password: String,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ApiKeyKind {
+    Personal,
+    Service,
+}
+
+impl From<api_key::Kind> for ApiKeyKind {
+    fn from(value: api_key::Kind) -> Self {
+        match value {
+            api_key::Kind::Personal => ApiKeyKind::Personal,
+            api_key::Kind::Service => ApiKeyKind::Service,
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "ApiKey")]
+pub struct ApiKeyNode {
+    pub id: ID,
+    #[graphql(name = "userId")]
+    pub user_id: ID,
+    pub name: String,
+    pub kind: ApiKeyKind,
+    /// Leading characters of the token, enough to recognise it.
+    pub prefix: String,
+    pub scopes: Vec<ApiScope>,
+    #[graphql(name = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[graphql(name = "expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[graphql(name = "lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[graphql(name = "revokedAt")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<api_key::Model> for ApiKeyNode {
+    fn from(model: api_key::Model) -> Self {
+        Self {
+            id: ID::from(model.id.to_string()),
+            user_id: ID::from(model.user_id.to_string()),
+            name: model.name,
+            kind: model.kind.into(),
+            prefix: model.prefix,
+            scopes: parse_scopes(&model.scopes),
+            created_at: model.created_at.into(),
+            expires_at: model.expires_at.map(Into::into),
+            last_used_at: model.last_used_at.map(Into::into),
+            revoked_at: model.revoked_at.map(Into::into),
+        }
+    }
+}
+
+#[derive(Clone, Debug, InputObject)]
+pub struct NewApiKeyInput {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    #[graphql(name = "expiresAt")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Service account to issue the key for; owners only.
+    #[graphql(name = "userId")]
+    pub user_id: Option<ID>,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct CreatedApiKey {
+    pub key: ApiKeyNode,
+    /// The bearer token. It is not stored and cannot be shown again.
+    pub token: String,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "Session")]
+pub struct SessionNode {
+    pub id: ID,
+    #[graphql(name = "userAgent")]
+    pub user_agent: Option<String>,
+    #[graphql(name = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[graphql(name = "lastSeenAt")]
+    pub last_seen_at: DateTime<Utc>,
+    #[graphql(name = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    /// True for the session that made this request.
+    pub current: bool,
+}
+
+impl SessionNode {
+    fn from_model(model: user_session::Model, current: Option<Uuid>) -> Self {
+        Self {
+            id: ID::from(model.id.to_string()),
+            user_agent: model.user_agent,
+            created_at: model.created_at.into(),
+            last_seen_at: model.last_seen_at.into(),
+            expires_at: model.expires_at.into(),
+            current: current == Some(model.id),
+        }
+    }
 }
 
 #[derive(Clone, Debug, SimpleObject, Default)]
@@ -1387,10 +4324,101 @@ pub struct AuthPayload {
     pub ok: bool,
     pub user: Option<UserNode>,
     pub error: Option<String>,
+    /// Set when the password was right but the login needs another step.
+    #[graphql(name = "nextStep")]
+    pub next_step: Option<LoginStep>,
+    /// Short-lived token for `verifySecondFactor` or the TOTP enrolment mutations.
+    #[graphql(name = "challengeToken")]
+    pub challenge_token: Option<String>,
+    /// Set while too many failures keep the account or address locked out.
+    #[graphql(name = "retryAfterSeconds")]
+    pub retry_after_seconds: Option<i32>,
+}
+
+impl AuthPayload {
+    fn failed(error: &str) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+
+    fn locked(until: DateTime<Utc>) -> Self {
+        let seconds = (until - Utc::now()).num_seconds().max(1) as i32;
+        Self {
+            error: Some("Too many failed attempts, try again later".into()),
+            retry_after_seconds: Some(seconds),
+            ..Default::default()
+        }
+    }
+
+    fn challenge(
+        user_id: Uuid,
+        org_id: Uuid,
+        purpose: ChallengePurpose,
+        auth: &AuthConfig,
+    ) -> async_graphql::Result<Self> {
+        let token = issue_challenge_token(user_id, org_id, purpose, auth)
+            .map_err(|_| error_with_code("INTERNAL", "Failed to issue challenge"))?;
+        let (next_step, error) = match purpose {
+            ChallengePurpose::Verify => (LoginStep::VerifyTotp, "Two-factor code required"),
+            ChallengePurpose::Enroll => (LoginStep::EnrollTotp, "Two-factor enrolment required"),
+        };
+        Ok(Self {
+            error: Some(error.into()),
+            next_step: Some(next_step),
+            challenge_token: Some(token),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LoginStep {
+    /// Call `verifySecondFactor` with an authenticator or recovery code.
+    VerifyTotp,
+    /// Policy requires TOTP for this account: `beginTotpEnrollment`, then
+    /// `confirmTotpEnrollment`, both with the challenge token.
+    EnrollTotp,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct TotpEnrollment {
+    /// Base32 seed for manual entry.
+    pub secret: String,
+    /// `otpauth://` URI to render as a QR code.
+    #[graphql(name = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct TotpConfirmation {
+    #[graphql(name = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+    /// True when confirming with a challenge token also started a session.
+    #[graphql(name = "signedIn")]
+    pub signed_in: bool,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    /// Policy requires TOTP for the caller's role.
+    pub required: bool,
+    #[graphql(name = "recoveryCodesRemaining")]
+    pub recovery_codes_remaining: i32,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct TwoFactorPolicy {
+    #[graphql(name = "requireForAdmins")]
+    pub require_for_admins: bool,
 }
 
 #[derive(Clone, Debug, SimpleObject)]
 pub struct PipelineStage {
+    /// Names the stage in the audit log; deals refer to it by `key`.
+    pub id: ID,
     pub key: String,
     #[graphql(name = "displayName")]
     pub display_name: String,
@@ -1401,19 +4429,231 @@ pub struct PipelineStage {
     pub is_won: bool,
     #[graphql(name = "isLost")]
     pub is_lost: bool,
+    #[graphql(name = "isArchived")]
+    pub is_archived: bool,
+    #[graphql(name = "pipelineId")]
+    pub pipeline_id: ID,
+    pub rules: StageRules,
+    #[graphql(name = "wipLimit")]
+    pub wip_limit: Option<i32>,
+}
+
+impl From<&stage_meta::Model> for PipelineStage {
+    fn from(model: &stage_meta::Model) -> Self {
+        Self {
+            id: ID::from(model.id.to_string()),
+            key: model.key.clone(),
+            pipeline_id: ID::from(model.pipeline_id.to_string()),
+            rules: StageRules::from(model),
+            display_name: model.display_name.clone(),
+            sort_order: model.sort_order as i32,
+            probability: model.probability as i32,
+            is_won: model.is_won,
+            is_lost: model.is_lost,
+            is_archived: model.is_archived,
+            wip_limit: model.wip_limit,
+        }
+    }
+}
+
+/// Deal fields a stage can require before a deal enters it.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DealField {
+    AmountCents,
+    Currency,
+    CloseDate,
+    AssignedUserId,
+}
+
+impl DealField {
+    /// Name stored in `stage_meta.required_fields` and reported in rule violations.
+    fn key(self) -> &'static str {
+        match self {
+            DealField::AmountCents => "AMOUNT_CENTS",
+            DealField::Currency => "CURRENCY",
+            DealField::CloseDate => "CLOSE_DATE",
+            DealField::AssignedUserId => "ASSIGNED_USER_ID",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "AMOUNT_CENTS" => Some(DealField::AmountCents),
+            "CURRENCY" => Some(DealField::Currency),
+            "CLOSE_DATE" => Some(DealField::CloseDate),
+            "ASSIGNED_USER_ID" => Some(DealField::AssignedUserId),
+            _ => None,
+        }
+    }
+
+    fn is_set(self, deal: &deal::Model) -> bool {
+        match self {
+            DealField::AmountCents => deal.amount_cents.is_some(),
+            DealField::Currency => deal.currency.is_some(),
+            DealField::CloseDate => deal.close_date.is_some(),
+            DealField::AssignedUserId => deal.assigned_user_id.is_some(),
+        }
+    }
+}
+
+/// What a deal must satisfy to enter a stage.
+#[derive(Clone, Debug, Default, SimpleObject)]
+pub struct StageRules {
+    /// Stages of the same pipeline a deal may come from; `null` allows any.
+    #[graphql(name = "allowedFrom")]
+    pub allowed_from: Option<Vec<String>>,
+    #[graphql(name = "requiredFields")]
+    pub required_fields: Vec<DealField>,
+    #[graphql(name = "requiresNote")]
+    pub requires_note: bool,
+}
+
+impl From<&stage_meta::Model> for StageRules {
+    fn from(model: &stage_meta::Model) -> Self {
+        let keys = |value: &serde_json::Value| -> Vec<String> {
+            serde_json::from_value(value.clone()).unwrap_or_default()
+        };
+        Self {
+            allowed_from: model.allowed_from.as_ref().map(keys),
+            required_fields: keys(&model.required_fields)
+                .iter()
+                .filter_map(|key| DealField::from_key(key))
+                .collect(),
+            requires_note: model.requires_note,
+        }
+    }
+}
+
+#[derive(Clone, Debug, InputObject)]
+pub struct StageRulesInput {
+    /// Stage keys of the same pipeline; omit or send `null` to allow any.
+    #[graphql(name = "allowedFrom")]
+    pub allowed_from: Option<Vec<String>>,
+    #[graphql(name = "requiredFields", default)]
+    pub required_fields: Vec<DealField>,
+    #[graphql(name = "requiresNote", default)]
+    pub requires_note: bool,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DealOutcome {
+    #[graphql(name = "WON")]
+    Won,
+    #[graphql(name = "LOST")]
+    Lost,
+}
+
+impl From<DealOutcome> for deal_reason::Outcome {
+    fn from(value: DealOutcome) -> Self {
+        match value {
+            DealOutcome::Won => deal_reason::Outcome::Won,
+            DealOutcome::Lost => deal_reason::Outcome::Lost,
+        }
+    }
+}
+
+impl From<deal_reason::Outcome> for DealOutcome {
+    fn from(value: deal_reason::Outcome) -> Self {
+        match value {
+            deal_reason::Outcome::Won => DealOutcome::Won,
+            deal_reason::Outcome::Lost => DealOutcome::Lost,
+        }
+    }
+}
+
+/// A reason a deal was won or lost, picked when it moves into a closed stage.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct DealReason {
+    pub id: ID,
+    pub outcome: DealOutcome,
+    pub label: String,
+    #[graphql(name = "isArchived")]
+    pub is_archived: bool,
+}
+
+impl From<deal_reason::Model> for DealReason {
+    fn from(model: deal_reason::Model) -> Self {
+        Self {
+            id: ID::from(model.id.to_string()),
+            outcome: model.outcome.into(),
+            label: model.label,
+            is_archived: model.is_archived,
+        }
+    }
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct Pipeline {
+    pub id: ID,
+    pub name: String,
+    #[graphql(name = "isDefault")]
+    pub is_default: bool,
+    #[graphql(name = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<pipeline::Model> for Pipeline {
+    fn from(model: pipeline::Model) -> Self {
+        Self {
+            id: ID::from(model.id.to_string()),
+            name: model.name,
+            is_default: model.is_default,
+            created_at: model.created_at.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, InputObject)]
+pub struct PipelineStageInput {
+    /// Upper-case letters, digits and underscores; fixed once created and unique within
+    /// the pipeline.
+    pub key: String,
+    #[graphql(name = "displayName")]
+    pub display_name: String,
+    pub probability: i32,
+    #[graphql(name = "isWon", default)]
+    pub is_won: bool,
+    #[graphql(name = "isLost", default)]
+    pub is_lost: bool,
+    #[graphql(name = "wipLimit")]
+    pub wip_limit: Option<i32>,
+}
+
+/// Omitted fields are left untouched; an explicit null `wipLimit` removes the limit.
+/// Keys are fixed, and order changes go through `reorderPipelineStages`.
+#[derive(Clone, Debug, InputObject)]
+pub struct UpdatePipelineStageInput {
+    /// Defaults to the organization's default pipeline.
+    #[graphql(name = "pipelineId")]
+    pub pipeline_id: Option<ID>,
+    pub key: String,
+    #[graphql(name = "displayName")]
+    pub display_name: Option<String>,
+    pub probability: Option<i32>,
+    #[graphql(name = "isWon")]
+    pub is_won: Option<bool>,
+    #[graphql(name = "isLost")]
+    pub is_lost: Option<bool>,
+    #[graphql(name = "wipLimit")]
+    pub wip_limit: MaybeUndefined<i32>,
+}
+
+#[derive(Clone, Debug, InputObject)]
+pub struct NewPipelineStageInput {
+    #[graphql(flatten)]
+    pub stage: PipelineStageInput,
+    /// Defaults to the organization's default pipeline.
+    #[graphql(name = "pipelineId")]
+    pub pipeline_id: Option<ID>,
+    /// Key of the active stage the new one goes in front of.
+    pub before: Option<String>,
 }
 
-impl From<&stage_meta::Model> for PipelineStage {
-    fn from(model: &stage_meta::Model) -> Self {
-        Self {
-            key: model.key.clone(),
-            display_name: model.display_name.clone(),
-            sort_order: model.sort_order as i32,
-            probability: model.probability as i32,
-            is_won: model.is_won,
-            is_lost: model.is_lost,
-        }
-    }
+#[derive(Clone, Debug, InputObject)]
+pub struct NewPipelineInput {
+    pub name: String,
+    /// In board order; at least one.
+    pub stages: Vec<PipelineStageInput>,
 }
 
 #[derive(Clone, Debug, SimpleObject)]
@@ -1444,6 +4684,10 @@ pub struct PipelineColumn {
     pub total_amount_cents: Option<i64>,
     #[graphql(name = "expectedValueCents")]
     pub expected_value_cents: Option<i64>,
+    /// Whether the stage holds more deals than its `wipLimit`, counting every deal in it
+    /// regardless of the board's filters.
+    #[graphql(name = "wipExceeded")]
+    pub wip_exceeded: bool,
     pub deals: Vec<PipelineDeal>,
 }
 
@@ -1472,6 +4716,45 @@ pub enum TimeGroup {
     Week,
 }
 
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WinLossGroup {
+    #[graphql(name = "REASON")]
+    Reason,
+    #[graphql(name = "OWNER")]
+    Owner,
+    #[graphql(name = "COMPANY")]
+    Company,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct WinLossRow {
+    /// The reason, owner or company id; null for deals without a reason or owner.
+    pub key: Option<ID>,
+    pub label: String,
+    pub won: i32,
+    pub lost: i32,
+    #[graphql(name = "wonAmountCents")]
+    pub won_amount_cents: i64,
+    #[graphql(name = "lostAmountCents")]
+    pub lost_amount_cents: i64,
+    /// Won deals over closed deals, between 0 and 1.
+    #[graphql(name = "winRate")]
+    pub win_rate: f64,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct WinLossReport {
+    pub rows: Vec<WinLossRow>,
+    pub won: i32,
+    pub lost: i32,
+    #[graphql(name = "wonAmountCents")]
+    pub won_amount_cents: i64,
+    #[graphql(name = "lostAmountCents")]
+    pub lost_amount_cents: i64,
+    #[graphql(name = "winRate")]
+    pub win_rate: f64,
+}
+
 #[derive(Clone, Debug, SimpleObject)]
 pub struct StageTotals {
     pub stage: PipelineStage,
@@ -1526,6 +4809,13 @@ pub struct PipelineReport {
 #[derive(Debug)]
 pub enum StageMoveError {
     NotFound,
+    /// The target is not one of the organization's active stages.
+    UnknownStage(String),
+    /// The target belongs to a pipeline other than the deal's.
+    OtherPipeline(String),
+    /// The win/loss reason or competitor does not fit the target stage.
+    InvalidOutcome(String),
+    RuleViolation(StageRuleViolation),
     Db(DbErr),
 }
 
@@ -1538,112 +4828,926 @@ impl From<DbErr> for StageMoveError {
 fn stage_move_error(err: StageMoveError) -> Error {
     match err {
         StageMoveError::NotFound => error_with_code("NOT_FOUND", "Deal not found"),
+        StageMoveError::UnknownStage(key) => unknown_stage_error(&key),
+        StageMoveError::OtherPipeline(key) => validation_error(format!(
+            "Stage {} is in another pipeline; use moveDealToPipeline",
+            key
+        )),
+        StageMoveError::InvalidOutcome(message) => validation_error(message),
+        StageMoveError::RuleViolation(violation) => violation.into_error(),
         StageMoveError::Db(e) => db_error(e),
     }
 }
 
+/// Why a deal was won or lost, given with a move into a closed stage.
+#[derive(Debug, Clone, Default)]
+pub struct DealOutcomeDetails {
+    pub reason_id: Option<Uuid>,
+    pub competitor: Option<String>,
+}
+
+/// The reason and competitor a deal carries into `target`. Open stages carry neither, so
+/// reopening a deal clears them; a reason must match the stage's outcome.
+async fn resolve_outcome<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    target: &stage_meta::Model,
+    details: DealOutcomeDetails,
+) -> Result<(Option<Uuid>, Option<String>), StageMoveError> {
+    let competitor = details
+        .competitor
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    let outcome = match (target.is_won, target.is_lost) {
+        (true, _) => deal_reason::Outcome::Won,
+        (_, true) => deal_reason::Outcome::Lost,
+        _ if details.reason_id.is_some() || competitor.is_some() => {
+            return Err(StageMoveError::InvalidOutcome(
+                "Reasons and competitors only apply to won or lost stages".to_string(),
+            ))
+        }
+        _ => return Ok((None, None)),
+    };
+    if competitor
+        .as_ref()
+        .is_some_and(|value| value.chars().count() > 200)
+    {
+        return Err(StageMoveError::InvalidOutcome(
+            "competitor must be at most 200 characters".to_string(),
+        ));
+    }
+    if let Some(reason_id) = details.reason_id {
+        let reason = deal_reason::Entity::find_by_id(reason_id)
+            .filter(deal_reason::Column::OrgId.eq(org_id))
+            .filter(deal_reason::Column::IsArchived.eq(false))
+            .one(conn)
+            .await?;
+        if !reason.is_some_and(|reason| reason.outcome == outcome) {
+            return Err(StageMoveError::InvalidOutcome(format!(
+                "Reason does not apply to stage {}",
+                target.key
+            )));
+        }
+    }
+    Ok((details.reason_id, competitor))
+}
+
+/// The ways a move broke the target stage's rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageRuleViolation {
+    pub stage: String,
+    pub from_stage: String,
+    /// The deal's current stage is not in the target's `allowedFrom`.
+    pub disallowed_from: bool,
+    pub missing_fields: Vec<DealField>,
+    pub note_required: bool,
+}
+
+impl StageRuleViolation {
+    /// A `STAGE_RULE_VIOLATION` error whose extensions name each broken rule, so
+    /// clients can point at the fields to fill in.
+    fn into_error(self) -> Error {
+        let mut problems = Vec::new();
+        if self.disallowed_from {
+            problems.push(format!("deals cannot come from {}", self.from_stage));
+        }
+        if !self.missing_fields.is_empty() {
+            let names: Vec<&str> = self
+                .missing_fields
+                .iter()
+                .map(|field| field.key())
+                .collect();
+            problems.push(format!("missing {}", names.join(", ")));
+        }
+        if self.note_required {
+            problems.push("a note or reason is required".to_string());
+        }
+        let message = format!("Cannot move to {}: {}", self.stage, problems.join("; "));
+        Error::new(message).extend_with(|_, e| {
+            e.set("code", "STAGE_RULE_VIOLATION");
+            e.set("stage", self.stage.as_str());
+            e.set("fromStage", self.from_stage.as_str());
+            e.set("disallowedFrom", self.disallowed_from);
+            e.set(
+                "missingFields",
+                async_graphql::Value::List(
+                    self.missing_fields
+                        .iter()
+                        .map(|field| async_graphql::Value::from(field.key()))
+                        .collect(),
+                ),
+            );
+            e.set("noteRequired", self.note_required);
+        })
+    }
+}
+
+/// Checks `deal`, currently in `from`, against the rules of `target`. `allowedFrom` is
+/// skipped for deals coming from another pipeline, and a win/loss reason stands in for
+/// a required note.
+fn check_stage_rules(
+    target: &stage_meta::Model,
+    deal: &deal::Model,
+    from: &str,
+    note: Option<&str>,
+    has_reason: bool,
+) -> Result<(), StageRuleViolation> {
+    let rules = StageRules::from(target);
+    let disallowed_from = deal.pipeline_id == target.pipeline_id
+        && rules
+            .allowed_from
+            .is_some_and(|allowed| !allowed.iter().any(|key| key == from));
+    let missing_fields: Vec<DealField> = rules
+        .required_fields
+        .into_iter()
+        .filter(|field| !field.is_set(deal))
+        .collect();
+    let note_required =
+        rules.requires_note && !has_reason && note.is_none_or(|note| note.trim().is_empty());
+    if disallowed_from || !missing_fields.is_empty() || note_required {
+        return Err(StageRuleViolation {
+            stage: target.key.clone(),
+            from_stage: from.to_string(),
+            disallowed_from,
+            missing_fields,
+            note_required,
+        });
+    }
+    Ok(())
+}
+
 async fn move_deal_stage_internal(
     db: &DatabaseConnection,
     deal_id: Uuid,
-    stage: deal::Stage,
+    stage: &str,
     note: Option<String>,
-    changed_by: Option<Uuid>,
-) -> Result<deal::Model, StageMoveError> {
+    outcome: DealOutcomeDetails,
+    actor: &AuditActor,
+) -> Result<(deal::Model, Option<String>), StageMoveError> {
     let txn = db.begin().await?;
     let existing = deal::Entity::find_by_id(deal_id)
+        .filter(deal::Column::OrgId.eq(actor.org_id))
         .one(&txn)
         .await?
         .ok_or(StageMoveError::NotFound)?;
+    let Some(target) = open_stage(&txn, existing.pipeline_id, stage).await? else {
+        let elsewhere = stage_meta::Entity::find()
+            .filter(stage_meta::Column::OrgId.eq(actor.org_id))
+            .filter(stage_meta::Column::Key.eq(stage))
+            .filter(stage_meta::Column::IsArchived.eq(false))
+            .count(&txn)
+            .await?;
+        return Err(if elsewhere > 0 {
+            StageMoveError::OtherPipeline(stage.to_string())
+        } else {
+            StageMoveError::UnknownStage(stage.to_string())
+        });
+    };
 
     let now: DateTimeWithTimeZone = Utc::now().into();
     if existing.stage == stage {
         let mut active: deal::ActiveModel = existing.into();
-        active.updated_at = Set(now.clone());
+        active.updated_at = Set(now);
         let updated = active.update(&txn).await?;
         txn.commit().await?;
-        return Ok(updated);
-    }
+        return Ok((updated, None));
+    }
+    let (reason_id, competitor) = resolve_outcome(&txn, actor.org_id, &target, outcome).await?;
+    check_stage_rules(
+        &target,
+        &existing,
+        &existing.stage,
+        note.as_deref(),
+        reason_id.is_some(),
+    )
+    .map_err(StageMoveError::RuleViolation)?;
 
-    let from_stage = existing.stage;
+    let before = existing.clone();
+    let from_stage = existing.stage.clone();
     let mut active: deal::ActiveModel = existing.into();
-    let actor = changed_by;
-    active.stage = Set(stage);
-    active.updated_at = Set(now.clone());
-    active.updated_by = Set(actor);
+    active.stage = Set(stage.to_string());
+    active.reason_id = Set(reason_id);
+    active.competitor = Set(competitor);
+    active.updated_at = Set(now);
+    active.updated_by = Set(actor.user_id);
     let updated = active.update(&txn).await?;
 
+    record_stage_change(
+        &txn,
+        &updated,
+        before.pipeline_id,
+        &from_stage,
+        stage,
+        note,
+        actor.user_id,
+        now,
+    )
+    .await?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Deal,
+        deal_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await?;
+
+    txn.commit().await?;
+    Ok((updated, Some(from_stage)))
+}
+
+async fn move_deal_to_pipeline_internal(
+    db: &DatabaseConnection,
+    deal_id: Uuid,
+    pipeline_id: &ID,
+    stage: Option<&str>,
+    note: Option<String>,
+    actor: &AuditActor,
+) -> async_graphql::Result<(deal::Model, String)> {
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = deal::Entity::find_by_id(deal_id)
+        .filter(deal::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "Deal not found"))?;
+    let pipeline = resolve_pipeline(&txn, actor.org_id, Some(pipeline_id)).await?;
+    if pipeline.id == existing.pipeline_id {
+        return Err(validation_error(
+            "The deal is already in this pipeline; use moveDealStage",
+        ));
+    }
+    let targets = load_stage_meta(&txn, pipeline.id, false).await?;
+    let target = match stage {
+        Some(key) => targets
+            .iter()
+            .find(|target| target.key == key)
+            .ok_or_else(|| unknown_stage_error(key))?,
+        None => {
+            let current = stage_by_key(existing.pipeline_id, &existing.stage)
+                .one(&txn)
+                .await
+                .map_err(db_error)?;
+            map_stage(current.as_ref(), &targets)
+                .ok_or_else(|| validation_error("The pipeline has no active stages"))?
+        }
+    };
+
+    // Reasons belong to the organization, so a closed deal keeps its reason when it
+    // lands in a stage with the same outcome.
+    let keeps_outcome = existing.stage != target.key
+        && stage_by_key(existing.pipeline_id, &existing.stage)
+            .one(&txn)
+            .await
+            .map_err(db_error)?
+            .is_some_and(|current| {
+                (current.is_won && target.is_won) || (current.is_lost && target.is_lost)
+            });
+    let (reason_id, competitor) = if keeps_outcome {
+        (existing.reason_id, existing.competitor.clone())
+    } else {
+        (None, None)
+    };
+    check_stage_rules(
+        target,
+        &existing,
+        &existing.stage,
+        note.as_deref(),
+        reason_id.is_some(),
+    )
+    .map_err(StageRuleViolation::into_error)?;
+
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let before = existing.clone();
+    let from_stage = existing.stage.clone();
+    let mut active: deal::ActiveModel = existing.into();
+    active.pipeline_id = Set(pipeline.id);
+    active.reason_id = Set(reason_id);
+    active.competitor = Set(competitor);
+    active.stage = Set(target.key.clone());
+    active.updated_at = Set(now);
+    active.updated_by = Set(actor.user_id);
+    let updated = active.update(&txn).await.map_err(db_error)?;
+    record_stage_change(
+        &txn,
+        &updated,
+        before.pipeline_id,
+        &from_stage,
+        &target.key,
+        note,
+        actor.user_id,
+        now,
+    )
+    .await
+    .map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Deal,
+        deal_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok((updated, from_stage))
+}
+
+/// The stage of `targets` (active, in board order) matching `current`: the first won or
+/// lost stage for a closed deal, otherwise the open stage nearest in probability, with
+/// ties going to the earlier stage. Falls back to the first stage.
+fn map_stage<'a>(
+    current: Option<&stage_meta::Model>,
+    targets: &'a [stage_meta::Model],
+) -> Option<&'a stage_meta::Model> {
+    let mapped = match current {
+        Some(current) if current.is_won => targets.iter().find(|target| target.is_won),
+        Some(current) if current.is_lost => targets.iter().find(|target| target.is_lost),
+        Some(current) => targets
+            .iter()
+            .filter(|target| !target.is_won && !target.is_lost)
+            .min_by_key(|target| (target.probability - current.probability).abs()),
+        None => None,
+    };
+    mapped.or_else(|| targets.first())
+}
+
+/// Writes the `deal_stage_history` row and matching `activity` entry for a stage change.
+/// `from` is a stage of `from_pipeline_id`, `to` one of the deal's pipeline.
+#[allow(clippy::too_many_arguments)]
+async fn record_stage_change<C: ConnectionTrait>(
+    conn: &C,
+    deal: &deal::Model,
+    from_pipeline_id: Uuid,
+    from: &str,
+    to: &str,
+    note: Option<String>,
+    changed_by: Option<Uuid>,
+    timestamp: DateTimeWithTimeZone,
+) -> Result<(), DbErr> {
     let history = deal_stage_history::ActiveModel {
         id: Set(Uuid::new_v4()),
-        deal_id: Set(deal_id),
-        from_stage: Set(from_stage),
-        to_stage: Set(stage),
-        changed_at: Set(now.clone()),
+        org_id: Set(deal.org_id),
+        deal_id: Set(deal.id),
+        from_stage: Set(from.to_string()),
+        to_stage: Set(to.to_string()),
+        from_pipeline_id: Set(from_pipeline_id),
+        to_pipeline_id: Set(deal.pipeline_id),
+        changed_at: Set(timestamp),
         note: Set(note.clone()),
-        changed_by: Set(actor.map(|id| id.to_string())),
+        reason_id: Set(deal.reason_id),
+        competitor: Set(deal.competitor.clone()),
+        changed_by: Set(changed_by.map(|id| id.to_string())),
     };
     deal_stage_history::Entity::insert(history)
-        .exec_without_returning(&txn)
+        .exec_without_returning(conn)
         .await?;
 
-    let activity = activity_stage_change(deal_id, from_stage, stage, note, actor, now.clone());
+    let activity = activity_stage_change(deal, from, to, note, changed_by, timestamp);
     activity::Entity::insert(activity)
-        .exec_without_returning(&txn)
+        .exec_without_returning(conn)
         .await?;
-
-    txn.commit().await?;
-    Ok(updated)
+    Ok(())
 }
 
 fn activity_stage_change(
-    deal_id: Uuid,
-    from: deal::Stage,
-    to: deal::Stage,
+    deal: &deal::Model,
+    from: &str,
+    to: &str,
     note: Option<String>,
     changed_by: Option<Uuid>,
     timestamp: DateTimeWithTimeZone,
 ) -> activity::ActiveModel {
-    let subject = format!("Stage: {} -> {}", stage_str(from), stage_str(to));
+    let subject = format!("Stage: {} -> {}", from, to);
     activity::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(deal.org_id),
         entity_type: Set("deal".to_string()),
-        entity_id: Set(deal_id),
+        entity_id: Set(deal.id),
         kind: Set(activity::Kind::StageChange),
         subject: Set(Some(subject)),
         body_md: Set(note.clone()),
-        meta_json: Set(json!({ "from": stage_str(from), "to": stage_str(to) })),
+        meta_json: Set(json!({ "from": from, "to": to })),
         created_at: Set(timestamp),
         created_by: Set(changed_by),
         updated_by: Set(None),
     }
 }
 
-fn stage_str(stage: deal::Stage) -> &'static str {
-    match stage {
-        deal::Stage::New => "NEW",
-        deal::Stage::Qualify => "QUALIFY",
-        deal::Stage::Proposal => "PROPOSAL",
-        deal::Stage::Negotiate => "NEGOTIATE",
-        deal::Stage::Won => "WON",
-        deal::Stage::Lost => "LOST",
+/// Stage `key` of `pipeline_id`, archived or not.
+fn stage_by_key(pipeline_id: Uuid, key: &str) -> Select<stage_meta::Entity> {
+    stage_meta::Entity::find()
+        .filter(stage_meta::Column::PipelineId.eq(pipeline_id))
+        .filter(stage_meta::Column::Key.eq(key))
+}
+
+/// The pipeline's stage `key`, unless it is unknown or archived.
+async fn open_stage<C: ConnectionTrait>(
+    conn: &C,
+    pipeline_id: Uuid,
+    key: &str,
+) -> Result<Option<stage_meta::Model>, DbErr> {
+    stage_by_key(pipeline_id, key)
+        .filter(stage_meta::Column::IsArchived.eq(false))
+        .one(conn)
+        .await
+}
+
+fn unknown_stage_error(key: &str) -> Error {
+    validation_error(format!("Unknown stage {}", key))
+}
+
+/// The pipeline's stage `key`, archived or not, or `NOT_FOUND`.
+async fn find_stage<C: ConnectionTrait>(
+    conn: &C,
+    pipeline_id: Uuid,
+    key: &str,
+) -> async_graphql::Result<stage_meta::Model> {
+    let key = normalize_stage_key(key).unwrap_or_default();
+    stage_by_key(pipeline_id, &key)
+        .one(conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", format!("Stage {} not found", key)))
+}
+
+/// Fails unless a stage of the pipeline other than `key` stays open, so new deals always
+/// have somewhere to start.
+async fn ensure_other_active_stage<C: ConnectionTrait>(
+    conn: &C,
+    pipeline_id: Uuid,
+    key: &str,
+) -> async_graphql::Result<()> {
+    let others = stage_meta::Entity::find()
+        .filter(stage_meta::Column::PipelineId.eq(pipeline_id))
+        .filter(stage_meta::Column::IsArchived.eq(false))
+        .filter(stage_meta::Column::Key.ne(key))
+        .count(conn)
+        .await
+        .map_err(db_error)?;
+    if others == 0 {
+        return Err(validation_error(
+            "The pipeline needs at least one active stage",
+        ));
+    }
+    Ok(())
+}
+
+const MAX_STAGES: usize = 50;
+const STAGE_ORDER_STEP: i16 = 10;
+
+fn ensure_stage_capacity(count: usize) -> async_graphql::Result<()> {
+    if count > MAX_STAGES {
+        return Err(error_with_code(
+            "LIMIT_EXCEEDED",
+            format!("A pipeline cannot have more than {} stages", MAX_STAGES),
+        ));
+    }
+    Ok(())
+}
+
+/// A validated, not yet inserted stage row for `pipeline_id`.
+fn new_stage_row(
+    org_id: Uuid,
+    pipeline_id: Uuid,
+    sort_order: i16,
+    input: &PipelineStageInput,
+) -> async_graphql::Result<stage_meta::ActiveModel> {
+    let key = validate_stage_key(&input.key)?;
+    let display_name = validate_stage_name(&input.display_name)?;
+    let probability = validate_probability(input.probability)?;
+    if input.is_won && input.is_lost {
+        return Err(validation_error("A stage cannot be both won and lost"));
+    }
+    let wip_limit = validate_wip_limit(input.wip_limit)?;
+    Ok(stage_meta::ActiveModel {
+        org_id: Set(org_id),
+        key: Set(key),
+        id: Set(Uuid::new_v4()),
+        pipeline_id: Set(pipeline_id),
+        display_name: Set(display_name),
+        sort_order: Set(sort_order),
+        probability: Set(probability),
+        is_won: Set(input.is_won),
+        is_lost: Set(input.is_lost),
+        is_archived: Set(false),
+        allowed_from: Set(None),
+        required_fields: Set(json!([])),
+        requires_note: Set(input.is_lost),
+        wip_limit: Set(wip_limit),
+    })
+}
+
+fn deal_reason_write_error(err: DbErr) -> Error {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => {
+            error_with_code("CONFLICT", "A reason with this label already exists")
+        }
+        _ => db_error(err),
+    }
+}
+
+fn pipeline_write_error(err: DbErr) -> Error {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => {
+            error_with_code("CONFLICT", "A pipeline with this name already exists")
+        }
+        _ => db_error(err),
+    }
+}
+
+/// Gives `keys` the sort orders 10, 20, ... in turn. Every row first moves to a negative
+/// slot, so no intermediate state trips the unique `(pipeline_id, sort_order)` constraint.
+async fn renumber_stages<C: ConnectionTrait>(
+    conn: &C,
+    pipeline_id: Uuid,
+    keys: &[String],
+) -> Result<(), DbErr> {
+    stage_meta::Entity::update_many()
+        .col_expr(
+            stage_meta::Column::SortOrder,
+            Expr::col(stage_meta::Column::SortOrder).mul(-1).sub(1),
+        )
+        .filter(stage_meta::Column::PipelineId.eq(pipeline_id))
+        .exec(conn)
+        .await?;
+    for (position, key) in (1..).zip(keys) {
+        stage_meta::Entity::update_many()
+            .col_expr(
+                stage_meta::Column::SortOrder,
+                Expr::value(position * STAGE_ORDER_STEP),
+            )
+            .filter(stage_meta::Column::PipelineId.eq(pipeline_id))
+            .filter(stage_meta::Column::Key.eq(key.as_str()))
+            .exec(conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Moves `source`'s deals onto `target`, sends the moves `allowed_from` permitted out of
+/// `source` through `target` instead and archives `source`, leaving the stage history that
+/// names it as it was. Returns the moved deals.
+async fn merge_stage_internal<C: ConnectionTrait>(
+    conn: &C,
+    actor: &AuditActor,
+    source: stage_meta::Model,
+    target: &str,
+) -> async_graphql::Result<Vec<deal::Model>> {
+    let deals = deal::Entity::find()
+        .filter(deal::Column::PipelineId.eq(source.pipeline_id))
+        .filter(deal::Column::Stage.eq(source.key.as_str()))
+        .all(conn)
+        .await
+        .map_err(db_error)?;
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let mut moved = Vec::with_capacity(deals.len());
+    for deal in deals {
+        let before = deal.clone();
+        let mut active: deal::ActiveModel = deal.into();
+        active.stage = Set(target.to_string());
+        active.updated_at = Set(now);
+        active.updated_by = Set(actor.user_id);
+        let updated = active.update(conn).await.map_err(db_error)?;
+        record_change(
+            conn,
+            actor,
+            audit_log::EntityType::Deal,
+            updated.id,
+            Some(&before),
+            Some(&updated),
+        )
+        .await
+        .map_err(db_error)?;
+        moved.push(updated);
+    }
+    replace_allowed_from(conn, actor, source.pipeline_id, &source.key, Some(target)).await?;
+    if !source.is_archived {
+        archive_stage(conn, actor, source).await?;
+    }
+    Ok(moved)
+}
+
+/// Hides `stage` from the board and closes it to new deals. An archived stage holds no
+/// deals, so it leaves every `allowed_from` that names it.
+async fn archive_stage<C: ConnectionTrait>(
+    conn: &C,
+    actor: &AuditActor,
+    stage: stage_meta::Model,
+) -> async_graphql::Result<stage_meta::Model> {
+    replace_allowed_from(conn, actor, stage.pipeline_id, &stage.key, None).await?;
+    let mut active: stage_meta::ActiveModel = stage.clone().into();
+    active.is_archived = Set(true);
+    let updated = active.update(conn).await.map_err(db_error)?;
+    record_stage_changes(conn, actor, &[stage], std::slice::from_ref(&updated)).await?;
+    Ok(updated)
+}
+
+/// Swaps `key` for `replacement` in the `allowed_from` of every stage in `pipeline_id`,
+/// or drops it when there is no replacement.
+async fn replace_allowed_from<C: ConnectionTrait>(
+    conn: &C,
+    actor: &AuditActor,
+    pipeline_id: Uuid,
+    key: &str,
+    replacement: Option<&str>,
+) -> async_graphql::Result<()> {
+    for stage in load_stage_meta(conn, pipeline_id, true).await? {
+        let Some(allowed) = StageRules::from(&stage).allowed_from else {
+            continue;
+        };
+        if !allowed.iter().any(|allowed| allowed == key) {
+            continue;
+        }
+        let mut remapped: Vec<&str> = Vec::with_capacity(allowed.len());
+        for allowed in &allowed {
+            let allowed = match (allowed == key, replacement) {
+                (false, _) => allowed.as_str(),
+                (true, Some(replacement)) => replacement,
+                (true, None) => continue,
+            };
+            if !remapped.contains(&allowed) {
+                remapped.push(allowed);
+            }
+        }
+        let mut active: stage_meta::ActiveModel = stage.clone().into();
+        active.allowed_from = Set(Some(json!(remapped)));
+        let updated = active.update(conn).await.map_err(db_error)?;
+        record_stage_changes(conn, actor, &[stage], &[updated]).await?;
+    }
+    Ok(())
+}
+
+/// Audits each stage in `after` against its row in `before`, matched by key; stages
+/// missing from `before` are recorded as created.
+async fn record_stage_changes<C: ConnectionTrait>(
+    conn: &C,
+    actor: &AuditActor,
+    before: &[stage_meta::Model],
+    after: &[stage_meta::Model],
+) -> async_graphql::Result<()> {
+    for stage in after {
+        let old = before.iter().find(|old| old.key == stage.key);
+        record_change(
+            conn,
+            actor,
+            audit_log::EntityType::Stage,
+            stage.id,
+            old,
+            Some(stage),
+        )
+        .await
+        .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+fn database(ctx: &Context<'_>) -> async_graphql::Result<Arc<DatabaseConnection>> {
+    ctx.data::<Arc<DatabaseConnection>>()
+        .cloned()
+        .map_err(|_| error_with_code("INTERNAL", "Missing database connection"))
+}
+
+fn auth_config(ctx: &Context<'_>) -> async_graphql::Result<Arc<AuthConfig>> {
+    ctx.data::<Arc<AuthConfig>>()
+        .cloned()
+        .map_err(|_| error_with_code("INTERNAL", "Missing auth configuration"))
+}
+
+fn current_user(ctx: &Context<'_>) -> async_graphql::Result<CurrentUser> {
+    ctx.data::<CurrentUser>()
+        .cloned()
+        .map_err(|_| error_with_code("UNAUTHENTICATED", "Login required"))
+}
+
+fn audit_actor(ctx: &Context<'_>) -> async_graphql::Result<AuditActor> {
+    let current = current_user(ctx)?;
+    Ok(actor_in(ctx, current.org_id, Some(current.user_id)))
+}
+
+/// The actor for a change made in `org_id`, also when nobody is signed in yet.
+fn actor_in(ctx: &Context<'_>, org_id: Uuid, user_id: Option<Uuid>) -> AuditActor {
+    AuditActor {
+        org_id,
+        user_id,
+        request_id: ctx
+            .data_opt::<ClientInfo>()
+            .and_then(|client| client.request_id.clone()),
+    }
+}
+
+/// Minimum role a resolver requires; higher roles pass through `CurrentUser::permits`,
+/// which also holds API keys to their scopes.
+struct RoleGuard {
+    min: UserRole,
+}
+
+impl RoleGuard {
+    fn new(min: UserRole) -> Self {
+        Self { min }
+    }
+}
+
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let viewer = ctx
+            .data::<CurrentUser>()
+            .map_err(|_| error_with_code("UNAUTHENTICATED", "Login required"))?;
+        if viewer.permits(self.min) {
+            Ok(())
+        } else if viewer.has_role(self.min) {
+            Err(error_with_code(
+                "FORBIDDEN",
+                "API key scopes do not allow this operation",
+            ))
+        } else {
+            Err(error_with_code(
+                "FORBIDDEN",
+                format!("Requires {} role", self.min.as_str()),
+            ))
+        }
+    }
+}
+
+/// Which CRM records a caller may see and edit: never anything outside their organization.
+/// Within it, admins and owners see everything; anyone else works their own book: records
+/// assigned to them, plus unassigned ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct Visibility {
+    org_id: Uuid,
+    book: Option<Uuid>,
+}
+
+impl Visibility {
+    fn of(user: &CurrentUser) -> Self {
+        Self {
+            org_id: user.org_id,
+            book: (!user.has_role(UserRole::Admin)).then_some(user.user_id),
+        }
+    }
+
+    fn condition(self, org: impl ColumnTrait, assigned_user: impl ColumnTrait) -> Condition {
+        let condition = Condition::all().add(org.eq(self.org_id));
+        match self.book {
+            None => condition,
+            Some(user_id) => condition.add(
+                Condition::any()
+                    .add(assigned_user.is_null())
+                    .add(assigned_user.eq(user_id)),
+            ),
+        }
+    }
+
+    fn scope<E: OwnedRecord>(self) -> Condition {
+        self.condition(E::org_column(), E::owner_column())
+    }
+
+    /// Raw-SQL counterpart of `condition` for the table aliased `table`, pushing its
+    /// placeholder values onto `values`.
+    fn sql(self, table: &str, values: &mut Vec<Value>) -> String {
+        values.push(self.org_id.into());
+        match self.book {
+            None => format!("{table}.org_id = ?"),
+            Some(user_id) => {
+                values.push(user_id.into());
+                format!(
+                    "{table}.org_id = ? AND ({table}.assigned_user_id IS NULL \
+                     OR {table}.assigned_user_id = ?)"
+                )
+            }
+        }
+    }
+
+    /// Whether a subscriber with this visibility hears about `event`.
+    fn admits(self, event: &CrmEvent) -> bool {
+        event.org_id == self.org_id
+            && match (self.book, event.assigned_user_id) {
+                (Some(user_id), Some(assigned)) => assigned == user_id,
+                _ => true,
+            }
+    }
+
+    /// Restricts rows keyed by a deal id (stage history, activities) to visible deals.
+    fn deal_children(self, deal_id: impl ColumnTrait) -> Condition {
+        Condition::all().add(
+            deal_id.in_subquery(
+                deal::Entity::find()
+                    .select_only()
+                    .column(deal::Column::Id)
+                    .filter(self.scope::<deal::Entity>())
+                    .into_query(),
+            ),
+        )
+    }
+
+    /// Fails with `NOT_FOUND` unless the record exists and is visible, so callers cannot
+    /// probe for records in another book or organization.
+    async fn ensure<E: OwnedRecord>(
+        self,
+        db: &impl ConnectionTrait,
+        id: Uuid,
+    ) -> async_graphql::Result<()>
+    where
+        E::Model: Sync,
+    {
+        let visible = E::find()
+            .filter(E::id_column().eq(id))
+            .filter(self.scope::<E>())
+            .count(db)
+            .await
+            .map_err(db_error)?;
+        if visible == 0 {
+            return Err(error_with_code(
+                "NOT_FOUND",
+                format!("{} not found", E::NOUN),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// CRM entities that belong to an organization and carry an `assigned_user_id` owner.
+trait OwnedRecord: EntityTrait {
+    const NOUN: &'static str;
+
+    fn id_column() -> Self::Column;
+    fn org_column() -> Self::Column;
+    fn owner_column() -> Self::Column;
+}
+
+impl OwnedRecord for company::Entity {
+    const NOUN: &'static str = "Company";
+
+    fn id_column() -> Self::Column {
+        company::Column::Id
+    }
+
+    fn org_column() -> Self::Column {
+        company::Column::OrgId
+    }
+
+    fn owner_column() -> Self::Column {
+        company::Column::AssignedUserId
+    }
+}
+
+impl OwnedRecord for contact::Entity {
+    const NOUN: &'static str = "Contact";
+
+    fn id_column() -> Self::Column {
+        contact::Column::Id
+    }
+
+    fn org_column() -> Self::Column {
+        contact::Column::OrgId
+    }
+
+    fn owner_column() -> Self::Column {
+        contact::Column::AssignedUserId
+    }
+}
+
+impl OwnedRecord for deal::Entity {
+    const NOUN: &'static str = "Deal";
+
+    fn id_column() -> Self::Column {
+        deal::Column::Id
+    }
+
+    fn org_column() -> Self::Column {
+        deal::Column::OrgId
+    }
+
+    fn owner_column() -> Self::Column {
+        deal::Column::AssignedUserId
     }
 }
 
-fn database(ctx: &Context<'_>) -> async_graphql::Result<Arc<DatabaseConnection>> {
-    ctx.data::<Arc<DatabaseConnection>>()
-        .cloned()
-        .map_err(|_| error_with_code("INTERNAL", "Missing database connection"))
+impl OwnedRecord for task::Entity {
+    const NOUN: &'static str = "Task";
+
+    fn id_column() -> Self::Column {
+        task::Column::Id
+    }
+
+    fn org_column() -> Self::Column {
+        task::Column::OrgId
+    }
+
+    fn owner_column() -> Self::Column {
+        task::Column::AssignedUserId
+    }
 }
 
-fn auth_config(ctx: &Context<'_>) -> async_graphql::Result<Arc<AuthConfig>> {
-    ctx.data::<Arc<AuthConfig>>()
-        .cloned()
-        .map_err(|_| error_with_code("INTERNAL", "Missing auth configuration"))
+fn visibility(ctx: &Context<'_>) -> async_graphql::Result<Visibility> {
+    current_user(ctx).map(|user| Visibility::of(&user))
 }
 
-fn current_user(ctx: &Context<'_>) -> async_graphql::Result<CurrentUser> {
-    ctx.data::<CurrentUser>()
-        .cloned()
-        .map_err(|_| error_with_code("UNAUTHENTICATED", "Login required"))
+fn crm_loader<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a DataLoader<CrmLoader>> {
+    ctx.data::<DataLoader<CrmLoader>>()
+        .map_err(|_| error_with_code("INTERNAL", "Missing CRM loader"))
 }
 
 fn parse_uuid(id: &ID) -> async_graphql::Result<Uuid> {
@@ -1660,6 +5764,8 @@ fn error_with_code(code: &'static str, message: impl Into<String>) -> Error {
 
 #[derive(Debug, Clone)]
 pub struct SeededCrmRecords {
+    /// The default organization everything below belongs to.
+    pub organization: organization::Model,
     pub users: Vec<app_user::Model>,
     pub companies: Vec<company::Model>,
     pub contacts: Vec<contact::Model>,
@@ -1685,10 +5791,15 @@ impl SeededCrmRecords {
 }
 
 pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords, DbErr> {
-    ensure_stage_meta_defaults(db).await?;
+    let organization = find_organization(db, DEFAULT_ORGANIZATION_SLUG)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound("default organization".into()))?;
+    let org_id = organization.id;
+    let pipeline_id = insert_default_pipeline(db, org_id).await?.id;
     let seeded_at: DateTimeWithTimeZone = Utc::now().into();
     let owner = insert_seed_user(
         db,
+        org_id,
         "owner@sme.test",
         "Owner One",
         &[user_role::Role::Owner, user_role::Role::Admin],
@@ -1697,6 +5808,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
     .await?;
     let admin = insert_seed_user(
         db,
+        org_id,
         "admin@sme.test",
         "Admin Ada",
         &[user_role::Role::Admin],
@@ -1705,6 +5817,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
     .await?;
     let sales = insert_seed_user(
         db,
+        org_id,
         "sales@sme.test",
         "Sales Sam",
         &[user_role::Role::Sales],
@@ -1713,6 +5826,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
     .await?;
     let acme = company::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         name: Set("ACME, Inc.".into()),
         website: Set(Some("https://acme.test".into())),
         phone: Set(Some("+1-555-0100".into())),
@@ -1727,6 +5841,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let fossrust = company::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         name: Set("FossRust Labs".into()),
         website: Set(Some("https://fossrust.test".into())),
         phone: Set(Some("+1-555-0300".into())),
@@ -1741,6 +5856,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let nuflights = company::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         name: Set("NuFlights LLC".into()),
         website: Set(Some("https://nuflights.test".into())),
         phone: Set(Some("+1-555-0200".into())),
@@ -1755,6 +5871,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let ada = contact::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         email: Set("ada@acme.test".into()),
         first_name: Set(Some("Ada".into())),
         last_name: Set(Some("Lovelace".into())),
@@ -1771,6 +5888,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let charles = contact::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         email: Set("charles@acme.test".into()),
         first_name: Set(Some("Charles".into())),
         last_name: Set(Some("Babbage".into())),
@@ -1787,6 +5905,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let linus = contact::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         email: Set("linus@fossrust.test".into()),
         first_name: Set(Some("Linus".into())),
         last_name: Set(Some("Torvalds".into())),
@@ -1803,6 +5922,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let grace = contact::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         email: Set("grace@nuflights.test".into()),
         first_name: Set(Some("Grace".into())),
         last_name: Set(Some("Hopper".into())),
@@ -1819,11 +5939,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let acme_pilot = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("ACME Pilot".into()),
         amount_cents: Set(Some(120_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::Qualify),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("QUALIFY".into()),
         close_date: Set(Some(naive_date(2025, 1, 10))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(acme.id),
         assigned_user_id: Set(Some(sales.id)),
         created_by: Set(Some(owner.id)),
@@ -1836,11 +5960,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let tooling = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("Rust Tooling Upgrade".into()),
         amount_cents: Set(Some(75_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::Proposal),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("PROPOSAL".into()),
         close_date: Set(Some(naive_date(2025, 2, 15))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(fossrust.id),
         assigned_user_id: Set(Some(admin.id)),
         created_by: Set(Some(owner.id)),
@@ -1853,11 +5981,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let renewal = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("NuFlights Annual".into()),
         amount_cents: Set(Some(210_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::Qualify),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("QUALIFY".into()),
         close_date: Set(Some(naive_date(2025, 3, 5))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(nuflights.id),
         assigned_user_id: Set(Some(sales.id)),
         created_by: Set(Some(owner.id)),
@@ -1870,11 +6002,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let retainer = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("ACME Retainer".into()),
         amount_cents: Set(Some(60_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::Negotiate),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("NEGOTIATE".into()),
         close_date: Set(Some(naive_date(2025, 2, 28))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(acme.id),
         assigned_user_id: Set(Some(sales.id)),
         created_by: Set(Some(owner.id)),
@@ -1887,11 +6023,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let expansion = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("FossRust Expansion".into()),
         amount_cents: Set(Some(95_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::Won),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("WON".into()),
         close_date: Set(Some(naive_date(2025, 1, 20))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(fossrust.id),
         assigned_user_id: Set(Some(admin.id)),
         created_by: Set(Some(owner.id)),
@@ -1904,11 +6044,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let quick_win = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("Quick Win".into()),
         amount_cents: Set(Some(40_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::Won),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("WON".into()),
         close_date: Set(Some(naive_date(2025, 2, 10))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(acme.id),
         assigned_user_id: Set(Some(sales.id)),
         created_by: Set(Some(owner.id)),
@@ -1921,11 +6065,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let lost_trial = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("Stalled Trial".into()),
         amount_cents: Set(Some(25_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::Lost),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("LOST".into()),
         close_date: Set(Some(naive_date(2025, 1, 25))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(nuflights.id),
         assigned_user_id: Set(Some(sales.id)),
         created_by: Set(Some(owner.id)),
@@ -1938,11 +6086,15 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
     let fresh_prospect = deal::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("Fresh Prospect".into()),
         amount_cents: Set(Some(55_000)),
         currency: Set(Some("USD".into())),
-        stage: Set(deal::Stage::New),
+        pipeline_id: Set(pipeline_id),
+        stage: Set("NEW".into()),
         close_date: Set(Some(naive_date(2025, 3, 15))),
+        reason_id: Set(None),
+        competitor: Set(None),
         company_id: Set(acme.id),
         assigned_user_id: Set(Some(sales.id)),
         created_by: Set(Some(owner.id)),
@@ -1956,20 +6108,30 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
     let won_histories = vec![
         deal_stage_history::ActiveModel {
             id: Set(Uuid::new_v4()),
+            org_id: Set(org_id),
             deal_id: Set(expansion.id),
-            from_stage: Set(deal::Stage::Negotiate),
-            to_stage: Set(deal::Stage::Won),
+            from_stage: Set("NEGOTIATE".into()),
+            to_stage: Set("WON".into()),
+            from_pipeline_id: Set(pipeline_id),
+            to_pipeline_id: Set(pipeline_id),
             changed_at: Set(timestamp(2025, 1, 22)),
             note: Set(Some("Signed master services.".into())),
+            reason_id: Set(None),
+            competitor: Set(None),
             changed_by: Set(Some(owner.id.to_string())),
         },
         deal_stage_history::ActiveModel {
             id: Set(Uuid::new_v4()),
+            org_id: Set(org_id),
             deal_id: Set(quick_win.id),
-            from_stage: Set(deal::Stage::Proposal),
-            to_stage: Set(deal::Stage::Won),
+            from_stage: Set("PROPOSAL".into()),
+            to_stage: Set("WON".into()),
+            from_pipeline_id: Set(pipeline_id),
+            to_pipeline_id: Set(pipeline_id),
             changed_at: Set(timestamp(2025, 2, 2)),
             note: Set(Some("Fast track approval.".into())),
+            reason_id: Set(None),
+            competitor: Set(None),
             changed_by: Set(Some(owner.id.to_string())),
         },
     ];
@@ -1980,6 +6142,7 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
     }
 
     Ok(SeededCrmRecords {
+        organization,
         users: vec![owner.clone(), admin.clone(), sales.clone()],
         companies: vec![acme, fossrust, nuflights],
         contacts: vec![ada, charles, linus, grace],
@@ -1998,6 +6161,20 @@ pub async fn seed_crm_demo(db: &DatabaseConnection) -> Result<SeededCrmRecords,
 
 async fn insert_seed_user(
     db: &DatabaseConnection,
+    org_id: Uuid,
+    email: &str,
+    display_name: &str,
+    roles: &[user_role::Role],
+    password: &str,
+) -> Result<app_user::Model, DbErr> {
+    insert_local_user(db, org_id, email, display_name, roles, password).await
+}
+
+/// Inserts an active user with a local identity and password, as a member of
+/// `org_id` with `roles`.
+async fn insert_local_user<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
     email: &str,
     display_name: &str,
     roles: &[user_role::Role],
@@ -2013,7 +6190,7 @@ async fn insert_seed_user(
         created_at: Set(now),
         updated_at: Set(now),
     }
-    .insert(db)
+    .insert(conn)
     .await?;
     user_identity::ActiveModel {
         id: Set(Uuid::new_v4()),
@@ -2022,57 +6199,53 @@ async fn insert_seed_user(
         subject: Set(email.to_string()),
         created_at: Set(now),
     }
-    .insert(db)
+    .insert(conn)
     .await?;
-    user_secret::ActiveModel {
-        user_id: Set(model.id),
+    upsert_password(conn, model.id, password).await?;
+    let roles: Vec<UserRole> = roles.iter().map(|role| UserRole::from(*role)).collect();
+    add_member(conn, org_id, model.id, &roles).await?;
+    Ok(model)
+}
+
+async fn upsert_password<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    password: &str,
+) -> Result<(), DbErr> {
+    user_secret::Entity::insert(user_secret::ActiveModel {
+        user_id: Set(user_id),
         password_hash: Set(hash_password(password)?),
-        updated_at: Set(now),
-    }
-    .insert(db)
+        updated_at: Set(Utc::now().into()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(user_secret::Column::UserId)
+            .update_columns([
+                user_secret::Column::PasswordHash,
+                user_secret::Column::UpdatedAt,
+            ])
+            .to_owned(),
+    )
+    .exec(conn)
     .await?;
+    Ok(())
+}
+
+async fn insert_user_roles<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+    roles: &[UserRole],
+) -> Result<(), DbErr> {
     for role in roles {
         user_role::ActiveModel {
-            user_id: Set(model.id),
-            role: Set(*role),
+            org_id: Set(org_id),
+            user_id: Set(user_id),
+            role: Set((*role).into()),
         }
-        .insert(db)
+        .insert(conn)
         .await?;
     }
-    Ok(model)
-}
-
-const STAGE_META_DEFAULTS: [(&str, &str, i16, i16, bool, bool); 6] = [
-    ("NEW", "New", 10, 10, false, false),
-    ("QUALIFY", "Qualify", 20, 25, false, false),
-    ("PROPOSAL", "Proposal", 30, 50, false, false),
-    ("NEGOTIATE", "Negotiate", 40, 70, false, false),
-    ("WON", "Won", 90, 100, true, false),
-    ("LOST", "Lost", 95, 0, false, true),
-];
-
-async fn ensure_stage_meta_defaults(db: &DatabaseConnection) -> Result<(), DbErr> {
-    let rows: Vec<stage_meta::ActiveModel> = STAGE_META_DEFAULTS
-        .iter()
-        .map(
-            |(key, display, order, prob, is_won, is_lost)| stage_meta::ActiveModel {
-                key: Set((*key).to_string()),
-                display_name: Set((*display).to_string()),
-                sort_order: Set(*order),
-                probability: Set(*prob),
-                is_won: Set(*is_won),
-                is_lost: Set(*is_lost),
-            },
-        )
-        .collect();
-    stage_meta::Entity::insert_many(rows)
-        .on_conflict(
-            OnConflict::column(stage_meta::Column::Key)
-                .do_nothing()
-                .to_owned(),
-        )
-        .exec(db)
-        .await?;
     Ok(())
 }
 
@@ -2090,110 +6263,833 @@ fn timestamp(year: i32, month: u32, day: u32) -> DateTimeWithTimeZone {
 /// Exposed for seeders/tests to drive the same transactional logic.
 pub async fn move_deal_stage_service(
     db: &DatabaseConnection,
+    org_id: Uuid,
     deal_id: Uuid,
-    stage: deal::Stage,
+    stage: &str,
     note: Option<String>,
     changed_by: Option<Uuid>,
 ) -> Result<deal::Model, StageMoveError> {
-    move_deal_stage_internal(db, deal_id, stage, note, changed_by).await
+    let actor = AuditActor {
+        org_id,
+        user_id: changed_by,
+        request_id: None,
+    };
+    move_deal_stage_internal(
+        db,
+        deal_id,
+        stage,
+        note,
+        DealOutcomeDetails::default(),
+        &actor,
+    )
+    .await
+    .map(|(deal, _)| deal)
+}
+
+async fn create_task_internal(
+    db: &DatabaseConnection,
+    input: NewTaskInput,
+    current: &CurrentUser,
+    actor: &AuditActor,
+) -> async_graphql::Result<task::Model> {
+    let title = validate_task_title(&input.title)?;
+    let notes_md = validate_notes_md(input.notes_md.clone())?;
+    let assignee = validate_assignee(input.assignee.clone())?;
+    let due_at = input.due_at.map(|d| d.into());
+    let target = select_task_target(&input.company_id, &input.contact_id, &input.deal_id)?;
+    ensure_task_target_exists(db, actor.org_id, &target).await?;
+    let visibility = Visibility::of(current);
+    match target {
+        TaskTarget::Company(id) => visibility.ensure::<company::Entity>(db, id).await?,
+        TaskTarget::Contact(id) => visibility.ensure::<contact::Entity>(db, id).await?,
+        TaskTarget::Deal(id) => visibility.ensure::<deal::Entity>(db, id).await?,
+    }
+
+    let task_id = Uuid::new_v4();
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let mut active = task::ActiveModel {
+        id: Set(task_id),
+        org_id: Set(actor.org_id),
+        title: Set(title),
+        notes_md: Set(notes_md),
+        status: Set(task::Status::Open),
+        priority: Set(task::Priority::from(input.priority)),
+        assignee: Set(assignee),
+        assigned_user_id: Set(None),
+        due_at: Set(due_at),
+        completed_at: Set(None),
+        company_id: Set(None),
+        contact_id: Set(None),
+        deal_id: Set(None),
+        created_by: Set(Some(current.user_id)),
+        updated_by: Set(Some(current.user_id)),
+        created_at: Set(now),
+        updated_at: Set(now),
+    };
+    apply_target_to_model(&mut active, &target);
+    let txn = db.begin().await.map_err(db_error)?;
+    task::Entity::insert(active)
+        .exec_without_returning(&txn)
+        .await
+        .map_err(db_error)?;
+    let record = task::Entity::find_by_id(task_id)
+        .one(&txn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("INTERNAL", "Failed to load inserted task"))?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Task,
+        task_id,
+        None,
+        Some(&record),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(record)
+}
+
+async fn update_task_internal(
+    db: &DatabaseConnection,
+    input: UpdateTaskInput,
+    current: &CurrentUser,
+    actor: &AuditActor,
+) -> async_graphql::Result<task::Model> {
+    let task_id = parse_uuid(&input.id)?;
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = task::Entity::find_by_id(task_id)
+        .filter(task::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "Task not found"))?;
+    let before = existing.clone();
+    let mut active: task::ActiveModel = existing.into();
+    if let Some(title) = &input.title {
+        active.title = Set(validate_task_title(title)?);
+    }
+    if input.notes_md.is_some() {
+        active.notes_md = Set(validate_notes_md(input.notes_md.clone())?);
+    }
+    if let Some(priority) = input.priority {
+        active.priority = Set(task::Priority::from(priority));
+    }
+    if input.assignee.is_some() {
+        active.assignee = Set(validate_assignee(input.assignee.clone())?);
+    }
+    if let Some(due_at) = input.due_at {
+        active.due_at = Set(Some(due_at.into()));
+    }
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    active.updated_at = Set(now);
+    active.updated_by = Set(Some(current.user_id));
+    let updated = active.update(&txn).await.map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Task,
+        task_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(updated)
+}
+
+async fn create_company_internal(
+    db: &DatabaseConnection,
+    input: NewCompanyInput,
+    current: &CurrentUser,
+    actor: &AuditActor,
+) -> async_graphql::Result<company::Model> {
+    let name = validate_company_name(&input.name)?;
+    let website = validate_website(input.website)?;
+    let phone = validate_phone(input.phone)?;
+    let assigned_user_id = match parse_optional_id("assignedUserId", &input.assigned_user_id)? {
+        Some(user_id) => Some(ensure_active_user(db, actor.org_id, user_id).await?),
+        None => None,
+    };
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let active = company::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        org_id: Set(actor.org_id),
+        name: Set(name),
+        website: Set(website),
+        phone: Set(phone),
+        assigned_user_id: Set(assigned_user_id),
+        created_by: Set(Some(current.user_id)),
+        updated_by: Set(Some(current.user_id)),
+        created_at: Set(now),
+        updated_at: Set(now),
+    };
+    let txn = db.begin().await.map_err(db_error)?;
+    let created = active.insert(&txn).await.map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Company,
+        created.id,
+        None,
+        Some(&created),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(created)
+}
+
+async fn update_company_internal(
+    db: &DatabaseConnection,
+    input: UpdateCompanyInput,
+    current: &CurrentUser,
+    actor: &AuditActor,
+) -> async_graphql::Result<company::Model> {
+    let company_id = parse_uuid(&input.id)?;
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = company::Entity::find_by_id(company_id)
+        .filter(company::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "Company not found"))?;
+    let before = existing.clone();
+    let mut active: company::ActiveModel = existing.into();
+    if let Some(name) = &input.name {
+        active.name = Set(validate_company_name(name)?);
+    }
+    if input.website.is_some() {
+        active.website = Set(validate_website(input.website)?);
+    }
+    if input.phone.is_some() {
+        active.phone = Set(validate_phone(input.phone)?);
+    }
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    active.updated_at = Set(now);
+    active.updated_by = Set(Some(current.user_id));
+    let updated = active.update(&txn).await.map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Company,
+        company_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(updated)
+}
+
+async fn delete_company_internal(
+    db: &DatabaseConnection,
+    company_id: Uuid,
+    cascade: bool,
+    actor: &AuditActor,
+) -> async_graphql::Result<bool> {
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = company::Entity::find_by_id(company_id)
+        .filter(company::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
+        .await
+        .map_err(db_error)?;
+    let Some(existing) = existing else {
+        return Ok(false);
+    };
+    let deals = deal::Entity::find()
+        .filter(deal::Column::OrgId.eq(actor.org_id))
+        .filter(deal::Column::CompanyId.eq(company_id))
+        .all(&txn)
+        .await
+        .map_err(db_error)?;
+    let deal_ids: Vec<Uuid> = deals.iter().map(|deal| deal.id).collect();
+    if !deal_ids.is_empty() {
+        if !cascade {
+            return Err(error_with_code(
+                "CONFLICT",
+                format!(
+                    "Company has {} deal(s); pass cascade: true to delete them",
+                    deal_ids.len()
+                ),
+            ));
+        }
+        delete_activities(&txn, actor.org_id, "deal", deal_ids.clone())
+            .await
+            .map_err(db_error)?;
+    }
+    delete_activities(&txn, actor.org_id, "company", vec![company_id])
+        .await
+        .map_err(db_error)?;
+    // Deals, deal/company tasks and stage history cascade; contacts are detached and
+    // keep their own activities.
+    audit_cascaded_tasks(
+        &txn,
+        actor,
+        Condition::any()
+            .add(task::Column::CompanyId.eq(company_id))
+            .add(task::Column::DealId.is_in(deal_ids)),
+    )
+    .await
+    .map_err(db_error)?;
+    for deal in &deals {
+        record_change(
+            &txn,
+            actor,
+            audit_log::EntityType::Deal,
+            deal.id,
+            Some(deal),
+            None,
+        )
+        .await
+        .map_err(db_error)?;
+    }
+    let contacts = contact::Entity::find()
+        .filter(contact::Column::OrgId.eq(actor.org_id))
+        .filter(contact::Column::CompanyId.eq(company_id))
+        .all(&txn)
+        .await
+        .map_err(db_error)?;
+    for contact in &contacts {
+        let detached = contact::Model {
+            company_id: None,
+            ..contact.clone()
+        };
+        record_change(
+            &txn,
+            actor,
+            audit_log::EntityType::Contact,
+            contact.id,
+            Some(contact),
+            Some(&detached),
+        )
+        .await
+        .map_err(db_error)?;
+    }
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Company,
+        company_id,
+        Some(&existing),
+        None,
+    )
+    .await
+    .map_err(db_error)?;
+    let res = company::Entity::delete_many()
+        .filter(company::Column::Id.eq(company_id))
+        .filter(company::Column::OrgId.eq(actor.org_id))
+        .exec(&txn)
+        .await
+        .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(res.rows_affected > 0)
+}
+
+async fn create_deal_internal(
+    db: &DatabaseConnection,
+    input: NewDealInput,
+    current: &CurrentUser,
+    actor: &AuditActor,
+) -> async_graphql::Result<deal::Model> {
+    let title = validate_deal_title(&input.title)?;
+    let amount_cents = validate_amount_cents(input.amount_cents)?;
+    let currency = validate_currency(input.currency)?;
+    let note = sanitize_optional_filter(input.note);
+    let company_id = parse_uuid(&input.company_id)?;
+    ensure_company_exists(db, actor.org_id, company_id).await?;
+    let assigned_user_id = match parse_optional_id("assignedUserId", &input.assigned_user_id)? {
+        Some(user_id) => Some(ensure_active_user(db, actor.org_id, user_id).await?),
+        None => None,
+    };
+
+    let txn = db.begin().await.map_err(db_error)?;
+    let pipeline = resolve_pipeline(&txn, actor.org_id, input.pipeline_id.as_ref()).await?;
+    let initial_stage = initial_deal_stage(&txn, pipeline.id).await?;
+    let target = match input.stage {
+        Some(DealStage(key)) => {
+            let open = open_stage(&txn, pipeline.id, &key)
+                .await
+                .map_err(db_error)?;
+            Some(open.ok_or_else(|| unknown_stage_error(&key))?)
+        }
+        None => None,
+    };
+    let stage = target
+        .as_ref()
+        .map_or_else(|| initial_stage.clone(), |target| target.key.clone());
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let active = deal::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        org_id: Set(actor.org_id),
+        title: Set(title),
+        amount_cents: Set(amount_cents),
+        currency: Set(currency),
+        pipeline_id: Set(pipeline.id),
+        stage: Set(stage.clone()),
+        close_date: Set(input.close_date),
+        reason_id: Set(None),
+        competitor: Set(None),
+        company_id: Set(company_id),
+        assigned_user_id: Set(assigned_user_id),
+        created_by: Set(Some(current.user_id)),
+        updated_by: Set(Some(current.user_id)),
+        created_at: Set(now),
+        updated_at: Set(now),
+    };
+    let created = active.insert(&txn).await.map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Deal,
+        created.id,
+        None,
+        Some(&created),
+    )
+    .await
+    .map_err(db_error)?;
+    if let Some(target) = target.as_ref().filter(|_| stage != initial_stage) {
+        // Starting a deal past the first stage counts as moving it there.
+        check_stage_rules(target, &created, &initial_stage, note.as_deref(), false)
+            .map_err(StageRuleViolation::into_error)?;
+        record_stage_change(
+            &txn,
+            &created,
+            created.pipeline_id,
+            &initial_stage,
+            &stage,
+            note,
+            Some(current.user_id),
+            now,
+        )
+        .await
+        .map_err(db_error)?;
+    }
+    txn.commit().await.map_err(db_error)?;
+    Ok(created)
+}
+
+/// New deals start in their pipeline's lowest-ordered active stage.
+async fn initial_deal_stage<C: ConnectionTrait>(
+    conn: &C,
+    pipeline_id: Uuid,
+) -> async_graphql::Result<String> {
+    let first = stage_meta::Entity::find()
+        .filter(stage_meta::Column::PipelineId.eq(pipeline_id))
+        .filter(stage_meta::Column::IsArchived.eq(false))
+        .order_by_asc(stage_meta::Column::SortOrder)
+        .one(conn)
+        .await
+        .map_err(db_error)?;
+    first
+        .map(|stage| stage.key)
+        .ok_or_else(|| validation_error("The pipeline has no active stages"))
+}
+
+async fn update_deal_internal(
+    db: &DatabaseConnection,
+    input: UpdateDealInput,
+    current: &CurrentUser,
+    actor: &AuditActor,
+) -> async_graphql::Result<deal::Model> {
+    let deal_id = parse_uuid(&input.id)?;
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = deal::Entity::find_by_id(deal_id)
+        .filter(deal::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "Deal not found"))?;
+    let before = existing.clone();
+    let mut active: deal::ActiveModel = existing.into();
+    if let Some(title) = &input.title {
+        active.title = Set(validate_deal_title(title)?);
+    }
+    match input.amount_cents {
+        MaybeUndefined::Value(amount) => {
+            active.amount_cents = Set(validate_amount_cents(Some(amount))?);
+        }
+        MaybeUndefined::Null => active.amount_cents = Set(None),
+        MaybeUndefined::Undefined => {}
+    }
+    if input.currency.is_some() {
+        active.currency = Set(validate_currency(input.currency)?);
+    }
+    match input.close_date {
+        MaybeUndefined::Value(date) => active.close_date = Set(Some(date)),
+        MaybeUndefined::Null => active.close_date = Set(None),
+        MaybeUndefined::Undefined => {}
+    }
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    active.updated_at = Set(now);
+    active.updated_by = Set(Some(current.user_id));
+    let updated = active.update(&txn).await.map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Deal,
+        deal_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(updated)
 }
 
-async fn create_task_internal(
+async fn create_contact_internal(
     db: &DatabaseConnection,
-    input: NewTaskInput,
+    input: NewContactInput,
     current: &CurrentUser,
-) -> async_graphql::Result<task::Model> {
-    let title = validate_task_title(&input.title)?;
-    let notes_md = validate_notes_md(input.notes_md.clone())?;
-    let assignee = validate_assignee(input.assignee.clone())?;
-    let due_at = input.due_at.map(|d| d.into());
-    let target = select_task_target(&input.company_id, &input.contact_id, &input.deal_id)?;
-    ensure_task_target_exists(db, &target).await?;
-
-    let task_id = Uuid::new_v4();
+    actor: &AuditActor,
+) -> async_graphql::Result<contact::Model> {
+    let email = validate_contact_email(&input.email)?;
+    let first_name = validate_person_name("firstName", input.first_name)?;
+    let last_name = validate_person_name("lastName", input.last_name)?;
+    let phone = validate_phone(input.phone)?;
+    let company_id = parse_optional_id("companyId", &input.company_id)?;
+    if let Some(company_id) = company_id {
+        ensure_company_exists(db, actor.org_id, company_id).await?;
+    }
+    let assigned_user_id = match parse_optional_id("assignedUserId", &input.assigned_user_id)? {
+        Some(user_id) => Some(ensure_active_user(db, actor.org_id, user_id).await?),
+        None => None,
+    };
+    ensure_contact_email_available(db, actor.org_id, company_id, &email, None).await?;
     let now: DateTimeWithTimeZone = Utc::now().into();
-    let mut active = task::ActiveModel {
-        id: Set(task_id),
-        title: Set(title),
-        notes_md: Set(notes_md),
-        status: Set(task::Status::Open),
-        priority: Set(task::Priority::from(input.priority)),
-        assignee: Set(assignee),
-        assigned_user_id: Set(None),
-        due_at: Set(due_at),
-        completed_at: Set(None),
-        company_id: Set(None),
-        contact_id: Set(None),
-        deal_id: Set(None),
+    let active = contact::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        org_id: Set(actor.org_id),
+        email: Set(email),
+        first_name: Set(first_name),
+        last_name: Set(last_name),
+        phone: Set(phone),
+        company_id: Set(company_id),
+        assigned_user_id: Set(assigned_user_id),
         created_by: Set(Some(current.user_id)),
         updated_by: Set(Some(current.user_id)),
         created_at: Set(now),
         updated_at: Set(now),
     };
-    apply_target_to_model(&mut active, &target);
-    task::Entity::insert(active)
-        .exec_without_returning(db)
-        .await
-        .map_err(db_error)?;
-    let record = task::Entity::find_by_id(task_id)
-        .one(db)
-        .await
-        .map_err(db_error)?
-        .ok_or_else(|| error_with_code("INTERNAL", "Failed to load inserted task"))?;
-    Ok(record)
+    let txn = db.begin().await.map_err(db_error)?;
+    let created = active.insert(&txn).await.map_err(contact_write_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Contact,
+        created.id,
+        None,
+        Some(&created),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(created)
 }
 
-async fn update_task_internal(
+async fn update_contact_internal(
     db: &DatabaseConnection,
-    input: UpdateTaskInput,
+    input: UpdateContactInput,
     current: &CurrentUser,
-) -> async_graphql::Result<task::Model> {
-    let task_id = parse_uuid(&input.id)?;
-    let existing = task::Entity::find_by_id(task_id)
-        .one(db)
+    actor: &AuditActor,
+) -> async_graphql::Result<contact::Model> {
+    let contact_id = parse_uuid(&input.id)?;
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = contact::Entity::find_by_id(contact_id)
+        .filter(contact::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
         .await
         .map_err(db_error)?
-        .ok_or_else(|| error_with_code("NOT_FOUND", "Task not found"))?;
-    let mut active: task::ActiveModel = existing.into();
-    if let Some(title) = &input.title {
-        active.title = Set(validate_task_title(title)?);
-    }
-    if input.notes_md.is_some() {
-        active.notes_md = Set(validate_notes_md(input.notes_md.clone())?);
+        .ok_or_else(|| error_with_code("NOT_FOUND", "Contact not found"))?;
+    let before = existing.clone();
+    let company_id = existing.company_id;
+    let mut active: contact::ActiveModel = existing.into();
+    if let Some(email) = &input.email {
+        let email = validate_contact_email(email)?;
+        ensure_contact_email_available(&txn, actor.org_id, company_id, &email, Some(contact_id))
+            .await?;
+        active.email = Set(email);
     }
-    if let Some(priority) = input.priority {
-        active.priority = Set(task::Priority::from(priority));
+    if input.first_name.is_some() {
+        active.first_name = Set(validate_person_name("firstName", input.first_name)?);
     }
-    if input.assignee.is_some() {
-        active.assignee = Set(validate_assignee(input.assignee.clone())?);
+    if input.last_name.is_some() {
+        active.last_name = Set(validate_person_name("lastName", input.last_name)?);
     }
-    if let Some(due_at) = input.due_at {
-        active.due_at = Set(Some(due_at.into()));
+    if input.phone.is_some() {
+        active.phone = Set(validate_phone(input.phone)?);
     }
     let now: DateTimeWithTimeZone = Utc::now().into();
     active.updated_at = Set(now);
     active.updated_by = Set(Some(current.user_id));
-    let updated = active.update(db).await.map_err(db_error)?;
+    let updated = active.update(&txn).await.map_err(contact_write_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Contact,
+        contact_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(updated)
+}
+
+async fn move_contact_internal(
+    db: &DatabaseConnection,
+    contact_id: Uuid,
+    company_id: Option<Uuid>,
+    current: &CurrentUser,
+    actor: &AuditActor,
+) -> async_graphql::Result<contact::Model> {
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = contact::Entity::find_by_id(contact_id)
+        .filter(contact::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "Contact not found"))?;
+    if existing.company_id == company_id {
+        return Ok(existing);
+    }
+    if let Some(company_id) = company_id {
+        ensure_company_exists(&txn, actor.org_id, company_id).await?;
+    }
+    ensure_contact_email_available(
+        &txn,
+        actor.org_id,
+        company_id,
+        &existing.email,
+        Some(contact_id),
+    )
+    .await?;
+    let before = existing.clone();
+    let mut active: contact::ActiveModel = existing.into();
+    active.company_id = Set(company_id);
+    active.updated_at = Set(Utc::now().into());
+    active.updated_by = Set(Some(current.user_id));
+    let updated = active.update(&txn).await.map_err(contact_write_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Contact,
+        contact_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
     Ok(updated)
 }
 
+/// Activities reference their record by `(entity_type, entity_id)` without a foreign
+/// key, so deleting the record has to take them along explicitly.
+async fn delete_activities<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    entity_type: &str,
+    entity_ids: Vec<Uuid>,
+) -> Result<(), DbErr> {
+    activity::Entity::delete_many()
+        .filter(activity::Column::OrgId.eq(org_id))
+        .filter(activity::Column::EntityType.eq(entity_type))
+        .filter(activity::Column::EntityId.is_in(entity_ids))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+async fn delete_contact_internal(
+    db: &DatabaseConnection,
+    contact_id: Uuid,
+    actor: &AuditActor,
+) -> async_graphql::Result<bool> {
+    let txn = db.begin().await.map_err(db_error)?;
+    let existing = contact::Entity::find_by_id(contact_id)
+        .filter(contact::Column::OrgId.eq(actor.org_id))
+        .one(&txn)
+        .await
+        .map_err(db_error)?;
+    let Some(existing) = existing else {
+        return Ok(false);
+    };
+    // The contact's tasks and activities go with it.
+    delete_activities(&txn, actor.org_id, "contact", vec![contact_id])
+        .await
+        .map_err(db_error)?;
+    audit_cascaded_tasks(
+        &txn,
+        actor,
+        Condition::all().add(task::Column::ContactId.eq(contact_id)),
+    )
+    .await
+    .map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Contact,
+        contact_id,
+        Some(&existing),
+        None,
+    )
+    .await
+    .map_err(db_error)?;
+    let res = contact::Entity::delete_many()
+        .filter(contact::Column::Id.eq(contact_id))
+        .filter(contact::Column::OrgId.eq(actor.org_id))
+        .exec(&txn)
+        .await
+        .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(res.rows_affected > 0)
+}
+
+async fn delete_task_internal(
+    db: &DatabaseConnection,
+    task_id: Uuid,
+    actor: &AuditActor,
+) -> async_graphql::Result<bool> {
+    let txn = db.begin().await.map_err(db_error)?;
+    audit_cascaded_tasks(
+        &txn,
+        actor,
+        Condition::all().add(task::Column::Id.eq(task_id)),
+    )
+    .await
+    .map_err(db_error)?;
+    let res = task::Entity::delete_many()
+        .filter(task::Column::Id.eq(task_id))
+        .filter(task::Column::OrgId.eq(actor.org_id))
+        .exec(&txn)
+        .await
+        .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok(res.rows_affected > 0)
+}
+
+/// Audits the tasks a delete removes, itself or through `ON DELETE CASCADE`.
+async fn audit_cascaded_tasks<C: ConnectionTrait>(
+    conn: &C,
+    actor: &AuditActor,
+    filter: Condition,
+) -> Result<(), DbErr> {
+    let tasks = task::Entity::find()
+        .filter(task::Column::OrgId.eq(actor.org_id))
+        .filter(filter)
+        .all(conn)
+        .await?;
+    for task in tasks {
+        record_change(
+            conn,
+            actor,
+            audit_log::EntityType::Task,
+            task.id,
+            Some(&task),
+            None,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn ensure_company_exists<C: ConnectionTrait>(
+    db: &C,
+    org_id: Uuid,
+    company_id: Uuid,
+) -> async_graphql::Result<()> {
+    let exists = company::Entity::find_by_id(company_id)
+        .filter(company::Column::OrgId.eq(org_id))
+        .one(db)
+        .await
+        .map_err(db_error)?
+        .is_some();
+    if !exists {
+        return Err(validation_error("Company not found"));
+    }
+    Ok(())
+}
+
+/// Contact emails are unique per company (case-insensitively); contacts without a
+/// company form their own bucket in each organization. Mirrors `idx_contact_company_email`.
+async fn ensure_contact_email_available<C: ConnectionTrait>(
+    db: &C,
+    org_id: Uuid,
+    company_id: Option<Uuid>,
+    email: &str,
+    exclude: Option<Uuid>,
+) -> async_graphql::Result<()> {
+    let email_expr = Expr::expr(Func::lower(Expr::col(contact::Column::Email)));
+    let mut query = contact::Entity::find()
+        .filter(contact::Column::OrgId.eq(org_id))
+        .filter(email_expr.eq(email.to_lowercase()));
+    query = match company_id {
+        Some(id) => query.filter(contact::Column::CompanyId.eq(id)),
+        None => query.filter(contact::Column::CompanyId.is_null()),
+    };
+    if let Some(id) = exclude {
+        query = query.filter(contact::Column::Id.ne(id));
+    }
+    let duplicate = query.one(db).await.map_err(db_error)?;
+    if duplicate.is_some() {
+        return Err(error_with_code(
+            "CONFLICT",
+            format!(
+                "A contact with email {} already exists for this company",
+                email
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn contact_write_error(err: DbErr) -> Error {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => error_with_code(
+            "CONFLICT",
+            "A contact with this email already exists for this company",
+        ),
+        _ => db_error(err),
+    }
+}
+
 async fn transition_task_status(
     db: &DatabaseConnection,
     existing: task::Model,
     next_status: task::Status,
     completed_at: Option<DateTimeWithTimeZone>,
     current: &CurrentUser,
+    actor: &AuditActor,
 ) -> async_graphql::Result<task::Model> {
     if existing.status == next_status && existing.completed_at == completed_at {
         return Ok(existing);
     }
+    let before = existing.clone();
     let mut active: task::ActiveModel = existing.into();
     active.status = Set(next_status);
     active.completed_at = Set(completed_at);
     let now: DateTimeWithTimeZone = Utc::now().into();
     active.updated_at = Set(now);
     active.updated_by = Set(Some(current.user_id));
-    let updated = active.update(db).await.map_err(db_error)?;
+    let txn = db.begin().await.map_err(db_error)?;
+    let updated = active.update(&txn).await.map_err(db_error)?;
+    record_change(
+        &txn,
+        actor,
+        audit_log::EntityType::Task,
+        updated.id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
     Ok(updated)
 }
 
@@ -2272,43 +7168,221 @@ impl TryFrom<SearchHitRow> for SearchHit {
     }
 }
 
+/// Tie-breaker for equally scored hits: companies, then contacts, then deals.
+const SEARCH_KIND_RANK: &str = "CASE kind WHEN 'COMPANY' THEN 0 WHEN 'CONTACT' THEN 1 ELSE 2 END";
+
+fn search_kind_rank(kind: &str) -> i64 {
+    match kind {
+        "COMPANY" => 0,
+        "CONTACT" => 1,
+        _ => 2,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SearchMode {
+    FullText,
+    Trigram,
+}
+
+impl SearchMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            SearchMode::FullText => "fts",
+            SearchMode::Trigram => "trgm",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fts" => Some(SearchMode::FullText),
+            "trgm" => Some(SearchMode::Trigram),
+            _ => None,
+        }
+    }
+}
+
+/// Position after a search hit. Search cursors lead with the matching mode so later
+/// pages stay on the strategy (full text or trigram) that produced the first one.
+struct SearchKey {
+    mode: SearchMode,
+    score: f64,
+    rank: i64,
+    title: String,
+    id: Uuid,
+}
+
+impl SearchKey {
+    fn from_row(mode: SearchMode, row: &SearchHitRow) -> Self {
+        Self {
+            mode,
+            score: row.score,
+            rank: search_kind_rank(&row.kind),
+            title: row.title.clone(),
+            id: row.id,
+        }
+    }
+
+    fn decode(values: &[CursorValue]) -> Option<Self> {
+        match values {
+            [CursorValue::Text(mode), CursorValue::Float(score), CursorValue::Int(rank), CursorValue::Text(title), CursorValue::Id(id)] => {
+                Some(Self {
+                    mode: SearchMode::parse(mode)?,
+                    score: *score,
+                    rank: *rank,
+                    title: title.clone(),
+                    id: *id,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> KeysetCursor {
+        OpaqueCursor(vec![
+            CursorValue::Text(self.mode.as_str().to_string()),
+            CursorValue::Float(self.score),
+            CursorValue::Int(self.rank),
+            CursorValue::Text(self.title.clone()),
+            CursorValue::Id(self.id),
+        ])
+    }
+}
+
+struct SearchPage {
+    mode: SearchMode,
+    rows: Vec<SearchHitRow>,
+    has_next: bool,
+}
+
+#[derive(Clone, Copy)]
+struct SearchScope<'a> {
+    q: &'a str,
+    visibility: Visibility,
+    company: bool,
+    contact: bool,
+    deal: bool,
+}
+
+impl<'a> SearchScope<'a> {
+    fn new(q: &'a str, kinds: &[CrmSearchKind], visibility: Visibility) -> Self {
+        Self {
+            q,
+            visibility,
+            company: kinds.contains(&CrmSearchKind::Company),
+            contact: kinds.contains(&CrmSearchKind::Contact),
+            deal: kinds.contains(&CrmSearchKind::Deal),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.company && !self.contact && !self.deal
+    }
+
+    /// Appends the caller's visibility filter to the select just pushed onto `selects`.
+    fn restrict(&self, selects: &mut [String], values: &mut Vec<Value>, table: &str) {
+        if let Some(select) = selects.last_mut() {
+            select.push_str(" AND ");
+            select.push_str(&self.visibility.sql(table, values));
+        }
+    }
+}
+
 async fn search_hits(
     db: &DatabaseConnection,
     q: &str,
     kinds: &[CrmSearchKind],
+    visibility: Visibility,
     limit: u64,
-    offset: u64,
-) -> async_graphql::Result<Vec<SearchHit>> {
-    let allow_company = kinds.iter().any(|k| *k == CrmSearchKind::Company);
-    let allow_contact = kinds.iter().any(|k| *k == CrmSearchKind::Contact);
-    let allow_deal = kinds.iter().any(|k| *k == CrmSearchKind::Deal);
-    if !allow_company && !allow_contact && !allow_deal {
-        return Ok(vec![]);
+    after: Option<&SearchKey>,
+) -> async_graphql::Result<SearchPage> {
+    let scope = SearchScope::new(q, kinds, visibility);
+    if scope.is_empty() {
+        return Ok(SearchPage {
+            mode: SearchMode::Trigram,
+            rows: vec![],
+            has_next: false,
+        });
+    }
+    if let Some(key) = after {
+        return search_page(db, key.mode, scope, limit, Some(key)).await;
     }
     let use_fts = q.len() >= 2 && has_tsquery_terms(db, q).await?;
     if use_fts {
-        run_fts_search(
-            db,
-            q,
-            allow_company,
-            allow_contact,
-            allow_deal,
-            limit,
-            offset,
-        )
-        .await
-    } else {
-        run_trgm_search(
-            db,
-            q,
-            allow_company,
-            allow_contact,
-            allow_deal,
-            limit,
-            offset,
-        )
+        let page = search_page(db, SearchMode::FullText, scope, limit, None).await?;
+        // Fall back to fuzzy matching so typos still find something on the first page.
+        if !page.rows.is_empty() {
+            return Ok(page);
+        }
+    }
+    search_page(db, SearchMode::Trigram, scope, limit, None).await
+}
+
+async fn search_page(
+    db: &DatabaseConnection,
+    mode: SearchMode,
+    scope: SearchScope<'_>,
+    limit: u64,
+    after: Option<&SearchKey>,
+) -> async_graphql::Result<SearchPage> {
+    let (selects, mut values) = search_selects(mode, scope);
+    let mut sql = format!("SELECT * FROM ({}) hits", selects.join(" UNION ALL "));
+    if let Some(key) = after {
+        sql.push_str(&format!(
+            " WHERE score < ? OR (score = ? AND ({rank} > ? OR ({rank} = ? AND \
+             (title > ? OR (title = ? AND id > ?)))))",
+            rank = SEARCH_KIND_RANK
+        ));
+        values.push(key.score.into());
+        values.push(key.score.into());
+        values.push(key.rank.into());
+        values.push(key.rank.into());
+        values.push(key.title.clone().into());
+        values.push(key.title.clone().into());
+        values.push(key.id.into());
+    }
+    sql.push_str(&format!(
+        " ORDER BY score DESC, {}, title ASC, id ASC LIMIT {}",
+        SEARCH_KIND_RANK,
+        limit + 1
+    ));
+    let stmt = postgres_statement(&sql, values);
+    let mut rows = SearchHitRow::find_by_statement(stmt)
+        .all(db)
         .await
+        .map_err(db_error)?;
+    let has_next = rows.len() as u64 > limit;
+    rows.truncate(limit as usize);
+    Ok(SearchPage {
+        mode,
+        rows,
+        has_next,
+    })
+}
+
+async fn count_search_hits(
+    db: &DatabaseConnection,
+    mode: SearchMode,
+    q: &str,
+    kinds: &[CrmSearchKind],
+    visibility: Visibility,
+) -> async_graphql::Result<i64> {
+    let scope = SearchScope::new(q, kinds, visibility);
+    if scope.is_empty() {
+        return Ok(0);
     }
+    let (selects, values) = search_selects(mode, scope);
+    let sql = format!(
+        "SELECT COUNT(*) AS total FROM ({}) hits",
+        selects.join(" UNION ALL ")
+    );
+    let row = db
+        .query_one(postgres_statement(&sql, values))
+        .await
+        .map_err(db_error)?;
+    Ok(row
+        .and_then(|r| r.try_get::<i64>("", "total").ok())
+        .unwrap_or(0))
 }
 
 async fn has_tsquery_terms(db: &DatabaseConnection, q: &str) -> async_graphql::Result<bool> {
@@ -2323,165 +7397,157 @@ async fn has_tsquery_terms(db: &DatabaseConnection, q: &str) -> async_graphql::R
         .unwrap_or(false))
 }
 
-async fn run_fts_search(
-    db: &DatabaseConnection,
-    q: &str,
-    allow_company: bool,
-    allow_contact: bool,
-    allow_deal: bool,
-    limit: u64,
-    offset: u64,
-) -> async_graphql::Result<Vec<SearchHit>> {
+fn search_selects(mode: SearchMode, scope: SearchScope<'_>) -> (Vec<String>, Vec<Value>) {
+    match mode {
+        SearchMode::FullText => fts_selects(scope),
+        SearchMode::Trigram => trgm_selects(scope),
+    }
+}
+
+fn fts_selects(scope: SearchScope<'_>) -> (Vec<String>, Vec<Value>) {
+    let q = scope.q;
     let mut selects: Vec<String> = Vec::new();
     let mut values: Vec<Value> = Vec::new();
-    if allow_company {
+    if scope.company {
         selects.push(
-            "SELECT 'COMPANY' AS kind, id, name AS title, website AS subtitle,\
-             LEAST(1.0, ts_rank_cd(tsv, websearch_to_tsquery('simple', ?), ARRAY[1.0,0.4,0.2,0.1])) AS score,\
-             '/crm/company/' || id::text AS href\
-             FROM company\
+            "SELECT 'COMPANY' AS kind, id, name AS title, website AS subtitle, \
+             LEAST(1.0, ts_rank_cd(ARRAY[0.1,0.2,0.4,1.0]::float4[], tsv, websearch_to_tsquery('simple', ?)))::float8 AS score, \
+             '/crm/company/' || id::text AS href \
+             FROM company \
              WHERE tsv @@ websearch_to_tsquery('simple', ?)"
                 .to_string(),
         );
         values.push(q.to_owned().into());
         values.push(q.to_owned().into());
+        scope.restrict(&mut selects, &mut values, "company");
     }
-    if allow_contact {
+    if scope.contact {
         selects.push(
-            "SELECT 'CONTACT' AS kind, contact.id,\
-             COALESCE(NULLIF(trim(coalesce(contact.first_name, '') || ' ' || coalesce(contact.last_name, '')), ''), contact.email) AS title,\
-             companies.name AS subtitle,\
-             LEAST(1.0, ts_rank_cd(contact.tsv, websearch_to_tsquery('simple', ?), ARRAY[1.0,0.4,0.2,0.1])) AS score,\
-             '/crm/contact/' || contact.id::text AS href\
-             FROM contact\
-             LEFT JOIN company AS companies ON companies.id = contact.company_id\
+            "SELECT 'CONTACT' AS kind, contact.id, \
+             COALESCE(NULLIF(trim(coalesce(contact.first_name, '') || ' ' || coalesce(contact.last_name, '')), ''), contact.email) AS title, \
+             companies.name AS subtitle, \
+             LEAST(1.0, ts_rank_cd(ARRAY[0.1,0.2,0.4,1.0]::float4[], contact.tsv, websearch_to_tsquery('simple', ?)))::float8 AS score, \
+             '/crm/contact/' || contact.id::text AS href \
+             FROM contact \
+             LEFT JOIN company AS companies ON companies.id = contact.company_id \
              WHERE contact.tsv @@ websearch_to_tsquery('simple', ?)"
                 .to_string(),
         );
         values.push(q.to_owned().into());
         values.push(q.to_owned().into());
+        scope.restrict(&mut selects, &mut values, "contact");
     }
-    if allow_deal {
+    if scope.deal {
         selects.push(
-            "SELECT 'DEAL' AS kind, deal.id, deal.title AS title, companies.name AS subtitle,\
-             LEAST(1.0, ts_rank_cd(deal.tsv, websearch_to_tsquery('simple', ?), ARRAY[1.0,0.4,0.2,0.1])) AS score,\
-             '/crm/deal/' || deal.id::text AS href\
-             FROM deal\
-             LEFT JOIN company AS companies ON companies.id = deal.company_id\
+            "SELECT 'DEAL' AS kind, deal.id, deal.title AS title, companies.name AS subtitle, \
+             LEAST(1.0, ts_rank_cd(ARRAY[0.1,0.2,0.4,1.0]::float4[], deal.tsv, websearch_to_tsquery('simple', ?)))::float8 AS score, \
+             '/crm/deal/' || deal.id::text AS href \
+             FROM deal \
+             LEFT JOIN company AS companies ON companies.id = deal.company_id \
              WHERE deal.tsv @@ websearch_to_tsquery('simple', ?)"
                 .to_string(),
         );
         values.push(q.to_owned().into());
         values.push(q.to_owned().into());
+        scope.restrict(&mut selects, &mut values, "deal");
     }
-    if selects.is_empty() {
-        return Ok(vec![]);
-    }
-    let sql = format!(
-        "{} ORDER BY score DESC, title ASC LIMIT {} OFFSET {}",
-        selects.join(" UNION ALL "),
-        limit,
-        offset
-    );
-    let stmt = Statement::from_sql_and_values(DatabaseBackend::Postgres, sql, values);
-    let rows = SearchHitRow::find_by_statement(stmt)
-        .all(db)
-        .await
-        .map_err(db_error)?;
-    Ok(rows
-        .into_iter()
-        .filter_map(|row| SearchHit::try_from(row).ok())
-        .collect())
+    (selects, values)
 }
 
-async fn run_trgm_search(
-    db: &DatabaseConnection,
-    q: &str,
-    allow_company: bool,
-    allow_contact: bool,
-    allow_deal: bool,
-    limit: u64,
-    offset: u64,
-) -> async_graphql::Result<Vec<SearchHit>> {
+fn trgm_selects(scope: SearchScope<'_>) -> (Vec<String>, Vec<Value>) {
+    let q = scope.q;
     let mut selects: Vec<String> = Vec::new();
     let mut values: Vec<Value> = Vec::new();
     let pattern = format!("%{}%", q);
-    if allow_company {
+    // `%` alone misses single-letter typos on short names ("Ackme" vs "ACME, Inc."), so
+    // word similarity against the best-matching word is accepted at a lower threshold.
+    if scope.company {
         selects.push(
-            "SELECT 'COMPANY' AS kind, id, name AS title, website AS subtitle,\
-             LEAST(1.0, GREATEST(similarity(name, ?), similarity(coalesce(website, ''), ?))) AS score,\
-             '/crm/company/' || id::text AS href\
-             FROM company\
-             WHERE name % ? OR name ILIKE ? OR website ILIKE ?"
+            "SELECT 'COMPANY' AS kind, id, name AS title, website AS subtitle, \
+             LEAST(1.0, GREATEST(similarity(name, ?), word_similarity(?, name), similarity(coalesce(website, ''), ?)))::float8 AS score, \
+             '/crm/company/' || id::text AS href \
+             FROM company \
+             WHERE (name % ? OR word_similarity(?, name) >= 0.3 OR name ILIKE ? OR website ILIKE ?)"
                 .to_string(),
         );
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
+        for _ in 0..5 {
+            values.push(q.to_owned().into());
+        }
         values.push(pattern.clone().into());
         values.push(pattern.clone().into());
+        scope.restrict(&mut selects, &mut values, "company");
     }
-    if allow_contact {
+    if scope.contact {
         selects.push(
-            "SELECT 'CONTACT' AS kind, contact.id,\
-             COALESCE(NULLIF(trim(coalesce(contact.first_name, '') || ' ' || coalesce(contact.last_name, '')), ''), contact.email) AS title,\
-             companies.name AS subtitle,\
-             LEAST(1.0, GREATEST(similarity(contact.email, ?), similarity(coalesce(contact.first_name, ''), ?), similarity(coalesce(contact.last_name, ''), ?))) AS score,\
-             '/crm/contact/' || contact.id::text AS href\
-             FROM contact\
-             LEFT JOIN company AS companies ON companies.id = contact.company_id\
-             WHERE contact.email % ? OR contact.first_name % ? OR contact.last_name % ?\
-             OR contact.email ILIKE ? OR contact.first_name ILIKE ? OR contact.last_name ILIKE ?"
+            "SELECT 'CONTACT' AS kind, contact.id, \
+             COALESCE(NULLIF(trim(coalesce(contact.first_name, '') || ' ' || coalesce(contact.last_name, '')), ''), contact.email) AS title, \
+             companies.name AS subtitle, \
+             LEAST(1.0, GREATEST(similarity(contact.email, ?), similarity(coalesce(contact.first_name, ''), ?), similarity(coalesce(contact.last_name, ''), ?)))::float8 AS score, \
+             '/crm/contact/' || contact.id::text AS href \
+             FROM contact \
+             LEFT JOIN company AS companies ON companies.id = contact.company_id \
+             WHERE (contact.email % ? OR contact.first_name % ? OR contact.last_name % ? \
+             OR contact.email ILIKE ? OR contact.first_name ILIKE ? OR contact.last_name ILIKE ?)"
                 .to_string(),
         );
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
-        values.push(pattern.clone().into());
-        values.push(pattern.clone().into());
-        values.push(pattern.clone().into());
+        for _ in 0..6 {
+            values.push(q.to_owned().into());
+        }
+        for _ in 0..3 {
+            values.push(pattern.clone().into());
+        }
+        scope.restrict(&mut selects, &mut values, "contact");
     }
-    if allow_deal {
+    if scope.deal {
         selects.push(
-            "SELECT 'DEAL' AS kind, deal.id, deal.title AS title, companies.name AS subtitle,\
-             LEAST(1.0, similarity(deal.title, ?)) AS score,\
-             '/crm/deal/' || deal.id::text AS href\
-             FROM deal\
-             LEFT JOIN company AS companies ON companies.id = deal.company_id\
-             WHERE deal.title % ? OR deal.title ILIKE ?"
+            "SELECT 'DEAL' AS kind, deal.id, deal.title AS title, companies.name AS subtitle, \
+             LEAST(1.0, GREATEST(similarity(deal.title, ?), word_similarity(?, deal.title)))::float8 AS score, \
+             '/crm/deal/' || deal.id::text AS href \
+             FROM deal \
+             LEFT JOIN company AS companies ON companies.id = deal.company_id \
+             WHERE (deal.title % ? OR word_similarity(?, deal.title) >= 0.3 OR deal.title ILIKE ?)"
                 .to_string(),
         );
-        values.push(q.to_owned().into());
-        values.push(q.to_owned().into());
+        for _ in 0..4 {
+            values.push(q.to_owned().into());
+        }
         values.push(pattern.clone().into());
+        scope.restrict(&mut selects, &mut values, "deal");
     }
-    if selects.is_empty() {
-        return Ok(vec![]);
-    }
-    let sql = format!(
-        "{} ORDER BY score DESC, title ASC LIMIT {} OFFSET {}",
-        selects.join(" UNION ALL "),
-        limit,
-        offset
-    );
-    let stmt = Statement::from_sql_and_values(DatabaseBackend::Postgres, sql, values);
-    let rows = SearchHitRow::find_by_statement(stmt)
-        .all(db)
+    (selects, values)
+}
+
+/// The organization's pipeline `id`, or its default pipeline when `id` is `None`.
+async fn resolve_pipeline<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    id: Option<&ID>,
+) -> async_graphql::Result<pipeline::Model> {
+    let mut query = pipeline::Entity::find().filter(pipeline::Column::OrgId.eq(org_id));
+    query = match id {
+        Some(id) => query.filter(pipeline::Column::Id.eq(parse_uuid(id)?)),
+        None => query.filter(pipeline::Column::IsDefault.eq(true)),
+    };
+    query
+        .one(conn)
         .await
-        .map_err(db_error)?;
-    Ok(rows
-        .into_iter()
-        .filter_map(|row| SearchHit::try_from(row).ok())
-        .collect())
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "Pipeline not found"))
 }
 
-async fn load_stage_meta(db: &DatabaseConnection) -> async_graphql::Result<Vec<stage_meta::Model>> {
-    stage_meta::Entity::find()
+async fn load_stage_meta<C: ConnectionTrait>(
+    conn: &C,
+    pipeline_id: Uuid,
+    include_archived: bool,
+) -> async_graphql::Result<Vec<stage_meta::Model>> {
+    let mut query =
+        stage_meta::Entity::find().filter(stage_meta::Column::PipelineId.eq(pipeline_id));
+    if !include_archived {
+        query = query.filter(stage_meta::Column::IsArchived.eq(false));
+    }
+    query
         .order_by_asc(stage_meta::Column::SortOrder)
-        .all(db)
+        .all(conn)
         .await
         .map_err(db_error)
 }
@@ -2544,9 +7610,17 @@ fn normalize_stage_key(value: &str) -> Option<String> {
     }
 }
 
-fn deal_filter_clauses(company_id: Option<Uuid>, q: Option<&str>) -> (Vec<String>, Vec<Value>) {
+fn deal_filter_clauses(
+    visibility: Visibility,
+    pipeline_id: Uuid,
+    company_id: Option<Uuid>,
+    q: Option<&str>,
+) -> (Vec<String>, Vec<Value>) {
     let mut clauses = Vec::new();
     let mut values = Vec::new();
+    clauses.push(visibility.sql("d", &mut values));
+    clauses.push("d.pipeline_id = ?".to_string());
+    values.push(pipeline_id.into());
     if let Some(uuid) = company_id {
         clauses.push("d.company_id = ?".to_string());
         values.push(uuid.into());
@@ -2558,6 +7632,21 @@ fn deal_filter_clauses(company_id: Option<Uuid>, q: Option<&str>) -> (Vec<String
     (clauses, values)
 }
 
+/// Builds a Postgres statement from SQL written with `?` placeholders, numbering them `$1..$n`.
+pub(crate) fn postgres_statement(sql: &str, values: Vec<Value>) -> Statement {
+    let mut numbered = String::with_capacity(sql.len() + values.len() * 2);
+    let mut index = 0;
+    for ch in sql.chars() {
+        if ch == '?' {
+            index += 1;
+            numbered.push_str(&format!("${index}"));
+        } else {
+            numbered.push(ch);
+        }
+    }
+    Statement::from_sql_and_values(DatabaseBackend::Postgres, numbered, values)
+}
+
 fn where_clause(clauses: &[String]) -> String {
     if clauses.is_empty() {
         String::new()
@@ -2576,21 +7665,23 @@ struct StageAggregateRow {
 
 async fn query_pipeline_stage_totals(
     db: &DatabaseConnection,
+    visibility: Visibility,
+    pipeline_id: Uuid,
     company_id: Option<Uuid>,
     q: Option<&str>,
 ) -> async_graphql::Result<Vec<StageAggregateRow>> {
-    let (clauses, values) = deal_filter_clauses(company_id, q);
+    let (clauses, values) = deal_filter_clauses(visibility, pipeline_id, company_id, q);
     let where_sql = where_clause(&clauses);
     let sql = format!(
-        "SELECT d.stage::text AS stage_key, COUNT(*) AS total_count,\
-         COALESCE(SUM(COALESCE(d.amount_cents, 0)), 0) AS total_amount_cents,\
-         COALESCE(SUM(((COALESCE(d.amount_cents, 0)::bigint) * sm.probability::bigint) / 100), 0) AS total_expected_cents\
-         FROM deal d\
-         JOIN stage_meta sm ON sm.key = d.stage::text\
-         {where_sql}\
+        "SELECT d.stage AS stage_key, COUNT(*) AS total_count, \
+         COALESCE(SUM(COALESCE(d.amount_cents, 0)), 0)::bigint AS total_amount_cents, \
+         COALESCE(SUM(((COALESCE(d.amount_cents, 0)::bigint) * sm.probability::bigint) / 100), 0)::bigint AS total_expected_cents \
+         FROM deal d \
+         JOIN stage_meta sm ON sm.pipeline_id = d.pipeline_id AND sm.key = d.stage \
+         {where_sql} \
          GROUP BY d.stage"
     );
-    let stmt = Statement::from_sql_and_values(DatabaseBackend::Postgres, sql, values);
+    let stmt = postgres_statement(&sql, values);
     StageAggregateRow::find_by_statement(stmt)
         .all(db)
         .await
@@ -2610,22 +7701,25 @@ struct PipelineDealRow {
     updated_at: DateTimeWithTimeZone,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn query_stage_deals(
     db: &DatabaseConnection,
+    visibility: Visibility,
+    pipeline_id: Uuid,
     stage_key: &str,
     company_id: Option<Uuid>,
     q: Option<&str>,
     order_by_updated: bool,
     limit: u64,
 ) -> async_graphql::Result<Vec<PipelineDeal>> {
-    let (clauses, mut values) = deal_filter_clauses(company_id, q);
+    let (clauses, mut values) = deal_filter_clauses(visibility, pipeline_id, company_id, q);
     let mut sql = String::from(
-        "SELECT d.id, d.title, d.amount_cents, d.currency,\
-         d.stage::text AS stage_key, d.company_id, c.name AS company_name,\
-         d.close_date AS expected_close, d.updated_at\
-         FROM deal d\
-         JOIN company c ON c.id = d.company_id\
-         WHERE d.stage = ?::deal_stage",
+        "SELECT d.id, d.title, d.amount_cents, d.currency, \
+         d.stage AS stage_key, d.company_id, c.name AS company_name, \
+         d.close_date AS expected_close, d.updated_at \
+         FROM deal d \
+         JOIN company c ON c.id = d.company_id \
+         WHERE d.stage = ?",
     );
     values.insert(0, stage_key.to_string().into());
     if !clauses.is_empty() {
@@ -2638,7 +7732,7 @@ async fn query_stage_deals(
         "d.created_at"
     };
     sql.push_str(&format!(" ORDER BY {order_col} DESC LIMIT {}", limit));
-    let stmt = Statement::from_sql_and_values(DatabaseBackend::Postgres, sql, values);
+    let stmt = postgres_statement(&sql, values);
     let rows = PipelineDealRow::find_by_statement(stmt)
         .all(db)
         .await
@@ -2670,31 +7764,127 @@ struct StageReportRow {
 
 async fn query_report_stage_totals(
     db: &DatabaseConnection,
+    visibility: Visibility,
+    pipeline_id: Uuid,
     range: &DateRange,
     include_lost: bool,
 ) -> async_graphql::Result<Vec<StageReportRow>> {
     let mut clauses = vec!["d.close_date BETWEEN ?::date AND ?::date".to_string()];
+    let mut values = vec![range.from.to_string().into(), range.to.to_string().into()];
     if !include_lost {
         clauses.push("sm.is_lost = false".to_string());
     }
+    clauses.push("d.pipeline_id = ?".to_string());
+    values.push(pipeline_id.into());
+    clauses.push(visibility.sql("d", &mut values));
     let where_sql = where_clause(&clauses);
     let sql = format!(
-        "SELECT d.stage::text AS stage_key, COUNT(*) AS total_count,\
-         COALESCE(SUM(COALESCE(d.amount_cents, 0)), 0) AS amount_cents,\
-         COALESCE(SUM(((COALESCE(d.amount_cents, 0)::bigint) * sm.probability::bigint) / 100), 0) AS expected_cents\
-         FROM deal d\
-         JOIN stage_meta sm ON sm.key = d.stage::text\
-         {where_sql}\
+        "SELECT d.stage AS stage_key, COUNT(*) AS total_count, \
+         COALESCE(SUM(COALESCE(d.amount_cents, 0)), 0)::bigint AS amount_cents, \
+         COALESCE(SUM(((COALESCE(d.amount_cents, 0)::bigint) * sm.probability::bigint) / 100), 0)::bigint AS expected_cents \
+         FROM deal d \
+         JOIN stage_meta sm ON sm.pipeline_id = d.pipeline_id AND sm.key = d.stage \
+         {where_sql} \
          GROUP BY d.stage",
     );
-    let values = vec![range.from.to_string().into(), range.to.to_string().into()];
-    let stmt = Statement::from_sql_and_values(DatabaseBackend::Postgres, sql, values);
+    let stmt = postgres_statement(&sql, values);
     StageReportRow::find_by_statement(stmt)
         .all(db)
         .await
         .map_err(db_error)
 }
 
+/// How many deals each of the pipeline's stages holds, whoever can see them.
+async fn count_deals_per_stage(
+    db: &DatabaseConnection,
+    pipeline_id: Uuid,
+) -> async_graphql::Result<HashMap<String, i64>> {
+    let rows: Vec<(String, i64)> = deal::Entity::find()
+        .select_only()
+        .column(deal::Column::Stage)
+        .column_as(deal::Column::Id.count(), "deals")
+        .filter(deal::Column::PipelineId.eq(pipeline_id))
+        .group_by(deal::Column::Stage)
+        .into_tuple()
+        .all(db)
+        .await
+        .map_err(db_error)?;
+    Ok(rows.into_iter().collect())
+}
+
+#[derive(Debug, FromQueryResult)]
+struct WinLossAggregateRow {
+    group_key: Option<String>,
+    label: String,
+    won: i64,
+    lost: i64,
+    won_amount_cents: i64,
+    lost_amount_cents: i64,
+}
+
+async fn query_win_loss_rows(
+    db: &DatabaseConnection,
+    visibility: Visibility,
+    pipeline_id: Option<Uuid>,
+    range: &DateRange,
+    group_by: WinLossGroup,
+) -> async_graphql::Result<Vec<WinLossAggregateRow>> {
+    let mut clauses = vec![
+        "d.close_date BETWEEN ?::date AND ?::date".to_string(),
+        "(sm.is_won OR sm.is_lost)".to_string(),
+    ];
+    let mut values = vec![range.from.to_string().into(), range.to.to_string().into()];
+    if let Some(pipeline_id) = pipeline_id {
+        clauses.push("d.pipeline_id = ?".to_string());
+        values.push(pipeline_id.into());
+    }
+    clauses.push(visibility.sql("d", &mut values));
+    let where_sql = where_clause(&clauses);
+    let (key, label, join) = match group_by {
+        WinLossGroup::Reason => (
+            "d.reason_id",
+            "COALESCE(r.label, 'No reason')",
+            "LEFT JOIN deal_reason r ON r.id = d.reason_id",
+        ),
+        WinLossGroup::Owner => (
+            "d.assigned_user_id",
+            "COALESCE(u.display_name, 'Unassigned')",
+            "LEFT JOIN app_user u ON u.id = d.assigned_user_id",
+        ),
+        WinLossGroup::Company => (
+            "d.company_id",
+            "c.name",
+            "JOIN company c ON c.id = d.company_id",
+        ),
+    };
+    let sql = format!(
+        "SELECT {key}::text AS group_key, {label} AS label, \
+         COUNT(*) FILTER (WHERE sm.is_won) AS won, \
+         COUNT(*) FILTER (WHERE sm.is_lost) AS lost, \
+         COALESCE(SUM(COALESCE(d.amount_cents, 0)) FILTER (WHERE sm.is_won), 0)::bigint AS won_amount_cents, \
+         COALESCE(SUM(COALESCE(d.amount_cents, 0)) FILTER (WHERE sm.is_lost), 0)::bigint AS lost_amount_cents \
+         FROM deal d \
+         JOIN stage_meta sm ON sm.pipeline_id = d.pipeline_id AND sm.key = d.stage \
+         {join} \
+         {where_sql} \
+         GROUP BY 1, 2 \
+         ORDER BY COUNT(*) DESC, label",
+    );
+    let stmt = postgres_statement(&sql, values);
+    WinLossAggregateRow::find_by_statement(stmt)
+        .all(db)
+        .await
+        .map_err(db_error)
+}
+
+fn win_rate(won: i64, lost: i64) -> f64 {
+    if won + lost == 0 {
+        0.0
+    } else {
+        won as f64 / (won + lost) as f64
+    }
+}
+
 #[derive(Debug, FromQueryResult)]
 struct ForecastAggregateRow {
     period: String,
@@ -2705,27 +7895,32 @@ struct ForecastAggregateRow {
 
 async fn query_forecast_points(
     db: &DatabaseConnection,
+    visibility: Visibility,
+    pipeline_id: Uuid,
     range: &DateRange,
     include_lost: bool,
 ) -> async_graphql::Result<Vec<ForecastAggregateRow>> {
     let mut clauses = vec!["d.close_date BETWEEN ?::date AND ?::date".to_string()];
+    let mut values = vec![range.from.to_string().into(), range.to.to_string().into()];
     if !include_lost {
         clauses.push("sm.is_lost = false".to_string());
     }
+    clauses.push("d.pipeline_id = ?".to_string());
+    values.push(pipeline_id.into());
+    clauses.push(visibility.sql("d", &mut values));
     let where_sql = where_clause(&clauses);
     let sql = format!(
-        "SELECT to_char(date_trunc('month', d.close_date::timestamp), 'YYYY-MM') AS period,\
-         COALESCE(SUM(COALESCE(d.amount_cents, 0)), 0) AS amount_cents,\
-         COALESCE(SUM(((COALESCE(d.amount_cents, 0)::bigint) * sm.probability::bigint) / 100), 0) AS expected_cents,\
-         COUNT(*) AS deals\
-         FROM deal d\
-         JOIN stage_meta sm ON sm.key = d.stage::text\
-         {where_sql}\
-         GROUP BY period\
+        "SELECT to_char(date_trunc('month', d.close_date::timestamp), 'YYYY-MM') AS period, \
+         COALESCE(SUM(COALESCE(d.amount_cents, 0)), 0)::bigint AS amount_cents, \
+         COALESCE(SUM(((COALESCE(d.amount_cents, 0)::bigint) * sm.probability::bigint) / 100), 0)::bigint AS expected_cents, \
+         COUNT(*) AS deals \
+         FROM deal d \
+         JOIN stage_meta sm ON sm.pipeline_id = d.pipeline_id AND sm.key = d.stage \
+         {where_sql} \
+         GROUP BY period \
          ORDER BY period",
     );
-    let values = vec![range.from.to_string().into(), range.to.to_string().into()];
-    let stmt = Statement::from_sql_and_values(DatabaseBackend::Postgres, sql, values);
+    let stmt = postgres_statement(&sql, values);
     ForecastAggregateRow::find_by_statement(stmt)
         .all(db)
         .await
@@ -2788,22 +7983,35 @@ struct VelocityRow {
     won_at: DateTimeWithTimeZone,
 }
 
+/// Deals first won in `range`, counting only wins into a stage of `pipeline_id`.
 async fn query_velocity_rows(
     db: &DatabaseConnection,
+    visibility: Visibility,
+    pipeline_id: Uuid,
     range: &DateRange,
 ) -> async_graphql::Result<Vec<VelocityRow>> {
-    let sql = "WITH won AS (
-            SELECT deal_id, MIN(changed_at) AS won_at
-            FROM deal_stage_history
-            WHERE to_stage = 'WON'
-            GROUP BY deal_id
+    let mut sql = String::from(
+        "WITH won AS (
+            SELECT h.deal_id, MIN(h.changed_at) AS won_at
+            FROM deal_stage_history h
+            JOIN stage_meta sm ON sm.pipeline_id = h.to_pipeline_id AND sm.key = h.to_stage
+            WHERE sm.is_won AND h.org_id = ? AND sm.pipeline_id = ?
+            GROUP BY h.deal_id
         )
         SELECT d.created_at, won.won_at
         FROM won
         JOIN deal d ON d.id = won.deal_id
-        WHERE won.won_at::date BETWEEN ?::date AND ?::date";
-    let values = vec![range.from.to_string().into(), range.to.to_string().into()];
-    let stmt = Statement::from_sql_and_values(DatabaseBackend::Postgres, sql, values);
+        WHERE won.won_at::date BETWEEN ?::date AND ?::date",
+    );
+    let mut values = vec![
+        visibility.org_id.into(),
+        pipeline_id.into(),
+        range.from.to_string().into(),
+        range.to.to_string().into(),
+    ];
+    sql.push_str(" AND ");
+    sql.push_str(&visibility.sql("d", &mut values));
+    let stmt = postgres_statement(&sql, values);
     VelocityRow::find_by_statement(stmt)
         .all(db)
         .await
@@ -2841,75 +8049,699 @@ fn percentile(values: &[f64], percentile: f64) -> f64 {
     values[idx]
 }
 
-async fn load_roles(
-    db: &DatabaseConnection,
-    user_id: Uuid,
-) -> async_graphql::Result<Vec<UserRole>> {
-    let rows = user_role::Entity::find()
-        .filter(user_role::Column::UserId.eq(user_id))
-        .all(db)
+/// Batches the lookups behind the nested `Company`/`Contact`/`Deal`/`Task` resolvers so
+/// resolving a relation across a page of records costs one query instead of one per row.
+struct CrmLoader {
+    db: Arc<DatabaseConnection>,
+}
+
+/// Single-record keys carry the caller's organization; a record from another one
+/// resolves to nothing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct UserKey(Uuid, Uuid);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct CompanyKey(Uuid, Uuid);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct ContactKey(Uuid, Uuid);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct DealKey(Uuid, Uuid);
+
+/// Relation lists carry the caller's visibility so nested lists stay inside their book.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct CompanyContactsKey(Uuid, Visibility);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct CompanyDealsKey(Uuid, Visibility);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+struct DealTasksKey(Uuid, Visibility);
+
+/// Groups loader keys by their scope (organization or visibility), yielding the ids to
+/// load for each.
+fn group_keys<K, G: Eq + std::hash::Hash>(
+    keys: &[K],
+    split: impl Fn(&K) -> (Uuid, G),
+) -> HashMap<G, Vec<Uuid>> {
+    let mut grouped: HashMap<G, Vec<Uuid>> = HashMap::new();
+    for key in keys {
+        let (id, scope) = split(key);
+        grouped.entry(scope).or_default().push(id);
+    }
+    grouped
+}
+
+impl Loader<UserKey> for CrmLoader {
+    type Value = UserNode;
+    type Error = Error;
+
+    async fn load(&self, keys: &[UserKey]) -> Result<HashMap<UserKey, UserNode>, Error> {
+        let mut loaded = HashMap::new();
+        for (org_id, ids) in group_keys(keys, |key| (key.0, key.1)) {
+            let mut memberships = load_memberships(self.db.as_ref(), org_id, &ids).await?;
+            let users = app_user::Entity::find()
+                .filter(app_user::Column::Id.is_in(ids))
+                .all(self.db.as_ref())
+                .await
+                .map_err(db_error)?;
+            for model in users {
+                if let Some(membership) = memberships.remove(&model.id) {
+                    loaded.insert(
+                        UserKey(model.id, org_id),
+                        UserNode::from_member(model, membership),
+                    );
+                }
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<CompanyKey> for CrmLoader {
+    type Value = company::Model;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[CompanyKey],
+    ) -> Result<HashMap<CompanyKey, company::Model>, Error> {
+        let mut loaded = HashMap::new();
+        for (org_id, ids) in group_keys(keys, |key| (key.0, key.1)) {
+            let rows = company::Entity::find()
+                .filter(company::Column::OrgId.eq(org_id))
+                .filter(company::Column::Id.is_in(ids))
+                .all(self.db.as_ref())
+                .await
+                .map_err(db_error)?;
+            loaded.extend(
+                rows.into_iter()
+                    .map(|model| (CompanyKey(model.id, org_id), model)),
+            );
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<ContactKey> for CrmLoader {
+    type Value = contact::Model;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[ContactKey],
+    ) -> Result<HashMap<ContactKey, contact::Model>, Error> {
+        let mut loaded = HashMap::new();
+        for (org_id, ids) in group_keys(keys, |key| (key.0, key.1)) {
+            let rows = contact::Entity::find()
+                .filter(contact::Column::OrgId.eq(org_id))
+                .filter(contact::Column::Id.is_in(ids))
+                .all(self.db.as_ref())
+                .await
+                .map_err(db_error)?;
+            loaded.extend(
+                rows.into_iter()
+                    .map(|model| (ContactKey(model.id, org_id), model)),
+            );
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<DealKey> for CrmLoader {
+    type Value = deal::Model;
+    type Error = Error;
+
+    async fn load(&self, keys: &[DealKey]) -> Result<HashMap<DealKey, deal::Model>, Error> {
+        let mut loaded = HashMap::new();
+        for (org_id, ids) in group_keys(keys, |key| (key.0, key.1)) {
+            let rows = deal::Entity::find()
+                .filter(deal::Column::OrgId.eq(org_id))
+                .filter(deal::Column::Id.is_in(ids))
+                .all(self.db.as_ref())
+                .await
+                .map_err(db_error)?;
+            loaded.extend(
+                rows.into_iter()
+                    .map(|model| (DealKey(model.id, org_id), model)),
+            );
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<CompanyContactsKey> for CrmLoader {
+    type Value = Vec<contact::Model>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[CompanyContactsKey],
+    ) -> Result<HashMap<CompanyContactsKey, Vec<contact::Model>>, Error> {
+        let mut grouped: HashMap<CompanyContactsKey, Vec<contact::Model>> = HashMap::new();
+        for (visibility, ids) in group_keys(keys, |key| (key.0, key.1)) {
+            let query = contact::Entity::find()
+                .filter(contact::Column::CompanyId.is_in(ids))
+                .filter(visibility.scope::<contact::Entity>());
+            let rows = apply_contact_ordering(query, ContactOrder::NameAsc)
+                .all(self.db.as_ref())
+                .await
+                .map_err(db_error)?;
+            for row in rows {
+                if let Some(company_id) = row.company_id {
+                    grouped
+                        .entry(CompanyContactsKey(company_id, visibility))
+                        .or_default()
+                        .push(row);
+                }
+            }
+        }
+        Ok(grouped)
+    }
+}
+
+impl Loader<CompanyDealsKey> for CrmLoader {
+    type Value = Vec<deal::Model>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[CompanyDealsKey],
+    ) -> Result<HashMap<CompanyDealsKey, Vec<deal::Model>>, Error> {
+        let mut grouped: HashMap<CompanyDealsKey, Vec<deal::Model>> = HashMap::new();
+        for (visibility, ids) in group_keys(keys, |key| (key.0, key.1)) {
+            let query = deal::Entity::find()
+                .filter(deal::Column::CompanyId.is_in(ids))
+                .filter(visibility.scope::<deal::Entity>());
+            let rows = apply_deal_ordering(query, DealOrder::UpdatedDesc)
+                .all(self.db.as_ref())
+                .await
+                .map_err(db_error)?;
+            for row in rows {
+                grouped
+                    .entry(CompanyDealsKey(row.company_id, visibility))
+                    .or_default()
+                    .push(row);
+            }
+        }
+        Ok(grouped)
+    }
+}
+
+impl Loader<DealTasksKey> for CrmLoader {
+    type Value = Vec<task::Model>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[DealTasksKey],
+    ) -> Result<HashMap<DealTasksKey, Vec<task::Model>>, Error> {
+        let mut grouped: HashMap<DealTasksKey, Vec<task::Model>> = HashMap::new();
+        for (visibility, ids) in group_keys(keys, |key| (key.0, key.1)) {
+            let query = task::Entity::find()
+                .filter(task::Column::DealId.is_in(ids))
+                .filter(visibility.scope::<task::Entity>());
+            let rows = apply_task_ordering(query, TaskOrder::DueAsc)
+                .all(self.db.as_ref())
+                .await
+                .map_err(db_error)?;
+            for row in rows {
+                if let Some(deal_id) = row.deal_id {
+                    grouped
+                        .entry(DealTasksKey(deal_id, visibility))
+                        .or_default()
+                        .push(row);
+                }
+            }
+        }
+        Ok(grouped)
+    }
+}
+
+async fn load_user(
+    ctx: &Context<'_>,
+    user_id: Option<Uuid>,
+) -> async_graphql::Result<Option<UserNode>> {
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+    crm_loader(ctx)?
+        .load_one(UserKey(user_id, current_user(ctx)?.org_id))
+        .await
+}
+
+async fn load_company(
+    ctx: &Context<'_>,
+    company_id: Option<Uuid>,
+) -> async_graphql::Result<Option<CompanyNode>> {
+    let Some(company_id) = company_id else {
+        return Ok(None);
+    };
+    let record = crm_loader(ctx)?
+        .load_one(CompanyKey(company_id, current_user(ctx)?.org_id))
+        .await?;
+    Ok(record.map(CompanyNode::from))
+}
+
+impl From<user_role::Role> for UserRole {
+    fn from(value: user_role::Role) -> Self {
+        match value {
+            user_role::Role::Owner => UserRole::Owner,
+            user_role::Role::Admin => UserRole::Admin,
+            user_role::Role::Sales => UserRole::Sales,
+            user_role::Role::Viewer => UserRole::Viewer,
+        }
+    }
+}
+
+impl From<UserRole> for user_role::Role {
+    fn from(value: UserRole) -> Self {
+        match value {
+            UserRole::Owner => user_role::Role::Owner,
+            UserRole::Admin => user_role::Role::Admin,
+            UserRole::Sales => user_role::Role::Sales,
+            UserRole::Viewer => user_role::Role::Viewer,
+        }
+    }
+}
+
+async fn load_roles<C: ConnectionTrait>(
+    db: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> async_graphql::Result<Vec<UserRole>> {
+    let rows = user_role::Entity::find()
+        .filter(user_role::Column::OrgId.eq(org_id))
+        .filter(user_role::Column::UserId.eq(user_id))
+        .all(db)
+        .await
+        .map_err(db_error)?;
+    Ok(rows.into_iter().map(|row| row.role.into()).collect())
+}
+
+/// Memberships in `org_id` among `user_ids`; non-members are left out.
+async fn load_memberships<C: ConnectionTrait>(
+    db: &C,
+    org_id: Uuid,
+    user_ids: &[Uuid],
+) -> async_graphql::Result<HashMap<Uuid, Membership>> {
+    if user_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let members = organization_member::Entity::find()
+        .filter(organization_member::Column::OrgId.eq(org_id))
+        .filter(organization_member::Column::UserId.is_in(user_ids.to_vec()))
+        .all(db)
+        .await
+        .map_err(db_error)?;
+    let mut map: HashMap<Uuid, Membership> = members
+        .into_iter()
+        .map(|member| {
+            let membership = Membership {
+                is_active: member.is_active,
+                roles: Vec::new(),
+            };
+            (member.user_id, membership)
+        })
+        .collect();
+    let rows = user_role::Entity::find()
+        .filter(user_role::Column::OrgId.eq(org_id))
+        .filter(user_role::Column::UserId.is_in(user_ids.to_vec()))
+        .all(db)
+        .await
+        .map_err(db_error)?;
+    for row in rows {
+        if let Some(membership) = map.get_mut(&row.user_id) {
+            membership.roles.push(row.role.into());
+        }
+    }
+    Ok(map)
+}
+
+async fn load_user_with_roles(
+    db: &DatabaseConnection,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> async_graphql::Result<(app_user::Model, Vec<UserRole>)> {
+    let model = app_user::Entity::find_by_id(user_id)
+        .one(db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("UNAUTHENTICATED", "User not found"))?;
+    if !model.is_active {
+        return Err(error_with_code("FORBIDDEN", "Account disabled"));
+    }
+    let roles = member_roles(db, org_id, user_id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("FORBIDDEN", "Account disabled"))?;
+    Ok((model, roles))
+}
+
+async fn create_user_internal(
+    db: &DatabaseConnection,
+    email: &str,
+    display_name: &str,
+    roles: Vec<UserRole>,
+    password: &str,
+    actor: &AuditActor,
+) -> async_graphql::Result<(app_user::Model, Vec<UserRole>)> {
+    let email = normalize_email(email)?;
+    validate_length("email", &email, 320)?;
+    let display_name = validate_display_name(display_name)?;
+    let roles = validate_roles(roles)?;
+    let entity_roles: Vec<user_role::Role> = roles.iter().map(|role| (*role).into()).collect();
+    let txn = db.begin().await.map_err(db_error)?;
+    let model = insert_local_user(
+        &txn,
+        actor.org_id,
+        &email,
+        &display_name,
+        &entity_roles,
+        password,
+    )
+    .await
+    .map_err(user_write_error)?;
+    record_audit(
+        &txn,
+        actor,
+        audit_log::EntityType::User,
+        model.id,
+        audit_log::Operation::Create,
+        diff(&Default::default(), &user_snapshot(&model, &roles)),
+    )
+    .await
+    .map_err(db_error)?;
+    txn.commit().await.map_err(db_error)?;
+    Ok((model, roles))
+}
+
+/// A user's recorded columns plus their roles, which live in `user_role`.
+fn user_snapshot(
+    model: &app_user::Model,
+    roles: &[UserRole],
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut snapshot = snapshot(model);
+    let roles: Vec<&str> = roles.iter().map(|role| role.as_str()).collect();
+    snapshot.insert("roles".into(), json!(roles));
+    snapshot
+}
+
+/// Records that a credential changed without copying either value.
+fn secret_change(field: &str, new: Option<&str>) -> serde_json::Map<String, serde_json::Value> {
+    let mut changes = serde_json::Map::new();
+    changes.insert(field.into(), json!({ "old": REDACTED, "new": new }));
+    changes
+}
+
+/// Starts a session in `org_id` for a user who has passed every login step.
+async fn sign_in(
+    ctx: &Context<'_>,
+    db: &DatabaseConnection,
+    auth: &AuthConfig,
+    user: app_user::Model,
+    org_id: Uuid,
+    roles: Vec<UserRole>,
+) -> async_graphql::Result<AuthPayload> {
+    let client = ctx.data_opt::<ClientInfo>();
+    let user_agent = client.and_then(|client| client.user_agent.as_deref());
+    let session = start_session(db, user.id, org_id, user_agent, auth)
+        .await
+        .map_err(db_error)?;
+    clear_failures(db, &ThrottleKey::Account(user.email.clone()))
+        .await
+        .map_err(db_error)?;
+    record_auth_event(
+        db,
+        auth_event::Kind::LoginSucceeded,
+        Some(user.id),
+        Some(&user.email),
+        client,
+        Some("password"),
+    )
+    .await
+    .map_err(db_error)?;
+    let token = issue_session_token(user.id, org_id, session.id, auth)
+        .map_err(|_| error_with_code("INTERNAL", "Failed to issue session"))?;
+    let cookie = build_session_cookie(&token, auth.session_ttl_minutes);
+    ctx.append_http_header("Set-Cookie", cookie);
+    Ok(AuthPayload {
+        ok: true,
+        user: Some(UserNode::from_model(user, roles)),
+        ..Default::default()
+    })
+}
+
+/// Logs a failed attempt and counts it towards lockouts, logging any lockout
+/// it starts.
+async fn login_failed(
+    ctx: &Context<'_>,
+    db: &DatabaseConnection,
+    keys: &[ThrottleKey],
+    user_id: Option<Uuid>,
+    email: &str,
+    reason: &str,
+) -> async_graphql::Result<()> {
+    let client = ctx.data_opt::<ClientInfo>();
+    let kind = auth_event::Kind::LoginFailed;
+    record_auth_event(db, kind, user_id, Some(email), client, Some(reason))
+        .await
+        .map_err(db_error)?;
+    for (key, until) in record_failure(db, keys).await.map_err(db_error)? {
+        let detail = format!("{} locked until {}", key.label(), until.to_rfc3339());
+        let kind = auth_event::Kind::LockedOut;
+        record_auth_event(db, kind, user_id, Some(email), client, Some(&detail))
+            .await
+            .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+async fn two_factor_required(
+    db: &DatabaseConnection,
+    org_id: Uuid,
+    roles: &[UserRole],
+) -> async_graphql::Result<bool> {
+    totp::two_factor_required(db, org_id, roles)
+        .await
+        .map_err(db_error)
+}
+
+/// The account a TOTP mutation acts on and the organization it signs in to:
+/// the holder of an enrolment challenge when one is given, otherwise the
+/// signed-in user. API keys never qualify.
+fn totp_subject(
+    ctx: &Context<'_>,
+    challenge_token: Option<&str>,
+) -> async_graphql::Result<(Uuid, Uuid)> {
+    if let Some(token) = challenge_token {
+        let auth = auth_config(ctx)?;
+        return decode_challenge_token(token, ChallengePurpose::Enroll, &auth)
+            .map(|claims| (claims.sub, claims.org))
+            .map_err(|_| error_with_code("UNAUTHENTICATED", "Sign-in expired, please retry"));
+    }
+    let current = current_user(ctx)?;
+    if current.scopes.is_some() {
+        return Err(error_with_code(
+            "FORBIDDEN",
+            "API keys cannot manage two-factor authentication",
+        ));
+    }
+    Ok((current.user_id, current.org_id))
+}
+
+/// Why a pending TOTP secret could not be stored for `user_id`.
+async fn totp_unavailable(db: &DatabaseConnection, user_id: Uuid) -> Error {
+    match user_secret::Entity::find_by_id(user_id).one(db).await {
+        Ok(Some(_)) => error_with_code("CONFLICT", "Two-factor authentication is already enabled"),
+        Ok(None) => validation_error("Two-factor authentication applies to password logins"),
+        Err(err) => db_error(err),
+    }
+}
+
+/// Whose API keys the caller may manage: their own, or anyone's for an owner.
+/// Keys cannot mint or revoke keys, so a narrow key never widens itself.
+fn api_key_holder(current: &CurrentUser, user_id: Option<Uuid>) -> async_graphql::Result<Uuid> {
+    if current.scopes.is_some() {
+        return Err(error_with_code(
+            "FORBIDDEN",
+            "API keys cannot manage API keys",
+        ));
+    }
+    let Some(user_id) = user_id else {
+        return Ok(current.user_id);
+    };
+    if user_id != current.user_id && !current.has_role(UserRole::Owner) {
+        return Err(error_with_code(
+            "FORBIDDEN",
+            "Requires OWNER role to manage another user's API keys",
+        ));
+    }
+    Ok(user_id)
+}
+
+async fn find_user<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+) -> async_graphql::Result<app_user::Model> {
+    app_user::Entity::find_by_id(user_id)
+        .one(conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "User not found"))
+}
+
+/// A member of `org_id` with their membership. Users outside it are not found.
+async fn find_member<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> async_graphql::Result<(app_user::Model, organization_member::Model)> {
+    let member = organization_member::Entity::find_by_id((org_id, user_id))
+        .one(conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| error_with_code("NOT_FOUND", "User not found"))?;
+    Ok((find_user(conn, user_id).await?, member))
+}
+
+/// A member of `org_id` who belongs to no other organization. Passwords and second
+/// factors are shared by all of a user's organizations, so only then may `org_id`'s
+/// owners reset them.
+async fn find_sole_member<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> async_graphql::Result<app_user::Model> {
+    let (user, _) = find_member(conn, org_id, user_id).await?;
+    let elsewhere = organization_member::Entity::find()
+        .filter(organization_member::Column::UserId.eq(user_id))
+        .filter(organization_member::Column::OrgId.ne(org_id))
+        .count(conn)
+        .await
+        .map_err(db_error)?;
+    if elsewhere > 0 {
+        return Err(error_with_code(
+            "FORBIDDEN",
+            "This user also belongs to other organizations",
+        ));
+    }
+    Ok(user)
+}
+
+/// The account that signs in locally as `email`, already normalized.
+async fn find_local_user<C: ConnectionTrait>(
+    conn: &C,
+    email: &str,
+) -> async_graphql::Result<Option<app_user::Model>> {
+    let identity = user_identity::Entity::find()
+        .filter(user_identity::Column::Provider.eq("local"))
+        .filter(user_identity::Column::Subject.eq(email))
+        .one(conn)
         .await
         .map_err(db_error)?;
-    Ok(rows
-        .into_iter()
-        .filter_map(|row| match row.role {
-            user_role::Role::Owner => Some(UserRole::Owner),
-            user_role::Role::Admin => Some(UserRole::Admin),
-            user_role::Role::Sales => Some(UserRole::Sales),
-            user_role::Role::Viewer => Some(UserRole::Viewer),
-        })
-        .collect())
+    let Some(identity) = identity else {
+        return Ok(None);
+    };
+    app_user::Entity::find_by_id(identity.user_id)
+        .one(conn)
+        .await
+        .map_err(db_error)
 }
 
-async fn load_roles_for_users(
-    db: &DatabaseConnection,
-    users: &[app_user::Model],
-) -> async_graphql::Result<HashMap<Uuid, Vec<UserRole>>> {
-    let ids: Vec<Uuid> = users.iter().map(|u| u.id).collect();
-    if ids.is_empty() {
-        return Ok(HashMap::new());
-    }
-    let rows = user_role::Entity::find()
-        .filter(user_role::Column::UserId.is_in(ids.clone()))
-        .all(db)
+fn password_reset_email(display_name: &str, link: &str) -> String {
+    format!(
+        "Hi {},\n\n\
+         Someone asked to reset the password for your SME Suite account. \
+         Open this link within {} minutes to choose a new one:\n\n\
+         {}\n\n\
+         If that wasn't you, ignore this email and your password stays as it is.\n",
+        display_name, RESET_TOKEN_TTL_MINUTES, link
+    )
+}
+
+/// Switches a user on or off in one organization; the account itself and its
+/// other memberships are untouched.
+async fn set_member_active<C: ConnectionTrait>(
+    conn: &C,
+    actor: &AuditActor,
+    member: organization_member::Model,
+    is_active: bool,
+) -> async_graphql::Result<organization_member::Model> {
+    if member.is_active == is_active {
+        return Ok(member);
+    }
+    let before = member.clone();
+    let mut active: organization_member::ActiveModel = member.into();
+    active.is_active = Set(is_active);
+    let updated = active.update(conn).await.map_err(db_error)?;
+    record_change(
+        conn,
+        actor,
+        audit_log::EntityType::User,
+        updated.user_id,
+        Some(&before),
+        Some(&updated),
+    )
+    .await
+    .map_err(db_error)?;
+    Ok(updated)
+}
+
+/// Fails unless `org_id` keeps an active OWNER other than `user_id`. Every
+/// active owner's role row is locked in a fixed order so concurrent demotions
+/// serialise instead of both passing the check.
+async fn ensure_other_owner<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> async_graphql::Result<()> {
+    let owners: Vec<Uuid> = user_role::Entity::find()
+        .select_only()
+        .column(user_role::Column::UserId)
+        .inner_join(app_user::Entity)
+        .join(
+            JoinType::InnerJoin,
+            user_role::Relation::OrganizationMember.def(),
+        )
+        .filter(user_role::Column::OrgId.eq(org_id))
+        .filter(user_role::Column::Role.eq(user_role::Role::Owner))
+        .filter(app_user::Column::IsActive.eq(true))
+        .filter(organization_member::Column::IsActive.eq(true))
+        .order_by_asc(user_role::Column::UserId)
+        .lock_exclusive()
+        .into_tuple()
+        .all(conn)
         .await
         .map_err(db_error)?;
-    let mut map: HashMap<Uuid, Vec<UserRole>> = HashMap::new();
-    for row in rows {
-        let role = match row.role {
-            user_role::Role::Owner => UserRole::Owner,
-            user_role::Role::Admin => UserRole::Admin,
-            user_role::Role::Sales => UserRole::Sales,
-            user_role::Role::Viewer => UserRole::Viewer,
-        };
-        map.entry(row.user_id).or_default().push(role);
+    if !owners.contains(&user_id) || owners.len() > 1 {
+        return Ok(());
     }
-    Ok(map)
+    Err(error_with_code(
+        "CONFLICT",
+        "The last active owner cannot be demoted or deactivated",
+    ))
 }
 
-async fn load_user_with_roles(
-    db: &DatabaseConnection,
-    user_id: Uuid,
-) -> async_graphql::Result<(app_user::Model, Vec<UserRole>)> {
-    let model = app_user::Entity::find_by_id(user_id)
-        .one(db)
-        .await
-        .map_err(db_error)?
-        .ok_or_else(|| error_with_code("UNAUTHENTICATED", "User not found"))?;
-    if !model.is_active {
-        return Err(error_with_code("FORBIDDEN", "Account disabled"));
+fn user_write_error(err: DbErr) -> Error {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => {
+            error_with_code("CONFLICT", "A user with this email already exists")
+        }
+        _ => db_error(err),
     }
-    let roles = load_roles(db, user_id).await?;
-    Ok((model, roles))
 }
 
-async fn ensure_active_user(db: &DatabaseConnection, user_id: Uuid) -> async_graphql::Result<Uuid> {
-    let user = app_user::Entity::find_by_id(user_id)
-        .one(db)
-        .await
-        .map_err(db_error)?
-        .ok_or_else(|| error_with_code("NOT_FOUND", "User not found"))?;
-    if !user.is_active {
+/// Checks that `user_id` can own records in `org_id`: an active account with an
+/// active membership there.
+async fn ensure_active_user<C: ConnectionTrait>(
+    db: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> async_graphql::Result<Uuid> {
+    let (user, member) = find_member(db, org_id, user_id).await?;
+    if !user.is_active || !member.is_active {
         return Err(error_with_code("VALIDATION", "User is inactive"));
     }
     Ok(user_id)
@@ -2938,6 +8770,200 @@ fn validate_assignee(value: Option<String>) -> async_graphql::Result<Option<Stri
     Ok(value)
 }
 
+fn validate_company_name(value: &str) -> async_graphql::Result<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(validation_error("Name is required"));
+    }
+    validate_length("name", trimmed, 256)?;
+    Ok(trimmed.to_string())
+}
+
+fn validate_website(value: Option<String>) -> async_graphql::Result<Option<String>> {
+    let Some(website) = sanitize_optional_filter(value) else {
+        return Ok(None);
+    };
+    validate_length("website", &website, 512)?;
+    if website.chars().any(char::is_whitespace) || !website.contains('.') {
+        return Err(validation_error("Invalid website"));
+    }
+    Ok(Some(website))
+}
+
+fn validate_phone(value: Option<String>) -> async_graphql::Result<Option<String>> {
+    let Some(phone) = sanitize_optional_filter(value) else {
+        return Ok(None);
+    };
+    validate_length("phone", &phone, 64)?;
+    let allowed = |c: char| c.is_ascii_digit() || " +-().x".contains(c);
+    if !phone.chars().all(allowed) || !phone.chars().any(|c| c.is_ascii_digit()) {
+        return Err(validation_error("Invalid phone number"));
+    }
+    Ok(Some(phone))
+}
+
+fn validate_deal_title(value: &str) -> async_graphql::Result<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(validation_error("Title is required"));
+    }
+    validate_length("title", trimmed, 300)?;
+    Ok(trimmed.to_string())
+}
+
+/// Stage keys end up in URLs and exports, so they stay plain: `A-Z`, `0-9` and `_`,
+/// starting with a letter.
+fn validate_stage_key(value: &str) -> async_graphql::Result<String> {
+    let key = normalize_stage_key(value).ok_or_else(|| validation_error("Key is required"))?;
+    validate_length("key", &key, 32)?;
+    let mut chars = key.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(validation_error(
+            "Key must start with a letter and use only A-Z, 0-9 and _",
+        ));
+    }
+    Ok(key)
+}
+
+fn validate_pipeline_name(value: &str) -> async_graphql::Result<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(validation_error("Name is required"));
+    }
+    validate_length("name", trimmed, 64)?;
+    Ok(trimmed.to_string())
+}
+
+fn validate_reason_label(value: &str) -> async_graphql::Result<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(validation_error("Label is required"));
+    }
+    validate_length("label", trimmed, 100)?;
+    Ok(trimmed.to_string())
+}
+
+fn validate_wip_limit(value: Option<i32>) -> async_graphql::Result<Option<i32>> {
+    match value {
+        Some(limit) if limit <= 0 => Err(validation_error("wipLimit must be positive")),
+        _ => Ok(value),
+    }
+}
+
+fn validate_stage_name(value: &str) -> async_graphql::Result<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(validation_error("Display name is required"));
+    }
+    validate_length("displayName", trimmed, 64)?;
+    Ok(trimmed.to_string())
+}
+
+fn validate_probability(value: i32) -> async_graphql::Result<i16> {
+    if !(0..=100).contains(&value) {
+        return Err(validation_error("probability must be between 0 and 100"));
+    }
+    Ok(value as i16)
+}
+
+fn validate_amount_cents(value: Option<i64>) -> async_graphql::Result<Option<i64>> {
+    if matches!(value, Some(amount) if amount < 0) {
+        return Err(validation_error("amountCents must not be negative"));
+    }
+    Ok(value)
+}
+
+fn validate_currency(value: Option<String>) -> async_graphql::Result<Option<String>> {
+    let Some(currency) = sanitize_optional_filter(value) else {
+        return Ok(None);
+    };
+    let code = currency.to_ascii_uppercase();
+    if !ISO_4217_CODES.contains(&code.as_str()) {
+        return Err(validation_error(format!(
+            "{} is not an ISO 4217 currency code",
+            currency
+        )));
+    }
+    Ok(Some(code))
+}
+
+/// Active ISO 4217 currency codes.
+const ISO_4217_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD",
+    "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD",
+    "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ",
+    "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD",
+    "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR",
+    "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR",
+    "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN",
+    "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR",
+    "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB",
+    "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS",
+    "VES", "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+fn validate_contact_email(value: &str) -> async_graphql::Result<String> {
+    let normalized = normalize_email(value)?;
+    validate_length("email", &normalized, 320)?;
+    Ok(normalized)
+}
+
+fn validate_person_name(
+    field: &str,
+    value: Option<String>,
+) -> async_graphql::Result<Option<String>> {
+    let Some(name) = sanitize_optional_filter(value) else {
+        return Ok(None);
+    };
+    validate_length(field, &name, 128)?;
+    Ok(Some(name))
+}
+
+fn validate_display_name(value: &str) -> async_graphql::Result<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(validation_error("Display name is required"));
+    }
+    validate_length("displayName", trimmed, 120)?;
+    Ok(trimmed.to_string())
+}
+
+fn validate_api_key_name(value: &str) -> async_graphql::Result<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(validation_error("Name is required"));
+    }
+    validate_length("name", trimmed, 120)?;
+    Ok(trimmed.to_string())
+}
+
+fn validate_password(policy: &PasswordPolicy, value: &str) -> async_graphql::Result<String> {
+    if value.chars().count() < policy.min_length {
+        return Err(validation_error(format!(
+            "password must be at least {} characters",
+            policy.min_length
+        )));
+    }
+    validate_length("password", value, MAX_PASSWORD_LENGTH)?;
+    if policy.is_denied(value) {
+        return Err(validation_error("password is too common, choose another"));
+    }
+    Ok(value.to_string())
+}
+
+fn validate_roles(roles: Vec<UserRole>) -> async_graphql::Result<Vec<UserRole>> {
+    let mut roles = roles;
+    roles.sort();
+    roles.dedup();
+    if roles.is_empty() {
+        return Err(validation_error("At least one role is required"));
+    }
+    Ok(roles)
+}
+
 fn validate_length(field: &str, value: &str, max: usize) -> async_graphql::Result<()> {
     if value.chars().count() > max {
         return Err(validation_error(format!(
@@ -2961,6 +8987,22 @@ fn enforce_task_limit(limit: i32) -> async_graphql::Result<u64> {
     Ok(limit as u64)
 }
 
+fn enforce_record_limit(limit: i32, noun: &str) -> async_graphql::Result<u64> {
+    if limit <= 0 {
+        return Err(validation_error("first must be positive"));
+    }
+    if limit > MAX_RECORDS_PAGE {
+        return Err(error_with_code(
+            "LIMIT_EXCEEDED",
+            format!(
+                "Cannot request more than {} {} at once",
+                MAX_RECORDS_PAGE, noun
+            ),
+        ));
+    }
+    Ok(limit as u64)
+}
+
 fn parse_optional_id(field: &str, value: &Option<ID>) -> async_graphql::Result<Option<Uuid>> {
     match value {
         Some(id) => Uuid::parse_str(id.as_str())
@@ -3005,22 +9047,26 @@ fn select_task_target(
     Ok(targets[0])
 }
 
-async fn ensure_task_target_exists(
-    db: &DatabaseConnection,
+async fn ensure_task_target_exists<C: ConnectionTrait>(
+    db: &C,
+    org_id: Uuid,
     target: &TaskTarget,
 ) -> async_graphql::Result<()> {
     let exists = match target {
         TaskTarget::Company(id) => company::Entity::find_by_id(*id)
+            .filter(company::Column::OrgId.eq(org_id))
             .one(db)
             .await
             .map_err(db_error)?
             .is_some(),
         TaskTarget::Contact(id) => contact::Entity::find_by_id(*id)
+            .filter(contact::Column::OrgId.eq(org_id))
             .one(db)
             .await
             .map_err(db_error)?
             .is_some(),
         TaskTarget::Deal(id) => deal::Entity::find_by_id(*id)
+            .filter(deal::Column::OrgId.eq(org_id))
             .one(db)
             .await
             .map_err(db_error)?
@@ -3040,34 +9086,484 @@ fn apply_target_to_model(model: &mut task::ActiveModel, target: &TaskTarget) {
     }
 }
 
-fn apply_task_ordering(mut query: Select<task::Entity>, order: TaskOrder) -> Select<task::Entity> {
-    match order {
-        TaskOrder::DueAsc => {
-            query = query
-                .order_by(due_nulls_expr(), Order::Asc)
-                .order_by(task::Column::DueAt, Order::Asc)
-                .order_by(task::Column::Id, Order::Asc);
+/// One sort-key value inside a keyset cursor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum CursorValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    /// Compared against `lower(...)` of the column, like the case-insensitive orderings.
+    Folded(String),
+    Time(DateTime<Utc>),
+    Date(NaiveDate),
+    Id(Uuid),
+}
+
+impl CursorValue {
+    fn time(value: DateTimeWithTimeZone) -> Self {
+        CursorValue::Time(value.into())
+    }
+
+    fn folded(value: Option<&String>) -> Self {
+        value
+            .map(|v| CursorValue::Folded(v.clone()))
+            .unwrap_or(CursorValue::Null)
+    }
+
+    fn to_expr(&self) -> Option<SimpleExpr> {
+        let expr = match self {
+            CursorValue::Null => return None,
+            CursorValue::Bool(v) => Expr::val(*v).into(),
+            CursorValue::Int(v) => Expr::val(*v).into(),
+            CursorValue::Float(v) => Expr::val(*v).into(),
+            CursorValue::Text(v) => Expr::val(v.clone()).into(),
+            CursorValue::Folded(v) => Func::lower(Expr::val(v.clone())).into(),
+            CursorValue::Time(v) => Expr::val(DateTimeWithTimeZone::from(*v)).into(),
+            CursorValue::Date(v) => Expr::val(*v).into(),
+            CursorValue::Id(v) => Expr::val(*v).into(),
+        };
+        Some(expr)
+    }
+}
+
+type KeysetCursor = OpaqueCursor<Vec<CursorValue>>;
+
+/// Relay connection over keyset cursors; `totalCount` ignores paging.
+type CrmConnection<N> = Connection<KeysetCursor, N, ConnectionTotals>;
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct ConnectionTotals {
+    #[graphql(name = "totalCount")]
+    pub total_count: i64,
+}
+
+fn decode_cursor(after: Option<&str>) -> async_graphql::Result<Option<Vec<CursorValue>>> {
+    match after {
+        Some(raw) => KeysetCursor::decode_cursor(raw)
+            .map(|cursor| Some(cursor.0))
+            .map_err(|_| invalid_cursor()),
+        None => Ok(None),
+    }
+}
+
+fn invalid_cursor() -> Error {
+    error_with_code("BAD_REQUEST", "Invalid cursor")
+}
+
+/// One column of a list ordering, plus how to read that column off a row for its cursor.
+struct SortKey<M> {
+    expr: SimpleExpr,
+    order: Order,
+    value: fn(&M) -> CursorValue,
+}
+
+impl<M> SortKey<M> {
+    fn asc(expr: impl IntoSimpleExpr, value: fn(&M) -> CursorValue) -> Self {
+        Self {
+            expr: expr.into_simple_expr(),
+            order: Order::Asc,
+            value,
         }
-        TaskOrder::DueDesc => {
-            query = query
-                .order_by(due_nulls_expr(), Order::Asc)
-                .order_by(task::Column::DueAt, Order::Desc)
-                .order_by(task::Column::Id, Order::Asc);
+    }
+
+    fn desc(expr: impl IntoSimpleExpr, value: fn(&M) -> CursorValue) -> Self {
+        Self {
+            expr: expr.into_simple_expr(),
+            order: Order::Desc,
+            value,
         }
-        TaskOrder::PriorityDesc => {
-            query = query
-                .order_by(priority_order_expr(), Order::Desc)
-                .order_by(task::Column::Id, Order::Asc);
+    }
+}
+
+fn apply_sort_keys<E: EntityTrait>(query: Select<E>, keys: &[SortKey<E::Model>]) -> Select<E> {
+    keys.iter().fold(query, |query, key| {
+        query.order_by(key.expr.clone(), key.order.clone())
+    })
+}
+
+/// Rows strictly after `cursor` in `keys` order: `k1 > v1 OR (k1 = v1 AND k2 > v2) ...`.
+/// A null only equals nulls and nothing sorts after it within its key, so orderings put an
+/// `IS NULL` flag ahead of every nullable column.
+fn keyset_condition<M>(
+    keys: &[SortKey<M>],
+    cursor: &[CursorValue],
+) -> async_graphql::Result<Condition> {
+    if keys.len() != cursor.len() {
+        return Err(invalid_cursor());
+    }
+    let mut after = Condition::any();
+    for (index, (key, value)) in keys.iter().zip(cursor).enumerate() {
+        let Some(bound) = value.to_expr() else {
+            continue;
+        };
+        let mut branch = Condition::all();
+        for (prev_key, prev_value) in keys[..index].iter().zip(cursor) {
+            let column = Expr::expr(prev_key.expr.clone());
+            branch = branch.add(match prev_value.to_expr() {
+                Some(prev) => column.eq(prev),
+                None => column.is_null(),
+            });
         }
+        let column = Expr::expr(key.expr.clone());
+        let beyond = match key.order {
+            Order::Desc => column.lt(bound),
+            _ => column.gt(bound),
+        };
+        after = after.add(branch.add(beyond));
+    }
+    Ok(after)
+}
+
+/// One keyset page of rows with their cursors, before the rows are turned into nodes.
+struct KeysetPage<M> {
+    rows: Vec<(KeysetCursor, M)>,
+    has_previous: bool,
+    has_next: bool,
+    total_count: i64,
+}
+
+impl<M> KeysetPage<M> {
+    fn models(&self) -> impl Iterator<Item = &M> {
+        self.rows.iter().map(|(_, model)| model)
+    }
+
+    fn into_connection<N: OutputType>(self, mut node: impl FnMut(M) -> N) -> CrmConnection<N> {
+        let mut connection = Connection::with_additional_fields(
+            self.has_previous,
+            self.has_next,
+            ConnectionTotals {
+                total_count: self.total_count,
+            },
+        );
+        connection.edges.extend(
+            self.rows
+                .into_iter()
+                .map(|(cursor, model)| Edge::new(cursor, node(model))),
+        );
+        connection
+    }
+}
+
+/// Pages `query` by `keys`, fetching one extra row to tell whether another page follows.
+async fn keyset_page<E>(
+    db: &DatabaseConnection,
+    query: Select<E>,
+    keys: &[SortKey<E::Model>],
+    first: u64,
+    after: Option<&str>,
+) -> async_graphql::Result<KeysetPage<E::Model>>
+where
+    E: EntityTrait,
+    E::Model: Sync,
+{
+    let cursor = decode_cursor(after)?;
+    let total_count = query.clone().count(db).await.map_err(db_error)?;
+    let mut page = apply_sort_keys(query, keys);
+    if let Some(cursor) = &cursor {
+        page = page.filter(keyset_condition(keys, cursor)?);
+    }
+    let mut rows = page.limit(first + 1).all(db).await.map_err(db_error)?;
+    let has_next = rows.len() as u64 > first;
+    rows.truncate(first as usize);
+    Ok(KeysetPage {
+        rows: rows
+            .into_iter()
+            .map(|row| {
+                let cursor = OpaqueCursor(keys.iter().map(|key| (key.value)(&row)).collect());
+                (cursor, row)
+            })
+            .collect(),
+        has_previous: cursor.is_some(),
+        has_next,
+        total_count: total_count as i64,
+    })
+}
+
+fn task_sort_keys(order: TaskOrder) -> Vec<SortKey<task::Model>> {
+    let mut keys = match order {
+        TaskOrder::DueAsc => vec![
+            SortKey::asc(due_nulls_expr(), |t: &task::Model| {
+                CursorValue::Int(t.due_at.is_none() as i64)
+            }),
+            SortKey::asc(task::Column::DueAt, |t: &task::Model| {
+                t.due_at.map(CursorValue::time).unwrap_or(CursorValue::Null)
+            }),
+        ],
+        TaskOrder::DueDesc => vec![
+            SortKey::asc(due_nulls_expr(), |t: &task::Model| {
+                CursorValue::Int(t.due_at.is_none() as i64)
+            }),
+            SortKey::desc(task::Column::DueAt, |t: &task::Model| {
+                t.due_at.map(CursorValue::time).unwrap_or(CursorValue::Null)
+            }),
+        ],
+        TaskOrder::PriorityDesc => vec![SortKey::desc(priority_order_expr(), |t: &task::Model| {
+            CursorValue::Int(match t.priority {
+                task::Priority::High => 2,
+                task::Priority::Medium => 1,
+                task::Priority::Low => 0,
+            })
+        })],
         TaskOrder::UpdatedDesc => {
-            query = query
-                .order_by(task::Column::UpdatedAt, Order::Desc)
-                .order_by(task::Column::Id, Order::Asc);
+            vec![SortKey::desc(task::Column::UpdatedAt, |t: &task::Model| {
+                CursorValue::time(t.updated_at)
+            })]
+        }
+    };
+    keys.push(SortKey::asc(task::Column::Id, |t: &task::Model| {
+        CursorValue::Id(t.id)
+    }));
+    keys
+}
+
+fn apply_task_ordering(query: Select<task::Entity>, order: TaskOrder) -> Select<task::Entity> {
+    apply_sort_keys(query, &task_sort_keys(order))
+}
+
+fn apply_company_filter(
+    mut query: Select<company::Entity>,
+    filter: CompanyFilter,
+) -> async_graphql::Result<Select<company::Entity>> {
+    if let Some(user_id) = parse_optional_id("assignedUserId", &filter.assigned_user_id)? {
+        query = query.filter(company::Column::AssignedUserId.eq(user_id));
+    }
+    query = filter_time_range(
+        query,
+        company::Column::CreatedAt,
+        filter.created_after,
+        filter.created_before,
+    );
+    query = filter_time_range(
+        query,
+        company::Column::UpdatedAt,
+        filter.updated_after,
+        filter.updated_before,
+    );
+    if let Some(q) = sanitize_optional_filter(filter.q) {
+        query = query.filter(text_match(
+            &[company::Column::Name, company::Column::Website],
+            &q,
+        ));
+    }
+    Ok(query)
+}
+
+fn company_sort_keys(order: CompanyOrder) -> Vec<SortKey<company::Model>> {
+    let mut keys = match order {
+        CompanyOrder::NameAsc => vec![SortKey::asc(
+            SimpleExpr::from(Func::lower(Expr::col(company::Column::Name))),
+            |c: &company::Model| CursorValue::Folded(c.name.clone()),
+        )],
+        CompanyOrder::CreatedDesc => vec![SortKey::desc(
+            company::Column::CreatedAt,
+            |c: &company::Model| CursorValue::time(c.created_at),
+        )],
+        CompanyOrder::UpdatedDesc => vec![SortKey::desc(
+            company::Column::UpdatedAt,
+            |c: &company::Model| CursorValue::time(c.updated_at),
+        )],
+    };
+    keys.push(SortKey::asc(company::Column::Id, |c: &company::Model| {
+        CursorValue::Id(c.id)
+    }));
+    keys
+}
+
+fn apply_contact_filter(
+    mut query: Select<contact::Entity>,
+    filter: ContactFilter,
+) -> async_graphql::Result<Select<contact::Entity>> {
+    if let Some(user_id) = parse_optional_id("assignedUserId", &filter.assigned_user_id)? {
+        query = query.filter(contact::Column::AssignedUserId.eq(user_id));
+    }
+    if let Some(company_id) = parse_optional_id("companyId", &filter.company_id)? {
+        query = query.filter(contact::Column::CompanyId.eq(company_id));
+    }
+    query = filter_time_range(
+        query,
+        contact::Column::CreatedAt,
+        filter.created_after,
+        filter.created_before,
+    );
+    query = filter_time_range(
+        query,
+        contact::Column::UpdatedAt,
+        filter.updated_after,
+        filter.updated_before,
+    );
+    if let Some(q) = sanitize_optional_filter(filter.q) {
+        query = query.filter(text_match(
+            &[
+                contact::Column::Email,
+                contact::Column::FirstName,
+                contact::Column::LastName,
+            ],
+            &q,
+        ));
+    }
+    Ok(query)
+}
+
+fn contact_sort_keys(order: ContactOrder) -> Vec<SortKey<contact::Model>> {
+    let mut keys = match order {
+        ContactOrder::NameAsc => vec![
+            SortKey::asc(contact::Column::LastName.is_null(), |c: &contact::Model| {
+                CursorValue::Bool(c.last_name.is_none())
+            }),
+            SortKey::asc(
+                SimpleExpr::from(Func::lower(Expr::col(contact::Column::LastName))),
+                |c: &contact::Model| CursorValue::folded(c.last_name.as_ref()),
+            ),
+            SortKey::asc(
+                contact::Column::FirstName.is_null(),
+                |c: &contact::Model| CursorValue::Bool(c.first_name.is_none()),
+            ),
+            SortKey::asc(
+                SimpleExpr::from(Func::lower(Expr::col(contact::Column::FirstName))),
+                |c: &contact::Model| CursorValue::folded(c.first_name.as_ref()),
+            ),
+            SortKey::asc(contact::Column::Email, |c: &contact::Model| {
+                CursorValue::Text(c.email.clone())
+            }),
+        ],
+        ContactOrder::EmailAsc => vec![SortKey::asc(
+            contact::Column::Email,
+            |c: &contact::Model| CursorValue::Text(c.email.clone()),
+        )],
+        ContactOrder::CreatedDesc => vec![SortKey::desc(
+            contact::Column::CreatedAt,
+            |c: &contact::Model| CursorValue::time(c.created_at),
+        )],
+        ContactOrder::UpdatedDesc => vec![SortKey::desc(
+            contact::Column::UpdatedAt,
+            |c: &contact::Model| CursorValue::time(c.updated_at),
+        )],
+    };
+    keys.push(SortKey::asc(contact::Column::Id, |c: &contact::Model| {
+        CursorValue::Id(c.id)
+    }));
+    keys
+}
+
+fn apply_contact_ordering(
+    query: Select<contact::Entity>,
+    order: ContactOrder,
+) -> Select<contact::Entity> {
+    apply_sort_keys(query, &contact_sort_keys(order))
+}
+
+fn apply_deal_filter(
+    mut query: Select<deal::Entity>,
+    filter: DealFilter,
+) -> async_graphql::Result<Select<deal::Entity>> {
+    if let Some(user_id) = parse_optional_id("assignedUserId", &filter.assigned_user_id)? {
+        query = query.filter(deal::Column::AssignedUserId.eq(user_id));
+    }
+    if let Some(company_id) = parse_optional_id("companyId", &filter.company_id)? {
+        query = query.filter(deal::Column::CompanyId.eq(company_id));
+    }
+    if let Some(pipeline_id) = parse_optional_id("pipelineId", &filter.pipeline_id)? {
+        query = query.filter(deal::Column::PipelineId.eq(pipeline_id));
+    }
+    if let Some(stage) = filter.stage {
+        query = query.filter(deal::Column::Stage.eq(stage.0));
+    }
+    query = filter_time_range(
+        query,
+        deal::Column::CreatedAt,
+        filter.created_after,
+        filter.created_before,
+    );
+    query = filter_time_range(
+        query,
+        deal::Column::UpdatedAt,
+        filter.updated_after,
+        filter.updated_before,
+    );
+    if let Some(q) = sanitize_optional_filter(filter.q) {
+        query = query.filter(text_match(&[deal::Column::Title], &q));
+    }
+    Ok(query)
+}
+
+fn deal_sort_keys(order: DealOrder) -> Vec<SortKey<deal::Model>> {
+    let mut keys = match order {
+        DealOrder::UpdatedDesc => {
+            vec![SortKey::desc(deal::Column::UpdatedAt, |d: &deal::Model| {
+                CursorValue::time(d.updated_at)
+            })]
         }
+        DealOrder::CreatedDesc => {
+            vec![SortKey::desc(deal::Column::CreatedAt, |d: &deal::Model| {
+                CursorValue::time(d.created_at)
+            })]
+        }
+        DealOrder::TitleAsc => vec![SortKey::asc(
+            SimpleExpr::from(Func::lower(Expr::col(deal::Column::Title))),
+            |d: &deal::Model| CursorValue::Folded(d.title.clone()),
+        )],
+        DealOrder::AmountDesc => vec![
+            SortKey::asc(deal::Column::AmountCents.is_null(), |d: &deal::Model| {
+                CursorValue::Bool(d.amount_cents.is_none())
+            }),
+            SortKey::desc(deal::Column::AmountCents, |d: &deal::Model| {
+                d.amount_cents
+                    .map(CursorValue::Int)
+                    .unwrap_or(CursorValue::Null)
+            }),
+        ],
+        DealOrder::CloseDateAsc => vec![
+            SortKey::asc(deal::Column::CloseDate.is_null(), |d: &deal::Model| {
+                CursorValue::Bool(d.close_date.is_none())
+            }),
+            SortKey::asc(deal::Column::CloseDate, |d: &deal::Model| {
+                d.close_date
+                    .map(CursorValue::Date)
+                    .unwrap_or(CursorValue::Null)
+            }),
+        ],
+    };
+    keys.push(SortKey::asc(deal::Column::Id, |d: &deal::Model| {
+        CursorValue::Id(d.id)
+    }));
+    keys
+}
+
+fn apply_deal_ordering(query: Select<deal::Entity>, order: DealOrder) -> Select<deal::Entity> {
+    apply_sort_keys(query, &deal_sort_keys(order))
+}
+
+fn filter_time_range<E, C>(
+    mut query: Select<E>,
+    column: C,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Select<E>
+where
+    E: EntityTrait,
+    C: ColumnTrait,
+{
+    if let Some(after) = after {
+        let ts: DateTimeWithTimeZone = after.into();
+        query = query.filter(column.gte(ts));
+    }
+    if let Some(before) = before {
+        let ts: DateTimeWithTimeZone = before.into();
+        query = query.filter(column.lt(ts));
     }
     query
 }
 
+/// Case-insensitive substring match against any of `columns`.
+fn text_match<C: ColumnTrait>(columns: &[C], term: &str) -> Condition {
+    let pattern = format!("%{}%", term.to_lowercase());
+    columns.iter().fold(Condition::any(), |condition, column| {
+        condition.add(Expr::expr(Func::lower(Expr::col(*column))).like(pattern.clone()))
+    })
+}
+
 fn normalize_email(value: &str) -> async_graphql::Result<String> {
     let trimmed = value.trim().to_lowercase();
     if trimmed.is_empty() || !trimmed.contains('@') {
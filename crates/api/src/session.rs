@@ -0,0 +1,134 @@
+//! Server-side records behind the `sid` claim of session tokens.
+
+use crate::auth::AuthConfig;
+use chrono::{Duration, Utc};
+use entity::user_session;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, Condition, ConnectionTrait, DbErr,
+    EntityTrait, QueryFilter, QueryOrder,
+};
+use uuid::Uuid;
+
+const MAX_USER_AGENT: usize = 512;
+
+pub async fn start_session<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    org_id: Uuid,
+    user_agent: Option<&str>,
+    config: &AuthConfig,
+) -> Result<user_session::Model, DbErr> {
+    let now = Utc::now();
+    let expires_at = now + Duration::minutes(config.session_max_age_minutes);
+    user_session::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
+        user_id: Set(user_id),
+        user_agent: Set(user_agent.map(|agent| agent.chars().take(MAX_USER_AGENT).collect())),
+        created_at: Set(now.into()),
+        last_seen_at: Set(now.into()),
+        expires_at: Set(expires_at.into()),
+        revoked_at: Set(None),
+    }
+    .insert(conn)
+    .await
+}
+
+/// Slides the idle window of a live session. Returns `false` once the session
+/// is revoked, idle past the TTL, past its absolute expiry or not `user_id`'s
+/// in `org_id`.
+pub async fn touch_session<C: ConnectionTrait>(
+    conn: &C,
+    session_id: Uuid,
+    user_id: Uuid,
+    org_id: Uuid,
+    config: &AuthConfig,
+) -> Result<bool, DbErr> {
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let res = user_session::Entity::update_many()
+        .col_expr(user_session::Column::LastSeenAt, Expr::value(now))
+        .filter(user_session::Column::Id.eq(session_id))
+        .filter(user_session::Column::UserId.eq(user_id))
+        .filter(user_session::Column::OrgId.eq(org_id))
+        .filter(live_condition(config))
+        .exec(conn)
+        .await?;
+    Ok(res.rows_affected == 1)
+}
+
+pub async fn live_sessions<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    config: &AuthConfig,
+) -> Result<Vec<user_session::Model>, DbErr> {
+    user_session::Entity::find()
+        .filter(user_session::Column::UserId.eq(user_id))
+        .filter(live_condition(config))
+        .order_by_desc(user_session::Column::LastSeenAt)
+        .all(conn)
+        .await
+}
+
+pub async fn revoke_session<C: ConnectionTrait>(
+    conn: &C,
+    session_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, DbErr> {
+    let res = user_session::Entity::update_many()
+        .col_expr(user_session::Column::RevokedAt, Expr::value(now()))
+        .filter(user_session::Column::Id.eq(session_id))
+        .filter(user_session::Column::UserId.eq(user_id))
+        .filter(user_session::Column::RevokedAt.is_null())
+        .exec(conn)
+        .await?;
+    Ok(res.rows_affected > 0)
+}
+
+/// Revokes every open session of `user_id`, optionally sparing `keep`.
+pub async fn revoke_user_sessions<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    keep: Option<Uuid>,
+) -> Result<u64, DbErr> {
+    let mut update = user_session::Entity::update_many()
+        .col_expr(user_session::Column::RevokedAt, Expr::value(now()))
+        .filter(user_session::Column::UserId.eq(user_id))
+        .filter(user_session::Column::RevokedAt.is_null());
+    if let Some(keep) = keep {
+        update = update.filter(user_session::Column::Id.ne(keep));
+    }
+    Ok(update.exec(conn).await?.rows_affected)
+}
+
+/// Revokes `user_id`'s open sessions in one organization, leaving the others.
+pub async fn revoke_member_sessions<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<u64, DbErr> {
+    let res = user_session::Entity::update_many()
+        .col_expr(user_session::Column::RevokedAt, Expr::value(now()))
+        .filter(user_session::Column::OrgId.eq(org_id))
+        .filter(user_session::Column::UserId.eq(user_id))
+        .filter(user_session::Column::RevokedAt.is_null())
+        .exec(conn)
+        .await?;
+    Ok(res.rows_affected)
+}
+
+fn live_condition(config: &AuthConfig) -> Condition {
+    let now = Utc::now();
+    let idle_cutoff: DateTimeWithTimeZone =
+        (now - Duration::minutes(config.session_ttl_minutes)).into();
+    let now: DateTimeWithTimeZone = now.into();
+    Condition::all()
+        .add(user_session::Column::RevokedAt.is_null())
+        .add(user_session::Column::ExpiresAt.gt(now))
+        .add(user_session::Column::LastSeenAt.gt(idle_cutoff))
+}
+
+fn now() -> DateTimeWithTimeZone {
+    Utc::now().into()
+}
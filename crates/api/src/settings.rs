@@ -0,0 +1,51 @@
+//! Per-organization switches kept as text in `app_setting`, so owners can
+//! change them without a restart.
+
+use chrono::Utc;
+use entity::app_setting;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue::Set, ConnectionTrait, DbErr, EntityTrait};
+use uuid::Uuid;
+
+/// ADMIN and OWNER accounts must pass TOTP to finish a password login.
+pub const REQUIRE_ADMIN_TWO_FACTOR: &str = "auth.require_admin_2fa";
+
+/// Reads a boolean setting; unset means `false`.
+pub async fn get_flag<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    key: &str,
+) -> Result<bool, DbErr> {
+    Ok(app_setting::Entity::find_by_id((org_id, key.to_string()))
+        .one(conn)
+        .await?
+        .is_some_and(|setting| setting.value == "true"))
+}
+
+pub async fn set_flag<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    key: &str,
+    value: bool,
+    updated_by: Uuid,
+) -> Result<(), DbErr> {
+    app_setting::Entity::insert(app_setting::ActiveModel {
+        org_id: Set(org_id),
+        key: Set(key.to_string()),
+        value: Set(value.to_string()),
+        updated_by: Set(Some(updated_by)),
+        updated_at: Set(Utc::now().into()),
+    })
+    .on_conflict(
+        OnConflict::columns([app_setting::Column::OrgId, app_setting::Column::Key])
+            .update_columns([
+                app_setting::Column::Value,
+                app_setting::Column::UpdatedBy,
+                app_setting::Column::UpdatedAt,
+            ])
+            .to_owned(),
+    )
+    .exec(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! Failed-login counters per account and per client address. Past a free
+//! allowance each further failure locks the key for twice as long as the last.
+
+use crate::auth::ClientInfo;
+use crate::schema::postgres_statement;
+use chrono::{DateTime, Duration, Utc};
+use entity::login_throttle;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+
+const BASE_LOCKOUT_SECS: i64 = 30;
+const MAX_LOCKOUT_SECS: i64 = 15 * 60;
+/// Failures older than this are forgotten at the next one.
+const FAILURE_WINDOW_SECS: i64 = 60 * 60;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ThrottleKey {
+    Account(String),
+    Ip(String),
+}
+
+impl ThrottleKey {
+    fn key(&self) -> String {
+        match self {
+            ThrottleKey::Account(email) => format!("account:{}", email),
+            ThrottleKey::Ip(ip) => format!("ip:{}", ip),
+        }
+    }
+
+    /// Failures tolerated before lockouts start. Addresses can be shared by a
+    /// whole office, so they get more room than a single account.
+    fn free_attempts(&self) -> i32 {
+        match self {
+            ThrottleKey::Account(_) => 5,
+            ThrottleKey::Ip(_) => 20,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ThrottleKey::Account(_) => "account",
+            ThrottleKey::Ip(_) => "ip",
+        }
+    }
+}
+
+/// Keys a login attempt for `email` counts against.
+pub fn login_keys(email: &str, client: Option<&ClientInfo>) -> Vec<ThrottleKey> {
+    let mut keys = vec![ThrottleKey::Account(email.to_string())];
+    if let Some(ip) = client.and_then(|client| client.ip.clone()) {
+        keys.push(ThrottleKey::Ip(ip));
+    }
+    keys
+}
+
+fn lockout_secs(failures: i32, free_attempts: i32) -> Option<i64> {
+    let over = failures - free_attempts;
+    if over <= 0 {
+        return None;
+    }
+    let doublings = (over - 1).min(16) as u32;
+    Some((BASE_LOCKOUT_SECS << doublings).min(MAX_LOCKOUT_SECS))
+}
+
+/// When the longest current lockout among `keys` ends, if any is locked.
+pub async fn locked_until<C: ConnectionTrait>(
+    conn: &C,
+    keys: &[ThrottleKey],
+) -> Result<Option<DateTime<Utc>>, DbErr> {
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let rows = login_throttle::Entity::find()
+        .filter(login_throttle::Column::Key.is_in(keys.iter().map(ThrottleKey::key)))
+        .filter(login_throttle::Column::LockedUntil.gt(now))
+        .all(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| row.locked_until)
+        .max()
+        .map(Into::into))
+}
+
+/// Counts a failure against every key and returns the ones this failure
+/// locked, with when each lock ends.
+pub async fn record_failure<C: ConnectionTrait>(
+    conn: &C,
+    keys: &[ThrottleKey],
+) -> Result<Vec<(ThrottleKey, DateTime<Utc>)>, DbErr> {
+    let now = Utc::now();
+    let window_start = now - Duration::seconds(FAILURE_WINDOW_SECS);
+    let mut locked = Vec::new();
+    for key in keys {
+        let row = conn
+            .query_one(postgres_statement(
+                r#"
+                INSERT INTO login_throttle (key, failures, last_failure_at)
+                VALUES (?, 1, ?)
+                ON CONFLICT (key) DO UPDATE SET
+                    failures = CASE
+                        WHEN login_throttle.last_failure_at < ? THEN 1
+                        ELSE login_throttle.failures + 1
+                    END,
+                    last_failure_at = EXCLUDED.last_failure_at
+                RETURNING failures
+                "#,
+                vec![
+                    key.key().into(),
+                    DateTimeWithTimeZone::from(now).into(),
+                    DateTimeWithTimeZone::from(window_start).into(),
+                ],
+            ))
+            .await?;
+        let failures: i32 = match row {
+            Some(row) => row.try_get("", "failures")?,
+            None => 1,
+        };
+        let Some(secs) = lockout_secs(failures, key.free_attempts()) else {
+            continue;
+        };
+        let until = now + Duration::seconds(secs);
+        login_throttle::Entity::update_many()
+            .col_expr(
+                login_throttle::Column::LockedUntil,
+                Expr::value(DateTimeWithTimeZone::from(until)),
+            )
+            .filter(login_throttle::Column::Key.eq(key.key()))
+            .exec(conn)
+            .await?;
+        locked.push((key.clone(), until));
+    }
+    Ok(locked)
+}
+
+/// Forgets past failures for `key`, after a successful sign-in.
+pub async fn clear_failures<C: ConnectionTrait>(conn: &C, key: &ThrottleKey) -> Result<(), DbErr> {
+    login_throttle::Entity::delete_many()
+        .filter(login_throttle::Column::Key.eq(key.key()))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
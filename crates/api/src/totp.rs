@@ -0,0 +1,311 @@
+//! RFC 6238 one-time passwords for local accounts (HMAC-SHA1, six digits,
+//! 30-second steps) and the single-use recovery codes issued with them.
+
+use crate::auth::{ChallengePurpose, UserRole};
+use crate::settings::{get_flag, REQUIRE_ADMIN_TWO_FACTOR};
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use entity::{user_recovery_code, user_secret};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter,
+};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub const TOTP_ISSUER: &str = "SME Suite";
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const STEP_SECS: i64 = 30;
+/// Steps either side of the current one that still verify, for clock drift.
+const DRIFT_STEPS: i64 = 1;
+
+/// A fresh 160-bit seed in the base32 form authenticator apps expect.
+pub fn generate_totp_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// The `otpauth://` URI authenticator apps scan from a QR code.
+pub fn otpauth_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits=6&period={}",
+        percent_encode(TOTP_ISSUER),
+        percent_encode(account),
+        secret,
+        percent_encode(TOTP_ISSUER),
+        STEP_SECS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The code for `unix_time`, or `None` when `secret` is not valid base32.
+pub fn totp_code(secret: &str, unix_time: i64) -> Option<String> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    Some(format!(
+        "{:06}",
+        hotp(&key, unix_time.div_euclid(STEP_SECS))
+    ))
+}
+
+fn hotp(key: &[u8], counter: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset],
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]) & 0x7fff_ffff;
+    binary % 1_000_000
+}
+
+/// The step `code` belongs to near `unix_time`, provided it is newer than
+/// `last_step`.
+fn matching_step(secret: &str, code: &str, unix_time: i64, last_step: Option<i64>) -> Option<i64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let current = unix_time.div_euclid(STEP_SECS);
+    (current - DRIFT_STEPS..=current + DRIFT_STEPS)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| format!("{:06}", hotp(&key, *step)) == code)
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == 6 && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Ten-character codes shown as `xxxxx-xxxxx`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 7];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let raw = BASE32_NOPAD.encode(&bytes).to_lowercase();
+            format!("{}-{}", &raw[..5], &raw[5..10])
+        })
+        .collect()
+}
+
+/// Hashes a recovery code, ignoring case, spaces and dashes.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// Whether `org_id` requires TOTP of an account holding `roles`.
+pub async fn two_factor_required<C: ConnectionTrait>(
+    conn: &C,
+    org_id: Uuid,
+    roles: &[UserRole],
+) -> Result<bool, DbErr> {
+    if !roles
+        .iter()
+        .any(|role| matches!(role, UserRole::Owner | UserRole::Admin))
+    {
+        return Ok(false);
+    }
+    get_flag(conn, org_id, REQUIRE_ADMIN_TWO_FACTOR).await
+}
+
+/// The step a login into `org_id` still owes once its first factor passed, whether
+/// that was a password or an identity provider: a code when the account has TOTP on,
+/// enrolment when the organization requires TOTP for `roles`.
+pub async fn pending_second_factor<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    org_id: Uuid,
+    roles: &[UserRole],
+) -> Result<Option<ChallengePurpose>, DbErr> {
+    let enabled = user_secret::Entity::find_by_id(user_id)
+        .one(conn)
+        .await?
+        .is_some_and(|secret| secret.totp_enabled_at.is_some());
+    if enabled {
+        return Ok(Some(ChallengePurpose::Verify));
+    }
+    if two_factor_required(conn, org_id, roles).await? {
+        return Ok(Some(ChallengePurpose::Enroll));
+    }
+    Ok(None)
+}
+
+/// Accepts a current TOTP code or an unused recovery code for a user with
+/// TOTP enabled, consuming it so it cannot be presented again.
+pub async fn verify_second_factor<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, DbErr> {
+    let Some(secret) = user_secret::Entity::find_by_id(user_id).one(conn).await? else {
+        return Ok(false);
+    };
+    let (Some(seed), Some(_)) = (secret.totp_secret.as_deref(), secret.totp_enabled_at) else {
+        return Ok(false);
+    };
+    let code = code.trim();
+    if is_totp_code(code) {
+        let Some(step) = matching_step(seed, code, Utc::now().timestamp(), secret.totp_last_step)
+        else {
+            return Ok(false);
+        };
+        return claim_step(conn, user_id, step, false).await;
+    }
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    let used = user_recovery_code::Entity::update_many()
+        .col_expr(user_recovery_code::Column::UsedAt, Expr::value(now))
+        .filter(user_recovery_code::Column::UserId.eq(user_id))
+        .filter(user_recovery_code::Column::CodeHash.eq(hash_recovery_code(code)))
+        .filter(user_recovery_code::Column::UsedAt.is_null())
+        .exec(conn)
+        .await?;
+    Ok(used.rows_affected > 0)
+}
+
+/// Turns a pending secret on when `code` matches it.
+pub async fn confirm_totp<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, DbErr> {
+    let Some(secret) = user_secret::Entity::find_by_id(user_id).one(conn).await? else {
+        return Ok(false);
+    };
+    let Some(seed) = secret.totp_secret.as_deref() else {
+        return Ok(false);
+    };
+    let code = code.trim();
+    if secret.totp_enabled_at.is_some() || !is_totp_code(code) {
+        return Ok(false);
+    }
+    match matching_step(seed, code, Utc::now().timestamp(), None) {
+        Some(step) => claim_step(conn, user_id, step, true).await,
+        None => Ok(false),
+    }
+}
+
+/// Records `step` as spent unless a concurrent request already did, enabling
+/// a pending secret on the way when `enable` is set.
+async fn claim_step<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    step: i64,
+    enable: bool,
+) -> Result<bool, DbErr> {
+    let mut update = user_secret::Entity::update_many()
+        .col_expr(user_secret::Column::TotpLastStep, Expr::value(step))
+        .filter(user_secret::Column::UserId.eq(user_id))
+        .filter(
+            Condition::any()
+                .add(user_secret::Column::TotpLastStep.is_null())
+                .add(user_secret::Column::TotpLastStep.lt(step)),
+        );
+    if enable {
+        let now: DateTimeWithTimeZone = Utc::now().into();
+        update = update
+            .col_expr(user_secret::Column::TotpEnabledAt, Expr::value(now))
+            .filter(user_secret::Column::TotpEnabledAt.is_null());
+    }
+    Ok(update.exec(conn).await?.rows_affected > 0)
+}
+
+/// Stores a pending secret, replacing any earlier unconfirmed one.
+pub async fn stage_totp_secret<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+    secret: &str,
+) -> Result<bool, DbErr> {
+    let staged = user_secret::Entity::update_many()
+        .col_expr(user_secret::Column::TotpSecret, Expr::value(secret))
+        .col_expr(
+            user_secret::Column::TotpLastStep,
+            Expr::value(Option::<i64>::None),
+        )
+        .filter(user_secret::Column::UserId.eq(user_id))
+        .filter(user_secret::Column::TotpEnabledAt.is_null())
+        .exec(conn)
+        .await?;
+    Ok(staged.rows_affected > 0)
+}
+
+/// Removes TOTP and any recovery codes, leaving password-only login.
+pub async fn clear_totp<C: ConnectionTrait>(conn: &C, user_id: Uuid) -> Result<(), DbErr> {
+    user_secret::Entity::update_many()
+        .col_expr(
+            user_secret::Column::TotpSecret,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(
+            user_secret::Column::TotpEnabledAt,
+            Expr::value(Option::<DateTimeWithTimeZone>::None),
+        )
+        .col_expr(
+            user_secret::Column::TotpLastStep,
+            Expr::value(Option::<i64>::None),
+        )
+        .filter(user_secret::Column::UserId.eq(user_id))
+        .exec(conn)
+        .await?;
+    user_recovery_code::Entity::delete_many()
+        .filter(user_recovery_code::Column::UserId.eq(user_id))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+/// Issues a new batch of recovery codes, invalidating the previous one.
+pub async fn replace_recovery_codes<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+) -> Result<Vec<String>, DbErr> {
+    user_recovery_code::Entity::delete_many()
+        .filter(user_recovery_code::Column::UserId.eq(user_id))
+        .exec(conn)
+        .await?;
+    let codes = generate_recovery_codes();
+    let now: DateTimeWithTimeZone = Utc::now().into();
+    user_recovery_code::Entity::insert_many(codes.iter().map(|code| {
+        user_recovery_code::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            code_hash: Set(hash_recovery_code(code)),
+            created_at: Set(now),
+            used_at: Set(None),
+        }
+    }))
+    .exec(conn)
+    .await?;
+    Ok(codes)
+}
+
+pub async fn unused_recovery_codes<C: ConnectionTrait>(
+    conn: &C,
+    user_id: Uuid,
+) -> Result<u64, DbErr> {
+    user_recovery_code::Entity::find()
+        .filter(user_recovery_code::Column::UserId.eq(user_id))
+        .filter(user_recovery_code::Column::UsedAt.is_null())
+        .count(conn)
+        .await
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use api::api_key::authenticate_api_key;
+use api::auth::{ApiScope, CurrentUser, UserRole};
+use common::{error_code, run, user_with_role, PgTestContext};
+use serde_json::json;
+
+const CREATE_KEY: &str = r#"
+    mutation Create($input: NewApiKeyInput!) {
+        crm {
+            createApiKey(input: $input) {
+                token
+                key { id kind prefix scopes userId lastUsedAt }
+            }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn keys_are_shown_once_and_resolve_until_revoked() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping api key tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let resp = run(
+        &ctx,
+        CREATE_KEY,
+        json!({ "input": { "name": "nightly export", "scopes": ["CRM_READ", "CRM_READ"] } }),
+        Some(&sales),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let created = resp.data.into_json().unwrap()["crm"]["createApiKey"].clone();
+    let token = created["token"].as_str().unwrap().to_string();
+    assert!(token.starts_with(created["key"]["prefix"].as_str().unwrap()));
+    assert_eq!(created["key"]["kind"], "PERSONAL");
+    assert_eq!(created["key"]["scopes"], json!(["CRM_READ"]));
+
+    let key = authenticate_api_key(ctx.db.as_ref(), &token)
+        .await
+        .unwrap()
+        .expect("live key");
+    assert_eq!(key.user_id, sales.user_id);
+    assert_ne!(key.token_hash, token);
+    assert!(authenticate_api_key(ctx.db.as_ref(), "sme_nope")
+        .await
+        .unwrap()
+        .is_none());
+
+    let resp = run(
+        &ctx,
+        "{ crm { apiKeys { name lastUsedAt } } }",
+        json!({}),
+        Some(&sales),
+    )
+    .await;
+    let keys = resp.data.into_json().unwrap()["crm"]["apiKeys"].clone();
+    assert_eq!(keys[0]["name"], "nightly export");
+    assert!(!keys[0]["lastUsedAt"].is_null());
+
+    let revoke = r#"mutation Revoke($id: ID!) { crm { revokeApiKey(id: $id) } }"#;
+    let resp = run(
+        &ctx,
+        revoke,
+        json!({ "id": created["key"]["id"] }),
+        Some(&sales),
+    )
+    .await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["revokeApiKey"], true);
+    assert!(authenticate_api_key(ctx.db.as_ref(), &token)
+        .await
+        .unwrap()
+        .is_none());
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn scopes_cap_what_a_key_can_do() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping api key tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let pilot = ctx.seeded.deal_titled("ACME Pilot").unwrap();
+    let read_only = CurrentUser {
+        scopes: Some(vec![ApiScope::CrmRead]),
+        ..owner.clone()
+    };
+    let writer = CurrentUser {
+        scopes: Some(vec![ApiScope::CrmWrite]),
+        ..owner.clone()
+    };
+
+    // Scopes cap the role but do not narrow an owner's view to a book.
+    let resp = run(
+        &ctx,
+        "{ crm { deals { totalCount } } }",
+        json!({}),
+        Some(&read_only),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["deals"]["totalCount"],
+        8
+    );
+
+    let create_task = r#"
+        mutation Create($dealId: ID!) {
+            crm { createTask(input: { title: "From a script", dealId: $dealId }) { id } }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        create_task,
+        json!({ "dealId": pilot.id }),
+        Some(&read_only),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let resp = run(
+        &ctx,
+        create_task,
+        json!({ "dealId": pilot.id }),
+        Some(&writer),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let ada = ctx.seeded.contact_email("ada@acme.test").unwrap();
+    let delete = r#"mutation Delete($id: ID!) { crm { deleteContact(id: $id) } }"#;
+    let resp = run(&ctx, delete, json!({ "id": ada.id }), Some(&writer)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+
+    // Keys never mint keys, whatever their scopes.
+    let admin_key = CurrentUser {
+        scopes: Some(vec![ApiScope::Admin]),
+        ..owner.clone()
+    };
+    let input = json!({ "input": { "name": "escalate", "scopes": ["ADMIN"] } });
+    let resp = run(&ctx, CREATE_KEY, input, Some(&admin_key)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn only_owners_issue_service_keys() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping api key tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let sales_id = ctx.seeded.user_email("sales@sme.test").unwrap().id;
+    let input = json!({
+        "input": { "name": "crm sync", "scopes": ["CRM_WRITE"], "userId": sales_id }
+    });
+
+    let resp = run(&ctx, CREATE_KEY, input.clone(), Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let resp = run(&ctx, CREATE_KEY, input, Some(&owner)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let key = resp.data.into_json().unwrap()["crm"]["createApiKey"]["key"].clone();
+    assert_eq!(key["kind"], "SERVICE");
+    assert_eq!(key["userId"], json!(sales_id));
+
+    let past = json!({
+        "input": { "name": "stale", "scopes": ["CRM_READ"], "expiresAt": "2020-01-01T00:00:00Z" }
+    });
+    let resp = run(&ctx, CREATE_KEY, past, Some(&owner)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    ctx.cleanup().await;
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use api::auth::{ClientInfo, CurrentUser, UserRole};
+use common::{error_code, request, user_with_role, PgTestContext};
+use sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+use serde_json::{json, Value};
+
+/// Runs `query` as `user`, tagging it with the client request id audit entries record.
+async fn run_with_request_id(
+    ctx: &PgTestContext,
+    query: &str,
+    vars: Value,
+    user: &CurrentUser,
+    request_id: &str,
+) -> async_graphql::Response {
+    let client = ClientInfo {
+        request_id: Some(request_id.to_string()),
+        ..ClientInfo::default()
+    };
+    ctx.schema
+        .execute(request(query, vars, Some(user)).data(client))
+        .await
+}
+
+const AUDIT_LOG: &str = r#"
+    query Audit($type: AuditEntityType, $id: ID, $actor: ID, $range: DateRange) {
+        crm {
+            auditLog(entityType: $type, entityId: $id, actor: $actor, range: $range) {
+                totalCount
+                nodes { actorId entityType entityId operation changes requestId }
+            }
+        }
+    }
+"#;
+
+async fn audit_entries(ctx: &PgTestContext, vars: Value) -> Vec<Value> {
+    let admin = user_with_role(ctx, "admin@sme.test", UserRole::Admin);
+    let resp = run_with_request_id(ctx, AUDIT_LOG, vars, &admin, "audit-read").await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let data = resp.data.into_json().unwrap();
+    data["crm"]["auditLog"]["nodes"]
+        .as_array()
+        .unwrap()
+        .to_vec()
+}
+
+#[tokio::test]
+async fn crm_changes_are_recorded_with_column_diffs() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping audit log tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let resp = run_with_request_id(
+        &ctx,
+        r#"mutation { crm { createCompany(input: { name: "Globex" }) { id } } }"#,
+        json!({}),
+        &sales,
+        "req-create",
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let company_id = resp.data.into_json().unwrap()["crm"]["createCompany"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    for (query, request_id) in [
+        (
+            r#"mutation($id: ID!) { crm { updateCompany(input: { id: $id, name: "Globex Corp" }) { id } } }"#,
+            "req-rename",
+        ),
+        // Saving the same values again is not a change.
+        (
+            r#"mutation($id: ID!) { crm { updateCompany(input: { id: $id, name: "Globex Corp" }) { id } } }"#,
+            "req-noop",
+        ),
+        (
+            r#"mutation($id: ID!) { crm { assignCompany(id: $id, userId: null) { id } } }"#,
+            "req-assign",
+        ),
+    ] {
+        let resp =
+            run_with_request_id(&ctx, query, json!({ "id": company_id }), &sales, request_id).await;
+        assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    }
+
+    let entries = audit_entries(&ctx, json!({ "type": "COMPANY", "id": company_id })).await;
+    assert_eq!(entries.len(), 2);
+    let (rename, create) = (&entries[0], &entries[1]);
+    assert_eq!(create["operation"], "CREATE");
+    assert_eq!(create["requestId"], "req-create");
+    assert_eq!(create["actorId"], sales.user_id.to_string());
+    assert_eq!(
+        create["changes"]["name"],
+        json!({ "old": null, "new": "Globex" })
+    );
+    assert!(create["changes"].get("updated_at").is_none());
+    assert_eq!(rename["operation"], "UPDATE");
+    assert_eq!(rename["requestId"], "req-rename");
+    assert_eq!(
+        rename["changes"],
+        json!({ "name": { "old": "Globex", "new": "Globex Corp" } })
+    );
+
+    let resp = run_with_request_id(
+        &ctx,
+        AUDIT_LOG,
+        json!({ "type": "COMPANY" }),
+        &sales,
+        "audit-read",
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn cascading_deletes_record_every_removed_row() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping audit log tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap().clone();
+    let pilot = ctx.seeded.deal_titled("ACME Pilot").unwrap().clone();
+    let resp = run_with_request_id(
+        &ctx,
+        r#"mutation($id: ID!) { crm { deleteCompany(id: $id, cascade: true) } }"#,
+        json!({ "id": acme.id.to_string() }),
+        &owner,
+        "req-delete",
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+
+    let today = chrono::Utc::now().date_naive().to_string();
+    let entries = audit_entries(
+        &ctx,
+        json!({
+            "actor": owner.user_id.to_string(),
+            "range": { "from": today, "to": today },
+        }),
+    )
+    .await;
+    // Seeding moved deals through the same service, without a request id.
+    let entries: Vec<Value> = entries
+        .into_iter()
+        .filter(|entry| entry["requestId"] == "req-delete")
+        .collect();
+    assert!(entries.iter().all(|entry| entry["operation"] != "CREATE"));
+    let find = |entity_type: &str, id: String| {
+        entries
+            .iter()
+            .find(|entry| entry["entityType"] == entity_type && entry["entityId"] == id)
+            .unwrap_or_else(|| panic!("no {} entry for {}", entity_type, id))
+    };
+    let company = find("COMPANY", acme.id.to_string());
+    assert_eq!(company["operation"], "DELETE");
+    assert_eq!(company["changes"]["name"]["old"], "ACME, Inc.");
+    assert_eq!(company["changes"]["name"]["new"], Value::Null);
+    assert_eq!(find("DEAL", pilot.id.to_string())["operation"], "DELETE");
+    for contact in ctx
+        .seeded
+        .contacts
+        .iter()
+        .filter(|c| c.company_id == Some(acme.id))
+    {
+        let detached = find("CONTACT", contact.id.to_string());
+        assert_eq!(detached["operation"], "UPDATE");
+        assert_eq!(
+            detached["changes"],
+            json!({ "company_id": { "old": acme.id.to_string(), "new": null } })
+        );
+    }
+
+    let yesterday = (chrono::Utc::now() - chrono::Duration::days(1))
+        .date_naive()
+        .to_string();
+    let earlier = audit_entries(
+        &ctx,
+        json!({ "range": { "from": yesterday, "to": yesterday } }),
+    )
+    .await;
+    assert!(earlier.is_empty());
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn audit_rows_cannot_be_rewritten() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping audit log tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let resp = run_with_request_id(
+        &ctx,
+        r#"mutation { crm { createCompany(input: { name: "Initech" }) { id } } }"#,
+        json!({}),
+        &sales,
+        "req-create",
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    for sql in [
+        "UPDATE audit_log SET actor_id = NULL",
+        "DELETE FROM audit_log",
+        "TRUNCATE audit_log",
+    ] {
+        let result = ctx
+            .db
+            .execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+            .await;
+        assert!(result.is_err(), "{} should be rejected", sql);
+    }
+    assert_eq!(
+        audit_entries(&ctx, json!({ "type": "COMPANY" }))
+            .await
+            .len(),
+        1
+    );
+    ctx.cleanup().await;
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use api::auth::UserRole;
+use common::{error_code, run, user_with_role, PgTestContext};
+use serde_json::json;
+
+const LIST_COMPANIES: &str = "{ crm { companies { totalCount } } }";
+
+const CREATE_TASK: &str = r#"
+    mutation Create($dealId: ID!) {
+        crm { createTask(input: { title: "Guarded", dealId: $dealId }) { id } }
+    }
+"#;
+
+const DELETE_TASK: &str = r#"
+    mutation Delete($id: ID!) { crm { deleteTask(id: $id) } }
+"#;
+
+const MOVE_STAGE: &str = r#"
+    mutation Move($id: ID!) { crm { moveDealStage(id: $id, stage: PROPOSAL) { id } } }
+"#;
+
+const ASSIGN_COMPANY: &str = r#"
+    mutation Assign($id: ID!) { crm { assignCompany(id: $id, userId: null) { id } } }
+"#;
+
+const DELETE_CONTACT: &str = r#"
+    mutation Delete($id: ID!) { crm { deleteContact(id: $id) } }
+"#;
+
+#[tokio::test]
+async fn anonymous_requests_are_unauthenticated() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping authz tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let resp = run(&ctx, LIST_COMPANIES, json!({}), None).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("UNAUTHENTICATED"));
+    let resp = run(&ctx, "{ crm { me { id } } }", json!({}), None).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn viewer_can_read_but_not_write() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping authz tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let viewer = user_with_role(&ctx, "sales@sme.test", UserRole::Viewer);
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let pilot = ctx.seeded.deal_titled("ACME Pilot").unwrap();
+
+    let resp = run(&ctx, LIST_COMPANIES, json!({}), Some(&viewer)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+
+    let resp = run(
+        &ctx,
+        CREATE_TASK,
+        json!({ "dealId": pilot.id }),
+        Some(&sales),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let task_id = resp.data.into_json().unwrap()["crm"]["createTask"]["id"].clone();
+
+    for (query, vars) in [
+        (CREATE_TASK, json!({ "dealId": pilot.id })),
+        (DELETE_TASK, json!({ "id": task_id })),
+        (MOVE_STAGE, json!({ "id": pilot.id })),
+        (ASSIGN_COMPANY, json!({ "id": acme.id })),
+    ] {
+        let resp = run(&ctx, query, vars, Some(&viewer)).await;
+        assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"), "{query}");
+    }
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn sales_can_work_deals_but_not_delete_records() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping authz tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let pilot = ctx.seeded.deal_titled("ACME Pilot").unwrap();
+    let ada = ctx.seeded.contact_email("ada@acme.test").unwrap();
+
+    let resp = run(&ctx, MOVE_STAGE, json!({ "id": pilot.id }), Some(&sales)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let resp = run(
+        &ctx,
+        CREATE_TASK,
+        json!({ "dealId": pilot.id }),
+        Some(&sales),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let task_id = resp.data.into_json().unwrap()["crm"]["createTask"]["id"].clone();
+    let resp = run(&ctx, DELETE_TASK, json!({ "id": task_id }), Some(&sales)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+
+    let resp = run(&ctx, DELETE_CONTACT, json!({ "id": ada.id }), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn admin_and_owner_can_delete_records() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping authz tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let ada = ctx.seeded.contact_email("ada@acme.test").unwrap();
+    let charles = ctx.seeded.contact_email("charles@acme.test").unwrap();
+
+    let resp = run(&ctx, DELETE_CONTACT, json!({ "id": ada.id }), Some(&admin)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["deleteContact"], true);
+
+    let resp = run(
+        &ctx,
+        DELETE_CONTACT,
+        json!({ "id": charles.id }),
+        Some(&owner),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["deleteContact"], true);
+    ctx.cleanup().await;
+}
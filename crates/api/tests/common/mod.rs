@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
 use api::{
-    auth::{AuthConfig, AuthMode},
+    auth::{AuthConfig, AuthMode, CurrentUser, UserRole},
     schema::{build_schema, seed_crm_demo, AppSchema, SeededCrmRecords},
 };
-use async_graphql::Schema;
+use async_graphql::{Request, Schema, Variables};
 use migration::Migrator;
 use migration::MigratorTrait;
 use sea_orm::{ConnectionTrait, Database, DatabaseBackend, DatabaseConnection, Statement};
@@ -15,7 +15,7 @@ pub struct PgTestContext {
     pub db: Arc<DatabaseConnection>,
     #[allow(dead_code)]
     pub schema:
-        Schema<api::schema::QueryRoot, api::schema::MutationRoot, async_graphql::EmptySubscription>,
+        Schema<api::schema::QueryRoot, api::schema::MutationRoot, api::schema::SubscriptionRoot>,
     #[allow(dead_code)]
     pub seeded: SeededCrmRecords,
     #[allow(dead_code)]
@@ -56,8 +56,8 @@ impl PgTestContext {
         let seeded = seed_crm_demo(&conn).await.ok()?;
         let db = Arc::new(conn);
         let secret = match mode {
-            AuthMode::Local => Some("test-secret".to_string()),
             AuthMode::Disabled => None,
+            _ => Some("test-secret".to_string()),
         };
         let auth = Arc::new(AuthConfig::new(mode, secret, 15));
         let AppSchema(schema) = build_schema(db.clone(), auth.clone());
@@ -88,6 +88,52 @@ impl PgTestContext {
     }
 }
 
+/// The seeded user `email`, acting in the seeded organization with only `role`.
+#[allow(dead_code)]
+pub fn user_with_role(ctx: &PgTestContext, email: &str, role: UserRole) -> CurrentUser {
+    let user = ctx.seeded.user_email(email).expect("seeded user");
+    CurrentUser {
+        user_id: user.id,
+        org_id: ctx.seeded.organization.id,
+        roles: vec![role],
+        scopes: None,
+    }
+}
+
+/// `query` as `user`, or anonymous without one.
+#[allow(dead_code)]
+pub fn request(query: &str, vars: serde_json::Value, user: Option<&CurrentUser>) -> Request {
+    let mut request = Request::new(query).variables(Variables::from_json(vars));
+    if let Some(user) = user {
+        request = request.data(user.clone());
+    }
+    request
+}
+
+#[allow(dead_code)]
+pub async fn run(
+    ctx: &PgTestContext,
+    query: &str,
+    vars: serde_json::Value,
+    user: Option<&CurrentUser>,
+) -> async_graphql::Response {
+    ctx.schema.execute(request(query, vars, user)).await
+}
+
+/// The `code` extension of the first error that has one.
+#[allow(dead_code)]
+pub fn error_code(resp: &async_graphql::Response) -> Option<String> {
+    resp.errors.iter().find_map(|err| {
+        err.extensions
+            .as_ref()
+            .and_then(|ext| ext.get("code"))
+            .and_then(|code| match code {
+                async_graphql::Value::String(inner) => Some(inner.clone()),
+                _ => None,
+            })
+    })
+}
+
 fn build_urls(base: &str) -> Option<(String, String, String)> {
     let url = Url::parse(base).ok()?;
     let db_path = url.path().trim_start_matches('/').to_string();
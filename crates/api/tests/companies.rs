@@ -0,0 +1,376 @@
+mod common;
+
+use api::auth::{CurrentUser, UserRole};
+use async_graphql::{Request, Variables};
+use common::{error_code, PgTestContext};
+use entity::{activity, company, contact, deal};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+
+fn owner_user(ctx: &PgTestContext) -> CurrentUser {
+    let owner = ctx
+        .seeded
+        .user_email("owner@sme.test")
+        .expect("seeded owner user");
+    CurrentUser {
+        user_id: owner.id,
+        org_id: ctx.seeded.organization.id,
+        roles: vec![UserRole::Owner, UserRole::Admin],
+        scopes: None,
+    }
+}
+
+#[tokio::test]
+async fn company_create_update_delete_flow() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping company tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let create = r#"
+        mutation Create($input: NewCompanyInput!) {
+            crm {
+                createCompany(input: $input) {
+                    id
+                    name
+                    website
+                    createdBy { id }
+                }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(create)
+                .variables(Variables::from_json(json!({
+                    "input": {
+                        "name": "  Globex Corp  ",
+                        "website": "https://globex.test",
+                        "phone": "+1 (555) 0400"
+                    }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let created = resp.data.into_json().unwrap()["crm"]["createCompany"].clone();
+    assert_eq!(created["name"], "Globex Corp");
+    assert_eq!(created["createdBy"]["id"], current_user.user_id.to_string());
+    let company_id = created["id"].as_str().unwrap().to_string();
+
+    let update = r#"
+        mutation Update($input: UpdateCompanyInput!) {
+            crm {
+                updateCompany(input: $input) { id name website }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(update)
+                .variables(Variables::from_json(json!({
+                    "input": { "id": company_id, "name": "Globex", "website": "" }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let updated = resp.data.into_json().unwrap()["crm"]["updateCompany"].clone();
+    assert_eq!(updated["name"], "Globex");
+    assert!(updated["website"].is_null());
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(update)
+                .variables(Variables::from_json(json!({
+                    "input": { "id": company_id, "phone": "call me" }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let delete = r#"
+        mutation Delete($id: ID!) {
+            crm { deleteCompany(id: $id) }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(delete)
+                .variables(Variables::from_json(json!({ "id": company_id })))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["deleteCompany"], true);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn delete_company_with_deals_requires_cascade() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping company tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let acme = ctx
+        .seeded
+        .company_named("ACME, Inc.")
+        .expect("seeded company");
+    let delete = r#"
+        mutation Delete($id: ID!, $cascade: Boolean!) {
+            crm { deleteCompany(id: $id, cascade: $cascade) }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(delete)
+                .variables(Variables::from_json(
+                    json!({ "id": acme.id, "cascade": false }),
+                ))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(delete)
+                .variables(Variables::from_json(
+                    json!({ "id": acme.id, "cascade": true }),
+                ))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert!(company::Entity::find_by_id(acme.id)
+        .one(ctx.db.as_ref())
+        .await
+        .unwrap()
+        .is_none());
+    let remaining_deals = deal::Entity::find()
+        .filter(deal::Column::CompanyId.eq(acme.id))
+        .all(ctx.db.as_ref())
+        .await
+        .unwrap();
+    assert!(remaining_deals.is_empty());
+    let ada = ctx.seeded.contact_email("ada@acme.test").unwrap();
+    let detached = contact::Entity::find_by_id(ada.id)
+        .one(ctx.db.as_ref())
+        .await
+        .unwrap()
+        .expect("contact survives company delete");
+    assert!(detached.company_id.is_none());
+    ctx.cleanup().await;
+}
+
+async fn add_activity(ctx: &PgTestContext, entity_type: &str, entity_id: uuid::Uuid) -> uuid::Uuid {
+    activity::ActiveModel {
+        id: Set(uuid::Uuid::new_v4()),
+        org_id: Set(ctx.seeded.organization.id),
+        entity_type: Set(entity_type.to_string()),
+        entity_id: Set(entity_id),
+        kind: Set(activity::Kind::StageChange),
+        subject: Set(None),
+        body_md: Set(None),
+        meta_json: Set(json!({})),
+        created_at: Set(chrono::Utc::now().into()),
+        created_by: Set(None),
+        updated_by: Set(None),
+    }
+    .insert(ctx.db.as_ref())
+    .await
+    .unwrap()
+    .id
+}
+
+#[tokio::test]
+async fn deleting_records_takes_their_activities_along() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping company tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let ada = ctx.seeded.contact_email("ada@acme.test").unwrap();
+    let acme_deal = ctx
+        .seeded
+        .deals
+        .iter()
+        .find(|deal| deal.company_id == acme.id)
+        .unwrap();
+    let company_note = add_activity(&ctx, "company", acme.id).await;
+    let deal_note = add_activity(&ctx, "deal", acme_deal.id).await;
+    let contact_note = add_activity(&ctx, "contact", ada.id).await;
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(
+                r#"mutation Delete($id: ID!) { crm { deleteCompany(id: $id, cascade: true) } }"#,
+            )
+            .variables(Variables::from_json(json!({ "id": acme.id })))
+            .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let remaining = |id| activity::Entity::find_by_id(id).one(ctx.db.as_ref());
+    assert!(remaining(company_note).await.unwrap().is_none());
+    assert!(remaining(deal_note).await.unwrap().is_none());
+    // Ada is only detached from the company, so her history stays with her.
+    assert!(remaining(contact_note).await.unwrap().is_some());
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(r#"mutation Delete($id: ID!) { crm { deleteContact(id: $id) } }"#)
+                .variables(Variables::from_json(json!({ "id": ada.id })))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert!(remaining(contact_note).await.unwrap().is_none());
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn company_lookup_and_filtered_list() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping company tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let sales = ctx.seeded.user_email("sales@sme.test").unwrap();
+    let query = r#"
+        query Companies($id: ID!, $owner: ID!) {
+            crm {
+                company(id: $id) { name }
+                owned: companies(filter: { assignedUserId: $owner }) { nodes { name } }
+                matching: companies(filter: { q: "labs" }) { nodes { name } }
+                recent: companies(first: 1, orderBy: CREATED_DESC) {
+                    totalCount
+                    pageInfo { hasNextPage }
+                    nodes { name }
+                }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(query)
+                .variables(Variables::from_json(
+                    json!({ "id": acme.id, "owner": sales.id }),
+                ))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let data = resp.data.into_json().unwrap()["crm"].clone();
+    assert_eq!(data["company"]["name"], "ACME, Inc.");
+    let owned: Vec<_> = data["owned"]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(owned, vec!["ACME, Inc.", "NuFlights LLC"]);
+    assert_eq!(
+        data["matching"]["nodes"],
+        json!([{ "name": "FossRust Labs" }])
+    );
+    assert_eq!(data["recent"]["nodes"].as_array().unwrap().len(), 1);
+    assert_eq!(data["recent"]["pageInfo"]["hasNextPage"], true);
+    assert_eq!(data["recent"]["totalCount"], 3);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn company_nested_relations_resolve() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping company tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let pilot = ctx.seeded.deal_titled("ACME Pilot").unwrap();
+    let create_task = r#"
+        mutation CreateTask($input: NewTaskInput!) {
+            crm { createTask(input: $input) { id } }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(create_task)
+                .variables(Variables::from_json(json!({
+                    "input": { "title": "Send pilot recap", "dealId": pilot.id }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let query = r#"
+        query {
+            crm {
+                companies(orderBy: NAME_ASC) {
+                    nodes {
+                        name
+                        assignedUser { email }
+                        createdBy { email roles }
+                        contacts { email company { name } }
+                        deals {
+                            title
+                            company { name }
+                            tasks { title createdBy { email } }
+                        }
+                    }
+                }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(Request::new(query).data(current_user))
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let companies = resp.data.into_json().unwrap()["crm"]["companies"]["nodes"].clone();
+    let acme = &companies[0];
+    assert_eq!(acme["name"], "ACME, Inc.");
+    assert_eq!(acme["assignedUser"]["email"], "sales@sme.test");
+    assert_eq!(acme["createdBy"]["email"], "owner@sme.test");
+    assert!(acme["createdBy"]["roles"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("OWNER")));
+    let contact_emails: Vec<_> = acme["contacts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["email"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(contact_emails, vec!["charles@acme.test", "ada@acme.test"]);
+    assert_eq!(acme["contacts"][0]["company"]["name"], "ACME, Inc.");
+    let deals = acme["deals"].as_array().unwrap();
+    assert_eq!(deals.len(), 4);
+    assert!(deals.iter().all(|d| d["company"]["name"] == "ACME, Inc."));
+    let pilot_node = deals
+        .iter()
+        .find(|d| d["title"] == "ACME Pilot")
+        .expect("pilot deal nested under ACME");
+    assert_eq!(
+        pilot_node["tasks"],
+        json!([{ "title": "Send pilot recap", "createdBy": { "email": "owner@sme.test" } }])
+    );
+    let fossrust = &companies[1];
+    assert_eq!(fossrust["name"], "FossRust Labs");
+    assert_eq!(fossrust["assignedUser"]["email"], "admin@sme.test");
+    ctx.cleanup().await;
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use api::auth::{CurrentUser, UserRole};
+use async_graphql::{Request, Variables};
+use common::{error_code, PgTestContext};
+use serde_json::json;
+
+fn owner_user(ctx: &PgTestContext) -> CurrentUser {
+    let owner = ctx
+        .seeded
+        .user_email("owner@sme.test")
+        .expect("seeded owner user");
+    CurrentUser {
+        user_id: owner.id,
+        org_id: ctx.seeded.organization.id,
+        roles: vec![UserRole::Owner, UserRole::Admin],
+        scopes: None,
+    }
+}
+
+const CREATE_CONTACT: &str = r#"
+    mutation Create($input: NewContactInput!) {
+        crm {
+            createContact(input: $input) { id email companyId }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn contact_email_is_normalized_and_unique_per_company() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping contact tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let fossrust = ctx.seeded.company_named("FossRust Labs").unwrap();
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(CREATE_CONTACT)
+                .variables(Variables::from_json(json!({
+                    "input": {
+                        "email": "  Alan@Turing.TEST ",
+                        "firstName": "Alan",
+                        "companyId": acme.id
+                    }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let created = resp.data.into_json().unwrap()["crm"]["createContact"].clone();
+    assert_eq!(created["email"], "alan@turing.test");
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(CREATE_CONTACT)
+                .variables(Variables::from_json(json!({
+                    "input": { "email": "ALAN@turing.test", "companyId": acme.id }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(CREATE_CONTACT)
+                .variables(Variables::from_json(json!({
+                    "input": { "email": "alan@turing.test", "companyId": fossrust.id }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let other_id = resp.data.into_json().unwrap()["crm"]["createContact"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let move_contact = r#"
+        mutation Move($id: ID!, $companyId: ID) {
+            crm {
+                moveContactToCompany(id: $id, companyId: $companyId) { id companyId }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(move_contact)
+                .variables(Variables::from_json(
+                    json!({ "id": other_id, "companyId": acme.id }),
+                ))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(move_contact)
+                .variables(Variables::from_json(
+                    json!({ "id": other_id, "companyId": null }),
+                ))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert!(resp.data.into_json().unwrap()["crm"]["moveContactToCompany"]["companyId"].is_null());
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn contact_update_and_delete() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping contact tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let ada = ctx.seeded.contact_email("ada@acme.test").unwrap();
+    let update = r#"
+        mutation Update($input: UpdateContactInput!) {
+            crm {
+                updateContact(input: $input) { id email lastName updatedBy { id } }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(update)
+                .variables(Variables::from_json(json!({
+                    "input": { "id": ada.id, "email": "Charles@acme.test" }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(update)
+                .variables(Variables::from_json(json!({
+                    "input": { "id": ada.id, "email": "ada.king@acme.test", "lastName": "King" }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let updated = resp.data.into_json().unwrap()["crm"]["updateContact"].clone();
+    assert_eq!(updated["email"], "ada.king@acme.test");
+    assert_eq!(updated["lastName"], "King");
+    assert_eq!(updated["updatedBy"]["id"], current_user.user_id.to_string());
+
+    let delete = r#"
+        mutation Delete($id: ID!) {
+            crm { deleteContact(id: $id) }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(delete)
+                .variables(Variables::from_json(json!({ "id": ada.id })))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["deleteContact"], true);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn contact_lookup_and_filtered_list() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping contact tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let ada = ctx.seeded.contact_email("ada@acme.test").unwrap();
+    let query = r#"
+        query Contacts($id: ID!, $companyId: ID!) {
+            crm {
+                contact(id: $id) { email }
+                byCompany: contacts(filter: { companyId: $companyId }, orderBy: EMAIL_ASC) {
+                    nodes { email }
+                }
+                byName: contacts(filter: { q: "TORV" }) { nodes { email } }
+                none: contacts(filter: { createdAfter: "2999-01-01T00:00:00Z" }) { totalCount nodes { email } }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(query)
+                .variables(Variables::from_json(
+                    json!({ "id": ada.id, "companyId": acme.id }),
+                ))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let data = resp.data.into_json().unwrap()["crm"].clone();
+    assert_eq!(data["contact"]["email"], "ada@acme.test");
+    assert_eq!(
+        data["byCompany"]["nodes"],
+        json!([{ "email": "ada@acme.test" }, { "email": "charles@acme.test" }])
+    );
+    assert_eq!(
+        data["byName"]["nodes"],
+        json!([{ "email": "linus@fossrust.test" }])
+    );
+    assert_eq!(data["none"]["nodes"], json!([]));
+    assert_eq!(data["none"]["totalCount"], 0);
+    ctx.cleanup().await;
+}
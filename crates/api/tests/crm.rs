@@ -2,8 +2,8 @@ mod common;
 
 use api::auth::{CurrentUser, UserRole};
 use async_graphql::{Request, Variables};
-use common::PgTestContext;
-use entity::{deal, deal_stage_history};
+use common::{error_code, PgTestContext};
+use entity::{activity, deal, deal_stage_history};
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use serde_json::json;
 use uuid::Uuid;
@@ -15,7 +15,9 @@ fn owner_user(ctx: &PgTestContext) -> CurrentUser {
         .expect("seeded owner user");
     CurrentUser {
         user_id: owner.id,
+        org_id: ctx.seeded.organization.id,
         roles: vec![UserRole::Owner, UserRole::Admin],
+        scopes: None,
     }
 }
 
@@ -59,16 +61,14 @@ async fn move_deal_stage_happy_path() {
         .await
         .unwrap()
         .unwrap();
-    assert_eq!(updated.stage, deal::Stage::Proposal);
+    assert_eq!(updated.stage, "PROPOSAL");
     let history = deal_stage_history::Entity::find()
         .filter(deal_stage_history::Column::DealId.eq(deal.id))
         .all(ctx.db.as_ref())
         .await
         .unwrap();
     assert!(
-        history
-            .iter()
-            .any(|row| row.to_stage == deal::Stage::Proposal),
+        history.iter().any(|row| row.to_stage == "PROPOSAL"),
         "expected history entry in {:?}",
         history
     );
@@ -194,7 +194,7 @@ async fn deal_stage_history_query_returns_latest_first() {
         query History($id: ID!) {
             crm {
                 dealStageHistory(dealId: $id, first: 10) {
-                    toStage
+                    nodes { toStage }
                 }
             }
         }
@@ -207,7 +207,7 @@ async fn deal_stage_history_query_returns_latest_first() {
         )
         .await;
     assert!(resp.errors.is_empty());
-    let nodes = resp.data.into_json().unwrap()["crm"]["dealStageHistory"]
+    let nodes = resp.data.into_json().unwrap()["crm"]["dealStageHistory"]["nodes"]
         .as_array()
         .cloned()
         .unwrap_or_default();
@@ -215,3 +215,228 @@ async fn deal_stage_history_query_returns_latest_first() {
     assert_eq!(nodes[0]["toStage"], "NEGOTIATE");
     ctx.cleanup().await;
 }
+
+const CREATE_DEAL: &str = r#"
+    mutation Create($input: NewDealInput!) {
+        crm {
+            createDeal(input: $input) { id stage currency amountCents closeDate }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn create_deal_defaults_stage_and_records_explicit_stage() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        return;
+    };
+    let schema = ctx.schema.clone();
+    let current_user = owner_user(&ctx);
+    let acme = ctx
+        .seeded
+        .company_named("ACME, Inc.")
+        .expect("seeded company");
+
+    let resp = schema
+        .execute(
+            Request::new(CREATE_DEAL)
+                .variables(Variables::from_json(json!({
+                    "input": {
+                        "title": "ACME Support",
+                        "companyId": acme.id,
+                        "amountCents": 125_000,
+                        "currency": "eur",
+                        "closeDate": "2026-12-31"
+                    }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let created = resp.data.into_json().unwrap()["crm"]["createDeal"].clone();
+    assert_eq!(created["stage"], "NEW");
+    assert_eq!(created["currency"], "EUR");
+    assert_eq!(created["closeDate"], "2026-12-31");
+    let default_id = Uuid::parse_str(created["id"].as_str().unwrap()).unwrap();
+    let history = deal_stage_history::Entity::find()
+        .filter(deal_stage_history::Column::DealId.eq(default_id))
+        .all(ctx.db.as_ref())
+        .await
+        .unwrap();
+    assert!(history.is_empty());
+
+    let resp = schema
+        .execute(
+            Request::new(CREATE_DEAL)
+                .variables(Variables::from_json(json!({
+                    "input": {
+                        "title": "ACME Renewal",
+                        "companyId": acme.id,
+                        "stage": "PROPOSAL",
+                        "note": "carried over"
+                    }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let created = resp.data.into_json().unwrap()["crm"]["createDeal"].clone();
+    assert_eq!(created["stage"], "PROPOSAL");
+    let staged_id = Uuid::parse_str(created["id"].as_str().unwrap()).unwrap();
+    let history = deal_stage_history::Entity::find()
+        .filter(deal_stage_history::Column::DealId.eq(staged_id))
+        .all(ctx.db.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].from_stage, "NEW");
+    assert_eq!(history[0].to_stage, "PROPOSAL");
+    assert_eq!(history[0].note.as_deref(), Some("carried over"));
+    let activities = activity::Entity::find()
+        .filter(activity::Column::EntityId.eq(staged_id))
+        .all(ctx.db.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(activities.len(), 1);
+    assert_eq!(activities[0].created_by, Some(current_user.user_id));
+
+    let resp = schema
+        .execute(
+            Request::new(CREATE_DEAL)
+                .variables(Variables::from_json(json!({
+                    "input": { "title": "Bad", "companyId": acme.id, "currency": "EURO" }
+                })))
+                .data(current_user),
+        )
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn update_deal_edits_and_clears_fields() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        return;
+    };
+    let schema = ctx.schema.clone();
+    let current_user = owner_user(&ctx);
+    let deal = ctx.seeded.deal_titled("ACME Pilot").expect("seeded deal");
+    let update = r#"
+        mutation Update($input: UpdateDealInput!) {
+            crm {
+                updateDeal(input: $input) {
+                    title amountCents currency closeDate stage updatedBy { id }
+                }
+            }
+        }
+    "#;
+    let resp = schema
+        .execute(
+            Request::new(update)
+                .variables(Variables::from_json(json!({
+                    "input": {
+                        "id": deal.id,
+                        "title": "ACME Pilot (extended)",
+                        "amountCents": 990_000,
+                        "currency": "usd",
+                        "closeDate": null
+                    }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let updated = resp.data.into_json().unwrap()["crm"]["updateDeal"].clone();
+    assert_eq!(updated["title"], "ACME Pilot (extended)");
+    assert_eq!(updated["amountCents"], 990_000);
+    assert_eq!(updated["currency"], "USD");
+    assert!(updated["closeDate"].is_null());
+    assert_eq!(updated["updatedBy"]["id"], current_user.user_id.to_string());
+
+    let resp = schema
+        .execute(
+            Request::new(update)
+                .variables(Variables::from_json(json!({
+                    "input": { "id": deal.id, "amountCents": -1 }
+                })))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let resp = schema
+        .execute(
+            Request::new(update)
+                .variables(Variables::from_json(json!({
+                    "input": { "id": deal.id, "amountCents": null, "currency": "" }
+                })))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let updated = resp.data.into_json().unwrap()["crm"]["updateDeal"].clone();
+    assert!(updated["amountCents"].is_null());
+    assert!(updated["currency"].is_null());
+    assert_eq!(updated["title"], "ACME Pilot (extended)");
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn deal_lookup_and_filtered_list() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let acme = ctx
+        .seeded
+        .company_named("ACME, Inc.")
+        .expect("seeded company");
+    let pilot = ctx.seeded.deal_titled("ACME Pilot").expect("seeded deal");
+    let query = r#"
+        query Deals($id: ID!, $companyId: ID!) {
+            crm {
+                deal(id: $id) { title stage }
+                byCompany: deals(filter: { companyId: $companyId }, orderBy: AMOUNT_DESC) {
+                    nodes { title }
+                }
+                qualifying: deals(filter: { stage: QUALIFY }, orderBy: TITLE_ASC) {
+                    nodes { title }
+                }
+                missing: deal(id: "00000000-0000-0000-0000-000000000000") { id }
+            }
+        }
+    "#;
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(query)
+                .variables(Variables::from_json(
+                    json!({ "id": pilot.id, "companyId": acme.id }),
+                ))
+                .data(current_user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let data = resp.data.into_json().unwrap()["crm"].clone();
+    assert_eq!(data["deal"]["stage"], "QUALIFY");
+    assert_eq!(
+        data["byCompany"]["nodes"],
+        json!([
+            { "title": "ACME Pilot" },
+            { "title": "ACME Retainer" },
+            { "title": "Fresh Prospect" },
+            { "title": "Quick Win" }
+        ])
+    );
+    assert_eq!(
+        data["qualifying"]["nodes"],
+        json!([{ "title": "ACME Pilot" }, { "title": "NuFlights Annual" }])
+    );
+    assert!(data["missing"].is_null());
+
+    let resp = ctx
+        .schema
+        .execute(Request::new("{ crm { deals(first: 500) { nodes { id } } } }").data(current_user))
+        .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("LIMIT_EXCEEDED"));
+    ctx.cleanup().await;
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use api::auth::{AuthMode, UserRole};
+use chrono::{Duration, Utc};
+use common::{error_code, run, user_with_role, PgTestContext};
+use entity::{auth_event, login_throttle};
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+
+async fn login(ctx: &PgTestContext, email: &str, password: &str) -> async_graphql::Response {
+    let query = r#"
+        mutation Login($email: String!, $password: String!) {
+            crm { login(email: $email, password: $password) { ok error retryAfterSeconds } }
+        }
+    "#;
+    run(
+        ctx,
+        query,
+        json!({ "email": email, "password": password }),
+        None,
+    )
+    .await
+}
+
+const EVENTS: &str = r#"
+    query Events($userId: ID, $kinds: [AuthEventKind!]) {
+        crm {
+            authEvents(userId: $userId, kinds: $kinds) {
+                totalCount
+                nodes { kind email detail }
+            }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn repeated_failures_lock_the_account_with_backoff() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping login throttle tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let sales = ctx.seeded.user_email("sales@sme.test").unwrap().clone();
+
+    for _ in 0..6 {
+        let resp = login(&ctx, "sales@sme.test", "wrong password").await;
+        assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    }
+    // The sixth failure started a lockout, so even the right password waits.
+    let resp = login(&ctx, "Sales@SME.test", "salespass").await;
+    let payload = resp.data.into_json().unwrap()["crm"]["login"].clone();
+    assert_eq!(payload["ok"], false);
+    let retry = payload["retryAfterSeconds"].as_i64().unwrap();
+    assert!((1..=30).contains(&retry), "retry after {}", retry);
+
+    let resp = run(
+        &ctx,
+        EVENTS,
+        json!({ "userId": sales.id, "kinds": ["LOCKED_OUT"] }),
+        Some(&admin),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let events = resp.data.into_json().unwrap()["crm"]["authEvents"].clone();
+    assert_eq!(events["totalCount"], 1);
+    assert!(events["nodes"][0]["detail"]
+        .as_str()
+        .unwrap()
+        .starts_with("account locked until"));
+    let resp = run(
+        &ctx,
+        EVENTS,
+        json!({ "kinds": ["LOGIN_FAILED"] }),
+        Some(&admin),
+    )
+    .await;
+    let events = resp.data.into_json().unwrap()["crm"]["authEvents"].clone();
+    assert_eq!(events["totalCount"], 7);
+    assert_eq!(events["nodes"][0]["detail"], "locked");
+    assert_eq!(events["nodes"][1]["detail"], "bad_password");
+
+    // Once the lock lapses a success wipes the slate, and the next lockout
+    // needs a fresh run of failures. A repeat offender waits twice as long.
+    login_throttle::Entity::update_many()
+        .col_expr(
+            login_throttle::Column::LockedUntil,
+            Expr::value(Utc::now() - Duration::seconds(1)),
+        )
+        .exec(ctx.db.as_ref())
+        .await
+        .unwrap();
+    let resp = login(&ctx, "sales@sme.test", "wrong password").await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let resp = login(&ctx, "sales@sme.test", "salespass").await;
+    let payload = resp.data.into_json().unwrap()["crm"]["login"].clone();
+    assert!(payload["retryAfterSeconds"].as_i64().unwrap() > 30);
+
+    login_throttle::Entity::update_many()
+        .col_expr(
+            login_throttle::Column::LockedUntil,
+            Expr::value(Utc::now() - Duration::seconds(1)),
+        )
+        .exec(ctx.db.as_ref())
+        .await
+        .unwrap();
+    let resp = login(&ctx, "sales@sme.test", "salespass").await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+    for _ in 0..5 {
+        login(&ctx, "sales@sme.test", "wrong password").await;
+    }
+    let resp = login(&ctx, "sales@sme.test", "salespass").await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn unknown_accounts_are_logged_and_events_need_admin() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping login throttle tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+
+    let resp = login(&ctx, "nobody@sme.test", "whatever").await;
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["login"]["error"],
+        "Invalid credentials"
+    );
+    let resp = login(&ctx, "owner@sme.test", "ownerpass").await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+
+    let resp = run(&ctx, EVENTS, json!({}), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let resp = run(&ctx, EVENTS, json!({}), Some(&admin)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    // An unknown email belongs to no organization, so no tenant's admins see it.
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["authEvents"]["nodes"],
+        json!([
+            { "kind": "LOGIN_SUCCEEDED", "email": "owner@sme.test", "detail": "password" },
+        ])
+    );
+    let unknown = auth_event::Entity::find()
+        .filter(auth_event::Column::Email.eq("nobody@sme.test"))
+        .one(ctx.db.as_ref())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(unknown.detail.as_deref(), Some("unknown_account"));
+    ctx.cleanup().await;
+}
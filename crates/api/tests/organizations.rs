@@ -0,0 +1,327 @@
+mod common;
+
+use api::auth::{AuthMode, CurrentUser, UserRole};
+use api::organization::{add_member, create_organization};
+use common::{error_code, run, PgTestContext};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+async fn data(ctx: &PgTestContext, query: &str, vars: Value, user: &CurrentUser) -> Value {
+    let resp = run(ctx, query, vars, Some(user)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    resp.data.into_json().unwrap()["crm"].clone()
+}
+
+fn owner_in(ctx: &PgTestContext, org_id: Uuid) -> CurrentUser {
+    CurrentUser {
+        user_id: ctx.seeded.user_email("owner@sme.test").unwrap().id,
+        org_id,
+        roles: vec![UserRole::Owner, UserRole::Admin],
+        scopes: None,
+    }
+}
+
+/// A second organization the seeded owner also belongs to, holding one company, contact
+/// and deal. Returns the owner's identity there and the new record ids.
+async fn globex(ctx: &PgTestContext) -> (CurrentUser, Value) {
+    let org = create_organization(ctx.db.as_ref(), "Globex", "globex")
+        .await
+        .unwrap();
+    let owner = owner_in(ctx, org.id);
+    add_member(ctx.db.as_ref(), org.id, owner.user_id, &owner.roles)
+        .await
+        .unwrap();
+    let create = r#"
+        mutation Seed {
+            crm {
+                company: createCompany(input: { name: "Globex Corporation" }) { id }
+            }
+        }
+    "#;
+    let company = data(ctx, create, json!({}), &owner).await["company"]["id"].clone();
+    let create = r#"
+        mutation Seed($companyId: ID!) {
+            crm {
+                contact: createContact(input: { email: "ada@globex.test", companyId: $companyId }) { id }
+                deal: createDeal(input: { title: "Globex Hammock Rollout", companyId: $companyId, amountCents: 500000, closeDate: "2025-02-15" }) { id }
+            }
+        }
+    "#;
+    let mut records = data(ctx, create, json!({ "companyId": company }), &owner).await;
+    records["company"] = json!({ "id": company });
+    let move_stage = r#"
+        mutation Move($id: ID!) { crm { moveDealStage(id: $id, stage: WON) { id } } }
+    "#;
+    data(
+        ctx,
+        move_stage,
+        json!({ "id": records["deal"]["id"] }),
+        &owner,
+    )
+    .await;
+    (owner, records)
+}
+
+const OVERVIEW: &str = r#"
+    query Overview($company: ID!, $contact: ID!, $deal: ID!) {
+        crm {
+            company(id: $company) { id }
+            contact(id: $contact) { id }
+            deal(id: $deal) { id }
+            companies { totalCount nodes { name } }
+            contacts { totalCount }
+            deals { totalCount }
+            search(q: "Globex") { nodes { title } }
+            acme: search(q: "ACME") { nodes { title } }
+            pipelineBoard { totalCount }
+            pipelineReport(range: { from: "2025-01-01", to: "2025-12-31" }, includeLost: true) {
+                forecast { deals }
+            }
+            dealStageHistory(dealId: $deal) { totalCount }
+            users { totalCount nodes { email } }
+        }
+    }
+"#;
+
+fn forecast_deals(data: &Value) -> i64 {
+    data["pipelineReport"]["forecast"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| point["deals"].as_i64().unwrap())
+        .sum()
+}
+
+#[tokio::test]
+async fn reads_stay_inside_the_callers_organization() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping organization tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let default_owner = owner_in(&ctx, ctx.seeded.organization.id);
+    let report = r#"
+        {
+            crm {
+                pipelineReport(range: { from: "2025-01-01", to: "2025-12-31" }, includeLost: true) {
+                    forecast { deals }
+                }
+            }
+        }
+    "#;
+    let forecast_before = forecast_deals(&data(&ctx, report, json!({}), &default_owner).await);
+    let (globex_owner, records) = globex(&ctx).await;
+    let vars = json!({
+        "company": records["company"]["id"],
+        "contact": records["contact"]["id"],
+        "deal": records["deal"]["id"],
+    });
+
+    let outside = data(&ctx, OVERVIEW, vars.clone(), &default_owner).await;
+    assert!(outside["company"].is_null());
+    assert!(outside["contact"].is_null());
+    assert!(outside["deal"].is_null());
+    assert_eq!(outside["companies"]["totalCount"], 3);
+    assert_eq!(outside["contacts"]["totalCount"], 4);
+    assert_eq!(outside["deals"]["totalCount"], 8);
+    assert_eq!(outside["search"]["nodes"], json!([]));
+    assert_eq!(outside["pipelineBoard"]["totalCount"], 8);
+    assert_eq!(outside["dealStageHistory"]["totalCount"], 0);
+    assert_eq!(outside["users"]["totalCount"], 3);
+
+    let inside = data(&ctx, OVERVIEW, vars, &globex_owner).await;
+    assert_eq!(inside["company"]["id"], records["company"]["id"]);
+    assert_eq!(inside["deal"]["id"], records["deal"]["id"]);
+    assert_eq!(
+        inside["companies"]["nodes"],
+        json!([{ "name": "Globex Corporation" }])
+    );
+    assert_eq!(inside["contacts"]["totalCount"], 1);
+    assert_eq!(inside["deals"]["totalCount"], 1);
+    assert_eq!(inside["acme"]["nodes"], json!([]));
+    assert_eq!(inside["pipelineBoard"]["totalCount"], 1);
+    assert_eq!(forecast_deals(&inside), 1);
+    assert_eq!(inside["dealStageHistory"]["totalCount"], 1);
+    assert_eq!(
+        inside["users"]["nodes"],
+        json!([{ "email": "owner@sme.test" }])
+    );
+    assert_eq!(forecast_deals(&outside), forecast_before);
+
+    let audit = r#"{ crm { auditLog { nodes { entityType entityId } } } }"#;
+    let entries = data(&ctx, audit, json!({}), &globex_owner).await["auditLog"]["nodes"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert!(entries
+        .iter()
+        .any(|entry| entry["entityId"] == records["deal"]["id"]));
+    let entries = data(&ctx, audit, json!({}), &default_owner).await["auditLog"]["nodes"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert!(entries
+        .iter()
+        .all(|entry| entry["entityId"] != records["deal"]["id"]));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn writes_cannot_reach_another_organization() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping organization tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let (globex_owner, records) = globex(&ctx).await;
+    let default_owner = owner_in(&ctx, ctx.seeded.organization.id);
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let sales = ctx.seeded.user_email("sales@sme.test").unwrap();
+
+    let update = r#"
+        mutation Update($id: ID!) { crm { updateDeal(input: { id: $id, title: "Taken" }) { id } } }
+    "#;
+    let resp = run(
+        &ctx,
+        update,
+        json!({ "id": records["deal"]["id"] }),
+        Some(&default_owner),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+
+    let create = r#"
+        mutation Create($companyId: ID!) {
+            crm { createDeal(input: { title: "Crossover", companyId: $companyId }) { id } }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        create,
+        json!({ "companyId": acme.id }),
+        Some(&globex_owner),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+
+    // Users outside the organization cannot be handed its records.
+    let assign = r#"
+        mutation Assign($id: ID!, $userId: ID) { crm { assignDeal(id: $id, userId: $userId) { id } } }
+    "#;
+    let resp = run(
+        &ctx,
+        assign,
+        json!({ "id": records["deal"]["id"], "userId": sales.id }),
+        Some(&globex_owner),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+
+    let delete = r#"mutation Delete($id: ID!) { crm { deleteCompany(id: $id, cascade: true) } }"#;
+    let resp = run(&ctx, delete, json!({ "id": acme.id }), Some(&globex_owner)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["deleteCompany"],
+        false
+    );
+    let companies = r#"{ crm { companies { totalCount } } }"#;
+    assert_eq!(
+        data(&ctx, companies, json!({}), &default_owner).await["companies"]["totalCount"],
+        3
+    );
+
+    let users = r#"
+        mutation Roles($id: ID!) { crm { setUserRoles(userId: $id, roles: [VIEWER]) { id } } }
+    "#;
+    let resp = run(&ctx, users, json!({ "id": sales.id }), Some(&globex_owner)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn login_lands_in_an_organization_the_user_belongs_to() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping organization tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let (globex_owner, _) = globex(&ctx).await;
+    let login = r#"
+        mutation Login($email: String!, $password: String!, $organization: String) {
+            crm { login(email: $email, password: $password, organization: $organization) { ok error } }
+        }
+    "#;
+    let attempt = |email: &'static str,
+                   password: &'static str,
+                   organization: Option<&'static str>| {
+        let vars = json!({ "email": email, "password": password, "organization": organization });
+        let ctx = &ctx;
+        async move {
+            let resp = run(ctx, login, vars, None).await;
+            assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+            resp.data.into_json().unwrap()["crm"]["login"].clone()
+        }
+    };
+    assert_eq!(
+        attempt("owner@sme.test", "ownerpass", Some("GLOBEX")).await["ok"],
+        true
+    );
+    assert_eq!(
+        attempt("sales@sme.test", "salespass", None).await["ok"],
+        true
+    );
+    for organization in ["globex", "initech"] {
+        let payload = attempt("sales@sme.test", "salespass", Some(organization)).await;
+        assert_eq!(payload["ok"], false);
+        assert_eq!(payload["error"], "No access to this organization");
+    }
+
+    let query = r#"{ crm { organizations { slug current } } }"#;
+    assert_eq!(
+        data(&ctx, query, json!({}), &globex_owner).await["organizations"],
+        json!([
+            { "slug": "default", "current": false },
+            { "slug": "globex", "current": true },
+        ])
+    );
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn credential_resets_need_a_user_in_no_other_organization() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping organization tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let (globex_owner, _) = globex(&ctx).await;
+    let sales = ctx.seeded.user_email("sales@sme.test").unwrap();
+    add_member(
+        ctx.db.as_ref(),
+        globex_owner.org_id,
+        sales.id,
+        &[UserRole::Sales],
+    )
+    .await
+    .unwrap();
+    let default_owner = owner_in(&ctx, ctx.seeded.organization.id);
+    let reset_password = r#"
+        mutation Reset($id: ID!) {
+            crm { adminResetPassword(userId: $id, password: "globex-chose-this") }
+        }
+    "#;
+    let reset_totp = r#"
+        mutation Reset($id: ID!) { crm { resetUserTotp(userId: $id) } }
+    "#;
+    for owner in [&globex_owner, &default_owner] {
+        for mutation in [reset_password, reset_totp] {
+            let resp = run(&ctx, mutation, json!({ "id": sales.id }), Some(owner)).await;
+            assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+        }
+    }
+
+    let login = r#"
+        mutation Login($password: String!) {
+            crm { login(email: "sales@sme.test", password: $password) { ok } }
+        }
+    "#;
+    let resp = run(&ctx, login, json!({ "password": "salespass" }), None).await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+    ctx.cleanup().await;
+}
@@ -0,0 +1,275 @@
+mod common;
+
+use api::auth::{ApiScope, AuthConfig, AuthMode, CurrentUser, PasswordPolicy, UserRole};
+use api::mailer::{Mailer, SmtpConfig, SmtpSecurity};
+use api::outbox::{deliver_due, enqueue_email};
+use api::schema::{build_schema, AppSchema};
+use async_graphql::{Request, Variables};
+use chrono::Utc;
+use common::{error_code, run, PgTestContext};
+use entity::email_outbox;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+const LOGIN: &str = r#"
+    mutation Login($email: String!, $password: String!) {
+        crm { login(email: $email, password: $password) { ok error } }
+    }
+"#;
+
+const REQUEST_RESET: &str = r#"
+    mutation Request($email: String!) { crm { requestPasswordReset(email: $email) } }
+"#;
+
+const RESET: &str = r#"
+    mutation Reset($token: String!, $password: String!) {
+        crm { resetPassword(token: $token, newPassword: $password) }
+    }
+"#;
+
+const CHANGE: &str = r#"
+    mutation Change($current: String!, $password: String!) {
+        crm { changePassword(currentPassword: $current, newPassword: $password) }
+    }
+"#;
+
+/// Accepts any SMTP conversation on localhost and keeps each message's DATA.
+async fn spawn_smtp_catcher() -> (u16, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let inbox = Arc::new(Mutex::new(Vec::new()));
+    let captured = inbox.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let inbox = captured.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                write.write_all(b"220 catcher ESMTP\r\n").await.unwrap();
+                let mut data: Option<String> = None;
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(body) = data.as_mut() {
+                        if line == "." {
+                            inbox.lock().unwrap().push(data.take().unwrap());
+                            write.write_all(b"250 queued\r\n").await.unwrap();
+                        } else {
+                            body.push_str(&line);
+                            body.push('\n');
+                        }
+                        continue;
+                    }
+                    let verb = line.split_whitespace().next().unwrap_or("").to_uppercase();
+                    let reply: &[u8] = match verb.as_str() {
+                        "DATA" => {
+                            data = Some(String::new());
+                            b"354 end with .\r\n"
+                        }
+                        "QUIT" => {
+                            let _ = write.write_all(b"221 bye\r\n").await;
+                            break;
+                        }
+                        _ => b"250 ok\r\n",
+                    };
+                    write.write_all(reply).await.unwrap();
+                }
+            });
+        }
+    });
+    (port, inbox)
+}
+
+fn plain_mailer(port: u16) -> Mailer {
+    let config = SmtpConfig {
+        port,
+        security: SmtpSecurity::None,
+        ..SmtpConfig::new("127.0.0.1", "SME Suite <no-reply@sme.test>")
+    };
+    Mailer::new(&config).unwrap()
+}
+
+/// Pulls the reset token out of a captured message, undoing quoted-printable
+/// line wrapping first.
+fn reset_token(message: &str) -> String {
+    let decoded = message.replace("=\n", "").replace("=3D", "=");
+    let start = decoded.find("token=").expect("reset link") + "token=".len();
+    decoded[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect()
+}
+
+async fn pending_count(ctx: &PgTestContext) -> usize {
+    email_outbox::Entity::find()
+        .filter(email_outbox::Column::SentAt.is_null())
+        .all(ctx.db.as_ref())
+        .await
+        .unwrap()
+        .len()
+}
+
+#[tokio::test]
+async fn reset_links_are_mailed_and_work_once() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping password reset tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let (port, inbox) = spawn_smtp_catcher().await;
+
+    for email in ["Sales@SME.test", "nobody@sme.test", "sales@sme.test"] {
+        let resp = run(&ctx, REQUEST_RESET, json!({ "email": email }), None).await;
+        assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+        assert_eq!(
+            resp.data.into_json().unwrap()["crm"]["requestPasswordReset"],
+            true
+        );
+    }
+    // Unknown accounts queue nothing and a repeat inside the cooldown is dropped.
+    assert_eq!(pending_count(&ctx).await, 1);
+
+    let sent = deliver_due(ctx.db.as_ref(), &plain_mailer(port), 10)
+        .await
+        .unwrap();
+    assert_eq!(sent, 1);
+    assert_eq!(pending_count(&ctx).await, 0);
+    let message = inbox.lock().unwrap().pop().expect("delivered message");
+    assert!(message.contains("sales@sme.test"));
+    let token = reset_token(&message);
+    assert_eq!(token.len(), 64);
+
+    let resp = run(
+        &ctx,
+        RESET,
+        json!({ "token": token, "password": "Password123" }),
+        None,
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let resp = run(
+        &ctx,
+        RESET,
+        json!({ "token": "not-a-token", "password": "a fresh passphrase" }),
+        None,
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let resp = run(
+        &ctx,
+        RESET,
+        json!({ "token": token, "password": "a fresh passphrase" }),
+        None,
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let resp = run(
+        &ctx,
+        RESET,
+        json!({ "token": token, "password": "another passphrase" }),
+        None,
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let resp = run(
+        &ctx,
+        LOGIN,
+        json!({ "email": "sales@sme.test", "password": "a fresh passphrase" }),
+        None,
+    )
+    .await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_later() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping password reset tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let db = ctx.db.as_ref();
+    let id = enqueue_email(db, "ada@acme.test", "Hello", "Body")
+        .await
+        .unwrap();
+    let closed_port = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let sent = deliver_due(db, &plain_mailer(closed_port), 10)
+        .await
+        .unwrap();
+    assert_eq!(sent, 0);
+    let row = email_outbox::Entity::find_by_id(id)
+        .one(db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.attempts, 1);
+    assert!(row.last_error.is_some());
+    assert!(row.next_attempt_at > Utc::now());
+
+    // Not due yet, so a working relay still has nothing to send.
+    let (port, inbox) = spawn_smtp_catcher().await;
+    let mailer = plain_mailer(port);
+    assert_eq!(deliver_due(db, &mailer, 10).await.unwrap(), 0);
+    let mut due: email_outbox::ActiveModel = row.into();
+    due.next_attempt_at = Set(Utc::now().into());
+    due.update(db).await.unwrap();
+    assert_eq!(deliver_due(db, &mailer, 10).await.unwrap(), 1);
+    assert_eq!(inbox.lock().unwrap().len(), 1);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn password_changes_need_the_current_password_and_meet_the_policy() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping password reset tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let policy = PasswordPolicy::new(12).with_denied(["Correct Horse Battery"]);
+    let auth = AuthConfig::new(AuthMode::Local, Some("test-secret".into()), 15)
+        .with_password_policy(policy);
+    let AppSchema(schema) = build_schema(ctx.db.clone(), Arc::new(auth));
+    let sales = CurrentUser {
+        user_id: ctx.seeded.user_email("sales@sme.test").unwrap().id,
+        org_id: ctx.seeded.organization.id,
+        roles: vec![UserRole::Sales],
+        scopes: None,
+    };
+    let change = |current: &str, password: &str, user: &CurrentUser| {
+        let request = Request::new(CHANGE)
+            .variables(Variables::from_json(
+                json!({ "current": current, "password": password }),
+            ))
+            .data(user.clone());
+        schema.execute(request)
+    };
+
+    let resp = change("wrong password", "a long enough secret", &sales).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let resp = change("salespass", "too short", &sales).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let resp = change("salespass", "correct horse battery", &sales).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let key = CurrentUser {
+        scopes: Some(vec![ApiScope::Admin]),
+        ..sales.clone()
+    };
+    let resp = change("salespass", "a long enough secret", &key).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+
+    let resp = change("salespass", "a long enough secret", &sales).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let resp = run(
+        &ctx,
+        LOGIN,
+        json!({ "email": "sales@sme.test", "password": "a long enough secret" }),
+        None,
+    )
+    .await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+    ctx.cleanup().await;
+}
@@ -2,8 +2,8 @@ mod common;
 
 use api::auth::{CurrentUser, UserRole};
 use async_graphql::{Request, Variables};
-use common::PgTestContext;
-use serde_json::json;
+use common::{error_code, run, user_with_role, PgTestContext};
+use serde_json::{json, Value};
 
 fn owner_user(ctx: &PgTestContext) -> CurrentUser {
     let owner = ctx
@@ -12,10 +12,81 @@ fn owner_user(ctx: &PgTestContext) -> CurrentUser {
         .expect("seeded owner user");
     CurrentUser {
         user_id: owner.id,
+        org_id: ctx.seeded.organization.id,
         roles: vec![UserRole::Owner, UserRole::Admin],
+        scopes: None,
     }
 }
 
+async fn data(ctx: &PgTestContext, query: &str, vars: Value, user: &CurrentUser) -> Value {
+    let resp = run(ctx, query, vars, Some(user)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    resp.data.into_json().unwrap()["crm"].clone()
+}
+
+/// Audit entries recorded for `entity_type`, newest first.
+async fn audit_entries(ctx: &PgTestContext, user: &CurrentUser, entity_type: &str) -> Vec<Value> {
+    let query = r#"
+        query Audit($type: AuditEntityType) {
+            crm { auditLog(entityType: $type, first: 200) { nodes { entityId operation changes } } }
+        }
+    "#;
+    data(ctx, query, json!({ "type": entity_type }), user).await["auditLog"]["nodes"]
+        .as_array()
+        .unwrap()
+        .clone()
+}
+
+/// `allowedFrom` of each stage that restricts it, by stage key.
+async fn allowed_from(ctx: &PgTestContext, user: &CurrentUser) -> Value {
+    let query =
+        r#"{ crm { pipelineStages(includeArchived: true) { key rules { allowedFrom } } } }"#;
+    data(ctx, query, json!({}), user).await["pipelineStages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|stage| !stage["rules"]["allowedFrom"].is_null())
+        .map(|stage| {
+            (
+                stage["key"].as_str().unwrap().to_string(),
+                stage["rules"]["allowedFrom"].clone(),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+async fn stage_keys(
+    ctx: &PgTestContext,
+    user: &CurrentUser,
+    include_archived: bool,
+) -> Vec<String> {
+    let query = r#"
+        query Stages($all: Boolean) { crm { pipelineStages(includeArchived: $all) { key } } }
+    "#;
+    data(ctx, query, json!({ "all": include_archived }), user).await["pipelineStages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|stage| stage["key"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn deal_in(ctx: &PgTestContext, stage: &str) -> uuid::Uuid {
+    ctx.seeded
+        .deals
+        .iter()
+        .find(|deal| deal.stage == stage)
+        .expect("seeded deal in stage")
+        .id
+}
+
+const MOVE: &str = r#"
+    mutation Move($id: ID!, $stage: DealStage!) {
+        crm { moveDealStage(id: $id, stage: $stage) { id stage } }
+    }
+"#;
+
 #[tokio::test]
 async fn pipeline_stages_return_defaults() {
     let Some(ctx) = PgTestContext::new_seeded().await else {
@@ -213,3 +284,1020 @@ async fn pipeline_report_returns_metrics() {
     );
     ctx.cleanup().await;
 }
+
+#[tokio::test]
+async fn admins_add_rename_and_reorder_stages() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let create = r#"
+        mutation Create($input: NewPipelineStageInput!) {
+            crm { createPipelineStage(input: $input) { key displayName probability } }
+        }
+    "#;
+    let input = json!({
+        "input": { "key": "demo", "displayName": "Demo", "probability": 40, "before": "PROPOSAL" }
+    });
+    let resp = run(&ctx, create, input.clone(), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let created = data(&ctx, create, input.clone(), &admin).await;
+    assert_eq!(
+        created["createPipelineStage"],
+        json!({ "key": "DEMO", "displayName": "Demo", "probability": 40 })
+    );
+    assert_eq!(
+        stage_keys(&ctx, &admin, false).await,
+        [
+            "NEW",
+            "QUALIFY",
+            "DEMO",
+            "PROPOSAL",
+            "NEGOTIATE",
+            "WON",
+            "LOST"
+        ]
+    );
+    let resp = run(&ctx, create, input, Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+    let bad_key = json!({ "input": { "key": "2nd", "displayName": "Second", "probability": 5 } });
+    let resp = run(&ctx, create, bad_key, Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let deal_id = deal_in(&ctx, "QUALIFY");
+    let moved = data(
+        &ctx,
+        MOVE,
+        json!({ "id": deal_id, "stage": "DEMO" }),
+        &sales,
+    )
+    .await;
+    assert_eq!(moved["moveDealStage"]["stage"], "DEMO");
+
+    let rename = r#"
+        mutation Rename { crm { renamePipelineStage(key: "DEMO", displayName: "Live demo") { displayName } } }
+    "#;
+    let renamed = data(&ctx, rename, json!({}), &admin).await;
+    assert_eq!(renamed["renamePipelineStage"]["displayName"], "Live demo");
+
+    let reorder = r#"
+        mutation Reorder($keys: [String!]!) { crm { reorderPipelineStages(keys: $keys) { key sortOrder } } }
+    "#;
+    let order = [
+        "NEW",
+        "DEMO",
+        "QUALIFY",
+        "PROPOSAL",
+        "NEGOTIATE",
+        "WON",
+        "LOST",
+    ];
+    let reordered = data(&ctx, reorder, json!({ "keys": order }), &admin).await;
+    let stages = reordered["reorderPipelineStages"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert_eq!(stages[1], json!({ "key": "DEMO", "sortOrder": 20 }));
+    assert_eq!(stage_keys(&ctx, &admin, false).await, order);
+    let resp = run(
+        &ctx,
+        reorder,
+        json!({ "keys": ["NEW", "DEMO"] }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let entries = audit_entries(&ctx, &admin, "STAGE").await;
+    assert!(entries
+        .iter()
+        .any(|entry| entry["operation"] == "CREATE" && entry["changes"]["key"]["new"] == "DEMO"));
+    assert!(entries
+        .iter()
+        .any(|entry| entry["changes"]["display_name"]
+            == json!({ "old": "Demo", "new": "Live demo" })));
+    assert!(entries
+        .iter()
+        .any(|entry| entry["changes"]["sort_order"] == json!({ "old": 30, "new": 20 })));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn archived_stages_leave_the_board_and_take_no_deals() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let archive = r#"
+        mutation Archive($key: String!) { crm { archivePipelineStage(key: $key) { key isArchived } } }
+    "#;
+    let resp = run(&ctx, archive, json!({ "key": "QUALIFY" }), Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    let create = r#"
+        mutation { crm { createPipelineStage(input: { key: "PILOT", displayName: "Pilot", probability: 50 }) { key } } }
+    "#;
+    data(&ctx, create, json!({}), &admin).await;
+    let set_rules = r#"
+        mutation { crm { setPipelineStageRules(key: "WON", rules: { allowedFrom: ["PILOT", "NEGOTIATE"] }) { key } } }
+    "#;
+    data(&ctx, set_rules, json!({}), &admin).await;
+    let archived = data(&ctx, archive, json!({ "key": "PILOT" }), &admin).await;
+    assert_eq!(
+        allowed_from(&ctx, &admin).await,
+        json!({ "WON": ["NEGOTIATE"] })
+    );
+    assert_eq!(archived["archivePipelineStage"]["isArchived"], true);
+    assert!(!stage_keys(&ctx, &admin, false)
+        .await
+        .contains(&"PILOT".to_string()));
+    assert_eq!(
+        stage_keys(&ctx, &admin, true).await.last().unwrap(),
+        "PILOT"
+    );
+
+    let board = r#"{ crm { pipelineBoard { columns { stage { key } } } } }"#;
+    let columns = data(&ctx, board, json!({}), &admin).await["pipelineBoard"]["columns"].clone();
+    assert!(columns
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|column| column["stage"]["key"] != "PILOT"));
+
+    let deal_id = deal_in(&ctx, "NEW");
+    let resp = run(
+        &ctx,
+        MOVE,
+        json!({ "id": deal_id, "stage": "PILOT" }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let resp = run(
+        &ctx,
+        MOVE,
+        json!({ "id": deal_id, "stage": "NOWHERE" }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let restore = r#"mutation { crm { restorePipelineStage(key: "PILOT") { id isArchived } } }"#;
+    let restored = data(&ctx, restore, json!({}), &admin).await;
+    let pilot = restored["restorePipelineStage"]["id"].clone();
+    let archived_flags: Vec<Value> = audit_entries(&ctx, &admin, "STAGE")
+        .await
+        .into_iter()
+        .filter(|entry| entry["entityId"] == pilot && entry["operation"] == "UPDATE")
+        .map(|entry| entry["changes"]["is_archived"].clone())
+        .filter(|change| !change.is_null())
+        .collect();
+    assert_eq!(
+        archived_flags,
+        [
+            json!({ "old": true, "new": false }),
+            json!({ "old": false, "new": true })
+        ]
+    );
+    let moved = data(
+        &ctx,
+        MOVE,
+        json!({ "id": deal_id, "stage": "PILOT" }),
+        &admin,
+    )
+    .await;
+    assert_eq!(moved["moveDealStage"]["stage"], "PILOT");
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn merging_a_stage_moves_its_deals_and_archives_it() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let qualified: Vec<String> = ctx
+        .seeded
+        .deals
+        .iter()
+        .filter(|deal| deal.stage == "QUALIFY")
+        .map(|deal| deal.id.to_string())
+        .collect();
+    assert!(!qualified.is_empty());
+    // Leaves a NEW -> QUALIFY row that the merge must not rewrite.
+    let deal_id = deal_in(&ctx, "NEW");
+    data(
+        &ctx,
+        MOVE,
+        json!({ "id": deal_id, "stage": "QUALIFY" }),
+        &admin,
+    )
+    .await;
+
+    let set_rules = r#"
+        mutation Rules($key: String!, $from: [String!]) {
+            crm { setPipelineStageRules(key: $key, rules: { allowedFrom: $from }) { key } }
+        }
+    "#;
+    for (key, from) in [
+        ("NEGOTIATE", json!(["QUALIFY", "PROPOSAL"])),
+        ("WON", json!(["QUALIFY", "NEGOTIATE"])),
+    ] {
+        data(&ctx, set_rules, json!({ "key": key, "from": from }), &admin).await;
+    }
+
+    let merge = r#"
+        mutation { crm { mergePipelineStages(source: "QUALIFY", target: "PROPOSAL") { key } } }
+    "#;
+    let merged = data(&ctx, merge, json!({}), &admin).await;
+    assert_eq!(merged["mergePipelineStages"]["key"], "PROPOSAL");
+    assert!(!stage_keys(&ctx, &admin, false)
+        .await
+        .contains(&"QUALIFY".to_string()));
+    assert!(stage_keys(&ctx, &admin, true)
+        .await
+        .contains(&"QUALIFY".to_string()));
+    assert!(audit_entries(&ctx, &admin, "STAGE")
+        .await
+        .iter()
+        .any(|entry| entry["changes"]["is_archived"] == json!({ "old": false, "new": true })));
+    assert_eq!(
+        allowed_from(&ctx, &admin).await,
+        json!({ "NEGOTIATE": ["PROPOSAL"], "WON": ["PROPOSAL", "NEGOTIATE"] })
+    );
+
+    let deal = r#"query Deal($id: ID!) { crm { deal(id: $id) { stage } } }"#;
+    for id in qualified.iter().cloned().chain([deal_id.to_string()]) {
+        let found = data(&ctx, deal, json!({ "id": id }), &admin).await;
+        assert_eq!(found["deal"]["stage"], "PROPOSAL");
+    }
+    let history = r#"
+        query History($id: ID!) { crm { dealStageHistory(dealId: $id) { nodes { fromStage toStage } } } }
+    "#;
+    let rows = data(&ctx, history, json!({ "id": deal_id }), &admin).await;
+    assert_eq!(
+        rows["dealStageHistory"]["nodes"],
+        json!([{ "fromStage": "NEW", "toStage": "QUALIFY" }])
+    );
+
+    let merge_self = r#"
+        mutation { crm { mergePipelineStages(source: "WON", target: "WON") { key } } }
+    "#;
+    let resp = run(&ctx, merge_self, json!({}), Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    ctx.cleanup().await;
+}
+
+const RENEWALS: &str = r#"
+    mutation Renewals {
+        crm {
+            createPipeline(input: {
+                name: "Renewals",
+                stages: [
+                    { key: "RENEWAL_DUE", displayName: "Due", probability: 30 },
+                    { key: "RENEWAL_COMMIT", displayName: "Committed", probability: 80 },
+                    { key: "RENEWED", displayName: "Renewed", probability: 100, isWon: true },
+                    { key: "CHURNED", displayName: "Churned", probability: 0, isLost: true },
+                ]
+            }) { id name isDefault }
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn pipelines_keep_their_own_stages_and_deals() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let resp = run(&ctx, RENEWALS, json!({}), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let created = data(&ctx, RENEWALS, json!({}), &admin).await["createPipeline"].clone();
+    assert_eq!(created["isDefault"], false);
+    let renewals = created["id"].clone();
+    let resp = run(&ctx, RENEWALS, json!({}), Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    let pipelines = r#"{ crm { pipelines { name isDefault } } }"#;
+    assert_eq!(
+        data(&ctx, pipelines, json!({}), &admin).await["pipelines"],
+        json!([
+            { "name": "Sales", "isDefault": true },
+            { "name": "Renewals", "isDefault": false },
+        ])
+    );
+    let stages = r#"
+        query Stages($pipelineId: ID) { crm { pipelineStages(pipelineId: $pipelineId) { key } } }
+    "#;
+    let keys = data(&ctx, stages, json!({ "pipelineId": renewals }), &admin).await;
+    assert_eq!(
+        keys["pipelineStages"],
+        json!([
+            { "key": "RENEWAL_DUE" },
+            { "key": "RENEWAL_COMMIT" },
+            { "key": "RENEWED" },
+            { "key": "CHURNED" },
+        ])
+    );
+    assert_eq!(stage_keys(&ctx, &admin, false).await.len(), 6);
+
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let create = r#"
+        mutation Create($companyId: ID!, $pipelineId: ID) {
+            crm {
+                createDeal(input: { title: "ACME renewal", companyId: $companyId, pipelineId: $pipelineId }) {
+                    id pipelineId stage
+                }
+            }
+        }
+    "#;
+    let vars = json!({ "companyId": acme.id, "pipelineId": renewals });
+    let deal = data(&ctx, create, vars, &sales).await["createDeal"].clone();
+    assert_eq!(deal["pipelineId"], renewals);
+    assert_eq!(deal["stage"], "RENEWAL_DUE");
+    let resp = run(
+        &ctx,
+        MOVE,
+        json!({ "id": deal["id"], "stage": "WON" }),
+        Some(&sales),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let board = r#"
+        query Board($pipelineId: ID) {
+            crm { pipelineBoard(pipelineId: $pipelineId) { totalCount columns { stage { key } totalCount } } }
+        }
+    "#;
+    let renewal_board = data(&ctx, board, json!({ "pipelineId": renewals }), &admin).await;
+    assert_eq!(renewal_board["pipelineBoard"]["totalCount"], 1);
+    assert_eq!(
+        renewal_board["pipelineBoard"]["columns"][0],
+        json!({ "stage": { "key": "RENEWAL_DUE" }, "totalCount": 1 })
+    );
+    let sales_board = data(&ctx, board, json!({}), &admin).await;
+    assert_eq!(sales_board["pipelineBoard"]["totalCount"], 8);
+    let filtered = r#"
+        query Deals($pipelineId: ID) { crm { deals(filter: { pipelineId: $pipelineId }) { totalCount } } }
+    "#;
+    let deals = data(&ctx, filtered, json!({ "pipelineId": renewals }), &admin).await;
+    assert_eq!(deals["deals"]["totalCount"], 1);
+
+    let rename = r#"
+        mutation Rename($id: ID!) { crm { renamePipeline(id: $id, name: "Renewals & upsells") { name } } }
+    "#;
+    let renamed = data(&ctx, rename, json!({ "id": renewals }), &admin).await;
+    assert_eq!(renamed["renamePipeline"]["name"], "Renewals & upsells");
+    let entries: Vec<Value> = audit_entries(&ctx, &admin, "PIPELINE")
+        .await
+        .into_iter()
+        .map(|entry| {
+            json!([
+                entry["entityId"],
+                entry["operation"],
+                entry["changes"]["name"]
+            ])
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            json!([renewals, "UPDATE", { "old": "Renewals", "new": "Renewals & upsells" }]),
+            json!([renewals, "CREATE", { "old": null, "new": "Renewals" }]),
+        ]
+    );
+    let renewal_stages = audit_entries(&ctx, &admin, "STAGE")
+        .await
+        .into_iter()
+        .filter(|entry| entry["changes"]["pipeline_id"]["new"] == renewals)
+        .count();
+    assert_eq!(renewal_stages, 4);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn moving_a_deal_between_pipelines_maps_its_stage() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let renewals = data(&ctx, RENEWALS, json!({}), &admin).await["createPipeline"]["id"].clone();
+    let transfer = r#"
+        mutation Transfer($id: ID!, $pipelineId: ID!, $stage: DealStage) {
+            crm {
+                moveDealToPipeline(id: $id, pipelineId: $pipelineId, stage: $stage, note: "handed to renewals") {
+                    pipelineId stage
+                }
+            }
+        }
+    "#;
+    let cases = [
+        ("NEGOTIATE", "RENEWAL_COMMIT"),
+        ("QUALIFY", "RENEWAL_DUE"),
+        ("WON", "RENEWED"),
+        ("LOST", "CHURNED"),
+    ];
+    for (from, to) in cases {
+        let deal_id = deal_in(&ctx, from);
+        let vars = json!({ "id": deal_id, "pipelineId": renewals });
+        let moved = data(&ctx, transfer, vars, &admin).await["moveDealToPipeline"].clone();
+        assert_eq!(
+            moved,
+            json!({ "pipelineId": renewals, "stage": to }),
+            "{from}"
+        );
+    }
+
+    let deal_id = deal_in(&ctx, "NEGOTIATE");
+    let history = r#"
+        query History($id: ID!) {
+            crm { dealStageHistory(dealId: $id) { nodes { fromStage toStage note } } }
+        }
+    "#;
+    let rows = data(&ctx, history, json!({ "id": deal_id }), &admin).await;
+    assert_eq!(
+        rows["dealStageHistory"]["nodes"][0],
+        json!({ "fromStage": "NEGOTIATE", "toStage": "RENEWAL_COMMIT", "note": "handed to renewals" })
+    );
+
+    // An explicit stage must belong to the target pipeline.
+    let deal_id = deal_in(&ctx, "NEW");
+    let vars = json!({ "id": deal_id, "pipelineId": renewals, "stage": "PROPOSAL" });
+    let resp = run(&ctx, transfer, vars, Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let vars = json!({ "id": deal_id, "pipelineId": renewals, "stage": "RENEWED" });
+    let moved = data(&ctx, transfer, vars, &admin).await;
+    assert_eq!(moved["moveDealToPipeline"]["stage"], "RENEWED");
+    let resp = run(
+        &ctx,
+        transfer,
+        json!({ "id": deal_id, "pipelineId": renewals }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    let report = r#"
+        query Report($pipelineId: ID) {
+            crm {
+                pipelineReport(range: { from: "2025-01-01", to: "2025-12-31" }, includeLost: true, pipelineId: $pipelineId) {
+                    stageTotals { stage { key } count }
+                }
+            }
+        }
+    "#;
+    let totals = data(&ctx, report, json!({ "pipelineId": renewals }), &admin).await;
+    let keys: Vec<_> = totals["pipelineReport"]["stageTotals"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["stage"]["key"].as_str().unwrap().to_string())
+        .collect();
+    assert!(
+        keys.iter()
+            .all(|key| key.starts_with("RENEW") || key == "CHURNED"),
+        "{keys:?}"
+    );
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn pipelines_each_close_deals_in_their_own_won_and_lost() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let partners = r#"
+        mutation {
+            crm {
+                createPipeline(input: {
+                    name: "Partners",
+                    stages: [
+                        { key: "PROSPECT", displayName: "Prospect", probability: 20 },
+                        { key: "WON", displayName: "Signed", probability: 100, isWon: true },
+                        { key: "LOST", displayName: "Dropped", probability: 0, isLost: true },
+                    ]
+                }) { id }
+            }
+        }
+    "#;
+    let partners = data(&ctx, partners, json!({}), &admin).await["createPipeline"]["id"].clone();
+    let resp = run(
+        &ctx,
+        r#"mutation Add($pipelineId: ID) {
+            crm { createPipelineStage(input: { pipelineId: $pipelineId, key: "WON", displayName: "Again", probability: 100 }) { key } }
+        }"#,
+        json!({ "pipelineId": partners }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    // Renaming a shared key only touches the named pipeline's stage.
+    let rename = r#"
+        mutation Rename($pipelineId: ID) {
+            crm { renamePipelineStage(pipelineId: $pipelineId, key: "WON", displayName: "Partnered") { displayName } }
+        }
+    "#;
+    data(&ctx, rename, json!({ "pipelineId": partners }), &admin).await;
+    let labels = r#"
+        query Stages($pipelineId: ID) { crm { pipelineStages(pipelineId: $pipelineId) { key displayName } } }
+    "#;
+    let won_label = |stages: &Value| {
+        stages["pipelineStages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|stage| stage["key"] == "WON")
+            .unwrap()["displayName"]
+            .clone()
+    };
+    let sales_stages = data(&ctx, labels, json!({}), &admin).await;
+    assert_ne!(won_label(&sales_stages), "Partnered");
+    let partner_stages = data(&ctx, labels, json!({ "pipelineId": partners }), &admin).await;
+    assert_eq!(won_label(&partner_stages), "Partnered");
+
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let create = r#"
+        mutation Create($companyId: ID!, $pipelineId: ID) {
+            crm { createDeal(input: { title: "ACME reseller", companyId: $companyId, pipelineId: $pipelineId, closeDate: "2026-03-31" }) { id } }
+        }
+    "#;
+    let vars = json!({ "companyId": acme.id, "pipelineId": partners });
+    let deal_id = data(&ctx, create, vars, &admin).await["createDeal"]["id"].clone();
+    let moved = data(&ctx, MOVE, json!({ "id": deal_id, "stage": "WON" }), &admin).await;
+    assert_eq!(moved["moveDealStage"]["stage"], "WON");
+    let history = r#"
+        query History($id: ID!) {
+            crm { dealStageHistory(dealId: $id, first: 1) { nodes { fromPipelineId fromStage toPipelineId toStage } } }
+        }
+    "#;
+    let latest = data(&ctx, history, json!({ "id": deal_id }), &admin).await;
+    assert_eq!(
+        latest["dealStageHistory"]["nodes"][0],
+        json!({
+            "fromPipelineId": partners, "fromStage": "PROSPECT",
+            "toPipelineId": partners, "toStage": "WON",
+        })
+    );
+
+    let sales_won = deal_in(&ctx, "WON");
+    let transfer = r#"
+        mutation Transfer($id: ID!, $pipelineId: ID!) {
+            crm { moveDealToPipeline(id: $id, pipelineId: $pipelineId) { pipelineId stage } }
+        }
+    "#;
+    let vars = json!({ "id": sales_won, "pipelineId": partners });
+    let moved = data(&ctx, transfer, vars, &admin).await;
+    assert_eq!(
+        moved["moveDealToPipeline"],
+        json!({ "pipelineId": partners, "stage": "WON" })
+    );
+
+    let board = r#"
+        query Board($pipelineId: ID) {
+            crm { pipelineBoard(pipelineId: $pipelineId) { totalCount columns { stage { key } totalCount } } }
+        }
+    "#;
+    let partner_board = data(&ctx, board, json!({ "pipelineId": partners }), &admin).await;
+    assert_eq!(partner_board["pipelineBoard"]["totalCount"], 2);
+    assert_eq!(
+        partner_board["pipelineBoard"]["columns"],
+        json!([
+            { "stage": { "key": "PROSPECT" }, "totalCount": 0 },
+            { "stage": { "key": "WON" }, "totalCount": 2 },
+            { "stage": { "key": "LOST" }, "totalCount": 0 },
+        ])
+    );
+    let sales_board = data(&ctx, board, json!({}), &admin).await;
+    assert_eq!(sales_board["pipelineBoard"]["totalCount"], 7);
+
+    let report = r#"
+        query Report($pipelineId: ID) {
+            crm {
+                pipelineReport(range: { from: "2025-01-01", to: "2026-12-31" }, includeLost: true, pipelineId: $pipelineId) {
+                    stageTotals { stage { key } count }
+                }
+                winLossReport(range: { from: "2025-01-01", to: "2026-12-31" }, groupBy: REASON, pipelineId: $pipelineId) {
+                    won lost
+                }
+            }
+        }
+    "#;
+    let partner_report = data(&ctx, report, json!({ "pipelineId": partners }), &admin).await;
+    let won_total = partner_report["pipelineReport"]["stageTotals"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|row| row["stage"]["key"] == "WON")
+        .unwrap()["count"]
+        .clone();
+    assert_eq!(won_total, 2);
+    assert_eq!(
+        partner_report["winLossReport"],
+        json!({ "won": 2, "lost": 0 })
+    );
+    // Without a pipeline the report spans them all, counting each closed deal once.
+    let all_report = data(&ctx, report, json!({}), &admin).await;
+    assert_eq!(all_report["winLossReport"], json!({ "won": 3, "lost": 1 }));
+    ctx.cleanup().await;
+}
+
+fn extensions(resp: &async_graphql::Response) -> Value {
+    let err = resp.errors.first().expect("an error");
+    serde_json::to_value(err.extensions.as_ref().expect("extensions")).unwrap()
+}
+
+#[tokio::test]
+async fn stage_rules_guard_moves_into_a_stage() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let set_rules = r#"
+        mutation Rules($key: String!, $rules: StageRulesInput!) {
+            crm { setPipelineStageRules(key: $key, rules: $rules) { key rules { allowedFrom requiredFields requiresNote } } }
+        }
+    "#;
+    let proposal = json!({
+        "key": "PROPOSAL",
+        "rules": { "allowedFrom": ["qualify", "NEGOTIATE"], "requiredFields": ["AMOUNT_CENTS", "CLOSE_DATE"] }
+    });
+    let resp = run(&ctx, set_rules, proposal.clone(), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let updated = data(&ctx, set_rules, proposal, &admin).await;
+    assert_eq!(
+        updated["setPipelineStageRules"]["rules"],
+        json!({
+            "allowedFrom": ["QUALIFY", "NEGOTIATE"],
+            "requiredFields": ["AMOUNT_CENTS", "CLOSE_DATE"],
+            "requiresNote": false,
+        })
+    );
+    let won = json!({ "key": "WON", "rules": { "allowedFrom": ["NEGOTIATE", "PROPOSAL"] } });
+    data(&ctx, set_rules, won, &admin).await;
+    let stray = json!({ "key": "WON", "rules": { "allowedFrom": ["NOWHERE"] } });
+    let resp = run(&ctx, set_rules, stray, Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let proposal_rules = audit_entries(&ctx, &admin, "STAGE")
+        .await
+        .into_iter()
+        .find(|entry| entry["changes"]["allowed_from"]["new"] == json!(["QUALIFY", "NEGOTIATE"]))
+        .expect("rules audited");
+    assert_eq!(
+        proposal_rules["changes"],
+        json!({
+            "allowed_from": { "old": null, "new": ["QUALIFY", "NEGOTIATE"] },
+            "required_fields": { "old": [], "new": ["AMOUNT_CENTS", "CLOSE_DATE"] },
+        })
+    );
+
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let create = r#"
+        mutation Create($companyId: ID!, $stage: DealStage) {
+            crm { createDeal(input: { title: "Rules", companyId: $companyId, stage: $stage }) { id } }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        create,
+        json!({ "companyId": acme.id, "stage": "PROPOSAL" }),
+        Some(&sales),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("STAGE_RULE_VIOLATION"));
+    let created = data(&ctx, create, json!({ "companyId": acme.id }), &sales).await;
+    let deal_id = created["createDeal"]["id"].clone();
+
+    let to_proposal = json!({ "id": deal_id, "stage": "PROPOSAL" });
+    let resp = run(&ctx, MOVE, to_proposal.clone(), Some(&sales)).await;
+    assert_eq!(
+        extensions(&resp),
+        json!({
+            "code": "STAGE_RULE_VIOLATION",
+            "stage": "PROPOSAL",
+            "fromStage": "NEW",
+            "disallowedFrom": true,
+            "missingFields": ["AMOUNT_CENTS", "CLOSE_DATE"],
+            "noteRequired": false,
+        })
+    );
+    data(
+        &ctx,
+        MOVE,
+        json!({ "id": deal_id, "stage": "QUALIFY" }),
+        &sales,
+    )
+    .await;
+    let resp = run(&ctx, MOVE, to_proposal.clone(), Some(&sales)).await;
+    let ext = extensions(&resp);
+    assert_eq!(ext["disallowedFrom"], false);
+    assert_eq!(ext["missingFields"], json!(["AMOUNT_CENTS", "CLOSE_DATE"]));
+
+    let fill = r#"
+        mutation Fill($id: ID!) {
+            crm { updateDeal(input: { id: $id, amountCents: 90000, closeDate: "2025-06-30" }) { id } }
+        }
+    "#;
+    data(&ctx, fill, json!({ "id": deal_id }), &sales).await;
+    let moved = data(&ctx, MOVE, to_proposal, &sales).await;
+    assert_eq!(moved["moveDealStage"]["stage"], "PROPOSAL");
+
+    // Lost stages ask for a note out of the box, and WON no longer takes lost deals.
+    let to_lost = json!({ "id": deal_id, "stage": "LOST" });
+    let resp = run(&ctx, MOVE, to_lost, Some(&sales)).await;
+    assert_eq!(extensions(&resp)["noteRequired"], true);
+    let lose = r#"
+        mutation Lose($id: ID!) {
+            crm { moveDealStage(id: $id, stage: LOST, note: "Went with a competitor") { stage } }
+        }
+    "#;
+    data(&ctx, lose, json!({ "id": deal_id }), &sales).await;
+    let resp = run(
+        &ctx,
+        MOVE,
+        json!({ "id": deal_id, "stage": "WON" }),
+        Some(&sales),
+    )
+    .await;
+    let ext = extensions(&resp);
+    assert_eq!(ext["code"], "STAGE_RULE_VIOLATION");
+    assert_eq!(ext["disallowedFrom"], true);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn closed_deals_record_a_reason_and_feed_the_win_loss_report() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let create_reason = r#"
+        mutation Reason($outcome: DealOutcome!, $label: String!) {
+            crm { createDealReason(outcome: $outcome, label: $label) { id outcome label isArchived } }
+        }
+    "#;
+    let price = json!({ "outcome": "LOST", "label": " Price " });
+    let resp = run(&ctx, create_reason, price.clone(), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let created = data(&ctx, create_reason, price.clone(), &admin).await;
+    assert_eq!(created["createDealReason"]["label"], "Price");
+    let price_id = created["createDealReason"]["id"].clone();
+    let resp = run(&ctx, create_reason, price, Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+    let champion = json!({ "outcome": "WON", "label": "Strong champion" });
+    let champion_id =
+        data(&ctx, create_reason, champion, &admin).await["createDealReason"]["id"].clone();
+    let reasons = data(
+        &ctx,
+        r#"query { crm { dealReasons(outcome: LOST) { label } } }"#,
+        json!({}),
+        &sales,
+    )
+    .await;
+    assert_eq!(reasons["dealReasons"], json!([{ "label": "Price" }]));
+    let update_reason = r#"
+        mutation Update($id: ID!) {
+            crm { updateDealReason(id: $id, label: "Champion", isArchived: true) { label isArchived } }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        update_reason,
+        json!({ "id": champion_id }),
+        Some(&sales),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let updated = data(&ctx, update_reason, json!({ "id": champion_id }), &admin).await;
+    assert_eq!(
+        updated["updateDealReason"],
+        json!({ "label": "Champion", "isArchived": true })
+    );
+    let entries: Vec<Value> = audit_entries(&ctx, &admin, "DEAL_REASON")
+        .await
+        .into_iter()
+        .filter(|entry| entry["entityId"] == champion_id)
+        .map(|entry| json!([entry["operation"], entry["changes"]]))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            json!(["UPDATE", {
+                "label": { "old": "Strong champion", "new": "Champion" },
+                "is_archived": { "old": false, "new": true },
+            }]),
+            json!(["CREATE", {
+                "outcome": { "old": null, "new": "won" },
+                "label": { "old": null, "new": "Strong champion" },
+                "is_archived": { "old": null, "new": false },
+            }]),
+        ]
+    );
+
+    let close = r#"
+        mutation Close($id: ID!, $stage: DealStage!, $reasonId: ID, $competitor: String) {
+            crm {
+                moveDealStage(id: $id, stage: $stage, reasonId: $reasonId, competitor: $competitor) {
+                    stage reasonId competitor reason { label }
+                }
+            }
+        }
+    "#;
+    let deal_id = deal_in(&ctx, "NEGOTIATE").to_string();
+    let mismatched = json!({ "id": deal_id, "stage": "LOST", "reasonId": champion_id });
+    let resp = run(&ctx, close, mismatched, Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let open = json!({ "id": deal_id, "stage": "PROPOSAL", "competitor": "Globex" });
+    let resp = run(&ctx, close, open, Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+
+    // The reason stands in for the note LOST otherwise asks for.
+    let lost = json!({
+        "id": deal_id, "stage": "LOST", "reasonId": price_id, "competitor": " Globex "
+    });
+    let moved = data(&ctx, close, lost.clone(), &sales).await;
+    assert_eq!(
+        moved["moveDealStage"],
+        json!({
+            "stage": "LOST",
+            "reasonId": price_id,
+            "competitor": "Globex",
+            "reason": { "label": "Price" },
+        })
+    );
+    let history = r#"
+        query History($id: ID!) {
+            crm { dealStageHistory(dealId: $id, first: 1) { nodes { toStage reasonId competitor } } }
+        }
+    "#;
+    let latest = data(&ctx, history, json!({ "id": deal_id }), &sales).await;
+    assert_eq!(
+        latest["dealStageHistory"]["nodes"][0],
+        json!({ "toStage": "LOST", "reasonId": price_id, "competitor": "Globex" })
+    );
+    let reopened = data(
+        &ctx,
+        close,
+        json!({ "id": deal_id, "stage": "NEGOTIATE" }),
+        &sales,
+    )
+    .await;
+    assert_eq!(reopened["moveDealStage"]["reasonId"], Value::Null);
+    assert_eq!(reopened["moveDealStage"]["competitor"], Value::Null);
+    data(&ctx, close, lost, &sales).await;
+
+    // Seeded closed deals: FossRust Expansion and Quick Win won, Stalled Trial lost, none
+    // with a reason.
+    let report = r#"
+        query Report($group: WinLossGroup!) {
+            crm {
+                winLossReport(range: { from: "2025-01-01", to: "2025-12-31" }, groupBy: $group) {
+                    won lost wonAmountCents lostAmountCents winRate
+                    rows { key label won lost wonAmountCents lostAmountCents winRate }
+                }
+            }
+        }
+    "#;
+    let by_reason = data(&ctx, report, json!({ "group": "REASON" }), &admin).await;
+    let by_reason = &by_reason["winLossReport"];
+    assert_eq!(by_reason["won"], 2);
+    assert_eq!(by_reason["lost"], 2);
+    assert_eq!(by_reason["wonAmountCents"], 135_000);
+    assert_eq!(by_reason["winRate"], 0.5);
+    let rows = by_reason["rows"].as_array().unwrap();
+    let price_row = rows.iter().find(|row| row["key"] == price_id).unwrap();
+    assert_eq!(price_row["label"], "Price");
+    assert_eq!(price_row["lost"], 1);
+    assert_eq!(price_row["lostAmountCents"], 60_000);
+    assert_eq!(price_row["winRate"], 0.0);
+    let unexplained = rows.iter().find(|row| row["key"].is_null()).unwrap();
+    assert_eq!(unexplained["label"], "No reason");
+    assert_eq!(unexplained["won"], 2);
+
+    let acme = ctx.seeded.company_named("ACME, Inc.").unwrap();
+    let by_company = data(&ctx, report, json!({ "group": "COMPANY" }), &admin).await;
+    let acme_row = by_company["winLossReport"]["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|row| row["key"] == acme.id.to_string())
+        .cloned()
+        .unwrap();
+    assert_eq!(acme_row["label"], "ACME, Inc.");
+    assert_eq!(acme_row["won"], 1);
+    assert_eq!(acme_row["lost"], 1);
+
+    let by_owner = data(&ctx, report, json!({ "group": "OWNER" }), &admin).await;
+    let owners = by_owner["winLossReport"]["rows"].as_array().unwrap();
+    let sales_row = owners
+        .iter()
+        .find(|row| row["key"] == sales.user_id.to_string())
+        .unwrap();
+    assert_eq!(sales_row["won"], 1);
+    assert_eq!(sales_row["lost"], 2);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn admins_update_stage_metadata_and_wip_limits() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping pipeline tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = owner_user(&ctx);
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let update = r#"
+        mutation Update($input: UpdatePipelineStageInput!) {
+            crm { updatePipelineStage(input: $input) { key displayName probability isWon isLost wipLimit } }
+        }
+    "#;
+    let qualify = json!({ "input": { "key": "QUALIFY", "probability": 30, "wipLimit": 1 } });
+    let resp = run(&ctx, update, qualify.clone(), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let updated = data(&ctx, update, qualify, &admin).await;
+    assert_eq!(
+        updated["updatePipelineStage"],
+        json!({
+            "key": "QUALIFY",
+            "displayName": "Qualify",
+            "probability": 30,
+            "isWon": false,
+            "isLost": false,
+            "wipLimit": 1,
+        })
+    );
+    let audited = audit_entries(&ctx, &admin, "STAGE").await;
+    assert_eq!(audited.len(), 1);
+    assert_eq!(audited[0]["operation"], "UPDATE");
+    assert_eq!(
+        audited[0]["changes"],
+        json!({
+            "probability": { "old": 25, "new": 30 },
+            "wip_limit": { "old": null, "new": 1 },
+        })
+    );
+    for invalid in [
+        json!({ "key": "QUALIFY", "wipLimit": 0 }),
+        json!({ "key": "QUALIFY", "probability": 101 }),
+        json!({ "key": "WON", "isLost": true }),
+    ] {
+        let resp = run(&ctx, update, json!({ "input": invalid }), Some(&admin)).await;
+        assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    }
+    let resp = run(
+        &ctx,
+        update,
+        json!({ "input": { "key": "NOPE" } }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+
+    // QUALIFY holds two seeded deals, neither at FossRust; the limit counts them anyway.
+    let fossrust = ctx.seeded.company_named("FossRust Labs").unwrap();
+    let board = r#"
+        query Board($companyId: ID) {
+            crm { pipelineBoard(firstPerStage: 0, companyId: $companyId) { columns { stage { key wipLimit } totalCount wipExceeded } } }
+        }
+    "#;
+    let columns = data(&ctx, board, json!({ "companyId": fossrust.id }), &sales).await
+        ["pipelineBoard"]["columns"]
+        .clone();
+    let qualify = columns
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|col| col["stage"]["key"] == "QUALIFY")
+        .cloned()
+        .unwrap();
+    assert_eq!(qualify["totalCount"], 0);
+    assert_eq!(qualify["stage"]["wipLimit"], 1);
+    assert_eq!(qualify["wipExceeded"], true);
+    assert!(columns
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|col| col["stage"]["key"] != "QUALIFY")
+        .all(|col| col["wipExceeded"] == false));
+
+    let cleared =
+        json!({ "input": { "key": "QUALIFY", "wipLimit": null, "displayName": "Qualified" } });
+    let updated = data(&ctx, update, cleared, &admin).await;
+    assert_eq!(updated["updatePipelineStage"]["wipLimit"], Value::Null);
+    assert_eq!(updated["updatePipelineStage"]["displayName"], "Qualified");
+    assert_eq!(updated["updatePipelineStage"]["probability"], 30);
+    ctx.cleanup().await;
+}
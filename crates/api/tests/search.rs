@@ -12,7 +12,9 @@ fn owner_user(ctx: &PgTestContext) -> CurrentUser {
         .expect("seeded owner user");
     CurrentUser {
         user_id: owner.id,
+        org_id: ctx.seeded.organization.id,
         roles: vec![UserRole::Owner, UserRole::Admin],
+        scopes: None,
     }
 }
 
@@ -26,9 +28,11 @@ async fn search_prefers_companies_and_contacts() {
         query Search($term: String!) {
             crm {
                 search(q: $term, first: 5) {
-                    kind
-                    title
-                    subtitle
+                    nodes {
+                        kind
+                        title
+                        subtitle
+                    }
                 }
             }
         }
@@ -39,7 +43,7 @@ async fn search_prefers_companies_and_contacts() {
         .execute(Request::new(query).variables(vars).data(owner_user(&ctx)))
         .await;
     assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
-    let hits = resp.data.into_json().unwrap()["crm"]["search"]
+    let hits = resp.data.into_json().unwrap()["crm"]["search"]["nodes"]
         .as_array()
         .cloned()
         .unwrap_or_default();
@@ -59,7 +63,7 @@ async fn search_handles_trgm_typos() {
         query Search($term: String!) {
             crm {
                 search(q: $term, first: 5) {
-                    title
+                    nodes { title }
                 }
             }
         }
@@ -70,7 +74,7 @@ async fn search_handles_trgm_typos() {
         .execute(Request::new(query).variables(vars).data(owner_user(&ctx)))
         .await;
     assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
-    let hits = resp.data.into_json().unwrap()["crm"]["search"]
+    let hits = resp.data.into_json().unwrap()["crm"]["search"]["nodes"]
         .as_array()
         .cloned()
         .unwrap_or_default();
@@ -88,7 +92,7 @@ async fn search_enforces_limit() {
         query Search($term: String!) {
             crm {
                 search(q: $term, first: 200) {
-                    title
+                    nodes { title }
                 }
             }
         }
@@ -113,3 +117,49 @@ async fn search_enforces_limit() {
     }));
     ctx.cleanup().await;
 }
+
+#[tokio::test]
+async fn search_pages_with_cursors() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping Postgres search tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let query = r#"
+        query Search($term: String!, $after: String) {
+            crm {
+                search(q: $term, first: 2, after: $after) {
+                    totalCount
+                    pageInfo { hasNextPage endCursor }
+                    nodes { id }
+                }
+            }
+        }
+    "#;
+    let mut after = serde_json::Value::Null;
+    let mut ids = Vec::new();
+    let mut totals = Vec::new();
+    loop {
+        let vars = Variables::from_json(json!({ "term": "acme", "after": after }));
+        let resp = ctx
+            .schema
+            .execute(Request::new(query).variables(vars).data(owner_user(&ctx)))
+            .await;
+        assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+        let conn = resp.data.into_json().unwrap()["crm"]["search"].clone();
+        totals.push(conn["totalCount"].as_u64().expect("total count") as usize);
+        for node in conn["nodes"].as_array().unwrap() {
+            ids.push(node["id"].as_str().unwrap().to_string());
+        }
+        if conn["pageInfo"]["hasNextPage"] != true {
+            break;
+        }
+        after = conn["pageInfo"]["endCursor"].clone();
+    }
+    let total = totals[0];
+    assert!(totals.iter().all(|t| *t == total));
+    assert!(total > 2, "expected more than one page of hits");
+    assert_eq!(ids.len(), total);
+    let unique: std::collections::HashSet<_> = ids.iter().collect();
+    assert_eq!(unique.len(), ids.len());
+    ctx.cleanup().await;
+}
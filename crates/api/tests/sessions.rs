@@ -0,0 +1,70 @@
+mod common;
+
+use api::auth::{AuthConfig, AuthMode};
+use api::session::{live_sessions, revoke_session, start_session, touch_session};
+use chrono::{Duration, Utc};
+use common::PgTestContext;
+use entity::user_session;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+#[tokio::test]
+async fn sessions_expire_when_idle_or_past_their_lifetime() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping sessions tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let db = ctx.db.as_ref();
+    let config = AuthConfig::new(AuthMode::Local, Some("s".into()), 15).with_session_max_age(60);
+    let user_id = ctx.seeded.user_email("sales@sme.test").unwrap().id;
+    let other_id = ctx.seeded.user_email("admin@sme.test").unwrap().id;
+    let org_id = ctx.seeded.organization.id;
+
+    let idle = start_session(db, user_id, org_id, Some("laptop"), &config)
+        .await
+        .unwrap();
+    assert!(touch_session(db, idle.id, user_id, org_id, &config)
+        .await
+        .unwrap());
+    assert!(!touch_session(db, idle.id, other_id, org_id, &config)
+        .await
+        .unwrap());
+    // A token claiming another organization does not revive the session there.
+    assert!(
+        !touch_session(db, idle.id, user_id, uuid::Uuid::new_v4(), &config)
+            .await
+            .unwrap()
+    );
+    let mut active: user_session::ActiveModel = idle.clone().into();
+    active.last_seen_at = Set((Utc::now() - Duration::minutes(16)).into());
+    active.update(db).await.unwrap();
+    assert!(!touch_session(db, idle.id, user_id, org_id, &config)
+        .await
+        .unwrap());
+
+    let old = start_session(db, user_id, org_id, None, &config)
+        .await
+        .unwrap();
+    let mut active: user_session::ActiveModel = old.clone().into();
+    active.expires_at = Set((Utc::now() - Duration::minutes(1)).into());
+    active.update(db).await.unwrap();
+    assert!(!touch_session(db, old.id, user_id, org_id, &config)
+        .await
+        .unwrap());
+
+    let live = start_session(db, user_id, org_id, None, &config)
+        .await
+        .unwrap();
+    let ids: Vec<_> = live_sessions(db, user_id, &config)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|s| s.id)
+        .collect();
+    assert_eq!(ids, vec![live.id]);
+    assert!(!revoke_session(db, live.id, other_id).await.unwrap());
+    assert!(revoke_session(db, live.id, user_id).await.unwrap());
+    assert!(!touch_session(db, live.id, user_id, org_id, &config)
+        .await
+        .unwrap());
+    ctx.cleanup().await;
+}
@@ -0,0 +1,116 @@
+use api::auth::{decode_session_token, issue_session_token, AuthConfig, AuthMode, SessionClaims};
+use api::keyring::{Keyring, SigningKey};
+use chrono::{Duration, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use uuid::Uuid;
+
+const ED25519_PEM: &[u8] = include_bytes!("../fixtures/session_ed25519.pem");
+const RSA_PEM: &[u8] = include_bytes!("../fixtures/session_rsa.pem");
+
+fn config(keyring: Keyring) -> AuthConfig {
+    AuthConfig::new(AuthMode::Local, None, 15).with_keyring(keyring)
+}
+
+fn claims() -> SessionClaims {
+    let now = Utc::now();
+    SessionClaims {
+        sub: Uuid::new_v4(),
+        sid: Uuid::new_v4(),
+        org: Uuid::new_v4(),
+        exp: (now + Duration::minutes(5)).timestamp() as usize,
+        iat: now.timestamp() as usize,
+    }
+}
+
+#[test]
+fn retired_secrets_verify_until_they_are_dropped() {
+    let (user_id, org_id, session_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let old = config(Keyring::new(SigningKey::hmac("old secret")));
+    let token = issue_session_token(user_id, org_id, session_id, &old).unwrap();
+    let kid = decode_header(&token).unwrap().kid;
+    assert_eq!(kid, Some(SigningKey::hmac("old secret").kid));
+
+    let rotated = config(
+        Keyring::new(SigningKey::hmac("new secret")).with_verifier(SigningKey::hmac("old secret")),
+    );
+    assert_eq!(
+        decode_session_token(&token, &rotated).unwrap().sid,
+        session_id
+    );
+    let fresh = issue_session_token(user_id, org_id, session_id, &rotated).unwrap();
+    assert!(decode_session_token(&fresh, &old).is_err());
+
+    // Tokens from before keys had ids carry no `kid` and still match a secret.
+    let legacy = encode(
+        &Header::default(),
+        &claims(),
+        &EncodingKey::from_secret(b"old secret"),
+    )
+    .unwrap();
+    assert!(decode_session_token(&legacy, &rotated).is_ok());
+
+    let dropped = config(Keyring::new(SigningKey::hmac("new secret")));
+    assert!(decode_session_token(&token, &dropped).is_err());
+    assert!(decode_session_token(&legacy, &dropped).is_err());
+}
+
+#[test]
+fn asymmetric_tokens_verify_with_the_published_key_alone() {
+    for (pem, algorithm) in [(ED25519_PEM, Algorithm::EdDSA), (RSA_PEM, Algorithm::RS256)] {
+        let key = SigningKey::from_private_pem(pem, None).unwrap();
+        assert_eq!(key.algorithm, algorithm);
+        let config = config(Keyring::new(key).with_verifier(SigningKey::hmac("legacy")));
+        let session_id = Uuid::new_v4();
+        let token =
+            issue_session_token(Uuid::new_v4(), Uuid::new_v4(), session_id, &config).unwrap();
+        assert_eq!(
+            decode_session_token(&token, &config).unwrap().sid,
+            session_id
+        );
+
+        // Shared secrets are never published.
+        let jwks: JwkSet = serde_json::from_value(config.keyring.jwks()).unwrap();
+        assert_eq!(jwks.keys.len(), 1);
+        let kid = decode_header(&token).unwrap().kid.unwrap();
+        let jwk = jwks.find(&kid).expect("signing key is published");
+        let decoded = decode::<SessionClaims>(
+            &token,
+            &DecodingKey::from_jwk(jwk).unwrap(),
+            &Validation::new(algorithm),
+        )
+        .unwrap();
+        assert_eq!(decoded.claims.sid, session_id);
+    }
+}
+
+#[test]
+fn a_kid_only_verifies_with_its_own_algorithm() {
+    let key = SigningKey::from_private_pem(ED25519_PEM, Some("ed-2026".into())).unwrap();
+    assert_eq!(key.kid, "ed-2026");
+    let config = config(Keyring::new(key));
+    let jwks = config.keyring.jwks();
+    let public_x = jwks["keys"][0]["x"].as_str().unwrap().to_string();
+    let forged = encode(
+        &Header {
+            kid: Some("ed-2026".into()),
+            ..Header::new(Algorithm::HS256)
+        },
+        &claims(),
+        &EncodingKey::from_secret(public_x.as_bytes()),
+    )
+    .unwrap();
+    assert!(decode_session_token(&forged, &config).is_err());
+    let unknown = encode(
+        &Header {
+            kid: Some("someone-else".into()),
+            ..Header::new(Algorithm::HS256)
+        },
+        &claims(),
+        &EncodingKey::from_secret(b"secret"),
+    )
+    .unwrap();
+    assert!(decode_session_token(&unknown, &config).is_err());
+}
@@ -0,0 +1,226 @@
+mod common;
+
+use std::future::Future;
+use std::time::Duration;
+
+use api::auth::{CurrentUser, UserRole};
+use api::organization::{add_member, create_organization};
+use async_graphql::futures_util::{Stream, StreamExt};
+use async_graphql::{Request, Response, Variables};
+use common::{user_with_role, PgTestContext};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// How long a subscriber waits for an event it should get.
+const DELIVERY: Duration = Duration::from_secs(5);
+/// How long a subscriber waits to be sure an event does not arrive.
+const SILENCE: Duration = Duration::from_millis(300);
+
+async fn mutate(ctx: &PgTestContext, query: &str, vars: Value, user: &CurrentUser) -> Value {
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(query)
+                .variables(Variables::from_json(vars))
+                .data(user.clone()),
+        )
+        .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    resp.data.into_json().unwrap()["crm"].clone()
+}
+
+fn subscribe<'a>(
+    ctx: &'a PgTestContext,
+    query: &str,
+    vars: Value,
+    user: &CurrentUser,
+) -> impl Stream<Item = Response> + Unpin + 'a {
+    ctx.schema.execute_stream(
+        Request::new(query)
+            .variables(Variables::from_json(vars))
+            .data(user.clone()),
+    )
+}
+
+/// Runs `change` once the subscription is live and returns what it delivers within
+/// `wait`, or `None`.
+async fn next_event<S, F>(stream: &mut S, change: F, wait: Duration) -> Option<Value>
+where
+    S: Stream<Item = Response> + Unpin,
+    F: Future,
+{
+    // The first poll registers the subscriber, before `change` gets to run.
+    let (event, _) = tokio::join!(tokio::time::timeout(wait, stream.next()), change);
+    let resp = event.ok()??;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    Some(resp.data.into_json().unwrap())
+}
+
+fn deal_assigned_to(ctx: &PgTestContext, email: &str) -> Uuid {
+    let user_id = ctx.seeded.user_email(email).unwrap().id;
+    ctx.seeded
+        .deals
+        .iter()
+        .find(|deal| deal.assigned_user_id == Some(user_id))
+        .unwrap()
+        .id
+}
+
+const UPDATE_DEAL: &str = r#"
+    mutation Update($id: ID!, $title: String!) {
+        crm { updateDeal(input: { id: $id, title: $title }) { id } }
+    }
+"#;
+
+#[tokio::test]
+async fn deal_events_reach_subscribers_who_can_see_the_deal() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping subscription tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let own = deal_assigned_to(&ctx, "sales@sme.test");
+    let others = deal_assigned_to(&ctx, "admin@sme.test");
+    let updated = "subscription { dealUpdated { id title } }";
+
+    let mut stream = subscribe(&ctx, updated, json!({}), &sales);
+    let change = mutate(
+        &ctx,
+        UPDATE_DEAL,
+        json!({ "id": own, "title": "Renamed" }),
+        &admin,
+    );
+    let event = next_event(&mut stream, change, DELIVERY).await.unwrap();
+    assert_eq!(
+        event["dealUpdated"],
+        json!({ "id": own.to_string(), "title": "Renamed" })
+    );
+
+    // Deals in someone else's book stay out of the sales rep's stream.
+    let change = mutate(
+        &ctx,
+        UPDATE_DEAL,
+        json!({ "id": others, "title": "Hidden" }),
+        &admin,
+    );
+    assert_eq!(next_event(&mut stream, change, SILENCE).await, None);
+
+    let moved = r#"
+        subscription Moved($dealId: ID) {
+            dealStageMoved(dealId: $dealId) { deal { id stage } fromStage toStage changedBy }
+        }
+    "#;
+    drop(stream);
+    let mut stream = subscribe(&ctx, moved, json!({ "dealId": own }), &sales);
+    let move_stage = r#"
+        mutation Move($id: ID!) { crm { moveDealStage(id: $id, stage: NEGOTIATE) { id } } }
+    "#;
+    let change = mutate(&ctx, move_stage, json!({ "id": own }), &admin);
+    let event = next_event(&mut stream, change, DELIVERY).await.unwrap();
+    let from = ctx
+        .seeded
+        .deals
+        .iter()
+        .find(|deal| deal.id == own)
+        .unwrap()
+        .stage
+        .clone();
+    assert_ne!(from, "NEGOTIATE");
+    assert_eq!(event["dealStageMoved"]["deal"]["stage"], "NEGOTIATE");
+    assert_eq!(event["dealStageMoved"]["toStage"], "NEGOTIATE");
+    assert_eq!(
+        event["dealStageMoved"]["changedBy"],
+        admin.user_id.to_string()
+    );
+
+    // Moving to the stage a deal is already in changes nothing worth telling.
+    let change = mutate(&ctx, move_stage, json!({ "id": own }), &admin);
+    assert_eq!(next_event(&mut stream, change, SILENCE).await, None);
+    drop(stream);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn task_events_report_each_change() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping subscription tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let deal_id = deal_assigned_to(&ctx, "sales@sme.test");
+    let query = r#"
+        subscription Tasks($dealId: ID) {
+            taskChanged(dealId: $dealId) { change taskId task { title status } }
+        }
+    "#;
+    let mut stream = subscribe(&ctx, query, json!({ "dealId": deal_id }), &sales);
+
+    let create = r#"
+        mutation Create($dealId: ID!) {
+            crm { createTask(input: { title: "Call back", dealId: $dealId }) { id } }
+        }
+    "#;
+    let (event, created) = tokio::join!(
+        tokio::time::timeout(DELIVERY, stream.next()),
+        mutate(&ctx, create, json!({ "dealId": deal_id }), &sales)
+    );
+    let task_id = created["createTask"]["id"].clone();
+    let event = event.unwrap().unwrap().data.into_json().unwrap();
+    assert_eq!(
+        event["taskChanged"],
+        json!({
+            "change": "CREATED",
+            "taskId": task_id,
+            "task": { "title": "Call back", "status": "OPEN" },
+        })
+    );
+
+    let complete = r#"mutation Done($id: ID!) { crm { completeTask(id: $id) { id } } }"#;
+    let change = mutate(&ctx, complete, json!({ "id": task_id }), &sales);
+    let event = next_event(&mut stream, change, DELIVERY).await.unwrap();
+    assert_eq!(event["taskChanged"]["change"], "UPDATED");
+    assert_eq!(event["taskChanged"]["task"]["status"], "DONE");
+
+    let delete = r#"mutation Delete($id: ID!) { crm { deleteTask(id: $id) } }"#;
+    let change = mutate(&ctx, delete, json!({ "id": task_id }), &sales);
+    let event = next_event(&mut stream, change, DELIVERY).await.unwrap();
+    assert_eq!(
+        event["taskChanged"],
+        json!({ "change": "DELETED", "taskId": task_id, "task": null })
+    );
+    drop(stream);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn events_stay_inside_their_organization() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping subscription tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let org = create_organization(ctx.db.as_ref(), "Globex", "globex")
+        .await
+        .unwrap();
+    let outsider = CurrentUser {
+        org_id: org.id,
+        ..user_with_role(&ctx, "owner@sme.test", UserRole::Owner)
+    };
+    add_member(ctx.db.as_ref(), org.id, outsider.user_id, &outsider.roles)
+        .await
+        .unwrap();
+
+    let query = "subscription { dealUpdated { id } }";
+    let mut stream = subscribe(&ctx, query, json!({}), &outsider);
+    let deal_id = deal_assigned_to(&ctx, "admin@sme.test");
+    let change = mutate(
+        &ctx,
+        UPDATE_DEAL,
+        json!({ "id": deal_id, "title": "Elsewhere" }),
+        &admin,
+    );
+    assert_eq!(next_event(&mut stream, change, SILENCE).await, None);
+    drop(stream);
+    ctx.cleanup().await;
+}
@@ -13,7 +13,9 @@ fn owner_user(ctx: &PgTestContext) -> CurrentUser {
         .expect("seeded owner");
     CurrentUser {
         user_id: owner.id,
+        org_id: ctx.seeded.organization.id,
         roles: vec![UserRole::Owner, UserRole::Admin],
+        scopes: None,
     }
 }
 
@@ -31,7 +33,7 @@ async fn task_list_limit_enforced() {
         query Tasks($first: Int!) {
             crm {
                 tasks(first: $first) {
-                    id
+                    nodes { id }
                 }
             }
         }
@@ -184,10 +186,6 @@ async fn task_filters_ordering_and_pagination() {
         return;
     };
     let current_user = owner_user(&ctx);
-    let contact = ctx
-        .seeded
-        .contact_email("ada@acme.test")
-        .expect("seeded contact");
     let deal = ctx.seeded.deal_titled("ACME Pilot").expect("seeded deal");
     let now = Utc::now();
     for days in 0..3 {
@@ -199,7 +197,8 @@ async fn task_filters_ordering_and_pagination() {
                 }
             }
         "#;
-        ctx.schema
+        let resp = ctx
+            .schema
             .execute(
                 Request::new(create)
                     .variables(Variables::from_json(json!({
@@ -207,7 +206,6 @@ async fn task_filters_ordering_and_pagination() {
                             "title": format!("Follow up {}", days),
                             "notesMd": "auto",
                             "priority": "MEDIUM",
-                            "contactId": contact.id,
                             "dealId": deal.id,
                             "dueAt": due.to_rfc3339()
                         }
@@ -215,13 +213,14 @@ async fn task_filters_ordering_and_pagination() {
                     .data(current_user.clone()),
             )
             .await;
+        assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
     }
 
     let query = r#"
         query Filter($term: String!) {
             crm {
-                tasks(first: 2, filter: { q: $term }, orderBy: { field: DUE_AT, direction: ASC }) {
-                    title
+                tasks(first: 2, filter: { q: $term }, orderBy: DUE_ASC) {
+                    nodes { title }
                 }
             }
         }
@@ -235,7 +234,7 @@ async fn task_filters_ordering_and_pagination() {
         )
         .await;
     assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
-    let tasks = resp.data.into_json().unwrap()["crm"]["tasks"]
+    let tasks = resp.data.into_json().unwrap()["crm"]["tasks"]["nodes"]
         .as_array()
         .cloned()
         .unwrap();
@@ -244,3 +243,101 @@ async fn task_filters_ordering_and_pagination() {
     assert_eq!(tasks[1]["title"], "Follow up 1");
     ctx.cleanup().await;
 }
+
+#[tokio::test]
+async fn task_cursor_pagination_walks_every_row_once() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping tasks tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let current_user = owner_user(&ctx);
+    let deal = ctx.seeded.deal_titled("ACME Pilot").expect("seeded deal");
+    let due = (Utc::now() + Duration::days(2)).to_rfc3339();
+    // Two tasks share a due date and two have none, so pages must break ties on id.
+    let inputs = [
+        json!({ "title": "Cursor a", "dueAt": due }),
+        json!({ "title": "Cursor b", "dueAt": due }),
+        json!({ "title": "Cursor c", "dueAt": (Utc::now() + Duration::days(1)).to_rfc3339() }),
+        json!({ "title": "Cursor d" }),
+        json!({ "title": "Cursor e" }),
+    ];
+    let create = r#"
+        mutation Create($input: NewTaskInput!) {
+            crm { createTask(input: $input) { id } }
+        }
+    "#;
+    for mut input in inputs {
+        input["dealId"] = json!(deal.id);
+        let resp = ctx
+            .schema
+            .execute(
+                Request::new(create)
+                    .variables(Variables::from_json(json!({ "input": input })))
+                    .data(current_user.clone()),
+            )
+            .await;
+        assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    }
+
+    let query = r#"
+        query Page($after: String) {
+            crm {
+                tasks(first: 2, after: $after, filter: { q: "cursor" }, orderBy: DUE_ASC) {
+                    totalCount
+                    pageInfo { hasNextPage hasPreviousPage endCursor }
+                    edges { cursor node { title } }
+                }
+            }
+        }
+    "#;
+    let mut after = serde_json::Value::Null;
+    let mut titles = Vec::new();
+    let mut pages = 0;
+    loop {
+        let resp = ctx
+            .schema
+            .execute(
+                Request::new(query)
+                    .variables(Variables::from_json(json!({ "after": after })))
+                    .data(current_user.clone()),
+            )
+            .await;
+        assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+        let conn = resp.data.into_json().unwrap()["crm"]["tasks"].clone();
+        assert_eq!(conn["totalCount"], 5);
+        assert_eq!(conn["pageInfo"]["hasPreviousPage"], pages > 0);
+        for edge in conn["edges"].as_array().unwrap() {
+            titles.push(edge["node"]["title"].as_str().unwrap().to_string());
+        }
+        pages += 1;
+        if conn["pageInfo"]["hasNextPage"] != true {
+            break;
+        }
+        after = conn["pageInfo"]["endCursor"].clone();
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(titles[0], "Cursor c");
+    let mut tied = titles[1..3].to_vec();
+    tied.sort();
+    assert_eq!(tied, vec!["Cursor a", "Cursor b"]);
+    let mut undated = titles[3..].to_vec();
+    undated.sort();
+    assert_eq!(undated, vec!["Cursor d", "Cursor e"]);
+
+    let resp = ctx
+        .schema
+        .execute(
+            Request::new(query)
+                .variables(Variables::from_json(json!({ "after": "not-a-cursor" })))
+                .data(current_user),
+        )
+        .await;
+    assert!(resp.errors.iter().any(|err| {
+        err.extensions
+            .as_ref()
+            .and_then(|ext| ext.get("code"))
+            .map(|code| *code == async_graphql::Value::from("BAD_REQUEST"))
+            .unwrap_or(false)
+    }));
+    ctx.cleanup().await;
+}
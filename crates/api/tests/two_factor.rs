@@ -0,0 +1,230 @@
+mod common;
+
+use api::auth::{AuthMode, CurrentUser, UserRole};
+use api::totp::totp_code;
+use chrono::Utc;
+use common::{error_code, run, user_with_role, PgTestContext};
+use serde_json::{json, Value};
+
+async fn data(ctx: &PgTestContext, query: &str, vars: Value, user: Option<&CurrentUser>) -> Value {
+    let resp = run(ctx, query, vars, user).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    resp.data.into_json().unwrap()["crm"].clone()
+}
+
+/// The code for the step after the current one, which is never the step an
+/// earlier call in the same test consumed.
+fn next_code(secret: &str) -> String {
+    totp_code(secret, Utc::now().timestamp() + 30).unwrap()
+}
+
+const LOGIN: &str = r#"
+    mutation Login($email: String!, $password: String!) {
+        crm { login(email: $email, password: $password) { ok error nextStep challengeToken } }
+    }
+"#;
+
+const VERIFY: &str = r#"
+    mutation Verify($token: String!, $code: String!) {
+        crm { verifySecondFactor(challengeToken: $token, code: $code) { ok error user { email } } }
+    }
+"#;
+
+const BEGIN: &str = r#"
+    mutation Begin($token: String) {
+        crm { beginTotpEnrollment(challengeToken: $token) { secret otpauthUri } }
+    }
+"#;
+
+const CONFIRM: &str = r#"
+    mutation Confirm($code: String!, $token: String) {
+        crm { confirmTotpEnrollment(code: $code, challengeToken: $token) { recoveryCodes signedIn } }
+    }
+"#;
+
+#[tokio::test]
+async fn totp_login_takes_a_second_step() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping two factor tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let creds = json!({ "email": "sales@sme.test", "password": "salespass" });
+
+    let enrollment =
+        data(&ctx, BEGIN, json!({}), Some(&sales)).await["beginTotpEnrollment"].clone();
+    let secret = enrollment["secret"].as_str().unwrap().to_string();
+    assert!(enrollment["otpauthUri"]
+        .as_str()
+        .unwrap()
+        .starts_with("otpauth://totp/SME%20Suite:sales@sme.test?secret="));
+    // Nothing changes until the authenticator proves it has the secret.
+    let login = data(&ctx, LOGIN, creds.clone(), None).await["login"].clone();
+    assert_eq!(login["ok"], true);
+    let resp = run(&ctx, CONFIRM, json!({ "code": "000000x" }), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let code = totp_code(&secret, Utc::now().timestamp()).unwrap();
+    let confirmed = data(&ctx, CONFIRM, json!({ "code": code }), Some(&sales)).await;
+    let recovery: Vec<String> =
+        serde_json::from_value(confirmed["confirmTotpEnrollment"]["recoveryCodes"].clone())
+            .unwrap();
+    assert_eq!(recovery.len(), 10);
+    assert_eq!(confirmed["confirmTotpEnrollment"]["signedIn"], false);
+
+    let login = data(&ctx, LOGIN, creds.clone(), None).await["login"].clone();
+    assert_eq!(login["ok"], false);
+    assert_eq!(login["nextStep"], "VERIFY_TOTP");
+    let token = login["challengeToken"].as_str().unwrap().to_string();
+    // The code that confirmed enrolment is spent.
+    let verified = data(&ctx, VERIFY, json!({ "token": token, "code": code }), None).await;
+    assert_eq!(verified["verifySecondFactor"]["error"], "Invalid code");
+    let verified = data(
+        &ctx,
+        VERIFY,
+        json!({ "token": token, "code": next_code(&secret) }),
+        None,
+    )
+    .await;
+    assert_eq!(verified["verifySecondFactor"]["ok"], true);
+    assert_eq!(
+        verified["verifySecondFactor"]["user"]["email"],
+        "sales@sme.test"
+    );
+
+    let recovery_code = recovery[0].to_uppercase();
+    let verified = data(
+        &ctx,
+        VERIFY,
+        json!({ "token": token, "code": recovery_code }),
+        None,
+    )
+    .await;
+    assert_eq!(verified["verifySecondFactor"]["ok"], true);
+    let verified = data(
+        &ctx,
+        VERIFY,
+        json!({ "token": token, "code": recovery_code }),
+        None,
+    )
+    .await;
+    assert_eq!(verified["verifySecondFactor"]["ok"], false);
+    let verified = data(
+        &ctx,
+        VERIFY,
+        json!({ "token": "forged", "code": recovery[1] }),
+        None,
+    )
+    .await;
+    assert_eq!(
+        verified["verifySecondFactor"]["error"],
+        "Sign-in expired, please retry"
+    );
+
+    let status = data(
+        &ctx,
+        "{ crm { twoFactor { enabled required recoveryCodesRemaining } } }",
+        json!({}),
+        Some(&sales),
+    )
+    .await;
+    assert_eq!(
+        status["twoFactor"],
+        json!({ "enabled": true, "required": false, "recoveryCodesRemaining": 9 })
+    );
+    let disable = r#"mutation Disable($code: String!) { crm { disableTotp(code: $code) } }"#;
+    let disabled = data(&ctx, disable, json!({ "code": recovery[1] }), Some(&sales)).await;
+    assert_eq!(disabled["disableTotp"], true);
+    let login = data(&ctx, LOGIN, creds, None).await["login"].clone();
+    assert_eq!(login["ok"], true);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn owners_can_require_totp_for_admins() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping two factor tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let policy = r#"
+        mutation Policy($on: Boolean!) { crm { setTwoFactorPolicy(requireForAdmins: $on) { requireForAdmins } } }
+    "#;
+
+    let resp = run(&ctx, policy, json!({ "on": true }), Some(&admin)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+    let resp = run(&ctx, policy, json!({ "on": true }), Some(&owner)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+    let secret = data(&ctx, BEGIN, json!({}), Some(&owner)).await["beginTotpEnrollment"]["secret"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let code = totp_code(&secret, Utc::now().timestamp()).unwrap();
+    data(&ctx, CONFIRM, json!({ "code": code }), Some(&owner)).await;
+    let set = data(&ctx, policy, json!({ "on": true }), Some(&owner)).await;
+    assert_eq!(set["setTwoFactorPolicy"]["requireForAdmins"], true);
+
+    // Sales accounts are unaffected; an unenrolled admin must enrol to finish.
+    let login = data(
+        &ctx,
+        LOGIN,
+        json!({ "email": "sales@sme.test", "password": "salespass" }),
+        None,
+    )
+    .await;
+    assert_eq!(login["login"]["ok"], true);
+    let login = data(
+        &ctx,
+        LOGIN,
+        json!({ "email": "admin@sme.test", "password": "adminpass" }),
+        None,
+    )
+    .await["login"]
+        .clone();
+    assert_eq!(login["ok"], false);
+    assert_eq!(login["nextStep"], "ENROLL_TOTP");
+    let token = login["challengeToken"].clone();
+    let verified = data(
+        &ctx,
+        VERIFY,
+        json!({ "token": token, "code": "123456" }),
+        None,
+    )
+    .await;
+    assert_eq!(
+        verified["verifySecondFactor"]["error"],
+        "Sign-in expired, please retry"
+    );
+    let secret = data(&ctx, BEGIN, json!({ "token": token }), None).await["beginTotpEnrollment"]
+        ["secret"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let code = totp_code(&secret, Utc::now().timestamp()).unwrap();
+    let confirmed = data(&ctx, CONFIRM, json!({ "code": code, "token": token }), None).await;
+    assert_eq!(confirmed["confirmTotpEnrollment"]["signedIn"], true);
+
+    let disable = r#"mutation Disable($code: String!) { crm { disableTotp(code: $code) } }"#;
+    let resp = run(
+        &ctx,
+        disable,
+        json!({ "code": next_code(&secret) }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+    let reset = r#"mutation Reset($id: ID!) { crm { resetUserTotp(userId: $id) } }"#;
+    data(&ctx, reset, json!({ "id": admin.user_id }), Some(&owner)).await;
+    let status = data(
+        &ctx,
+        "{ crm { twoFactor { enabled required } } }",
+        json!({}),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(
+        status["twoFactor"],
+        json!({ "enabled": false, "required": true })
+    );
+    ctx.cleanup().await;
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use api::auth::{AuthMode, UserRole};
+use common::{error_code, run, user_with_role, PgTestContext};
+use serde_json::json;
+
+const LOGIN: &str = r#"
+    mutation Login($email: String!, $password: String!) {
+        crm { login(email: $email, password: $password) { ok error } }
+    }
+"#;
+
+const SET_ROLES: &str = r#"
+    mutation Roles($id: ID!, $roles: [UserRole!]!) {
+        crm { setUserRoles(userId: $id, roles: $roles) { roles } }
+    }
+"#;
+
+const DEACTIVATE: &str = r#"
+    mutation Deactivate($id: ID!) { crm { deactivateUser(id: $id) { isActive } } }
+"#;
+
+#[tokio::test]
+async fn owner_creates_and_invites_users() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping users tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let create = r#"
+        mutation Create($email: String!) {
+            crm {
+                createUser(input: {
+                    email: $email, displayName: "Dana", roles: [SALES, SALES], password: "correct horse"
+                }) { email displayName isActive roles }
+            }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        create,
+        json!({ "email": "Dana@SME.test" }),
+        Some(&admin),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("FORBIDDEN"));
+
+    let resp = run(
+        &ctx,
+        create,
+        json!({ "email": "Dana@SME.test" }),
+        Some(&owner),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["createUser"],
+        json!({ "email": "dana@sme.test", "displayName": "Dana", "isActive": true, "roles": ["SALES"] })
+    );
+    let resp = run(
+        &ctx,
+        create,
+        json!({ "email": "dana@sme.test" }),
+        Some(&owner),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    let resp = run(
+        &ctx,
+        LOGIN,
+        json!({ "email": "dana@sme.test", "password": "correct horse" }),
+        None,
+    )
+    .await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+
+    let invite = r#"
+        mutation {
+            crm {
+                inviteUser(input: { email: "erin@sme.test", displayName: "Erin", roles: [VIEWER] }) {
+                    user { roles }
+                    temporaryPassword
+                }
+            }
+        }
+    "#;
+    let resp = run(&ctx, invite, json!({}), Some(&owner)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let payload = resp.data.into_json().unwrap()["crm"]["inviteUser"].clone();
+    assert_eq!(payload["user"]["roles"], json!(["VIEWER"]));
+    let resp = run(
+        &ctx,
+        LOGIN,
+        json!({ "email": "erin@sme.test", "password": payload["temporaryPassword"] }),
+        None,
+    )
+    .await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn last_owner_cannot_be_demoted_or_deactivated() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping users tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let owner_id = owner.user_id;
+    let admin_id = ctx.seeded.user_email("admin@sme.test").unwrap().id;
+
+    let resp = run(
+        &ctx,
+        SET_ROLES,
+        json!({ "id": owner_id, "roles": ["ADMIN"] }),
+        Some(&owner),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+    let resp = run(&ctx, DEACTIVATE, json!({ "id": owner_id }), Some(&owner)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("CONFLICT"));
+
+    // Once a second owner exists the first can step down.
+    let resp = run(
+        &ctx,
+        SET_ROLES,
+        json!({ "id": admin_id, "roles": ["OWNER", "ADMIN"] }),
+        Some(&owner),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["setUserRoles"]["roles"],
+        json!(["OWNER", "ADMIN"])
+    );
+    let resp = run(
+        &ctx,
+        SET_ROLES,
+        json!({ "id": owner_id, "roles": ["ADMIN"] }),
+        Some(&owner),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let resp = run(
+        &ctx,
+        SET_ROLES,
+        json!({ "id": admin_id, "roles": [] }),
+        Some(&owner),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn deactivation_and_password_reset_gate_login() {
+    let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+        eprintln!("skipping users tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let owner = user_with_role(&ctx, "owner@sme.test", UserRole::Owner);
+    let sales_id = ctx.seeded.user_email("sales@sme.test").unwrap().id;
+    let creds = json!({ "email": "sales@sme.test", "password": "a brand new secret" });
+
+    let reset = r#"
+        mutation Reset($id: ID!, $password: String!) {
+            crm { adminResetPassword(userId: $id, password: $password) }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        reset,
+        json!({ "id": sales_id, "password": "short" }),
+        Some(&owner),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("VALIDATION"));
+    let resp = run(
+        &ctx,
+        reset,
+        json!({ "id": sales_id, "password": "a brand new secret" }),
+        Some(&owner),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let resp = run(&ctx, LOGIN, creds.clone(), None).await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+
+    let resp = run(&ctx, DEACTIVATE, json!({ "id": sales_id }), Some(&owner)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["deactivateUser"]["isActive"],
+        false
+    );
+    let resp = run(&ctx, LOGIN, creds.clone(), None).await;
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["login"]["error"],
+        "Account disabled"
+    );
+
+    let reactivate = r#"
+        mutation Reactivate($id: ID!) { crm { reactivateUser(id: $id) { isActive } } }
+    "#;
+    let resp = run(&ctx, reactivate, json!({ "id": sales_id }), Some(&owner)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let resp = run(&ctx, LOGIN, creds, None).await;
+    assert_eq!(resp.data.into_json().unwrap()["crm"]["login"]["ok"], true);
+    ctx.cleanup().await;
+}
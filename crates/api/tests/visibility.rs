@@ -0,0 +1,211 @@
+mod common;
+
+use api::auth::UserRole;
+use common::{error_code, run, user_with_role, PgTestContext};
+use serde_json::json;
+
+fn names(list: &serde_json::Value, field: &str) -> Vec<String> {
+    let mut names: Vec<String> = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item[field].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn sales_lists_only_cover_their_book() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping visibility tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let fossrust = ctx.seeded.company_named("FossRust Labs").unwrap();
+    let query = r#"
+        query Book($fossrust: ID!) {
+            crm {
+                hidden: company(id: $fossrust) { id }
+                companies(orderBy: NAME_ASC) {
+                    totalCount
+                    nodes { name contacts { email } }
+                }
+                contacts { nodes { email } }
+                deals { totalCount }
+                search(q: "FossRust") { nodes { title } }
+                pipelineBoard { totalCount }
+            }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        query,
+        json!({ "fossrust": fossrust.id }),
+        Some(&sales),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let data = resp.data.into_json().unwrap()["crm"].clone();
+    assert!(data["hidden"].is_null());
+    assert_eq!(data["companies"]["totalCount"], 2);
+    assert_eq!(
+        names(&data["companies"]["nodes"], "name"),
+        vec!["ACME, Inc.", "NuFlights LLC"]
+    );
+    // Charles sits under ACME but belongs to the admin.
+    assert_eq!(
+        data["companies"]["nodes"][0]["contacts"],
+        json!([{ "email": "ada@acme.test" }])
+    );
+    assert_eq!(
+        names(&data["contacts"]["nodes"], "email"),
+        vec!["ada@acme.test", "grace@nuflights.test"]
+    );
+    assert_eq!(data["deals"]["totalCount"], 6);
+    assert!(data["search"]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|hit| !hit["title"].as_str().unwrap().contains("Rust")));
+    assert_eq!(data["pipelineBoard"]["totalCount"], 6);
+
+    let resp = run(
+        &ctx,
+        query,
+        json!({ "fossrust": fossrust.id }),
+        Some(&admin),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let data = resp.data.into_json().unwrap()["crm"].clone();
+    assert_eq!(data["hidden"]["id"], json!(fossrust.id));
+    assert_eq!(data["companies"]["totalCount"], 3);
+    assert_eq!(data["deals"]["totalCount"], 8);
+    assert_eq!(data["pipelineBoard"]["totalCount"], 8);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn sales_reports_and_history_skip_other_books() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping visibility tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let tooling = ctx.seeded.deal_titled("Rust Tooling Upgrade").unwrap();
+    let move_stage = r#"
+        mutation Move($id: ID!) { crm { moveDealStage(id: $id, stage: NEGOTIATE) { id } } }
+    "#;
+    let resp = run(&ctx, move_stage, json!({ "id": tooling.id }), Some(&admin)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+
+    let query = r#"
+        query Report($id: ID!) {
+            crm {
+                dealStageHistory(dealId: $id) { totalCount }
+                pipelineReport(range: { from: "2025-01-01", to: "2025-03-31" }, includeLost: true) {
+                    forecast { deals }
+                }
+            }
+        }
+    "#;
+    let forecast_deals = |data: &serde_json::Value| -> i64 {
+        data["pipelineReport"]["forecast"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|point| point["deals"].as_i64().unwrap())
+            .sum()
+    };
+    let resp = run(&ctx, query, json!({ "id": tooling.id }), Some(&admin)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let admin_data = resp.data.into_json().unwrap()["crm"].clone();
+    assert_eq!(admin_data["dealStageHistory"]["totalCount"], 1);
+
+    let resp = run(&ctx, query, json!({ "id": tooling.id }), Some(&sales)).await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let sales_data = resp.data.into_json().unwrap()["crm"].clone();
+    assert_eq!(sales_data["dealStageHistory"]["totalCount"], 0);
+    // Both FossRust deals close inside the range and belong to the admin.
+    assert_eq!(forecast_deals(&admin_data) - forecast_deals(&sales_data), 2);
+    ctx.cleanup().await;
+}
+
+#[tokio::test]
+async fn sales_cannot_edit_records_outside_their_book() {
+    let Some(ctx) = PgTestContext::new_seeded().await else {
+        eprintln!("skipping visibility tests: TEST_DATABASE_URL not set");
+        return;
+    };
+    let sales = user_with_role(&ctx, "sales@sme.test", UserRole::Sales);
+    let admin = user_with_role(&ctx, "admin@sme.test", UserRole::Admin);
+    let tooling = ctx.seeded.deal_titled("Rust Tooling Upgrade").unwrap();
+    let fossrust = ctx.seeded.company_named("FossRust Labs").unwrap();
+    let pilot = ctx.seeded.deal_titled("ACME Pilot").unwrap();
+
+    let update = r#"
+        mutation Update($id: ID!) { crm { updateDeal(input: { id: $id, title: "Mine now" }) { id } } }
+    "#;
+    let resp = run(&ctx, update, json!({ "id": tooling.id }), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+
+    let create = r#"
+        mutation Create($companyId: ID!) {
+            crm { createDeal(input: { title: "Sneaky", companyId: $companyId }) { id } }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        create,
+        json!({ "companyId": fossrust.id }),
+        Some(&sales),
+    )
+    .await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+
+    // A task reassigned to the admin drops out of the sales book.
+    let create_task = r#"
+        mutation Create($dealId: ID!) {
+            crm { createTask(input: { title: "Handover", dealId: $dealId }) { id } }
+        }
+    "#;
+    let resp = run(
+        &ctx,
+        create_task,
+        json!({ "dealId": pilot.id }),
+        Some(&sales),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let task_id = resp.data.into_json().unwrap()["crm"]["createTask"]["id"].clone();
+    let assign = r#"
+        mutation Assign($id: ID!, $userId: ID) { crm { assignTask(id: $id, userId: $userId) { id } } }
+    "#;
+    let admin_id = ctx.seeded.user_email("admin@sme.test").unwrap().id;
+    let resp = run(
+        &ctx,
+        assign,
+        json!({ "id": task_id, "userId": admin_id }),
+        Some(&sales),
+    )
+    .await;
+    assert!(resp.errors.is_empty(), "errors: {:?}", resp.errors);
+    let tasks = r#"{ crm { tasks(filter: { q: "handover" }) { totalCount } } }"#;
+    let resp = run(&ctx, tasks, json!({}), Some(&sales)).await;
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["tasks"]["totalCount"],
+        0
+    );
+    let resp = run(&ctx, tasks, json!({}), Some(&admin)).await;
+    assert_eq!(
+        resp.data.into_json().unwrap()["crm"]["tasks"]["totalCount"],
+        1
+    );
+    let complete = r#"mutation Complete($id: ID!) { crm { completeTask(id: $id) { id } } }"#;
+    let resp = run(&ctx, complete, json!({ "id": task_id }), Some(&sales)).await;
+    assert_eq!(error_code(&resp).as_deref(), Some("NOT_FOUND"));
+    ctx.cleanup().await;
+}
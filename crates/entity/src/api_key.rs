@@ -0,0 +1,53 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "api_key")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    #[sea_orm(indexed)]
+    pub user_id: Uuid,
+    pub name: String,
+    pub kind: Kind,
+    pub prefix: String,
+    #[sea_orm(unique)]
+    pub token_hash: String,
+    pub scopes: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTimeWithTimeZone,
+    pub expires_at: Option<DateTimeWithTimeZone>,
+    pub last_used_at: Option<DateTimeWithTimeZone>,
+    pub revoked_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::app_user::Entity",
+        from = "Column::UserId",
+        to = "super::app_user::Column::Id",
+        on_delete = "Cascade"
+    )]
+    AppUser,
+}
+
+impl Related<super::app_user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AppUser.def()
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveActiveEnum, Eq, PartialEq)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+pub enum Kind {
+    /// Issued by a user for their own scripts.
+    #[sea_orm(string_value = "PERSONAL")]
+    Personal,
+    /// Issued by an owner on behalf of a service account.
+    #[sea_orm(string_value = "SERVICE")]
+    Service,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
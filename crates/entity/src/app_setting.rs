@@ -0,0 +1,18 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "app_setting")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub org_id: Uuid,
+    #[sea_orm(primary_key)]
+    pub key: String,
+    pub value: String,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -17,6 +17,7 @@ pub struct Model {
 #[derive(Copy, Clone, Debug, EnumIter)]
 pub enum Relation {
     Identity,
+    Membership,
     Role,
     Secret,
 }
@@ -25,6 +26,7 @@ impl RelationTrait for Relation {
     fn def(&self) -> RelationDef {
         match self {
             Relation::Identity => Entity::has_many(super::user_identity::Entity).into(),
+            Relation::Membership => Entity::has_many(super::organization_member::Entity).into(),
             Relation::Role => Entity::has_many(super::user_role::Entity).into(),
             Relation::Secret => Entity::has_one(super::user_secret::Entity).into(),
         }
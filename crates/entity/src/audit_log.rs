@@ -0,0 +1,58 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "audit_log")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    #[sea_orm(indexed)]
+    pub actor_id: Option<Uuid>,
+    pub entity_type: EntityType,
+    pub entity_id: Uuid,
+    pub operation: Operation,
+    /// `{"column": {"old": ..., "new": ...}}` for each column the change touched.
+    pub changes: Json,
+    pub request_id: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveActiveEnum, Eq, PartialEq)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(32))")]
+pub enum EntityType {
+    #[sea_orm(string_value = "COMPANY")]
+    Company,
+    #[sea_orm(string_value = "CONTACT")]
+    Contact,
+    #[sea_orm(string_value = "DEAL")]
+    Deal,
+    #[sea_orm(string_value = "TASK")]
+    Task,
+    #[sea_orm(string_value = "USER")]
+    User,
+    #[sea_orm(string_value = "API_KEY")]
+    ApiKey,
+    #[sea_orm(string_value = "STAGE")]
+    Stage,
+    #[sea_orm(string_value = "PIPELINE")]
+    Pipeline,
+    #[sea_orm(string_value = "DEAL_REASON")]
+    DealReason,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveActiveEnum, Eq, PartialEq)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+pub enum Operation {
+    #[sea_orm(string_value = "CREATE")]
+    Create,
+    #[sea_orm(string_value = "UPDATE")]
+    Update,
+    #[sea_orm(string_value = "DELETE")]
+    Delete,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,55 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "auth_event")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub kind: Kind,
+    #[sea_orm(indexed)]
+    pub user_id: Option<Uuid>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::app_user::Entity",
+        from = "Column::UserId",
+        to = "super::app_user::Column::Id",
+        on_delete = "SetNull"
+    )]
+    AppUser,
+}
+
+impl Related<super::app_user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AppUser.def()
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveActiveEnum, Eq, PartialEq)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(32))")]
+pub enum Kind {
+    #[sea_orm(string_value = "LOGIN_SUCCEEDED")]
+    LoginSucceeded,
+    /// Wrong credentials or code, an inactive account, or an attempt while locked.
+    #[sea_orm(string_value = "LOGIN_FAILED")]
+    LoginFailed,
+    /// A failure pushed an account or address into a lockout.
+    #[sea_orm(string_value = "LOCKED_OUT")]
+    LockedOut,
+    #[sea_orm(string_value = "LOGOUT")]
+    Logout,
+    #[sea_orm(string_value = "PASSWORD_CHANGED")]
+    PasswordChanged,
+    /// Completed through an emailed reset link.
+    #[sea_orm(string_value = "PASSWORD_RESET")]
+    PasswordReset,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
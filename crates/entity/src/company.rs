@@ -6,6 +6,8 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: Uuid,
     #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    #[sea_orm(indexed)]
     pub name: String,
     pub website: Option<String>,
     pub phone: Option<String>,
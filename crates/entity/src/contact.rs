@@ -6,6 +6,8 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: Uuid,
     #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    #[sea_orm(indexed)]
     pub email: String,
     pub first_name: Option<String>,
     pub last_name: Option<String>,
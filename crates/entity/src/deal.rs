@@ -5,11 +5,18 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: Uuid,
+    #[sea_orm(indexed)]
+    pub org_id: Uuid,
     pub title: String,
     pub amount_cents: Option<i64>,
     pub currency: Option<String>,
-    pub stage: Stage,
+    pub pipeline_id: Uuid,
+    /// Key of a `stage_meta` row in the deal's pipeline.
+    pub stage: String,
     pub close_date: Option<Date>,
+    /// Why the deal was won or lost; cleared when it reopens.
+    pub reason_id: Option<Uuid>,
+    pub competitor: Option<String>,
     #[sea_orm(indexed)]
     pub company_id: Uuid,
     #[sea_orm(indexed)]
@@ -48,21 +55,4 @@ impl Related<super::app_user::Entity> for Entity {
     }
 }
 
-#[derive(Copy, Clone, Debug, EnumIter, DeriveActiveEnum, Eq, PartialEq)]
-#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "deal_stage")]
-pub enum Stage {
-    #[sea_orm(string_value = "NEW")]
-    New,
-    #[sea_orm(string_value = "QUALIFY")]
-    Qualify,
-    #[sea_orm(string_value = "PROPOSAL")]
-    Proposal,
-    #[sea_orm(string_value = "NEGOTIATE")]
-    Negotiate,
-    #[sea_orm(string_value = "WON")]
-    Won,
-    #[sea_orm(string_value = "LOST")]
-    Lost,
-}
-
 impl ActiveModelBehavior for ActiveModel {}
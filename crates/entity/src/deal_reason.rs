@@ -0,0 +1,30 @@
+use sea_orm::entity::prelude::*;
+
+/// An admin-managed answer to "why did we win/lose this deal?".
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "deal_reason")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    pub outcome: Outcome,
+    pub label: String,
+    /// Kept for deals that already cite it, but no longer offered.
+    pub is_archived: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveActiveEnum, Eq, PartialEq)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(8))")]
+pub enum Outcome {
+    #[sea_orm(string_value = "won")]
+    Won,
+    #[sea_orm(string_value = "lost")]
+    Lost,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -6,11 +6,19 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: Uuid,
     #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    #[sea_orm(indexed)]
     pub deal_id: Uuid,
-    pub from_stage: super::deal::Stage,
-    pub to_stage: super::deal::Stage,
+    pub from_stage: String,
+    pub to_stage: String,
+    /// Pipelines of `from_stage` and `to_stage`; they differ when a deal changed pipeline.
+    pub from_pipeline_id: Uuid,
+    pub to_pipeline_id: Uuid,
     pub changed_at: DateTimeWithTimeZone,
     pub note: Option<String>,
+    /// The deal's win/loss reason and competitor as of this move.
+    pub reason_id: Option<Uuid>,
+    pub competitor: Option<String>,
     pub changed_by: Option<String>,
 }
 
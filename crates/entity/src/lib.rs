@@ -1,12 +1,25 @@
 pub mod activity;
+pub mod api_key;
+pub mod app_setting;
 pub mod app_user;
+pub mod audit_log;
+pub mod auth_event;
 pub mod company;
 pub mod contact;
 pub mod deal;
+pub mod deal_reason;
 pub mod deal_stage_history;
+pub mod email_outbox;
+pub mod login_throttle;
+pub mod organization;
+pub mod organization_member;
+pub mod password_reset_token;
+pub mod pipeline;
 pub mod prelude;
 pub mod stage_meta;
 pub mod task;
 pub mod user_identity;
+pub mod user_recovery_code;
 pub mod user_role;
 pub mod user_secret;
+pub mod user_session;
@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "pipeline")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    pub name: String,
+    /// Where deals go when no pipeline is named; exactly one per organization.
+    pub is_default: bool,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::stage_meta::Entity")]
+    StageMeta,
+}
+
+impl Related<super::stage_meta::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::StageMeta.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
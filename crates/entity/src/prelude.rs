@@ -1,11 +1,24 @@
 pub use super::activity::Entity as Activity;
+pub use super::api_key::Entity as ApiKey;
+pub use super::app_setting::Entity as AppSetting;
 pub use super::app_user::Entity as AppUser;
+pub use super::audit_log::Entity as AuditLog;
+pub use super::auth_event::Entity as AuthEvent;
 pub use super::company::Entity as Company;
 pub use super::contact::Entity as Contact;
 pub use super::deal::Entity as Deal;
+pub use super::deal_reason::Entity as DealReason;
 pub use super::deal_stage_history::Entity as DealStageHistory;
+pub use super::email_outbox::Entity as EmailOutbox;
+pub use super::login_throttle::Entity as LoginThrottle;
+pub use super::organization::Entity as Organization;
+pub use super::organization_member::Entity as OrganizationMember;
+pub use super::password_reset_token::Entity as PasswordResetToken;
+pub use super::pipeline::Entity as Pipeline;
 pub use super::stage_meta::Entity as StageMeta;
 pub use super::task::Entity as Task;
 pub use super::user_identity::Entity as UserIdentity;
+pub use super::user_recovery_code::Entity as UserRecoveryCode;
 pub use super::user_role::Entity as UserRole;
 pub use super::user_secret::Entity as UserSecret;
+pub use super::user_session::Entity as UserSession;
@@ -3,21 +3,46 @@ use sea_orm::entity::prelude::*;
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "stage_meta")]
 pub struct Model {
+    /// Names the stage in the audit log; deals use `key`.
     #[sea_orm(primary_key)]
+    pub id: Uuid,
+    #[sea_orm(indexed)]
+    pub org_id: Uuid,
+    /// Stable identifier deals reference; renaming a stage changes `display_name` only.
+    /// Unique within the pipeline.
     pub key: String,
+    #[sea_orm(indexed)]
+    pub pipeline_id: Uuid,
     pub display_name: String,
     pub sort_order: i16,
     pub probability: i16,
     pub is_won: bool,
     pub is_lost: bool,
+    /// Hidden from the board and closed to new deals.
+    pub is_archived: bool,
+    /// Keys of the stages deals may arrive from, or `None` for any.
+    pub allowed_from: Option<Json>,
+    /// `DealField` names a deal must have filled in before it enters the stage.
+    pub required_fields: Json,
+    /// Whether a move into the stage needs a note.
+    pub requires_note: bool,
+    /// How many deals the stage should hold at once; the board flags columns over it.
+    pub wip_limit: Option<i32>,
 }
 
-#[derive(Copy, Clone, Debug, EnumIter)]
-pub enum Relation {}
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::pipeline::Entity",
+        from = "Column::PipelineId",
+        to = "super::pipeline::Column::Id"
+    )]
+    Pipeline,
+}
 
-impl RelationTrait for Relation {
-    fn def(&self) -> RelationDef {
-        panic!("no relations for stage_meta")
+impl Related<super::pipeline::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Pipeline.def()
     }
 }
 
@@ -5,6 +5,8 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: Uuid,
+    #[sea_orm(indexed)]
+    pub org_id: Uuid,
     pub title: String,
     pub notes_md: Option<String>,
     pub status: Status,
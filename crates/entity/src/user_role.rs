@@ -3,6 +3,8 @@ use sea_orm::entity::prelude::*;
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "user_role")]
 pub struct Model {
+    #[sea_orm(primary_key)]
+    pub org_id: Uuid,
     #[sea_orm(primary_key)]
     pub user_id: Uuid,
     #[sea_orm(primary_key)]
@@ -18,6 +20,13 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     AppUser,
+    #[sea_orm(
+        belongs_to = "super::organization_member::Entity",
+        from = "(Column::OrgId, Column::UserId)",
+        to = "(super::organization_member::Column::OrgId, super::organization_member::Column::UserId)",
+        on_delete = "Cascade"
+    )]
+    OrganizationMember,
 }
 
 impl Related<super::app_user::Entity> for Entity {
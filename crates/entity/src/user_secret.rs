@@ -7,6 +7,11 @@ pub struct Model {
     pub user_id: Uuid,
     pub password_hash: String,
     pub updated_at: DateTimeWithTimeZone,
+    /// Base32 TOTP seed; pending until `totp_enabled_at` is set.
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTimeWithTimeZone>,
+    /// Last accepted 30-second step, so a code verifies at most once.
+    pub totp_last_step: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -2,10 +2,29 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20251116_000001_init;
 mod m20251116_120000_crm_core;
+// Applied migrations stay byte-for-byte as shipped, so lints newer than them are
+// silenced here rather than fixed in place.
+#[allow(clippy::needless_borrows_for_generic_args)]
 mod m20251116_130000_crm_v2;
 mod m20251116_140000_crm_search;
 mod m20251116_150000_crm_pipeline;
+#[allow(clippy::too_many_arguments)]
 mod m20251116_160000_auth_rbac;
+mod m20261016_100000_contact_email_scope;
+mod m20261016_110000_activity_audit;
+mod m20261016_120000_user_session;
+mod m20261016_130000_api_key;
+mod m20261017_100000_user_totp;
+mod m20261017_110000_auth_event;
+mod m20261017_120000_password_reset;
+mod m20261018_100000_audit_log;
+mod m20261019_100000_organization;
+mod m20261020_100000_stage_key;
+mod m20261021_100000_pipeline;
+mod m20261022_100000_stage_rules;
+mod m20261023_100000_deal_reason;
+mod m20261024_100000_stage_wip_limit;
+mod m20261025_100000_pipeline_stage_key;
 
 pub struct Migrator;
 #[async_trait::async_trait]
@@ -18,6 +37,21 @@ impl MigratorTrait for Migrator {
             Box::new(m20251116_140000_crm_search::Migration),
             Box::new(m20251116_150000_crm_pipeline::Migration),
             Box::new(m20251116_160000_auth_rbac::Migration),
+            Box::new(m20261016_100000_contact_email_scope::Migration),
+            Box::new(m20261016_110000_activity_audit::Migration),
+            Box::new(m20261016_120000_user_session::Migration),
+            Box::new(m20261016_130000_api_key::Migration),
+            Box::new(m20261017_100000_user_totp::Migration),
+            Box::new(m20261017_110000_auth_event::Migration),
+            Box::new(m20261017_120000_password_reset::Migration),
+            Box::new(m20261018_100000_audit_log::Migration),
+            Box::new(m20261019_100000_organization::Migration),
+            Box::new(m20261020_100000_stage_key::Migration),
+            Box::new(m20261021_100000_pipeline::Migration),
+            Box::new(m20261022_100000_stage_rules::Migration),
+            Box::new(m20261023_100000_deal_reason::Migration),
+            Box::new(m20261024_100000_stage_wip_limit::Migration),
+            Box::new(m20261025_100000_pipeline_stage_key::Migration),
         ]
     }
 }
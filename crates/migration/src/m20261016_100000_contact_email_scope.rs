@@ -0,0 +1,55 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP INDEX IF EXISTS idx_contact_email;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "CREATE INDEX IF NOT EXISTS idx_contact_email ON contact (email);",
+        ))
+        .await?;
+        // Contacts without a company share the nil UUID bucket so they stay unique too.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_contact_company_email ON contact (
+                COALESCE(company_id, '00000000-0000-0000-0000-000000000000'::uuid),
+                lower(email)
+            );
+            "#,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP INDEX IF EXISTS idx_contact_company_email;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP INDEX IF EXISTS idx_contact_email;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_contact_email ON contact (email);",
+        ))
+        .await?;
+        Ok(())
+    }
+}
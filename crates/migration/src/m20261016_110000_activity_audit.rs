@@ -0,0 +1,102 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn run(conn: &SchemaManagerConnection<'_>, sql: &str) -> Result<(), DbErr> {
+    conn.execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // crm_v2 created `activity.created_by` as free text; the auth_rbac audit columns are
+        // user references, so bring activity in line. Legacy values are user ids or emails;
+        // anything that names no user stops the migration instead of being dropped.
+        run(
+            conn,
+            "ALTER TABLE activity ADD COLUMN created_by_user uuid;",
+        )
+        .await?;
+        run(
+            conn,
+            r#"
+            UPDATE activity a SET created_by_user = u.id
+            FROM app_user u
+            WHERE a.created_by IS NOT NULL
+              AND (u.id::text = lower(trim(a.created_by))
+                   OR lower(u.email) = lower(trim(a.created_by)));
+            "#,
+        )
+        .await?;
+        let row = conn
+            .query_one(Statement::from_string(
+                DatabaseBackend::Postgres,
+                r#"
+                SELECT COUNT(*) AS unmatched,
+                       string_agg(DISTINCT created_by, ', ') AS sample
+                FROM (
+                    SELECT created_by FROM activity
+                    WHERE created_by IS NOT NULL AND trim(created_by) <> ''
+                      AND created_by_user IS NULL
+                    LIMIT 20
+                ) missing;
+                "#,
+            ))
+            .await?
+            .ok_or_else(|| DbErr::Migration("activity author check returned no row".into()))?;
+        let unmatched: i64 = row.try_get("", "unmatched")?;
+        if unmatched > 0 {
+            let sample: Option<String> = row.try_get("", "sample")?;
+            return Err(DbErr::Migration(format!(
+                "activity.created_by has values that match no user id or email ({}); \
+                 map them to users before migrating",
+                sample.unwrap_or_default()
+            )));
+        }
+        run(conn, "ALTER TABLE activity DROP COLUMN created_by;").await?;
+        run(
+            conn,
+            "ALTER TABLE activity RENAME COLUMN created_by_user TO created_by;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE activity ADD COLUMN IF NOT EXISTS updated_by uuid;",
+        )
+        .await?;
+        run(
+            conn,
+            r#"
+            ALTER TABLE activity
+                ADD CONSTRAINT fk_activity_created_by FOREIGN KEY (created_by)
+                    REFERENCES app_user (id) ON DELETE SET NULL,
+                ADD CONSTRAINT fk_activity_updated_by FOREIGN KEY (updated_by)
+                    REFERENCES app_user (id) ON DELETE SET NULL;
+            "#,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        run(
+            conn,
+            r#"
+            ALTER TABLE activity
+                DROP CONSTRAINT IF EXISTS fk_activity_updated_by,
+                DROP CONSTRAINT IF EXISTS fk_activity_created_by,
+                DROP COLUMN IF EXISTS updated_by,
+                ALTER COLUMN created_by TYPE varchar(128) USING created_by::text;
+            "#,
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Session cookies carry a `sid` claim pointing here; a row that is revoked, idle past
+        // the session TTL or older than its absolute `expires_at` no longer authenticates.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS user_session (
+                id uuid PRIMARY KEY,
+                user_id uuid NOT NULL REFERENCES app_user (id) ON DELETE CASCADE,
+                user_agent varchar(512),
+                created_at timestamptz NOT NULL,
+                last_seen_at timestamptz NOT NULL,
+                expires_at timestamptz NOT NULL,
+                revoked_at timestamptz
+            );
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_user_session_user_live
+                ON user_session (user_id)
+                WHERE revoked_at IS NULL;
+            "#,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS user_session;",
+        ))
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Only the SHA-256 of a key is stored; `prefix` is the non-secret head shown in
+        // listings. `scopes` is space separated, OAuth style.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS api_key (
+                id uuid PRIMARY KEY,
+                user_id uuid NOT NULL REFERENCES app_user (id) ON DELETE CASCADE,
+                name varchar(120) NOT NULL,
+                kind varchar(16) NOT NULL,
+                prefix varchar(16) NOT NULL,
+                token_hash varchar(64) NOT NULL,
+                scopes varchar(128) NOT NULL,
+                created_by uuid REFERENCES app_user (id) ON DELETE SET NULL,
+                created_at timestamptz NOT NULL,
+                expires_at timestamptz,
+                last_used_at timestamptz,
+                revoked_at timestamptz,
+                CONSTRAINT chk_api_key_kind CHECK (kind IN ('PERSONAL', 'SERVICE'))
+            );
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_api_key_token_hash ON api_key (token_hash);",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "CREATE INDEX IF NOT EXISTS idx_api_key_user ON api_key (user_id);",
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS api_key;",
+        ))
+        .await?;
+        Ok(())
+    }
+}
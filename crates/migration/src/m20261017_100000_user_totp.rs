@@ -0,0 +1,85 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // The TOTP seed lives beside the password hash. It only counts once `totp_enabled_at`
+        // is set; `totp_last_step` stops a code from being replayed inside its window.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            ALTER TABLE user_secret
+                ADD COLUMN IF NOT EXISTS totp_secret varchar(64),
+                ADD COLUMN IF NOT EXISTS totp_enabled_at timestamptz,
+                ADD COLUMN IF NOT EXISTS totp_last_step bigint;
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS user_recovery_code (
+                id uuid PRIMARY KEY,
+                user_id uuid NOT NULL REFERENCES app_user (id) ON DELETE CASCADE,
+                code_hash varchar(64) NOT NULL,
+                created_at timestamptz NOT NULL,
+                used_at timestamptz
+            );
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_user_recovery_code_user
+                ON user_recovery_code (user_id);
+            "#,
+        ))
+        .await?;
+        // Instance-wide switches that owners flip at runtime.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS app_setting (
+                key varchar(64) PRIMARY KEY,
+                value text NOT NULL,
+                updated_by uuid REFERENCES app_user (id) ON DELETE SET NULL,
+                updated_at timestamptz NOT NULL
+            );
+            "#,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS app_setting;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS user_recovery_code;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            ALTER TABLE user_secret
+                DROP COLUMN IF EXISTS totp_last_step,
+                DROP COLUMN IF EXISTS totp_enabled_at,
+                DROP COLUMN IF EXISTS totp_secret;
+            "#,
+        ))
+        .await?;
+        Ok(())
+    }
+}
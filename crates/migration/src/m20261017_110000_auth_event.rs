@@ -0,0 +1,76 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Append-only trail of sign-in activity. `email` keeps what was typed, so failures
+        // against unknown accounts are still attributable.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS auth_event (
+                id uuid PRIMARY KEY,
+                kind varchar(32) NOT NULL,
+                user_id uuid REFERENCES app_user (id) ON DELETE SET NULL,
+                email varchar(254),
+                ip varchar(64),
+                user_agent varchar(512),
+                detail varchar(255),
+                created_at timestamptz NOT NULL
+            );
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_auth_event_created
+                ON auth_event (created_at DESC, id);
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_auth_event_user
+                ON auth_event (user_id, created_at DESC);
+            "#,
+        ))
+        .await?;
+        // One row per throttled key (`account:<email>` or `ip:<address>`).
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS login_throttle (
+                key varchar(320) PRIMARY KEY,
+                failures integer NOT NULL,
+                last_failure_at timestamptz NOT NULL,
+                locked_until timestamptz
+            );
+            "#,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS login_throttle;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS auth_event;",
+        ))
+        .await?;
+        Ok(())
+    }
+}
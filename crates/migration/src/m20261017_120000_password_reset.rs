@@ -0,0 +1,80 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Single-use reset links; only the SHA-256 of the token is stored.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS password_reset_token (
+                id uuid PRIMARY KEY,
+                user_id uuid NOT NULL REFERENCES app_user (id) ON DELETE CASCADE,
+                token_hash varchar(64) NOT NULL UNIQUE,
+                created_at timestamptz NOT NULL,
+                expires_at timestamptz NOT NULL,
+                used_at timestamptz
+            );
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_password_reset_token_user
+                ON password_reset_token (user_id, created_at DESC);
+            "#,
+        ))
+        .await?;
+        // Transactional outbox: mail is queued in the same transaction as the change
+        // that triggers it and delivered by a background worker.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS email_outbox (
+                id uuid PRIMARY KEY,
+                to_address varchar(320) NOT NULL,
+                subject varchar(255) NOT NULL,
+                body_text text NOT NULL,
+                created_at timestamptz NOT NULL,
+                attempts integer NOT NULL DEFAULT 0,
+                next_attempt_at timestamptz NOT NULL,
+                last_error varchar(1000),
+                sent_at timestamptz
+            );
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_email_outbox_pending
+                ON email_outbox (next_attempt_at)
+                WHERE sent_at IS NULL;
+            "#,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS email_outbox;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS password_reset_token;",
+        ))
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // `actor_id` has no foreign key: rows are never updated, so they cannot
+        // follow a user row with ON DELETE SET NULL.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id uuid PRIMARY KEY,
+                actor_id uuid,
+                entity_type varchar(32) NOT NULL,
+                entity_id uuid NOT NULL,
+                operation varchar(16) NOT NULL,
+                changes jsonb NOT NULL,
+                request_id varchar(64),
+                created_at timestamptz NOT NULL
+            );
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_audit_log_entity
+                ON audit_log (entity_type, entity_id, created_at DESC);
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_audit_log_actor
+                ON audit_log (actor_id, created_at DESC);
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_audit_log_created
+                ON audit_log (created_at DESC);
+            "#,
+        ))
+        .await?;
+        // Append-only: the application role cannot rewrite history either.
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger
+            LANGUAGE plpgsql AS $$
+            BEGIN
+                RAISE EXCEPTION 'audit_log is append-only';
+            END;
+            $$;
+            "#,
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            r#"
+            CREATE TRIGGER audit_log_append_only
+                BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+                FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
+            "#,
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP TABLE IF EXISTS audit_log;",
+        ))
+        .await?;
+        conn.execute(Statement::from_string(
+            DatabaseBackend::Postgres,
+            "DROP FUNCTION IF EXISTS audit_log_append_only();",
+        ))
+        .await?;
+        Ok(())
+    }
+}
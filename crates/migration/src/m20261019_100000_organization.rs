@@ -0,0 +1,232 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Tables whose rows belong to exactly one organization.
+const SCOPED_TABLES: [&str; 11] = [
+    "company",
+    "contact",
+    "deal",
+    "task",
+    "activity",
+    "deal_stage_history",
+    "api_key",
+    "user_session",
+    "user_role",
+    "app_setting",
+    "audit_log",
+];
+
+async fn run(conn: &SchemaManagerConnection<'_>, sql: &str) -> Result<(), DbErr> {
+    conn.execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        run(
+            conn,
+            r#"
+            CREATE TABLE IF NOT EXISTS organization (
+                id uuid PRIMARY KEY,
+                name varchar(200) NOT NULL,
+                slug varchar(64) NOT NULL UNIQUE,
+                created_at timestamptz NOT NULL DEFAULT now()
+            );
+            "#,
+        )
+        .await?;
+        // Everything that exists today moves into a single default organization.
+        let row = conn
+            .query_one(Statement::from_string(
+                DatabaseBackend::Postgres,
+                r#"
+                INSERT INTO organization (id, name, slug)
+                VALUES (gen_random_uuid(), 'Default', 'default')
+                RETURNING id::text AS id;
+                "#,
+            ))
+            .await?
+            .ok_or_else(|| DbErr::Migration("default organization was not created".into()))?;
+        let default_org: String = row.try_get("", "id")?;
+
+        run(
+            conn,
+            r#"
+            CREATE TABLE IF NOT EXISTS organization_member (
+                org_id uuid NOT NULL REFERENCES organization (id) ON DELETE CASCADE,
+                user_id uuid NOT NULL REFERENCES app_user (id) ON DELETE CASCADE,
+                is_active boolean NOT NULL DEFAULT true,
+                created_at timestamptz NOT NULL DEFAULT now(),
+                PRIMARY KEY (org_id, user_id)
+            );
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_organization_member_user ON organization_member (user_id);",
+        )
+        .await?;
+        run(
+            conn,
+            &format!(
+                "INSERT INTO organization_member (org_id, user_id, is_active, created_at) \
+                 SELECT '{default_org}'::uuid, id, is_active, created_at FROM app_user;"
+            ),
+        )
+        .await?;
+
+        // A constant default fills existing rows without an UPDATE, which the
+        // append-only trigger on audit_log would reject.
+        for table in SCOPED_TABLES {
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE {table} ADD COLUMN org_id uuid NOT NULL DEFAULT '{default_org}'::uuid;"
+                ),
+            )
+            .await?;
+            run(
+                conn,
+                &format!("ALTER TABLE {table} ALTER COLUMN org_id DROP DEFAULT;"),
+            )
+            .await?;
+            // Audit rows outlive the organization they describe.
+            if table != "audit_log" {
+                run(
+                    conn,
+                    &format!(
+                        "ALTER TABLE {table} ADD CONSTRAINT fk_{table}_organization \
+                         FOREIGN KEY (org_id) REFERENCES organization (id) ON DELETE CASCADE;"
+                    ),
+                )
+                .await?;
+            }
+        }
+
+        for table in [
+            "company",
+            "contact",
+            "deal",
+            "task",
+            "api_key",
+            "user_session",
+        ] {
+            run(
+                conn,
+                &format!("CREATE INDEX IF NOT EXISTS idx_{table}_org ON {table} (org_id);"),
+            )
+            .await?;
+        }
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_activity_org ON activity (org_id, entity_type, entity_id);",
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_deal_stage_history_org ON deal_stage_history (org_id, deal_id);",
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_audit_log_org ON audit_log (org_id, created_at DESC);",
+        )
+        .await?;
+
+        // Roles are granted per membership.
+        run(conn, "ALTER TABLE user_role DROP CONSTRAINT pk_user_role;").await?;
+        run(
+            conn,
+            "ALTER TABLE user_role ADD CONSTRAINT pk_user_role PRIMARY KEY (org_id, user_id, role);",
+        )
+        .await?;
+        run(
+            conn,
+            r#"
+            ALTER TABLE user_role ADD CONSTRAINT fk_user_role_member
+                FOREIGN KEY (org_id, user_id)
+                REFERENCES organization_member (org_id, user_id) ON DELETE CASCADE;
+            "#,
+        )
+        .await?;
+
+        run(
+            conn,
+            "ALTER TABLE app_setting DROP CONSTRAINT app_setting_pkey;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE app_setting ADD CONSTRAINT app_setting_pkey PRIMARY KEY (org_id, key);",
+        )
+        .await?;
+
+        run(conn, "DROP INDEX IF EXISTS idx_contact_company_email;").await?;
+        run(
+            conn,
+            r#"
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_contact_company_email ON contact (
+                org_id,
+                COALESCE(company_id, '00000000-0000-0000-0000-000000000000'::uuid),
+                lower(email)
+            );
+            "#,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        run(conn, "DROP INDEX IF EXISTS idx_contact_company_email;").await?;
+        run(
+            conn,
+            r#"
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_contact_company_email ON contact (
+                COALESCE(company_id, '00000000-0000-0000-0000-000000000000'::uuid),
+                lower(email)
+            );
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE app_setting DROP CONSTRAINT app_setting_pkey;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE app_setting ADD CONSTRAINT app_setting_pkey PRIMARY KEY (key);",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE user_role DROP CONSTRAINT fk_user_role_member;",
+        )
+        .await?;
+        run(conn, "ALTER TABLE user_role DROP CONSTRAINT pk_user_role;").await?;
+        run(
+            conn,
+            "ALTER TABLE user_role ADD CONSTRAINT pk_user_role PRIMARY KEY (user_id, role);",
+        )
+        .await?;
+        for table in SCOPED_TABLES {
+            run(
+                conn,
+                &format!("ALTER TABLE {table} DROP COLUMN IF EXISTS org_id;"),
+            )
+            .await?;
+        }
+        run(conn, "DROP TABLE IF EXISTS organization_member;").await?;
+        run(conn, "DROP TABLE IF EXISTS organization;").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const STAGE_KEYS: &str = "'NEW', 'QUALIFY', 'PROPOSAL', 'NEGOTIATE', 'WON', 'LOST'";
+
+async fn run(conn: &SchemaManagerConnection<'_>, sql: &str) -> Result<(), DbErr> {
+    conn.execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Stages become per-organization rows that deals point at, so each
+        // organization starts with its own copy of the shared defaults.
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_pkey;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT IF EXISTS stage_meta_sort_order_key;",
+        )
+        .await?;
+        run(conn, "DROP INDEX IF EXISTS idx_stage_meta_order;").await?;
+        run(conn, "ALTER TABLE stage_meta ADD COLUMN org_id uuid;").await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD COLUMN is_archived boolean NOT NULL DEFAULT false;",
+        )
+        .await?;
+        // Deals reference stages by key; the id only gives audit entries a subject.
+        run(
+            conn,
+            r#"
+            ALTER TABLE stage_meta ADD COLUMN id uuid NOT NULL DEFAULT gen_random_uuid()
+                CONSTRAINT stage_meta_id_key UNIQUE;
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            r#"
+            INSERT INTO stage_meta (org_id, key, display_name, sort_order, probability, is_won, is_lost)
+            SELECT o.id, s.key, s.display_name, s.sort_order, s.probability, s.is_won, s.is_lost
+            FROM organization o CROSS JOIN stage_meta s
+            WHERE s.org_id IS NULL;
+            "#,
+        )
+        .await?;
+        run(conn, "DELETE FROM stage_meta WHERE org_id IS NULL;").await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ALTER COLUMN org_id SET NOT NULL;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_pkey PRIMARY KEY (org_id, key);",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_sort_order_key UNIQUE (org_id, sort_order);",
+        )
+        .await?;
+        run(
+            conn,
+            r#"
+            ALTER TABLE stage_meta ADD CONSTRAINT fk_stage_meta_organization
+                FOREIGN KEY (org_id) REFERENCES organization (id) ON DELETE CASCADE;
+            "#,
+        )
+        .await?;
+
+        run(conn, "ALTER TABLE deal ALTER COLUMN stage DROP DEFAULT;").await?;
+        run(
+            conn,
+            "ALTER TABLE deal ALTER COLUMN stage TYPE varchar(32) USING stage::text;",
+        )
+        .await?;
+        run(
+            conn,
+            r#"
+            ALTER TABLE deal ADD CONSTRAINT fk_deal_stage
+                FOREIGN KEY (org_id, stage) REFERENCES stage_meta (org_id, key);
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_deal_org_stage ON deal (org_id, stage);",
+        )
+        .await?;
+        for column in ["from_stage", "to_stage"] {
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history ALTER COLUMN {column} \
+                     TYPE varchar(32) USING {column}::text;"
+                ),
+            )
+            .await?;
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history ADD CONSTRAINT fk_deal_stage_history_{column} \
+                     FOREIGN KEY (org_id, {column}) REFERENCES stage_meta (org_id, key);"
+                ),
+            )
+            .await?;
+        }
+        run(conn, "DROP TYPE IF EXISTS deal_stage;").await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Only the original six stages fit the enum; deals in added stages go back to NEW.
+        run(
+            conn,
+            &format!("CREATE TYPE deal_stage AS ENUM ({STAGE_KEYS});"),
+        )
+        .await?;
+        for column in ["from_stage", "to_stage"] {
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history DROP CONSTRAINT fk_deal_stage_history_{column};"
+                ),
+            )
+            .await?;
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history ALTER COLUMN {column} TYPE deal_stage \
+                     USING (CASE WHEN {column} IN ({STAGE_KEYS}) THEN {column} ELSE 'NEW' END)::deal_stage;"
+                ),
+            )
+            .await?;
+        }
+        run(conn, "DROP INDEX IF EXISTS idx_deal_org_stage;").await?;
+        run(conn, "ALTER TABLE deal DROP CONSTRAINT fk_deal_stage;").await?;
+        run(
+            conn,
+            &format!(
+                "ALTER TABLE deal ALTER COLUMN stage TYPE deal_stage \
+                 USING (CASE WHEN stage IN ({STAGE_KEYS}) THEN stage ELSE 'NEW' END)::deal_stage;"
+            ),
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE deal ALTER COLUMN stage SET DEFAULT 'NEW'::deal_stage;",
+        )
+        .await?;
+
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT fk_stage_meta_organization;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_sort_order_key;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_pkey;",
+        )
+        .await?;
+        // The shared list is taken from the default organization.
+        run(
+            conn,
+            &format!(
+                "DELETE FROM stage_meta \
+                 WHERE org_id <> (SELECT id FROM organization WHERE slug = 'default') \
+                 OR key NOT IN ({STAGE_KEYS});"
+            ),
+        )
+        .await?;
+        run(conn, "ALTER TABLE stage_meta DROP COLUMN org_id;").await?;
+        run(conn, "ALTER TABLE stage_meta DROP COLUMN is_archived;").await?;
+        run(conn, "ALTER TABLE stage_meta DROP COLUMN id;").await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_pkey PRIMARY KEY (key);",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_sort_order_key UNIQUE (sort_order);",
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_stage_meta_order ON stage_meta (sort_order);",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn run(conn: &SchemaManagerConnection<'_>, sql: &str) -> Result<(), DbErr> {
+    conn.execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        run(
+            conn,
+            r#"
+            CREATE TABLE IF NOT EXISTS pipeline (
+                id uuid PRIMARY KEY,
+                org_id uuid NOT NULL REFERENCES organization (id) ON DELETE CASCADE,
+                name varchar(64) NOT NULL,
+                is_default boolean NOT NULL DEFAULT false,
+                created_at timestamptz NOT NULL DEFAULT now(),
+                CONSTRAINT pipeline_org_name_key UNIQUE (org_id, name)
+            );
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE UNIQUE INDEX IF NOT EXISTS uq_pipeline_default ON pipeline (org_id) WHERE is_default;",
+        )
+        .await?;
+        // Each organization's existing stages and deals become its default pipeline.
+        run(
+            conn,
+            r#"
+            INSERT INTO pipeline (id, org_id, name, is_default)
+            SELECT gen_random_uuid(), o.id, 'Sales', true FROM organization o;
+            "#,
+        )
+        .await?;
+
+        run(conn, "ALTER TABLE stage_meta ADD COLUMN pipeline_id uuid;").await?;
+        run(
+            conn,
+            r#"
+            UPDATE stage_meta s SET pipeline_id = p.id
+            FROM pipeline p WHERE p.org_id = s.org_id AND p.is_default;
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ALTER COLUMN pipeline_id SET NOT NULL;",
+        )
+        .await?;
+        run(
+            conn,
+            r#"
+            ALTER TABLE stage_meta ADD CONSTRAINT fk_stage_meta_pipeline
+                FOREIGN KEY (pipeline_id) REFERENCES pipeline (id) ON DELETE CASCADE;
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_sort_order_key;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_sort_order_key UNIQUE (pipeline_id, sort_order);",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_pipeline_key UNIQUE (pipeline_id, key);",
+        )
+        .await?;
+
+        run(conn, "ALTER TABLE deal ADD COLUMN pipeline_id uuid;").await?;
+        run(
+            conn,
+            r#"
+            UPDATE deal d SET pipeline_id = s.pipeline_id
+            FROM stage_meta s WHERE s.org_id = d.org_id AND s.key = d.stage;
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE deal ALTER COLUMN pipeline_id SET NOT NULL;",
+        )
+        .await?;
+        // Together with fk_deal_stage this keeps a deal's stage inside its own pipeline.
+        run(
+            conn,
+            r#"
+            ALTER TABLE deal ADD CONSTRAINT fk_deal_pipeline_stage
+                FOREIGN KEY (pipeline_id, stage) REFERENCES stage_meta (pipeline_id, key);
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_deal_pipeline_stage ON deal (pipeline_id, stage);",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Stage keys are unique per organization, so every stage survives; deals simply
+        // stop recording which pipeline they were in.
+        run(conn, "DROP INDEX IF EXISTS idx_deal_pipeline_stage;").await?;
+        run(
+            conn,
+            "ALTER TABLE deal DROP CONSTRAINT fk_deal_pipeline_stage;",
+        )
+        .await?;
+        run(conn, "ALTER TABLE deal DROP COLUMN pipeline_id;").await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_pipeline_key;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_sort_order_key;",
+        )
+        .await?;
+        // Sort orders only have to be unique per organization again.
+        run(
+            conn,
+            r#"
+            UPDATE stage_meta s SET sort_order = r.position * 10
+            FROM (
+                SELECT m.org_id, m.key,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY m.org_id ORDER BY p.is_default DESC, p.created_at, m.sort_order
+                       ) AS position
+                FROM stage_meta m JOIN pipeline p ON p.id = m.pipeline_id
+            ) r
+            WHERE r.org_id = s.org_id AND r.key = s.key;
+            "#,
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_sort_order_key UNIQUE (org_id, sort_order);",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT fk_stage_meta_pipeline;",
+        )
+        .await?;
+        run(conn, "ALTER TABLE stage_meta DROP COLUMN pipeline_id;").await?;
+        run(conn, "DROP TABLE IF EXISTS pipeline;").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn run(conn: &SchemaManagerConnection<'_>, sql: &str) -> Result<(), DbErr> {
+    conn.execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD COLUMN allowed_from jsonb;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD COLUMN required_fields jsonb NOT NULL DEFAULT '[]'::jsonb;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD COLUMN requires_note boolean NOT NULL DEFAULT false;",
+        )
+        .await?;
+        // Losing a deal should always say why.
+        run(
+            conn,
+            "UPDATE stage_meta SET requires_note = true WHERE is_lost;",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        for column in ["allowed_from", "required_fields", "requires_note"] {
+            run(
+                conn,
+                &format!("ALTER TABLE stage_meta DROP COLUMN {column};"),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn run(conn: &SchemaManagerConnection<'_>, sql: &str) -> Result<(), DbErr> {
+    conn.execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        run(
+            conn,
+            r#"
+            CREATE TABLE IF NOT EXISTS deal_reason (
+                id uuid PRIMARY KEY,
+                org_id uuid NOT NULL REFERENCES organization (id) ON DELETE CASCADE,
+                outcome varchar(8) NOT NULL CHECK (outcome IN ('won', 'lost')),
+                label varchar(100) NOT NULL,
+                is_archived boolean NOT NULL DEFAULT false,
+                created_at timestamptz NOT NULL DEFAULT now(),
+                CONSTRAINT deal_reason_label_key UNIQUE (org_id, outcome, label)
+            );
+            "#,
+        )
+        .await?;
+        for table in ["deal", "deal_stage_history"] {
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE {table} ADD COLUMN reason_id uuid \
+                     REFERENCES deal_reason (id) ON DELETE SET NULL;"
+                ),
+            )
+            .await?;
+            run(
+                conn,
+                &format!("ALTER TABLE {table} ADD COLUMN competitor varchar(200);"),
+            )
+            .await?;
+        }
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_deal_reason ON deal (reason_id);",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        run(conn, "DROP INDEX IF EXISTS idx_deal_reason;").await?;
+        for table in ["deal", "deal_stage_history"] {
+            run(
+                conn,
+                &format!("ALTER TABLE {table} DROP COLUMN competitor;"),
+            )
+            .await?;
+            run(conn, &format!("ALTER TABLE {table} DROP COLUMN reason_id;")).await?;
+        }
+        run(conn, "DROP TABLE IF EXISTS deal_reason;").await?;
+        Ok(())
+    }
+}
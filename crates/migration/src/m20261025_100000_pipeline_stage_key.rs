@@ -0,0 +1,138 @@
+use sea_orm::DatabaseBackend;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn run(conn: &SchemaManagerConnection<'_>, sql: &str) -> Result<(), DbErr> {
+    conn.execute(Statement::from_string(DatabaseBackend::Postgres, sql))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Stage keys only have to be unique within a pipeline, so every pipeline can have
+        // its own WON and LOST. Deals already reference (pipeline_id, stage) through
+        // fk_deal_pipeline_stage; history rows learn which pipelines their keys belong to.
+        run(conn, "ALTER TABLE deal DROP CONSTRAINT fk_deal_stage;").await?;
+        for column in ["from", "to"] {
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history DROP CONSTRAINT fk_deal_stage_history_{column}_stage;"
+                ),
+            )
+            .await?;
+            run(
+                conn,
+                &format!("ALTER TABLE deal_stage_history ADD COLUMN {column}_pipeline_id uuid;"),
+            )
+            .await?;
+            run(
+                conn,
+                &format!(
+                    "UPDATE deal_stage_history h SET {column}_pipeline_id = s.pipeline_id \
+                     FROM stage_meta s WHERE s.org_id = h.org_id AND s.key = h.{column}_stage;"
+                ),
+            )
+            .await?;
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history ALTER COLUMN {column}_pipeline_id SET NOT NULL;"
+                ),
+            )
+            .await?;
+        }
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_pkey;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_id_key;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_pkey PRIMARY KEY (id);",
+        )
+        .await?;
+        run(
+            conn,
+            "CREATE INDEX IF NOT EXISTS idx_stage_meta_org ON stage_meta (org_id);",
+        )
+        .await?;
+        for column in ["from", "to"] {
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history ADD CONSTRAINT fk_deal_stage_history_{column}_stage \
+                     FOREIGN KEY ({column}_pipeline_id, {column}_stage) \
+                     REFERENCES stage_meta (pipeline_id, key);"
+                ),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let conn = manager.get_connection();
+        // Fails while two of an organization's pipelines share a stage key.
+        for column in ["from", "to"] {
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history DROP CONSTRAINT fk_deal_stage_history_{column}_stage;"
+                ),
+            )
+            .await?;
+        }
+        run(conn, "DROP INDEX IF EXISTS idx_stage_meta_org;").await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta DROP CONSTRAINT stage_meta_pkey;",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_pkey PRIMARY KEY (org_id, key);",
+        )
+        .await?;
+        run(
+            conn,
+            "ALTER TABLE stage_meta ADD CONSTRAINT stage_meta_id_key UNIQUE (id);",
+        )
+        .await?;
+        for column in ["from", "to"] {
+            run(
+                conn,
+                &format!("ALTER TABLE deal_stage_history DROP COLUMN {column}_pipeline_id;"),
+            )
+            .await?;
+            run(
+                conn,
+                &format!(
+                    "ALTER TABLE deal_stage_history ADD CONSTRAINT fk_deal_stage_history_{column}_stage \
+                     FOREIGN KEY (org_id, {column}_stage) REFERENCES stage_meta (org_id, key);"
+                ),
+            )
+            .await?;
+        }
+        run(
+            conn,
+            r#"
+            ALTER TABLE deal ADD CONSTRAINT fk_deal_stage
+                FOREIGN KEY (org_id, stage) REFERENCES stage_meta (org_id, key);
+            "#,
+        )
+        .await?;
+        Ok(())
+    }
+}
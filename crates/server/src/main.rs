@@ -1,37 +1,66 @@
 use api::{
+    api_key::{authenticate_api_key, parse_scopes},
     auth::{
-        build_session_cookie, decode_session_token, issue_session_token, AuthConfig, AuthMode,
-        CurrentUser, UserRole, SESSION_COOKIE,
+        build_csrf_cookie, build_session_cookie, clear_csrf_cookie, csrf_matches,
+        decode_session_token, generate_csrf_token, issue_challenge_token, issue_session_token,
+        sign_claims, verify_claims, AuthConfig, AuthMode, ChallengePurpose, ClientInfo,
+        CurrentSession, CurrentUser, PasswordPolicy, UserRole, CSRF_COOKIE, CSRF_HEADER,
+        DEFAULT_MIN_PASSWORD_LENGTH, DEFAULT_SESSION_MAX_AGE_MINUTES, SESSION_COOKIE,
     },
-    schema::{build_schema, AppSchema},
+    auth_event::record_auth_event,
+    events::EventBus,
+    keyring::{Keyring, SigningKey},
+    mailer::{Mailer, SmtpConfig, SmtpSecurity},
+    oidc::{
+        resolve_oidc_user, OidcClient, OidcConfig, OidcFlow, OIDC_FLOW_COOKIE, OIDC_FLOW_TTL_SECS,
+    },
+    organization::{
+        add_member, create_organization, default_organization, find_organization, member_roles,
+        normalize_slug,
+    },
+    outbox::deliver_due,
+    schema::{build_schema_with_events, AppSchema},
+    session::{start_session, touch_session},
+    totp::pending_second_factor,
 };
+use async_graphql::Data;
 use async_graphql::{http::GraphiQLSource, Schema};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use axum::{
     body::Body,
-    extract::{Extension, State},
-    http::{HeaderMap, Request as AxumRequest},
+    extract::{ws::WebSocketUpgrade, ConnectInfo, Extension, Query, State},
+    http::{
+        header, HeaderMap, HeaderName, HeaderValue, Method, Request as AxumRequest, StatusCode,
+    },
     middleware::{from_fn_with_state, Next},
-    response::Response,
+    response::{IntoResponse, Response},
     routing::get,
     Router,
 };
 use chrono::{Duration, Utc};
 use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
-use entity::{app_user, user_role};
+use entity::{app_user, auth_event, user_secret};
 use migration::{Migrator, MigratorTrait};
-use sea_orm::{ColumnTrait, Database, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use sea_orm::{
+    ColumnTrait, Database, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    TransactionTrait,
+};
+use serde::Deserialize;
 use std::{net::SocketAddr, sync::Arc};
 use tokio::net::TcpListener;
 use tower_http::{
     compression::CompressionLayer,
-    cors::{Any, CorsLayer},
+    cors::{AllowOrigin, Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     trace::TraceLayer,
 };
-use tracing::{info, Level};
+use tracing::{info, warn, Level};
 use uuid::Uuid;
 
+const OUTBOX_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+const OUTBOX_BATCH: u64 = 20;
+
 #[derive(Parser, Debug)]
 #[command(name = "fossrust-sme-suite", version)]
 struct Cli {
@@ -52,6 +81,15 @@ enum Cmd {
     },
     /// Seed sample data
     Seed,
+    /// Create an organization owned by an existing user
+    CreateOrganization {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        slug: String,
+        #[arg(long)]
+        owner_email: String,
+    },
     /// Print GraphQL SDL
     PrintSchema,
 }
@@ -59,10 +97,24 @@ enum Cmd {
 #[derive(Clone)]
 struct AppState {
     schema:
-        Schema<api::schema::QueryRoot, api::schema::MutationRoot, async_graphql::EmptySubscription>,
+        Schema<api::schema::QueryRoot, api::schema::MutationRoot, api::schema::SubscriptionRoot>,
     db: Arc<DatabaseConnection>,
     auth: Arc<AuthConfig>,
     dev_user: Option<CurrentUser>,
+    oidc: Option<Arc<OidcClient>>,
+    cors: CorsOrigins,
+}
+
+/// Which other sites may call the API from a browser, from `CORS_ALLOWED_ORIGINS`.
+#[derive(Clone, Debug, Default)]
+enum CorsOrigins {
+    /// Same-origin only.
+    #[default]
+    None,
+    /// `*`: any site, but never with the session cookie.
+    Any,
+    /// These sites, which may send the session cookie.
+    List(Vec<HeaderValue>),
 }
 
 #[tokio::main]
@@ -82,6 +134,9 @@ async fn main() -> anyhow::Result<()> {
     };
     let db = Arc::new(Database::connect(&db_url).await?);
     let auth_config = Arc::new(load_auth_config_from_env()?);
+    let cors = load_cors_origins_from_env()?;
+    let oidc = load_oidc_config_from_env(auth_config.mode)?
+        .map(|config| Arc::new(OidcClient::new(config)));
 
     match cli.cmd {
         Cmd::Migrate { action } => {
@@ -97,14 +152,29 @@ async fn main() -> anyhow::Result<()> {
             seed(db.as_ref()).await?;
             Ok(())
         }
+        Cmd::CreateOrganization {
+            name,
+            slug,
+            owner_email,
+        } => {
+            let org = create_organization_for(db.as_ref(), &name, &slug, &owner_email).await?;
+            println!("{} ({})", org.slug, org.id);
+            Ok(())
+        }
         Cmd::PrintSchema => {
-            let AppSchema(schema) = build_schema(db.clone(), auth_config.clone());
+            let AppSchema(schema) =
+                build_schema_with_events(db.clone(), auth_config.clone(), EventBus::default());
             println!("{}", schema.sdl());
             Ok(())
         }
         Cmd::Serve { bind } => {
             Migrator::up(db.as_ref(), None).await?;
-            let AppSchema(schema) = build_schema(db.clone(), auth_config.clone());
+            let events = load_event_bus_from_env(db.clone())?;
+            if events.fans_out() {
+                tokio::spawn(events.clone().listen(db_url.clone()));
+            }
+            let AppSchema(schema) =
+                build_schema_with_events(db.clone(), auth_config.clone(), events);
             let dev_user = if auth_config.mode == AuthMode::Disabled {
                 load_default_user(db.as_ref()).await?
             } else {
@@ -115,7 +185,18 @@ async fn main() -> anyhow::Result<()> {
                 db: db.clone(),
                 auth: auth_config.clone(),
                 dev_user,
+                oidc,
+                cors,
             };
+            match load_smtp_config_from_env()? {
+                Some(config) => {
+                    tokio::spawn(deliver_outbox(db.clone(), Mailer::new(&config)?));
+                }
+                None if auth_config.mode.allows_password() => {
+                    warn!("SMTP_HOST is not set; password reset emails stay queued");
+                }
+                None => {}
+            }
             let app = app_router(state);
 
             let addr: SocketAddr = bind.parse()?;
@@ -134,50 +215,403 @@ async fn main() -> anyhow::Result<()> {
 
 fn app_router(state: AppState) -> Router {
     let middleware_state = state.clone();
+    let cors = cors_layer(&state.cors);
     Router::new()
         .route("/healthz", get(|| async { "ok" }))
+        .route("/.well-known/jwks.json", get(jwks))
         .route("/graphiql", get(graphiql))
         .route("/graphql", get(graphql_get).post(graphql_post))
+        .route("/graphql/ws", get(graphql_ws))
+        .route("/auth/oidc/login", get(oidc_login))
+        .route("/auth/oidc/callback", get(oidc_callback))
         .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http())
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
-        )
         .layer(from_fn_with_state(middleware_state, auth_middleware))
+        // Keeps a caller's `x-request-id` or assigns one, and echoes it back.
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        // Outermost, so CSRF rejections still carry CORS headers the client can read.
+        .layer(cors)
         .with_state(state)
 }
 
+fn cors_layer(origins: &CorsOrigins) -> CorsLayer {
+    let csrf_header = HeaderName::from_static(CSRF_HEADER);
+    match origins {
+        CorsOrigins::None => CorsLayer::new(),
+        CorsOrigins::Any => CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers([csrf_header]),
+        CorsOrigins::List(list) => CorsLayer::new()
+            .allow_origin(AllowOrigin::list(list.clone()))
+            .allow_credentials(true)
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::AUTHORIZATION,
+                csrf_header.clone(),
+            ])
+            .expose_headers([csrf_header]),
+    }
+}
+
+/// Queries only: links and images can trigger GETs from any site, and those
+/// bypass the CSRF check.
 async fn graphql_get(
     State(state): State<AppState>,
     current_user: Option<Extension<CurrentUser>>,
+    current_session: Option<Extension<CurrentSession>>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
     req: GraphQLRequest,
-) -> GraphQLResponse {
-    execute_graphql(state, current_user, req).await
+) -> Response {
+    if is_mutation(&req.0) {
+        let body = serde_json::json!({
+            "errors": [{
+                "message": "Mutations must be sent with POST",
+                "extensions": { "code": "BAD_REQUEST" },
+            }]
+        });
+        let allow = [(header::ALLOW, "POST")];
+        return (StatusCode::METHOD_NOT_ALLOWED, allow, axum::Json(body)).into_response();
+    }
+    let client = client_info(&headers, connect_info);
+    execute_graphql(state, current_user, current_session, client, req)
+        .await
+        .into_response()
+}
+
+/// Whether the operation the request would run is a mutation. Unparsable
+/// documents are left for the executor to report.
+fn is_mutation(request: &async_graphql::Request) -> bool {
+    use async_graphql::parser::{parse_query, types::DocumentOperations, types::OperationType};
+    let Ok(document) = parse_query(&request.query) else {
+        return false;
+    };
+    let operations: Vec<_> = match &document.operations {
+        DocumentOperations::Single(operation) => vec![operation],
+        DocumentOperations::Multiple(operations) => operations
+            .iter()
+            .filter(|(name, _)| {
+                request
+                    .operation_name
+                    .as_deref()
+                    .is_none_or(|wanted| name.as_str() == wanted)
+            })
+            .map(|(_, operation)| operation)
+            .collect(),
+    };
+    operations
+        .iter()
+        .any(|operation| operation.node.ty == OperationType::Mutation)
 }
 
 async fn graphql_post(
     State(state): State<AppState>,
     current_user: Option<Extension<CurrentUser>>,
+    current_session: Option<Extension<CurrentSession>>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
-    execute_graphql(state, current_user, req).await
+    let client = client_info(&headers, connect_info);
+    execute_graphql(state, current_user, current_session, client, req).await
+}
+
+/// Subscriptions over `graphql-ws` (or the older `subscriptions-transport-ws`), for
+/// the same session as `/graphql`. The upgrade is a GET that browsers send cross-site
+/// with the cookie attached, so unlike `/graphql` it is guarded by `Origin` instead of
+/// the CSRF header.
+async fn graphql_ws(
+    State(state): State<AppState>,
+    current_user: Option<Extension<CurrentUser>>,
+    current_session: Option<Extension<CurrentSession>>,
+    headers: HeaderMap,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    if !websocket_origin_allowed(&headers, &state.cors) {
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    let Some(Extension(user)) = current_user else {
+        return (StatusCode::UNAUTHORIZED, "Not authenticated").into_response();
+    };
+    let mut data = Data::default();
+    data.insert(user);
+    if let Some(Extension(session)) = current_session {
+        data.insert(session);
+    }
+    let schema = state.schema.clone();
+    upgrade
+        .protocols(async_graphql::http::ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |socket| {
+            GraphQLWebSocket::new(socket, schema, protocol)
+                .with_data(data)
+                .serve()
+        })
+}
+
+/// Same-origin pages and the sites listed in `CORS_ALLOWED_ORIGINS` may open a
+/// socket; clients that send no `Origin` are not browsers acting for another site.
+fn websocket_origin_allowed(headers: &HeaderMap, cors: &CorsOrigins) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    if let CorsOrigins::List(list) = cors {
+        if list.contains(origin) {
+            return true;
+        }
+    }
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    let authority = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, authority)| authority);
+    matches!((authority, host), (Some(authority), Some(host)) if authority.eq_ignore_ascii_case(host))
 }
 
 async fn execute_graphql(
     state: AppState,
     current_user: Option<Extension<CurrentUser>>,
+    current_session: Option<Extension<CurrentSession>>,
+    client: ClientInfo,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
     let mut request = req.into_inner();
     if let Some(Extension(user)) = current_user {
         request = request.data(user);
     }
+    if let Some(Extension(session)) = current_session {
+        request = request.data(session);
+    }
+    request = request.data(client);
     state.schema.execute(request).await.into()
 }
 
+/// The user agent, peer address and request id the schema records for sign-in
+/// activity and audit rows.
+fn client_info(
+    headers: &HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+) -> ClientInfo {
+    ClientInfo {
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        ip: connect_info.map(|Extension(ConnectInfo(addr))| addr.ip().to_string()),
+        request_id: headers
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+/// Sends the browser to the IdP with fresh state, nonce and PKCE challenge.
+async fn oidc_login(State(state): State<AppState>) -> Response {
+    let Some(oidc) = state.oidc.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (url, flow) = match oidc.begin().await {
+        Ok(started) => started,
+        Err(err) => {
+            warn!(error = %err, "oidc login could not start");
+            return (StatusCode::BAD_GATEWAY, "Identity provider unavailable").into_response();
+        }
+    };
+    let Ok(flow_token) = sign_claims(&flow, &state.auth) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let cookie = format!(
+        "{}={}; Max-Age={}; Path=/auth/oidc; HttpOnly; SameSite=Lax",
+        OIDC_FLOW_COOKIE, flow_token, OIDC_FLOW_TTL_SECS
+    );
+    (
+        StatusCode::SEE_OTHER,
+        [(header::LOCATION, url), (header::SET_COOKIE, cookie)],
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct OidcCallback {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Redeems the code, maps the ID token to a user and starts a session. When the
+/// account owes a second factor, the redirect instead carries a challenge token
+/// in its fragment for `verifySecondFactor` or TOTP enrolment, as `login` does.
+async fn oidc_callback(
+    State(state): State<AppState>,
+    Query(params): Query<OidcCallback>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(oidc) = state.oidc.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Some(error) = params.error {
+        return (
+            StatusCode::UNAUTHORIZED,
+            format!("Sign-in failed: {}", error),
+        )
+            .into_response();
+    }
+    let flow = extract_cookie(&headers, OIDC_FLOW_COOKIE)
+        .and_then(|token| verify_claims::<OidcFlow>(&token, &state.auth).ok());
+    let (Some(flow), Some(code)) = (flow, params.code) else {
+        return (StatusCode::BAD_REQUEST, "Sign-in expired, please retry").into_response();
+    };
+    if params.state.as_deref() != Some(flow.state.as_str()) {
+        return (StatusCode::BAD_REQUEST, "Sign-in state mismatch").into_response();
+    }
+    let claims = match oidc.finish(&flow, &code).await {
+        Ok(claims) => claims,
+        Err(err) => {
+            warn!(error = %err, "oidc code exchange failed");
+            return (StatusCode::UNAUTHORIZED, "Sign-in failed").into_response();
+        }
+    };
+    let client = client_info(&headers, connect_info);
+    let user = match resolve_oidc_user(state.db.as_ref(), oidc.config(), &claims).await {
+        Ok(user) => user,
+        Err(err) => {
+            warn!(error = %err, subject = %claims.sub, "oidc user rejected");
+            let event = record_auth_event(
+                state.db.as_ref(),
+                auth_event::Kind::LoginFailed,
+                None,
+                claims.email.as_deref(),
+                Some(&client),
+                Some("oidc_rejected"),
+            );
+            if let Err(err) = event.await {
+                warn!(error = %err, "auth event not recorded");
+            }
+            return (StatusCode::FORBIDDEN, format!("Sign-in rejected: {}", err)).into_response();
+        }
+    };
+    let org = match default_organization(state.db.as_ref(), user.id).await {
+        Ok(Some(org)) => org,
+        Ok(None) => {
+            return (StatusCode::FORBIDDEN, "No access to any organization").into_response();
+        }
+        Err(err) => {
+            warn!(error = %err, "oidc organization lookup failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    match oidc_second_factor(state.db.as_ref(), user.id, org.id).await {
+        Ok(None) => {}
+        Ok(Some(purpose)) => {
+            let Ok(challenge) = issue_challenge_token(user.id, org.id, purpose, &state.auth) else {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            };
+            let next_step = match purpose {
+                ChallengePurpose::Verify => "VERIFY_TOTP",
+                ChallengePurpose::Enroll => "ENROLL_TOTP",
+            };
+            let location = format!(
+                "{}#next_step={}&challenge_token={}",
+                oidc.config().post_login_redirect,
+                next_step,
+                challenge
+            );
+            return oidc_redirect(location, None, &state.auth);
+        }
+        Err(status) => {
+            return (
+                status,
+                "Two-factor authentication is required; set a password and enrol first",
+            )
+                .into_response();
+        }
+    }
+    let user_agent = client.user_agent.as_deref();
+    let session =
+        match start_session(state.db.as_ref(), user.id, org.id, user_agent, &state.auth).await {
+            Ok(session) => session,
+            Err(err) => {
+                warn!(error = %err, "oidc session could not start");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+    let Ok(token) = issue_session_token(user.id, org.id, session.id, &state.auth) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let event = record_auth_event(
+        state.db.as_ref(),
+        auth_event::Kind::LoginSucceeded,
+        Some(user.id),
+        Some(&user.email),
+        Some(&client),
+        Some("oidc"),
+    );
+    if let Err(err) = event.await {
+        warn!(error = %err, "auth event not recorded");
+    }
+    oidc_redirect(
+        oidc.config().post_login_redirect.clone(),
+        Some(&token),
+        &state.auth,
+    )
+}
+
+/// The second factor an OIDC login into `org_id` still owes. TOTP lives on the
+/// password secret, so an account without one cannot enrol from here and is
+/// refused (`FORBIDDEN`) rather than let past the policy.
+async fn oidc_second_factor(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    org_id: Uuid,
+) -> Result<Option<ChallengePurpose>, StatusCode> {
+    let internal = |err: sea_orm::DbErr| {
+        warn!(error = %err, "oidc second factor lookup failed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let roles = member_roles(db, org_id, user_id)
+        .await
+        .map_err(internal)?
+        .unwrap_or_default();
+    let purpose = pending_second_factor(db, user_id, org_id, &roles)
+        .await
+        .map_err(internal)?;
+    if purpose == Some(ChallengePurpose::Enroll)
+        && user_secret::Entity::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(internal)?
+            .is_none()
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(purpose)
+}
+
+/// Ends the OIDC flow with a redirect to `location`, dropping the flow cookie and
+/// setting the session cookie when one was issued.
+fn oidc_redirect(location: String, session_token: Option<&str>, auth: &AuthConfig) -> Response {
+    let mut response = (StatusCode::SEE_OTHER, [(header::LOCATION, location)]).into_response();
+    let cookies = session_token
+        .map(|token| build_session_cookie(token, auth.session_ttl_minutes))
+        .into_iter()
+        .chain([format!(
+            "{}=; Max-Age=0; Path=/auth/oidc; HttpOnly; SameSite=Lax",
+            OIDC_FLOW_COOKIE
+        )]);
+    for cookie in cookies {
+        if let Ok(value) = cookie.parse() {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    response
+}
+
 async fn load_default_user(db: &DatabaseConnection) -> anyhow::Result<Option<CurrentUser>> {
     let user = match app_user::Entity::find()
         .order_by_asc(app_user::Column::CreatedAt)
@@ -187,7 +621,38 @@ async fn load_default_user(db: &DatabaseConnection) -> anyhow::Result<Option<Cur
         Some(user) => user,
         None => return Ok(None),
     };
-    Ok(load_current_user(db, user.id).await)
+    let Some(org) = default_organization(db, user.id).await? else {
+        return Ok(None);
+    };
+    Ok(load_current_user(db, user.id, org.id).await)
+}
+
+async fn create_organization_for(
+    db: &DatabaseConnection,
+    name: &str,
+    slug: &str,
+    owner_email: &str,
+) -> anyhow::Result<entity::organization::Model> {
+    let slug = normalize_slug(slug)
+        .ok_or_else(|| anyhow::anyhow!("slug must be lowercase letters, digits and dashes"))?;
+    if find_organization(db, &slug).await?.is_some() {
+        anyhow::bail!("organization {} already exists", slug);
+    }
+    let owner = app_user::Entity::find()
+        .filter(app_user::Column::Email.eq(owner_email.trim().to_lowercase()))
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("no user with email {}", owner_email))?;
+    let txn = db.begin().await?;
+    let org = create_organization(&txn, name.trim(), &slug).await?;
+    add_member(&txn, org.id, owner.id, &[UserRole::Owner, UserRole::Admin]).await?;
+    txn.commit().await?;
+    Ok(org)
+}
+
+/// Public session signing keys; empty while only shared secrets are in use.
+async fn jwks(State(state): State<AppState>) -> axum::Json<serde_json::Value> {
+    axum::Json(state.auth.keyring.jwks())
 }
 
 async fn graphiql() -> (axum::http::HeaderMap, String) {
@@ -200,6 +665,17 @@ async fn graphiql() -> (axum::http::HeaderMap, String) {
     (headers, html)
 }
 
+/// Drains the email outbox for as long as the server runs.
+async fn deliver_outbox(db: Arc<DatabaseConnection>, mailer: Mailer) {
+    let mut ticker = tokio::time::interval(OUTBOX_POLL_INTERVAL);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = deliver_due(db.as_ref(), &mailer, OUTBOX_BATCH).await {
+            warn!("email outbox: {}", err);
+        }
+    }
+}
+
 async fn shutdown_signal() {
     use tokio::signal;
     let ctrl_c = async {
@@ -236,6 +712,7 @@ async fn seed(db: &DatabaseConnection) -> anyhow::Result<()> {
         .deal_titled("ACME Pilot")
         .ok_or_else(|| anyhow::anyhow!("missing seeded ACME Pilot deal"))?;
 
+    let org_id = seeded.organization.id;
     let owner_user = seeded
         .user_email("owner@sme.test")
         .ok_or_else(|| anyhow::anyhow!("missing seeded owner user"))?;
@@ -247,6 +724,7 @@ async fn seed(db: &DatabaseConnection) -> anyhow::Result<()> {
     let open_due = now + Duration::days(7);
     task::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("Schedule kickoff call".into()),
         notes_md: Set(Some("Prepare deck with milestones.".into())),
         status: Set(task::Status::Open),
@@ -269,6 +747,7 @@ async fn seed(db: &DatabaseConnection) -> anyhow::Result<()> {
     let done_due = now - Duration::days(3);
     task::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("Send proposal".into()),
         notes_md: Set(Some("Proposal approved internally.".into())),
         status: Set(task::Status::Done),
@@ -290,6 +769,7 @@ async fn seed(db: &DatabaseConnection) -> anyhow::Result<()> {
 
     task::ActiveModel {
         id: Set(Uuid::new_v4()),
+        org_id: Set(org_id),
         title: Set("Reschedule intro".into()),
         notes_md: Set(Some("Waiting on contact availability.".into())),
         status: Set(task::Status::Cancelled),
@@ -318,18 +798,60 @@ async fn auth_middleware(
     next: Next,
 ) -> Response {
     let mut refresh_cookie: Option<String> = None;
+    let mut csrf_token: Option<String> = None;
     match state.auth.mode {
         AuthMode::Disabled => {
             if let Some(dev) = &state.dev_user {
                 req.extensions_mut().insert(dev.clone());
             }
         }
-        AuthMode::Local => {
-            if let Some(token) = extract_session_token(req.headers()) {
+        AuthMode::Local | AuthMode::Oidc | AuthMode::Mixed => {
+            // A bearer header is authoritative: a bad key never falls back to the cookie.
+            if let Some(token) = extract_bearer_token(req.headers()) {
+                if let Some(user) = load_api_key_user(state.db.as_ref(), &token).await {
+                    req.extensions_mut().insert(user);
+                }
+            } else if let Some(token) = extract_session_token(req.headers()) {
                 if let Ok(claims) = decode_session_token(&token, &state.auth) {
-                    if let Some(user) = load_current_user(state.db.as_ref(), claims.sub).await {
+                    let live = touch_session(
+                        state.db.as_ref(),
+                        claims.sid,
+                        claims.sub,
+                        claims.org,
+                        &state.auth,
+                    )
+                    .await
+                    .unwrap_or(false);
+                    let user = match live {
+                        true => load_current_user(state.db.as_ref(), claims.sub, claims.org).await,
+                        false => None,
+                    };
+                    if let Some(user) = user {
+                        // Browsers attach the cookie to cross-site requests too, so
+                        // writes must echo the CSRF cookie in a header another site
+                        // cannot read or set.
+                        let cookie = extract_cookie(req.headers(), CSRF_COOKIE);
+                        if !is_safe_method(req.method()) {
+                            let submitted = req
+                                .headers()
+                                .get(CSRF_HEADER)
+                                .and_then(|value| value.to_str().ok());
+                            let valid = matches!(
+                                (cookie.as_deref(), submitted),
+                                (Some(cookie), Some(submitted)) if csrf_matches(cookie, submitted)
+                            );
+                            if !valid {
+                                let token = cookie.unwrap_or_else(generate_csrf_token);
+                                return csrf_rejection(&token, &state.auth);
+                            }
+                        }
+                        csrf_token = Some(cookie.unwrap_or_else(generate_csrf_token));
                         req.extensions_mut().insert(user.clone());
-                        if let Ok(new_token) = issue_session_token(user.user_id, &state.auth) {
+                        req.extensions_mut()
+                            .insert(CurrentSession { id: claims.sid });
+                        if let Ok(new_token) =
+                            issue_session_token(user.user_id, user.org_id, claims.sid, &state.auth)
+                        {
                             refresh_cookie = Some(build_session_cookie(
                                 &new_token,
                                 state.auth.session_ttl_minutes,
@@ -341,24 +863,95 @@ async fn auth_middleware(
         }
     }
     let mut response = next.run(req).await;
-    if let Some(cookie) = refresh_cookie {
-        if let Ok(value) = cookie.parse() {
-            response
-                .headers_mut()
-                .append(axum::http::header::SET_COOKIE, value);
+    // Login, logout and revocation set the session cookie themselves; refreshing
+    // on top of that would resurrect a session that was just ended.
+    let set_session = response
+        .headers()
+        .get_all(axum::http::header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|value| value.strip_prefix(&format!("{}=", SESSION_COOKIE)))
+        .map(|rest| !rest.starts_with(';') && !rest.is_empty());
+    match set_session {
+        // A new session gets a new CSRF token.
+        Some(true) => {
+            refresh_cookie = None;
+            csrf_token = Some(generate_csrf_token());
+        }
+        Some(false) => {
+            refresh_cookie = None;
+            csrf_token = None;
+            append_set_cookie(&mut response, clear_csrf_cookie());
         }
+        None => {}
+    }
+    if let Some(cookie) = refresh_cookie {
+        append_set_cookie(&mut response, cookie);
+    }
+    if let Some(token) = csrf_token {
+        append_csrf_token(&mut response, &token, &state.auth);
+    }
+    response
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// The token travels in a readable cookie and is repeated in a response header,
+/// so clients can pick it up from either.
+fn append_csrf_token(response: &mut Response, token: &str, auth: &AuthConfig) {
+    append_set_cookie(response, build_csrf_cookie(token, auth.session_ttl_minutes));
+    if let Ok(value) = HeaderValue::from_str(token) {
+        response.headers_mut().insert(CSRF_HEADER, value);
+    }
+}
+
+fn append_set_cookie(response: &mut Response, cookie: String) {
+    if let Ok(value) = cookie.parse() {
+        response
+            .headers_mut()
+            .append(axum::http::header::SET_COOKIE, value);
     }
+}
+
+/// GraphQL-shaped 403 that also hands out a token, so a client whose cookie was
+/// missing can retry.
+fn csrf_rejection(token: &str, auth: &AuthConfig) -> Response {
+    let body = serde_json::json!({
+        "errors": [{
+            "message": "Missing or invalid CSRF token",
+            "extensions": { "code": "FORBIDDEN" },
+        }]
+    });
+    let mut response = (StatusCode::FORBIDDEN, axum::Json(body)).into_response();
+    append_csrf_token(&mut response, token, auth);
     response
 }
 
+fn extract_bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    Some(token.trim().to_string())
+}
+
 fn extract_session_token(headers: &HeaderMap) -> Option<String> {
+    extract_cookie(headers, SESSION_COOKIE)
+}
+
+fn extract_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
     let raw = headers.get(axum::http::header::COOKIE)?;
     let value = raw.to_str().ok()?;
     for part in value.split(';') {
-        let trimmed = part.trim();
-        if let Some(rest) = trimmed.strip_prefix(SESSION_COOKIE) {
-            let token = rest.trim_start_matches('=').trim();
-            if !token.is_empty() {
+        if let Some((key, token)) = part.trim().split_once('=') {
+            let token = token.trim();
+            if key == name && !token.is_empty() {
                 return Some(token.to_string());
             }
         }
@@ -366,25 +959,30 @@ fn extract_session_token(headers: &HeaderMap) -> Option<String> {
     None
 }
 
-async fn load_current_user(db: &DatabaseConnection, user_id: Uuid) -> Option<CurrentUser> {
+/// The caller inside `org_id`; `None` once their account or membership is disabled.
+async fn load_current_user(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    org_id: Uuid,
+) -> Option<CurrentUser> {
     let user = app_user::Entity::find_by_id(user_id).one(db).await.ok()??;
     if !user.is_active {
         return None;
     }
-    let roles = user_role::Entity::find()
-        .filter(user_role::Column::UserId.eq(user_id))
-        .all(db)
-        .await
-        .ok()?
-        .into_iter()
-        .filter_map(|row| match row.role {
-            user_role::Role::Owner => Some(UserRole::Owner),
-            user_role::Role::Admin => Some(UserRole::Admin),
-            user_role::Role::Sales => Some(UserRole::Sales),
-            user_role::Role::Viewer => Some(UserRole::Viewer),
-        })
-        .collect();
-    Some(CurrentUser { user_id, roles })
+    let roles = member_roles(db, org_id, user_id).await.ok()??;
+    Some(CurrentUser {
+        user_id,
+        org_id,
+        roles,
+        scopes: None,
+    })
+}
+
+async fn load_api_key_user(db: &DatabaseConnection, token: &str) -> Option<CurrentUser> {
+    let key = authenticate_api_key(db, token).await.ok()??;
+    let mut user = load_current_user(db, key.user_id, key.org_id).await?;
+    user.scopes = Some(parse_scopes(&key.scopes));
+    Some(user)
 }
 
 fn load_auth_config_from_env() -> anyhow::Result<AuthConfig> {
@@ -394,22 +992,195 @@ fn load_auth_config_from_env() -> anyhow::Result<AuthConfig> {
         .as_str()
     {
         "local" => AuthMode::Local,
+        "oidc" => AuthMode::Oidc,
+        "mixed" => AuthMode::Mixed,
         _ => AuthMode::Disabled,
     };
     let ttl = std::env::var("AUTH_SESSION_TTL_MINUTES")
         .ok()
         .and_then(|raw| raw.parse::<i64>().ok())
         .unwrap_or(15);
+    let max_age = std::env::var("AUTH_SESSION_MAX_AGE_MINUTES")
+        .ok()
+        .and_then(|raw| raw.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_SESSION_MAX_AGE_MINUTES);
+    let keyring = load_keyring_from_env()?;
+    if mode != AuthMode::Disabled && keyring.signer().is_none() {
+        anyhow::bail!(
+            "AUTH_SESSION_SECRET or AUTH_SIGNING_KEY_FILE must be set unless AUTH_MODE=disabled"
+        );
+    }
+    let min_length = std::env::var("PASSWORD_MIN_LENGTH")
+        .ok()
+        .and_then(|raw| raw.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MIN_PASSWORD_LENGTH);
+    let mut policy = PasswordPolicy::new(min_length);
+    if let Ok(path) = std::env::var("PASSWORD_DENY_LIST_FILE") {
+        let words = std::fs::read_to_string(&path).map_err(|err| {
+            anyhow::anyhow!("cannot read PASSWORD_DENY_LIST_FILE {}: {}", path, err)
+        })?;
+        policy = policy.with_denied(words.lines());
+    }
+    let mut config = AuthConfig::new(mode, None, ttl)
+        .with_keyring(keyring)
+        .with_session_max_age(max_age)
+        .with_password_policy(policy);
+    if let Ok(url) = std::env::var("APP_URL") {
+        config = config.with_app_url(url);
+    }
+    Ok(config)
+}
+
+/// Signs with the private key in `AUTH_SIGNING_KEY_FILE` when set, otherwise
+/// with `AUTH_SESSION_SECRET`. Retired secrets and key files listed in
+/// `AUTH_PREVIOUS_SESSION_SECRETS` and `AUTH_PREVIOUS_SIGNING_KEY_FILES`
+/// (comma-separated) still verify, so rotating does not sign anyone out.
+fn load_keyring_from_env() -> anyhow::Result<Keyring> {
+    let list = |name: &str| -> Vec<String> {
+        std::env::var(name)
+            .unwrap_or_default()
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    };
+    let key_file = |path: &str, kid: Option<String>| {
+        let pem = std::fs::read(path)
+            .map_err(|err| anyhow::anyhow!("cannot read signing key {}: {}", path, err))?;
+        SigningKey::from_private_pem(&pem, kid)
+            .map_err(|err| anyhow::anyhow!("invalid signing key {}: {:#}", path, err))
+    };
     let secret = std::env::var("AUTH_SESSION_SECRET").ok();
-    if mode == AuthMode::Local && secret.is_none() {
-        anyhow::bail!("AUTH_SESSION_SECRET must be set when AUTH_MODE=local");
+    let mut keyring = match std::env::var("AUTH_SIGNING_KEY_FILE") {
+        Ok(path) => {
+            let keyring = Keyring::new(key_file(&path, std::env::var("AUTH_SIGNING_KID").ok())?);
+            // Sessions signed with the secret before the switch stay valid.
+            match &secret {
+                Some(secret) => keyring.with_verifier(SigningKey::hmac(secret)),
+                None => keyring,
+            }
+        }
+        Err(_) => match &secret {
+            Some(secret) => Keyring::new(SigningKey::hmac(secret)),
+            None => Keyring::default(),
+        },
+    };
+    for secret in list("AUTH_PREVIOUS_SESSION_SECRETS") {
+        keyring = keyring.with_verifier(SigningKey::hmac(&secret));
+    }
+    for path in list("AUTH_PREVIOUS_SIGNING_KEY_FILES") {
+        keyring = keyring.with_verifier(key_file(&path, None)?);
     }
-    Ok(AuthConfig::new(mode, secret, ttl))
+    Ok(keyring)
+}
+
+/// `CORS_ALLOWED_ORIGINS` is a comma-separated list of origins, or `*` for any
+/// origin without cookies. Unset means same-origin only.
+fn load_cors_origins_from_env() -> anyhow::Result<CorsOrigins> {
+    let raw = std::env::var("CORS_ALLOWED_ORIGINS").unwrap_or_default();
+    let origins: Vec<&str> = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .collect();
+    if origins.is_empty() {
+        return Ok(CorsOrigins::None);
+    }
+    if origins == ["*"] {
+        return Ok(CorsOrigins::Any);
+    }
+    let list = origins
+        .into_iter()
+        .map(|origin| {
+            if origin == "*" || !origin.contains("://") {
+                anyhow::bail!(
+                    "CORS_ALLOWED_ORIGINS entries must be origins like https://app.example.com, got {:?}",
+                    origin
+                );
+            }
+            Ok(HeaderValue::from_str(origin.trim_end_matches('/'))?)
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(CorsOrigins::List(list))
+}
+
+/// `EVENT_FANOUT=postgres` relays subscription events between server instances
+/// through `LISTEN/NOTIFY`; unset or `local` keeps them in this process.
+fn load_event_bus_from_env(db: Arc<DatabaseConnection>) -> anyhow::Result<EventBus> {
+    match std::env::var("EVENT_FANOUT")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "" | "local" => Ok(EventBus::default()),
+        "postgres" => Ok(EventBus::new(Some(db))),
+        other => anyhow::bail!("EVENT_FANOUT must be local or postgres, got {other:?}"),
+    }
+}
+
+fn load_smtp_config_from_env() -> anyhow::Result<Option<SmtpConfig>> {
+    let Ok(host) = std::env::var("SMTP_HOST") else {
+        return Ok(None);
+    };
+    let from =
+        std::env::var("MAIL_FROM").unwrap_or_else(|_| "SME Suite <no-reply@localhost>".into());
+    let mut config = SmtpConfig::new(host, from);
+    if let Ok(security) = std::env::var("SMTP_SECURITY") {
+        config.security = SmtpSecurity::parse(&security.to_lowercase())
+            .ok_or_else(|| anyhow::anyhow!("SMTP_SECURITY must be none, starttls or tls"))?;
+        config.port = config.security.default_port();
+    }
+    if let Ok(port) = std::env::var("SMTP_PORT") {
+        config.port = port
+            .parse()
+            .map_err(|_| anyhow::anyhow!("SMTP_PORT must be a port number"))?;
+    }
+    config.username = std::env::var("SMTP_USERNAME").ok();
+    config.password = std::env::var("SMTP_PASSWORD").ok();
+    Ok(Some(config))
+}
+
+fn load_oidc_config_from_env(mode: AuthMode) -> anyhow::Result<Option<OidcConfig>> {
+    if !mode.allows_oidc() {
+        return Ok(None);
+    }
+    let required = |name: &str| {
+        std::env::var(name)
+            .map_err(|_| anyhow::anyhow!("{} must be set when AUTH_MODE=oidc or mixed", name))
+    };
+    let mut config = OidcConfig::new(
+        required("OIDC_ISSUER")?,
+        required("OIDC_CLIENT_ID")?,
+        required("OIDC_REDIRECT_URL")?,
+    );
+    config.client_secret = std::env::var("OIDC_CLIENT_SECRET").ok();
+    if let Ok(scopes) = std::env::var("OIDC_SCOPES") {
+        config.scopes = scopes;
+    }
+    if let Ok(provider) = std::env::var("OIDC_PROVIDER") {
+        config.provider = provider;
+    }
+    if let Ok(redirect) = std::env::var("OIDC_POST_LOGIN_REDIRECT") {
+        config.post_login_redirect = redirect;
+    }
+    if let Ok(role) = std::env::var("OIDC_JIT_ROLE") {
+        let role = UserRole::from_str(&role.to_uppercase()).ok_or_else(|| {
+            anyhow::anyhow!("OIDC_JIT_ROLE must be OWNER, ADMIN, SALES or VIEWER")
+        })?;
+        config.jit_role = Some(role);
+    }
+    if let Ok(slug) = std::env::var("OIDC_JIT_ORGANIZATION") {
+        config.jit_organization = normalize_slug(&slug)
+            .ok_or_else(|| anyhow::anyhow!("OIDC_JIT_ORGANIZATION must be an organization slug"))?;
+    }
+    Ok(Some(config))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use api::schema::build_schema;
     use axum::http::Request;
     use http_body_util::BodyExt;
     use serde_json::{json, Value};
@@ -435,6 +1206,8 @@ mod tests {
             db: ctx.db.clone(),
             auth: ctx.auth.clone(),
             dev_user: None,
+            oidc: None,
+            cors: CorsOrigins::default(),
         };
         let app = app_router(state);
 
@@ -499,6 +1272,7 @@ mod tests {
         );
 
         let response = app
+            .clone()
             .oneshot(add_cookie(
                 json_request(
                     r#"
@@ -524,23 +1298,906 @@ mod tests {
             logout_cookie
         );
 
+        assert!(me_email(&app, &cookie).await.is_none());
+
         ctx.cleanup().await;
     }
 
-    fn json_request(query: &str, variables: serde_json::Value) -> Request<Body> {
-        let payload = json!({ "query": query, "variables": variables }).to_string();
-        Request::builder()
-            .method(axum::http::Method::POST)
-            .uri("/graphql")
-            .header(axum::http::header::CONTENT_TYPE, "application/json")
-            .body(Body::from(payload))
+    #[tokio::test]
+    async fn revoked_and_deactivated_sessions_stop_authenticating() {
+        let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+            eprintln!("skipping auth server test: TEST_DATABASE_URL not set");
+            return;
+        };
+        let AppSchema(schema) = build_schema(ctx.db.clone(), ctx.auth.clone());
+        let state = AppState {
+            schema,
+            db: ctx.db.clone(),
+            auth: ctx.auth.clone(),
+            dev_user: None,
+            oidc: None,
+            cors: CorsOrigins::default(),
+        };
+        let app = app_router(state);
+        let laptop = login(&app, "sales@sme.test", "salespass").await;
+        let phone = login(&app, "sales@sme.test", "salespass").await;
+
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                json_request("{ crm { mySessions { current } } }", json!({})),
+                &laptop,
+            ))
+            .await
+            .expect("sessions response");
+        let body = response_json(response).await;
+        let mut current: Vec<bool> = body["data"]["crm"]["mySessions"]
+            .as_array()
             .unwrap()
+            .iter()
+            .map(|s| s["current"].as_bool().unwrap())
+            .collect();
+        current.sort();
+        assert_eq!(current, vec![false, true]);
+
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                json_request("mutation { crm { revokeAllSessions } }", json!({})),
+                &laptop,
+            ))
+            .await
+            .expect("revoke response");
+        let body = response_json(response).await;
+        assert_eq!(body["data"]["crm"]["revokeAllSessions"], 1);
+        assert!(me_email(&app, &phone).await.is_none());
+        assert_eq!(
+            me_email(&app, &laptop).await.as_deref(),
+            Some("sales@sme.test")
+        );
+
+        let owner = login(&app, "owner@sme.test", "ownerpass").await;
+        let sales_id = ctx.seeded.user_email("sales@sme.test").unwrap().id;
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                json_request(
+                    "mutation Off($id: ID!) { crm { deactivateUser(id: $id) { id } } }",
+                    json!({ "id": sales_id }),
+                ),
+                &owner,
+            ))
+            .await
+            .expect("deactivate response");
+        let body = response_json(response).await;
+        assert!(body["errors"].is_null(), "errors: {}", body["errors"]);
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                json_request(
+                    "mutation On($id: ID!) { crm { reactivateUser(id: $id) { id } } }",
+                    json!({ "id": sales_id }),
+                ),
+                &owner,
+            ))
+            .await
+            .expect("reactivate response");
+        let body = response_json(response).await;
+        assert!(body["errors"].is_null(), "errors: {}", body["errors"]);
+        assert!(me_email(&app, &laptop).await.is_none());
+
+        ctx.cleanup().await;
     }
 
-    fn add_cookie(mut request: Request<Body>, cookie: &str) -> Request<Body> {
-        request
-            .headers_mut()
-            .insert(axum::http::header::COOKIE, cookie.parse().unwrap());
+    #[tokio::test]
+    async fn bearer_api_keys_authenticate_without_cookies() {
+        let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+            eprintln!("skipping auth server test: TEST_DATABASE_URL not set");
+            return;
+        };
+        let AppSchema(schema) = build_schema(ctx.db.clone(), ctx.auth.clone());
+        let state = AppState {
+            schema,
+            db: ctx.db.clone(),
+            auth: ctx.auth.clone(),
+            dev_user: None,
+            oidc: None,
+            cors: CorsOrigins::default(),
+        };
+        let app = app_router(state);
+        let cookie = login(&app, "sales@sme.test", "salespass").await;
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                json_request(
+                    r#"mutation { crm { createApiKey(input: { name: "ci", scopes: [CRM_READ] }) { token } } }"#,
+                    json!({}),
+                ),
+                &cookie,
+            ))
+            .await
+            .expect("create key response");
+        let body = response_json(response).await;
+        let token = body["data"]["crm"]["createApiKey"]["token"]
+            .as_str()
+            .expect("token")
+            .to_string();
+
+        let companies = "{ crm { companies { totalCount } } }";
+        let response = app
+            .clone()
+            .oneshot(add_bearer(json_request(companies, json!({})), &token))
+            .await
+            .expect("bearer response");
+        assert!(response
+            .headers()
+            .get(axum::http::header::SET_COOKIE)
+            .is_none());
+        let body = response_json(response).await;
+        assert_eq!(body["data"]["crm"]["companies"]["totalCount"], 2);
+
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                add_bearer(json_request(companies, json!({})), "sme_forged"),
+                &cookie,
+            ))
+            .await
+            .expect("forged response");
+        let body = response_json(response).await;
+        assert_eq!(body["errors"][0]["extensions"]["code"], "UNAUTHENTICATED");
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn cookie_sessions_need_a_csrf_token_to_write() {
+        let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+            eprintln!("skipping auth server test: TEST_DATABASE_URL not set");
+            return;
+        };
+        let AppSchema(schema) = build_schema(ctx.db.clone(), ctx.auth.clone());
+        let state = AppState {
+            schema,
+            db: ctx.db.clone(),
+            auth: ctx.auth.clone(),
+            dev_user: None,
+            oidc: None,
+            cors: CorsOrigins::List(vec![HeaderValue::from_static("https://app.sme.test")]),
+        };
+        let app = app_router(state);
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                r#"mutation { crm { login(email: "sales@sme.test", password: "salespass") { ok } } }"#,
+                json!({}),
+            ))
+            .await
+            .expect("login response");
+        let session = session_cookie(&response).expect("session cookie");
+        let token = response
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .expect("csrf header")
+            .to_string();
+        let csrf_cookie = response
+            .headers()
+            .get_all(axum::http::header::SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find(|value| value.starts_with(&format!("{}=", CSRF_COOKIE)))
+            .expect("csrf cookie")
+            .to_string();
+        assert!(csrf_cookie.starts_with(&format!("{}={};", CSRF_COOKIE, token)));
+        assert!(!csrf_cookie.contains("HttpOnly"));
+        let cookies = format!("{}; {}={}", session, CSRF_COOKIE, token);
+
+        // A forged form post carries the cookies but cannot know the token.
+        let logout = || json_request("mutation { crm { logout } }", json!({}));
+        for header in [None, Some("0".repeat(64))] {
+            let mut request = add_raw_cookie(logout(), &cookies);
+            if let Some(header) = header {
+                request
+                    .headers_mut()
+                    .insert(CSRF_HEADER, header.parse().unwrap());
+            }
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            let body = response_json(response).await;
+            assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+        }
+
+        // Queries may still use GET, mutations may not.
+        let get = |query: &str| {
+            add_raw_cookie(
+                Request::builder()
+                    .uri(format!("/graphql?query={}", query))
+                    .body(Body::empty())
+                    .unwrap(),
+                &cookies,
+            )
+        };
+        let response = app
+            .clone()
+            .oneshot(get("%7Bcrm%7Bme%7Bemail%7D%7D%7D"))
+            .await
+            .unwrap();
+        let body = response_json(response).await;
+        assert_eq!(body["data"]["crm"]["me"]["email"], "sales@sme.test");
+        let response = app
+            .clone()
+            .oneshot(get("mutation%7Bcrm%7Blogout%7D%7D"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        // Listed origins may send cookies; others get no CORS grant at all.
+        let preflight = |origin: &'static str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/graphql")
+                .header(axum::http::header::ORIGIN, origin)
+                .header(axum::http::header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(
+                    axum::http::header::ACCESS_CONTROL_REQUEST_HEADERS,
+                    CSRF_HEADER,
+                )
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app
+            .clone()
+            .oneshot(preflight("https://app.sme.test"))
+            .await
+            .unwrap();
+        let headers = response.headers();
+        assert_eq!(
+            headers[axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.sme.test"
+        );
+        assert_eq!(
+            headers[axum::http::header::ACCESS_CONTROL_ALLOW_CREDENTIALS],
+            "true"
+        );
+        let response = app
+            .clone()
+            .oneshot(preflight("https://evil.test"))
+            .await
+            .unwrap();
+        assert!(response
+            .headers()
+            .get(axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+
+        let mut request = add_raw_cookie(logout(), &cookies);
+        request
+            .headers_mut()
+            .insert(CSRF_HEADER, token.parse().unwrap());
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get_all(axum::http::header::SET_COOKIE)
+            .iter()
+            .any(|value| value.to_str().unwrap() == clear_csrf_cookie()));
+        assert!(me_email(&app, &session).await.is_none());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn oidc_login_links_provisions_and_rejects() {
+        let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Mixed).await else {
+            eprintln!("skipping auth server test: TEST_DATABASE_URL not set");
+            return;
+        };
+        let idp = spawn_mock_idp().await;
+        let oidc_app = |jit_role: Option<UserRole>| {
+            let AppSchema(schema) = build_schema(ctx.db.clone(), ctx.auth.clone());
+            let mut config = OidcConfig::new(
+                idp.issuer.clone(),
+                "sme-suite".into(),
+                "http://localhost/auth/oidc/callback".into(),
+            );
+            config.jit_role = jit_role;
+            app_router(AppState {
+                schema,
+                db: ctx.db.clone(),
+                auth: ctx.auth.clone(),
+                dev_user: None,
+                oidc: Some(Arc::new(OidcClient::new(config))),
+                cors: CorsOrigins::default(),
+            })
+        };
+        let app = oidc_app(None);
+
+        // An existing account is matched by email and the identity linked.
+        let claims =
+            json!({ "sub": "idp-admin", "email": "Admin@SME.test", "email_verified": true });
+        let response = oidc_sign_in(&app, &idp, claims, None).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookie = session_cookie(&response).expect("session cookie");
+        assert_eq!(
+            me_email(&app, &cookie).await.as_deref(),
+            Some("admin@sme.test")
+        );
+        let claims = json!({ "sub": "idp-admin", "email": "renamed@elsewhere.test" });
+        let response = oidc_sign_in(&app, &idp, claims, None).await;
+        let cookie = session_cookie(&response).expect("session cookie");
+        assert_eq!(
+            me_email(&app, &cookie).await.as_deref(),
+            Some("admin@sme.test")
+        );
+
+        let claims = json!({
+            "sub": "idp-new", "email": "newbie@sme.test", "email_verified": true, "name": "Newbie"
+        });
+        let response = oidc_sign_in(&app, &idp, claims.clone(), None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        // Without a verified email the IdP identity never links to a local account.
+        let unverified =
+            json!({ "sub": "idp-sales", "email": "sales@sme.test", "email_verified": false });
+        let response = oidc_sign_in(&app, &idp, unverified, None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let unclaimed = json!({ "sub": "idp-owner", "email": "owner@sme.test" });
+        let response = oidc_sign_in(&app, &idp, unclaimed, None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let app_with_jit = oidc_app(Some(UserRole::Viewer));
+        let unclaimed = json!({ "sub": "idp-stranger", "email": "stranger@sme.test" });
+        let response = oidc_sign_in(&app_with_jit, &idp, unclaimed, None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = oidc_sign_in(&app, &idp, claims.clone(), Some("forged")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let app = oidc_app(Some(UserRole::Viewer));
+        let response = oidc_sign_in(&app, &idp, claims, None).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookie = session_cookie(&response).expect("session cookie");
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                json_request("{ crm { me { email displayName roles } } }", json!({})),
+                &cookie,
+            ))
+            .await
+            .expect("me response");
+        let body = response_json(response).await;
+        assert_eq!(
+            body["data"]["crm"]["me"],
+            json!({ "email": "newbie@sme.test", "displayName": "Newbie", "roles": ["VIEWER"] })
+        );
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn oidc_login_owes_the_second_factor() {
+        let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Mixed).await else {
+            eprintln!("skipping auth server test: TEST_DATABASE_URL not set");
+            return;
+        };
+        let idp = spawn_mock_idp().await;
+        let AppSchema(schema) = build_schema(ctx.db.clone(), ctx.auth.clone());
+        let mut config = OidcConfig::new(
+            idp.issuer.clone(),
+            "sme-suite".into(),
+            "http://localhost/auth/oidc/callback".into(),
+        );
+        config.jit_role = Some(UserRole::Admin);
+        let app = app_router(AppState {
+            schema,
+            db: ctx.db.clone(),
+            auth: ctx.auth.clone(),
+            dev_user: None,
+            oidc: Some(Arc::new(OidcClient::new(config))),
+            cors: CorsOrigins::default(),
+        });
+        let org_id = ctx.seeded.organization.id;
+        let sales = ctx.seeded.user_email("sales@sme.test").unwrap().id;
+        let owner = ctx.seeded.user_email("owner@sme.test").unwrap().id;
+
+        // An account with TOTP on gets a challenge instead of a session.
+        let secret = api::totp::generate_totp_secret();
+        assert!(
+            api::totp::stage_totp_secret(ctx.db.as_ref(), sales, &secret)
+                .await
+                .unwrap()
+        );
+        let code = api::totp::totp_code(&secret, Utc::now().timestamp()).unwrap();
+        assert!(api::totp::confirm_totp(ctx.db.as_ref(), sales, &code)
+            .await
+            .unwrap());
+        let claims =
+            json!({ "sub": "idp-sales", "email": "sales@sme.test", "email_verified": true });
+        let response = oidc_sign_in(&app, &idp, claims, None).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(!sets_session(&response));
+        let (next_step, token) = challenge_fragment(&response);
+        assert_eq!(next_step, "VERIFY_TOTP");
+        let code = api::totp::totp_code(&secret, Utc::now().timestamp() + 30).unwrap();
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                r#"
+                mutation Verify($token: String!, $code: String!) {
+                    crm { verifySecondFactor(challengeToken: $token, code: $code) { ok } }
+                }
+                "#,
+                json!({ "token": token, "code": code }),
+            ))
+            .await
+            .expect("verify response");
+        let cookie = session_cookie(&response).expect("session cookie");
+        assert_eq!(
+            me_email(&app, &cookie).await.as_deref(),
+            Some("sales@sme.test")
+        );
+
+        // The admin policy sends an owner without TOTP to enrolment.
+        api::settings::set_flag(
+            ctx.db.as_ref(),
+            org_id,
+            api::settings::REQUIRE_ADMIN_TWO_FACTOR,
+            true,
+            owner,
+        )
+        .await
+        .unwrap();
+        let claims =
+            json!({ "sub": "idp-owner", "email": "owner@sme.test", "email_verified": true });
+        let response = oidc_sign_in(&app, &idp, claims, None).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(!sets_session(&response));
+        assert_eq!(challenge_fragment(&response).0, "ENROLL_TOTP");
+
+        // An IdP-only admin has nowhere to keep TOTP, so the policy turns them away.
+        let claims =
+            json!({ "sub": "idp-new", "email": "newbie@sme.test", "email_verified": true });
+        let response = oidc_sign_in(&app, &idp, claims, None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        ctx.cleanup().await;
+    }
+
+    fn sets_session(response: &Response) -> bool {
+        response
+            .headers()
+            .get_all(axum::http::header::SET_COOKIE)
+            .iter()
+            .any(|value| {
+                value
+                    .to_str()
+                    .is_ok_and(|value| value.starts_with(&format!("{}=", SESSION_COOKIE)))
+            })
+    }
+
+    /// The `next_step` and `challenge_token` an OIDC redirect carries in its fragment.
+    fn challenge_fragment(response: &Response) -> (String, String) {
+        let location = response.headers()[axum::http::header::LOCATION]
+            .to_str()
+            .unwrap();
+        let fragment = location.split_once('#').expect("fragment").1;
+        let params: std::collections::HashMap<String, String> =
+            url::form_urlencoded::parse(fragment.as_bytes())
+                .into_owned()
+                .collect();
+        (
+            params["next_step"].clone(),
+            params["challenge_token"].clone(),
+        )
+    }
+
+    const OIDC_KID: &str = "test-rs256";
+    const OIDC_JWK_N: &str = "7FYJdDkv4Zz1MIRv074CsAD9BpmywSJWhTJ4PKbQQda_JysSDCr5G3hhmp1d-OJPl64HB8XqeykjjxXOxwWoj4ZyYhEh6miWYVzeyymAmOISzFGZngFvYg5C0hoR2f-bN7mahYk8-LRuYinaTxm50LxP6n_xH_DBVALcpbgWeLraSBw673AVqz1YmjAGM1vqc5hXydUeNI-WBXHv3XiM-_AFYm1c-JfJin8V-xO1atISM7PfluA7Dr06k97zsLwD-eWpbPSYGXOWXJWhSKkmsHp1KHL140w-1ssEfZiFhbaMWd8Qnh5B6tdEtmVcLqswf-WcwMWCGlJxM7bkqpYfPQ";
+
+    /// A local identity provider: discovery, JWKS and a token endpoint that
+    /// checks the PKCE verifier before handing out an RS256 ID token.
+    struct MockIdp {
+        issuer: String,
+        grants: std::sync::Mutex<std::collections::HashMap<String, (String, Value)>>,
+    }
+
+    async fn spawn_mock_idp() -> Arc<MockIdp> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let idp = Arc::new(MockIdp {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            grants: Default::default(),
+        });
+        let discovery = {
+            let issuer = idp.issuer.clone();
+            move || async move {
+                axum::Json(json!({
+                    "issuer": issuer,
+                    "authorization_endpoint": format!("{}/authorize", issuer),
+                    "token_endpoint": format!("{}/token", issuer),
+                    "jwks_uri": format!("{}/jwks", issuer),
+                }))
+            }
+        };
+        let jwks = || async {
+            axum::Json(json!({ "keys": [{
+                "kty": "RSA", "use": "sig", "alg": "RS256", "kid": OIDC_KID, "n": OIDC_JWK_N, "e": "AQAB"
+            }] }))
+        };
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", axum::routing::post(mock_token))
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        idp
+    }
+
+    async fn mock_token(
+        State(idp): State<Arc<MockIdp>>,
+        axum::Form(form): axum::Form<std::collections::HashMap<String, String>>,
+    ) -> Response {
+        let grant = idp.grants.lock().unwrap().remove(&form["code"]);
+        let Some((challenge, mut claims)) = grant else {
+            return (StatusCode::BAD_REQUEST, "invalid_grant").into_response();
+        };
+        if api::oidc::pkce_challenge(&form["code_verifier"]) != challenge {
+            return (StatusCode::BAD_REQUEST, "invalid_grant").into_response();
+        }
+        claims["iss"] = json!(idp.issuer);
+        claims["aud"] = json!(form["client_id"]);
+        claims["exp"] = json!(chrono::Utc::now().timestamp() + 300);
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+        header.kid = Some(OIDC_KID.into());
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(include_bytes!(
+            "../fixtures/oidc_test_key.pem"
+        ))
+        .unwrap();
+        let id_token = jsonwebtoken::encode(&header, &claims, &key).unwrap();
+        axum::Json(
+            json!({ "access_token": "opaque", "token_type": "Bearer", "id_token": id_token }),
+        )
+        .into_response()
+    }
+
+    /// Runs the browser side of a sign-in; the IdP will vouch for `claims`.
+    async fn oidc_sign_in(
+        app: &Router,
+        idp: &MockIdp,
+        mut claims: Value,
+        state_override: Option<&str>,
+    ) -> Response {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/auth/oidc/login")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("login redirect");
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers()[axum::http::header::LOCATION]
+            .to_str()
+            .unwrap();
+        let params: std::collections::HashMap<String, String> = url::Url::parse(location)
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect();
+        let flow_cookie = session_cookie(&response).expect("flow cookie");
+        let code = uuid::Uuid::new_v4().to_string();
+        claims["nonce"] = json!(params["nonce"]);
+        idp.grants
+            .lock()
+            .unwrap()
+            .insert(code.clone(), (params["code_challenge"].clone(), claims));
+        let state = state_override.unwrap_or(&params["state"]);
+        let request = Request::builder()
+            .uri(format!("/auth/oidc/callback?code={}&state={}", code, state))
+            .body(Body::empty())
+            .unwrap();
+        app.clone()
+            .oneshot(add_cookie(request, &flow_cookie))
+            .await
+            .expect("callback response")
+    }
+
+    fn session_cookie(response: &Response) -> Option<String> {
+        response
+            .headers()
+            .get(axum::http::header::SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::to_string)
+    }
+
+    #[tokio::test]
+    async fn failed_logins_from_one_address_lock_it_out() {
+        let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+            eprintln!("skipping auth server test: TEST_DATABASE_URL not set");
+            return;
+        };
+        let AppSchema(schema) = build_schema(ctx.db.clone(), ctx.auth.clone());
+        let state = AppState {
+            schema,
+            db: ctx.db.clone(),
+            auth: ctx.auth.clone(),
+            dev_user: None,
+            oidc: None,
+            cors: CorsOrigins::default(),
+        };
+        let app = app_router(state);
+        let attempt = |email: String, password: &str, ip: [u8; 4]| {
+            let mut request = json_request(
+                r#"
+                mutation Login($email: String!, $password: String!) {
+                    crm { login(email: $email, password: $password) { ok retryAfterSeconds } }
+                }
+                "#,
+                json!({ "email": email, "password": password }),
+            );
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from((ip, 40000))));
+            app.clone().oneshot(request)
+        };
+
+        // Spraying many accounts from one address trips the address lock even
+        // though no single account reaches its own limit.
+        for n in 0..21 {
+            let email = format!("guess{}@sme.test", n);
+            attempt(email, "hunter2", [203, 0, 113, 7]).await.unwrap();
+        }
+        let response = attempt("owner@sme.test".into(), "ownerpass", [203, 0, 113, 7])
+            .await
+            .unwrap();
+        let body = response_json(response).await;
+        assert_eq!(body["data"]["crm"]["login"]["ok"], false);
+        assert!(body["data"]["crm"]["login"]["retryAfterSeconds"].is_number());
+        let response = attempt("owner@sme.test".into(), "ownerpass", [198, 51, 100, 1])
+            .await
+            .unwrap();
+        let cookie = response
+            .headers()
+            .get(axum::http::header::SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.split(';').next().unwrap().to_string())
+            .expect("session cookie");
+
+        let response = app
+            .clone()
+            .oneshot(add_cookie(
+                json_request("mutation { crm { logout } }", json!({})),
+                &cookie,
+            ))
+            .await
+            .expect("logout response");
+        assert_eq!(response.status(), StatusCode::OK);
+        let events = auth_event::Entity::find()
+            .order_by_desc(auth_event::Column::CreatedAt)
+            .all(ctx.db.as_ref())
+            .await
+            .unwrap();
+        assert_eq!(events[0].kind, auth_event::Kind::Logout);
+        assert_eq!(events[1].kind, auth_event::Kind::LoginSucceeded);
+        assert_eq!(events[1].ip.as_deref(), Some("198.51.100.1"));
+        assert!(events
+            .iter()
+            .any(|event| event.kind == auth_event::Kind::LockedOut
+                && event.ip.as_deref() == Some("203.0.113.7")));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn subscriptions_run_over_an_authenticated_websocket() {
+        let Some(ctx) = PgTestContext::new_seeded_with_mode(AuthMode::Local).await else {
+            eprintln!("skipping auth server test: TEST_DATABASE_URL not set");
+            return;
+        };
+        let AppSchema(schema) = build_schema(ctx.db.clone(), ctx.auth.clone());
+        let state = AppState {
+            schema,
+            db: ctx.db.clone(),
+            auth: ctx.auth.clone(),
+            dev_user: None,
+            oidc: None,
+            cors: CorsOrigins::List(vec![HeaderValue::from_static("https://app.sme.test")]),
+        };
+        let app = app_router(state);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = app
+            .clone()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, service).await });
+        let cookie = login(&app, "sales@sme.test", "salespass").await;
+
+        let (status, _) = ws_connect(addr, None, None).await;
+        assert_eq!(status, 401);
+        let (status, _) = ws_connect(addr, Some(&cookie), Some("https://evil.test")).await;
+        assert_eq!(status, 403);
+        let (status, _) = ws_connect(addr, Some(&cookie), Some("https://app.sme.test")).await;
+        assert_eq!(status, 101);
+
+        let (status, mut socket) = ws_connect(addr, Some(&cookie), None).await;
+        assert_eq!(status, 101);
+        ws_send(&mut socket, json!({ "type": "connection_init" })).await;
+        assert_eq!(ws_receive(&mut socket).await["type"], "connection_ack");
+        ws_send(
+            &mut socket,
+            json!({
+                "id": "1",
+                "type": "subscribe",
+                "payload": { "query": "subscription { dealUpdated { title } }" },
+            }),
+        )
+        .await;
+        // The subscription is registered once the server has read the message;
+        // keep writing until an event shows up.
+        let sales = ctx.seeded.user_email("sales@sme.test").unwrap().id;
+        let deal = ctx
+            .seeded
+            .deals
+            .iter()
+            .find(|deal| deal.assigned_user_id == Some(sales))
+            .unwrap();
+        let update = r#"
+            mutation Update($id: ID!) { crm { updateDeal(input: { id: $id, title: "Live" }) { id } } }
+        "#;
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                app.clone()
+                    .oneshot(add_cookie(
+                        json_request(update, json!({ "id": deal.id })),
+                        &cookie,
+                    ))
+                    .await
+                    .unwrap();
+                let wait = std::time::Duration::from_millis(200);
+                if let Ok(message) = tokio::time::timeout(wait, ws_receive(&mut socket)).await {
+                    break message;
+                }
+            }
+        })
+        .await
+        .expect("subscription event");
+        assert_eq!(message["type"], "next");
+        assert_eq!(message["payload"]["data"]["dealUpdated"]["title"], "Live");
+        ctx.cleanup().await;
+    }
+
+    /// Sends a `graphql-transport-ws` upgrade request and returns the response
+    /// status with the socket, positioned after the response headers.
+    async fn ws_connect(
+        addr: SocketAddr,
+        cookie: Option<&str>,
+        origin: Option<&str>,
+    ) -> (u16, tokio::net::TcpStream) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut socket = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut request = format!(
+            "GET /graphql/ws HTTP/1.1\r\nHost: {addr}\r\nConnection: Upgrade\r\n\
+             Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Protocol: graphql-transport-ws\r\n"
+        );
+        if let Some(cookie) = cookie {
+            request.push_str(&format!("Cookie: {cookie}\r\n"));
+        }
+        if let Some(origin) = origin {
+            request.push_str(&format!("Origin: {origin}\r\n"));
+        }
+        request.push_str("\r\n");
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(socket.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, socket)
+    }
+
+    /// Writes `message` as one masked text frame, as clients must.
+    async fn ws_send(socket: &mut tokio::net::TcpStream, message: Value) {
+        use tokio::io::AsyncWriteExt;
+        let payload = message.to_string().into_bytes();
+        let mut frame = vec![0x81];
+        match payload.len() {
+            len if len < 126 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+        }
+        // An all-zero mask leaves the payload as is.
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(&payload);
+        socket.write_all(&frame).await.unwrap();
+    }
+
+    /// Reads the next unmasked text frame from the server.
+    async fn ws_receive(socket: &mut tokio::net::TcpStream) -> Value {
+        use tokio::io::AsyncReadExt;
+        let opcode = socket.read_u8().await.unwrap() & 0x0f;
+        assert_eq!(opcode, 1, "expected a text frame");
+        let len = match socket.read_u8().await.unwrap() & 0x7f {
+            126 => socket.read_u16().await.unwrap() as usize,
+            127 => socket.read_u64().await.unwrap() as usize,
+            len => len as usize,
+        };
+        let mut payload = vec![0; len];
+        socket.read_exact(&mut payload).await.unwrap();
+        serde_json::from_slice(&payload).unwrap()
+    }
+
+    const ME: &str = "{ crm { me { email } } }";
+
+    async fn login(app: &Router, email: &str, password: &str) -> String {
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                r#"
+                mutation Login($email: String!, $password: String!) {
+                    crm { login(email: $email, password: $password) { ok } }
+                }
+                "#,
+                json!({ "email": email, "password": password }),
+            ))
+            .await
+            .expect("login response");
+        let cookie = response
+            .headers()
+            .get(axum::http::header::SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .expect("session cookie");
+        cookie.split(';').next().unwrap().to_string()
+    }
+
+    async fn me_email(app: &Router, cookie: &str) -> Option<String> {
+        let response = app
+            .clone()
+            .oneshot(add_cookie(json_request(ME, json!({})), cookie))
+            .await
+            .expect("me response");
+        let body = response_json(response).await;
+        body["data"]["crm"]["me"]["email"]
+            .as_str()
+            .map(str::to_string)
+    }
+
+    fn json_request(query: &str, variables: serde_json::Value) -> Request<Body> {
+        let payload = json!({ "query": query, "variables": variables }).to_string();
+        Request::builder()
+            .method(axum::http::Method::POST)
+            .uri("/graphql")
+            .header(axum::http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(payload))
+            .unwrap()
+    }
+
+    /// Sends `cookie` the way the web app does, with a matching CSRF cookie and
+    /// header alongside.
+    fn add_cookie(request: Request<Body>, cookie: &str) -> Request<Body> {
+        let mut request =
+            add_raw_cookie(request, &format!("{}; {}=test-csrf", cookie, CSRF_COOKIE));
+        request
+            .headers_mut()
+            .insert(CSRF_HEADER, HeaderValue::from_static("test-csrf"));
+        request
+    }
+
+    fn add_raw_cookie(mut request: Request<Body>, cookie: &str) -> Request<Body> {
+        request
+            .headers_mut()
+            .insert(axum::http::header::COOKIE, cookie.parse().unwrap());
+        request
+    }
+
+    fn add_bearer(mut request: Request<Body>, token: &str) -> Request<Body> {
+        request.headers_mut().insert(
+            axum::http::header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
         request
     }
 